@@ -1,3 +1,4 @@
 mod runner;
 
 pub use self::runner::GraphQlRunner;
+pub use graph_graphql::prelude::QueryLimits;
@@ -1,38 +1,63 @@
 use futures::future;
-use std::sync::Mutex;
 
 use graph::prelude::{GraphQlRunner as GraphQlRunnerTrait, *};
+use graph::util::metrics::{GRAPHQL_QUERIES, GRAPHQL_QUERY_DURATION};
 use graph_graphql::prelude::*;
 
 /// GraphQL runner implementation for The Graph.
 pub struct GraphQlRunner<S> {
     logger: Logger,
-    store: Arc<Mutex<S>>,
+    store: Arc<S>,
+    query_limits: QueryLimits,
 }
 
 impl<S> GraphQlRunner<S>
 where
-    S: Store + 'static,
+    S: Store + SubgraphDeploymentStore + 'static,
 {
     /// Creates a new query runner.
-    pub fn new(logger: &Logger, store: Arc<Mutex<S>>) -> Self {
+    pub fn new(logger: &Logger, store: Arc<S>, query_limits: QueryLimits) -> Self {
         GraphQlRunner {
             logger: logger.new(o!("component" => "GraphQlRunner")),
             store: store,
+            query_limits,
         }
     }
 }
 
 impl<S> GraphQlRunnerTrait for GraphQlRunner<S>
 where
-    S: Store + 'static,
+    S: Store + SubgraphDeploymentStore + 'static,
 {
     fn run_query(&self, query: Query) -> QueryResultFuture {
+        let timer = GRAPHQL_QUERY_DURATION.start_timer();
         let result = execute_query(
             &query,
             QueryExecutionOptions {
                 logger: self.logger.clone(),
                 resolver: StoreResolver::new(&self.logger, self.store.clone()),
+                limits: self.query_limits,
+            },
+        );
+        timer.observe_duration();
+
+        let status = if result.errors.is_some() {
+            "error"
+        } else {
+            "success"
+        };
+        GRAPHQL_QUERIES.with_label_values(&[status]).inc();
+
+        Box::new(future::ok(result))
+    }
+
+    fn run_indexing_status_query(&self, query: Query) -> QueryResultFuture {
+        let result = execute_query(
+            &query,
+            QueryExecutionOptions {
+                logger: self.logger.clone(),
+                resolver: IndexingStatusResolver::new(&self.logger, self.store.clone()),
+                limits: self.query_limits,
             },
         );
         Box::new(future::ok(result))
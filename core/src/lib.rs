@@ -8,6 +8,6 @@ extern crate serde_yaml;
 mod graphql;
 mod subgraph;
 
-pub use graphql::GraphQlRunner;
+pub use graphql::{GraphQlRunner, QueryLimits};
 pub use subgraph::RuntimeManager;
 pub use subgraph::SubgraphProvider;
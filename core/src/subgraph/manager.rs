@@ -1,9 +1,62 @@
 use futures::sync::mpsc::{channel, Receiver, Sender};
+use futures::sync::oneshot;
+use std::collections::HashMap;
 use std::sync::Mutex;
 
 use graph::components::subgraph::RuntimeHostEvent;
 use graph::components::subgraph::SubgraphProviderEvent;
 use graph::prelude::*;
+use graph::web3::types::{Block, Log, Transaction, H256};
+
+/// An error that stopped a subgraph, along with the block that could not be
+/// processed because of it, if any.
+struct SubgraphError {
+    error: Error,
+    block_ptr: Option<EthereumBlockPointer>,
+}
+
+impl From<Error> for SubgraphError {
+    fn from(error: Error) -> Self {
+        SubgraphError {
+            error,
+            block_ptr: None,
+        }
+    }
+}
+
+/// A log or a call of a block that triggers handlers in the mappings.
+enum BlockTrigger {
+    Log(Log),
+    Call(EthereumCall),
+}
+
+impl BlockTrigger {
+    /// Sorts the triggers of a block in the order of their transactions.
+    /// Within a transaction, logs come first, in the order of their log
+    /// indices, followed by the calls, in the order they were made.
+    fn sort(triggers: &mut Vec<BlockTrigger>, block: &Block<Transaction>) {
+        let transaction_indices = block
+            .transactions
+            .iter()
+            .enumerate()
+            .map(|(index, transaction)| (transaction.hash, index))
+            .collect::<HashMap<H256, usize>>();
+        let transaction_index = |hash: Option<H256>| {
+            hash.and_then(|hash| transaction_indices.get(&hash).cloned())
+                .unwrap_or(usize::max_value())
+        };
+
+        // The sort is stable, so calls keep their order
+        triggers.sort_by_key(|trigger| match trigger {
+            BlockTrigger::Log(log) => (
+                transaction_index(log.transaction_hash),
+                0,
+                log.log_index.map_or(0, |index| index.low_u64()),
+            ),
+            BlockTrigger::Call(call) => (transaction_index(Some(call.transaction_hash)), 1, 0),
+        });
+    }
+}
 
 pub struct RuntimeManager {
     logger: Logger,
@@ -11,11 +64,19 @@ pub struct RuntimeManager {
 }
 
 impl RuntimeManager where {
-    /// Creates a new runtime manager.
-    pub fn new<S, T>(logger: &Logger, store: Arc<Mutex<S>>, host_builder: T) -> Self
+    /// Creates a new runtime manager for subgraphs. Subgraphs whose data
+    /// sources don't specify an Ethereum network index `default_network_name`.
+    pub fn new<S, T, B>(
+        logger: &Logger,
+        store: Arc<S>,
+        default_network_name: Option<String>,
+        host_builder: T,
+        block_stream_builder: B,
+    ) -> Self
     where
         S: Store + 'static,
         T: RuntimeHostBuilder,
+        B: BlockStreamBuilder,
     {
         let logger = logger.new(o!("component" => "RuntimeManager"));
 
@@ -23,7 +84,14 @@ impl RuntimeManager where {
         let (subgraph_sender, subgraph_receiver) = channel(100);
 
         // Handle incoming events from the subgraph provider.
-        Self::handle_subgraph_events(logger.clone(), store, host_builder, subgraph_receiver);
+        Self::handle_subgraph_events(
+            logger.clone(),
+            store,
+            default_network_name,
+            host_builder,
+            block_stream_builder,
+            subgraph_receiver,
+        );
 
         RuntimeManager {
             logger,
@@ -32,36 +100,227 @@ impl RuntimeManager where {
     }
 
     /// Handle incoming events from subgraph providers.
-    fn handle_subgraph_events<S, T>(
+    fn handle_subgraph_events<S, T, B>(
         logger: Logger,
-        store: Arc<Mutex<S>>,
-        mut host_builder: T,
+        store: Arc<S>,
+        default_network_name: Option<String>,
+        host_builder: T,
+        block_stream_builder: B,
         receiver: Receiver<SubgraphProviderEvent>,
     ) where
         S: Store + 'static,
         T: RuntimeHostBuilder,
+        B: BlockStreamBuilder,
     {
-        // Handles each incoming event from the subgraph.
-        fn handle_event<S: Store + 'static>(store: Arc<Mutex<S>>, event: RuntimeHostEvent) {
-            match event {
-                RuntimeHostEvent::EntitySet(store_key, entity, event_source) => {
-                    store
-                        .lock()
-                        .unwrap()
-                        .set(store_key, entity, event_source)
-                        .expect("Failed to set entity in the store");
-                }
-                RuntimeHostEvent::EntityRemoved(store_key, event_source) => {
-                    store
-                        .lock()
-                        .unwrap()
-                        .delete(store_key, event_source)
-                        .expect("Failed to delete entity from the store");
+        // Passes the logs and calls of a block to the runtime hosts of a
+        // subgraph, in the order they happened in the block, and calls their
+        // block handlers. The hosts run one at a time and share
+        // an entity cache for the block, so that handlers see the entity
+        // changes of the handlers that ran before them. If the mappings
+        // created new data sources, these are persisted and the block is left
+        // unprocessed so that it can be processed again by a set of runtime
+        // hosts that includes the new data sources; resolves to `true` in
+        // that case. Otherwise, applies the entity changes in the cache and
+        // moves the block pointer of the subgraph to the block in a single
+        // store transaction.
+        fn process_block<S, H>(
+            logger: Logger,
+            store: Arc<S>,
+            hosts: Arc<Vec<H>>,
+            dynamic_data_sources: Arc<Vec<DynamicDataSource>>,
+            subgraph_id: String,
+            block: EthereumBlockWithLogs,
+        ) -> impl Future<Item = bool, Error = Error>
+        where
+            S: Store + 'static,
+            H: RuntimeHost,
+        {
+            debug!(logger, "Process block";
+                   "number" => block.block_ptr.number,
+                   "hash" => format!("{:x}", block.block_ptr.hash),
+                   "logs" => block.logs.len(),
+                   "calls" => block.calls.len());
+
+            let block_ptr = block.block_ptr;
+            let block_header = EthereumBlockHeader::from(&block.block);
+
+            // Process logs and calls in the order they happened in the block
+            let mut triggers = block
+                .logs
+                .into_iter()
+                .map(BlockTrigger::Log)
+                .chain(block.calls.into_iter().map(BlockTrigger::Call))
+                .collect::<Vec<_>>();
+            BlockTrigger::sort(&mut triggers, &block.block);
+
+            let full_block = Arc::new(block.block);
+            let block_hosts = hosts.clone();
+
+            let entity_cache = Arc::new(Mutex::new(EntityCache::new()));
+            let trigger_entity_cache = entity_cache.clone();
+            let block_entity_cache = entity_cache.clone();
+
+            stream::iter_ok::<_, Error>(triggers)
+                .fold(vec![], move |mut events, trigger| {
+                    // Let all runtime hosts with a handler for the trigger
+                    // process it, one after the other, and collect their
+                    // events in the order of the hosts
+                    let results = match trigger {
+                        BlockTrigger::Log(log) => hosts
+                            .iter()
+                            .filter(|host| host.matches_log(&log))
+                            .map(|host| {
+                                host.process_log(
+                                    full_block.clone(),
+                                    log.clone(),
+                                    trigger_entity_cache.clone(),
+                                )
+                            }).collect::<Vec<_>>(),
+                        BlockTrigger::Call(call) => hosts
+                            .iter()
+                            .filter(|host| host.matches_call(&call))
+                            .map(|host| {
+                                host.process_call(call.clone(), trigger_entity_cache.clone())
+                            }).collect::<Vec<_>>(),
+                    };
+
+                    stream::iter_ok(results)
+                        .and_then(|result| result)
+                        .concat2()
+                        .map(move |host_events| {
+                            events.extend(host_events);
+                            events
+                        })
+                }).and_then(move |mut events| {
+                    // Call the block handlers after all logs and calls of the
+                    // block have been processed
+                    let results = block_hosts
+                        .iter()
+                        .filter(|host| host.has_block_handlers())
+                        .map(|host| {
+                            host.process_block(block_header.clone(), block_entity_cache.clone())
+                        }).collect::<Vec<_>>();
+
+                    stream::iter_ok(results)
+                        .and_then(|result| result)
+                        .concat2()
+                        .map(move |host_events| {
+                            events.extend(host_events);
+                            events
+                        })
+                }).and_then(move |events| {
+                    // Collect the data sources that don't exist yet
+                    let mut created_data_sources = vec![];
+                    for event in events {
+                        match event {
+                            RuntimeHostEvent::DataSourceCreated(data_source) => {
+                                if !dynamic_data_sources.contains(&data_source)
+                                    && !created_data_sources.contains(&data_source)
+                                {
+                                    created_data_sources.push(data_source)
+                                }
+                            }
+                        }
+                    }
+
+                    if created_data_sources.is_empty() {
+                        let operations = entity_cache.lock().unwrap().take_operations();
+                        store
+                            .transact_block_operations(subgraph_id, block_ptr, operations)
+                            .map(|_| false)
+                    } else {
+                        info!(logger, "Mappings created new data sources, reprocess block";
+                              "number" => block_ptr.number,
+                              "hash" => format!("{:x}", block_ptr.hash),
+                              "data_sources" => created_data_sources.len());
+
+                        store
+                            .add_dynamic_data_sources(subgraph_id, block_ptr, created_data_sources)
+                            .map(|_| true)
+                    }
+                })
+        }
+
+        // Runs the runtime hosts of a subgraph, including those for the data
+        // sources its mappings created so far, against the blocks of its
+        // block stream. Resolves to `Loop::Continue` as soon as the mappings
+        // create new data sources, so the subgraph can be restarted with
+        // runtime hosts for them, and to `Loop::Break` when the block stream
+        // ends. A block whose mappings failed because a host call timed out
+        // also restarts the subgraph, which processes the block again.
+        // Fails at the first block that cannot be processed.
+        fn run_subgraph<S, T, B>(
+            logger: Logger,
+            store: Arc<S>,
+            manifest: SubgraphManifest,
+            mut host_builder: T,
+            block_stream_builder: B,
+        ) -> impl Future<Item = future::Loop<(), ()>, Error = SubgraphError>
+        where
+            S: Store + 'static,
+            T: RuntimeHostBuilder,
+            B: BlockStreamBuilder,
+        {
+            let subgraph_id = manifest.id.clone();
+            let timeout_logger = logger.clone();
+
+            future::result(store.dynamic_data_sources(subgraph_id.clone()).and_then(
+                |dynamic_data_sources| {
+                    let manifest = manifest.with_dynamic_data_sources(&dynamic_data_sources)?;
+
+                    // Create a new runtime host for each data source in the
+                    // subgraph manifest, including the dynamic ones
+                    let hosts = manifest
+                        .data_sources
+                        .iter()
+                        .map(|d| host_builder.build(manifest.clone(), d.clone()))
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    let block_stream =
+                        block_stream_builder.from_subgraph(&manifest, logger.clone())?;
+
+                    Ok((
+                        block_stream,
+                        Arc::new(hosts),
+                        Arc::new(dynamic_data_sources),
+                    ))
+                },
+            )).map_err(SubgraphError::from)
+            .and_then(move |(block_stream, hosts, dynamic_data_sources)| {
+                block_stream
+                    .map_err(SubgraphError::from)
+                    .and_then(move |block| {
+                        let block_ptr = block.block_ptr;
+                        process_block(
+                            logger.clone(),
+                            store.clone(),
+                            hosts.clone(),
+                            dynamic_data_sources.clone(),
+                            subgraph_id.clone(),
+                            block,
+                        ).map_err(move |error| SubgraphError {
+                            error,
+                            block_ptr: Some(block_ptr),
+                        })
+                    }).filter(|created_data_sources| *created_data_sources)
+                    .into_future()
+                    .map(|(restart, _)| match restart {
+                        Some(_) => future::Loop::Continue(()),
+                        None => future::Loop::Break(()),
+                    }).map_err(|(e, _)| e)
+            }).or_else(move |e| {
+                if e.error.downcast_ref::<HostCallTimeoutError>().is_none() {
+                    return Err(e);
                 }
-            }
+                warn!(timeout_logger, "Host call timed out, process block again";
+                      "block_number" => e.block_ptr.map(|ptr| ptr.number),
+                      "error" => format!("{}", e.error));
+                Ok(future::Loop::Continue(()))
+            })
         }
 
-        let mut runtime_hosts = vec![];
+        // Cancelation guards for the block streams of all running subgraphs
+        let mut block_stream_guards = HashMap::new();
 
         tokio::spawn(receiver.for_each(move |event| {
             match event {
@@ -69,31 +328,116 @@ impl RuntimeManager where {
                     info!(logger, "Host mapping runtimes for subgraph";
                           "location" => &manifest.location);
 
-                    // Create a new runtime host for each data source in the subgraph manifest
-                    let mut new_hosts = manifest
-                        .data_sources
-                        .iter()
-                        .map(|d| host_builder.build(manifest.clone(), d.clone()));
-
-                    // Forward events from the runtime host to the store; this
-                    // Tokio task will terminate when the corresponding subgraph
-                    // is removed and the host and its event sender are dropped
-                    for mut new_host in new_hosts {
-                        let store = store.clone();
-                        tokio::spawn(new_host.take_event_stream().unwrap().for_each(
-                            move |event| {
-                                handle_event(store.clone(), event);
-                                Ok(())
-                            },
-                        ));
-                        // Add the new host to the list of managed runtime hosts
-                        runtime_hosts.push(new_host);
+                    let location = manifest.location.clone();
+                    let subgraph_id = manifest.id.clone();
+                    let (manifest, network_name) = match manifest
+                        .with_default_network(default_network_name.as_ref().map(String::as_str))
+                        .and_then(|manifest| {
+                            let network_name = manifest.network_name()?;
+                            Ok((manifest, network_name))
+                        }) {
+                        Ok(result) => result,
+                        Err(e) => {
+                            error!(logger, "Failed to determine Ethereum network of subgraph";
+                                   "location" => &location,
+                                   "error" => format!("{}", e));
+
+                            let health = SubgraphHealth::Failed {
+                                error: e.to_string(),
+                                block_ptr: None,
+                            };
+                            if let Err(e) = store.set_subgraph_health(subgraph_id, health) {
+                                error!(logger, "Failed to mark subgraph as failed";
+                                       "error" => format!("{}", e));
+                            }
+                            return Ok(());
+                        }
+                    };
+
+                    // Make sure the subgraph has an entry for its block pointer
+                    // in the store before its block stream tries to read it
+                    if let Err(e) = store.add_subgraph_if_missing(manifest.id.clone(), network_name)
+                    {
+                        error!(logger, "Failed to add subgraph to the store";
+                               "location" => &manifest.location,
+                               "error" => format!("{}", e));
+                        return Ok(());
                     }
+
+                    // Create the tables for the entities of the subgraph
+                    if let Err(e) =
+                        store.create_entity_tables(manifest.id.clone(), &manifest.schema)
+                    {
+                        error!(logger, "Failed to create the entity tables of the subgraph";
+                               "location" => &manifest.location,
+                               "error" => format!("{}", e));
+
+                        // Report the subgraph as failed instead of leaving
+                        // it in its previous state
+                        let health = SubgraphHealth::Failed {
+                            error: e.to_string(),
+                            block_ptr: None,
+                        };
+                        if let Err(e) = store.set_subgraph_health(manifest.id.clone(), health) {
+                            error!(logger, "Failed to mark subgraph as failed";
+                                   "error" => format!("{}", e));
+                        }
+                        return Ok(());
+                    }
+
+                    // Create channel as cancelation guard for the block stream
+                    let (cancel_sender, cancel_receiver) = oneshot::channel();
+
+                    // Process the blocks of the subgraph one at a time,
+                    // restarting whenever its mappings create new data
+                    // sources; this Tokio task will terminate when the
+                    // corresponding subgraph is removed and the cancelation
+                    // guard is dropped, which also drops the runtime hosts of
+                    // the subgraph
+                    let subgraph_logger = logger.clone();
+                    let error_logger = logger.clone();
+                    let error_store = store.clone();
+                    let subgraph_id = manifest.id.clone();
+                    let store = store.clone();
+                    let subgraph_manifest = manifest.clone();
+                    let host_builder = host_builder.clone();
+                    let block_stream_builder = block_stream_builder.clone();
+                    tokio::spawn(
+                        future::loop_fn((), move |()| {
+                            run_subgraph(
+                                subgraph_logger.clone(),
+                                store.clone(),
+                                subgraph_manifest.clone(),
+                                host_builder.clone(),
+                                block_stream_builder.clone(),
+                            )
+                        }).select(cancel_receiver.then(|_| Ok(())))
+                        .map(|_| ())
+                        .map_err(move |(e, _)| {
+                            // Stop the subgraph at the failed block; other
+                            // subgraphs are not affected
+                            error!(error_logger, "Subgraph failed, stopping it";
+                                   "subgraph_id" => &subgraph_id,
+                                   "block_number" => e.block_ptr.map(|ptr| ptr.number),
+                                   "error" => format!("{}", e.error));
+
+                            let health = SubgraphHealth::Failed {
+                                error: e.error.to_string(),
+                                block_ptr: e.block_ptr,
+                            };
+                            if let Err(e) = error_store.set_subgraph_health(subgraph_id, health) {
+                                error!(error_logger, "Failed to mark subgraph as failed";
+                                       "error" => format!("{}", e));
+                            }
+                        }),
+                    );
+
+                    block_stream_guards.insert(manifest.id, cancel_sender);
                 }
                 SubgraphProviderEvent::SubgraphRemoved(id) => {
-                    // Destroy all runtime hosts for this subgraph; this will
-                    // also terminate the host's event stream
-                    runtime_hosts.retain(|host| host.subgraph_manifest().id != id);
+                    // Drop the cancelation guard of the subgraph; this will
+                    // stop its block stream and destroy its runtime hosts
+                    block_stream_guards.remove(&id);
                 }
             }
 
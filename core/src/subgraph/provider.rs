@@ -1,41 +1,147 @@
-use std::collections::BTreeMap;
-use std::sync::Mutex;
-
 use futures::sync::mpsc::{channel, Receiver, Sender};
 
 use graph::prelude::{SubgraphProvider as SubgraphProviderTrait, *};
 use graph_graphql::prelude::validate_schema;
 
-pub struct SubgraphProvider<L> {
-    _logger: slog::Logger,
+pub struct SubgraphProvider<L, S> {
+    logger: slog::Logger,
     event_stream: Option<Receiver<SubgraphProviderEvent>>,
     event_sink: Sender<SubgraphProviderEvent>,
     schema_event_stream: Option<Receiver<SchemaEvent>>,
     schema_event_sink: Sender<SchemaEvent>,
     resolver: Arc<L>,
-    // Maps subgraph name to id.
-    subgraphs: Arc<Mutex<BTreeMap<String, String>>>,
+    // Keeps track of the subgraph deployed under each name.
+    store: Arc<S>,
 }
 
-impl<L: LinkResolver> SubgraphProvider<L> {
-    pub fn new(logger: slog::Logger, resolver: Arc<L>) -> Self {
+impl<L, S> SubgraphProvider<L, S>
+where
+    L: LinkResolver,
+    S: SubgraphDeploymentStore + 'static,
+{
+    pub fn new(logger: slog::Logger, resolver: Arc<L>, store: Arc<S>) -> Self {
         let (schema_event_sink, schema_event_stream) = channel(100);
         let (event_sink, event_stream) = channel(100);
 
         // Create the subgraph provider
         let provider = SubgraphProvider {
-            _logger: logger.new(o!("component" => "SubgraphProvider")),
+            logger: logger.new(o!("component" => "SubgraphProvider")),
             event_stream: Some(event_stream),
             event_sink,
             schema_event_stream: Some(schema_event_stream),
             schema_event_sink,
             resolver,
-            subgraphs: Arc::new(Mutex::new(BTreeMap::new())),
+            store,
         };
 
         provider
     }
 
+    /// Resolves the subgraphs deployed in the store and starts them again,
+    /// e.g. after the node was restarted.
+    ///
+    /// Subgraphs that can no longer be resolved are marked as failed and
+    /// don't prevent the other subgraphs from being started.
+    pub fn start_deployed_subgraphs(
+        &self,
+    ) -> impl Future<Item = (), Error = SubgraphProviderError> + Send + 'static {
+        let self_clone = self.clone();
+        self.store
+            .subgraph_deployments()
+            .map_err(SubgraphProviderError::StoreError)
+            .into_future()
+            .and_then(move |deployments| {
+                stream::iter_ok(deployments)
+                    .for_each(move |deployment| self_clone.start_deployment(deployment))
+            })
+    }
+
+    /// Starts a subgraph deployment from the store and updates its status
+    /// depending on whether it could be resolved.
+    fn start_deployment(
+        &self,
+        deployment: SubgraphDeployment,
+    ) -> Box<Future<Item = (), Error = SubgraphProviderError> + Send + 'static> {
+        let self_clone = self.clone();
+        let name = deployment.name;
+
+        info!(self.logger, "Redeploy subgraph";
+              "name" => &name,
+              "id" => &deployment.subgraph_id);
+
+        Box::new(
+            self.resolve(name.clone(), format!("/ipfs/{}", deployment.subgraph_id))
+                .then(move |result| {
+                    let status = match result {
+                        Ok(_) => SubgraphDeploymentStatus::Deployed,
+                        Err(ref e) => {
+                            error!(self_clone.logger, "Failed to redeploy subgraph";
+                                   "name" => &name,
+                                   "error" => e.to_string());
+                            SubgraphDeploymentStatus::Failed
+                        }
+                    };
+
+                    self_clone
+                        .store
+                        .set_subgraph_deployment_status(name, status)
+                        .map_err(SubgraphProviderError::StoreError)
+                        .into_future()
+                        .and_then(move |_| match result {
+                            Ok(subgraph) => Box::new(self_clone.send_add_events(subgraph))
+                                as Box<Future<Item = _, Error = _> + Send + 'static>,
+                            Err(_) => Box::new(future::ok(())),
+                        })
+                }),
+        )
+    }
+
+    /// Resolves the subgraph manifest behind `link` and validates its schema.
+    fn resolve(
+        &self,
+        name: String,
+        link: String,
+    ) -> impl Future<Item = SubgraphManifest, Error = SubgraphProviderError> + Send + 'static {
+        SubgraphManifest::resolve(name, Link { link }, self.resolver.clone())
+            .map_err(SubgraphProviderError::ResolveError)
+            .and_then(
+                // Validate the subgraph schema before deploying the subgraph
+                |subgraph| match validate_schema(&subgraph.schema.document) {
+                    Err(e) => Err(SubgraphProviderError::SchemaValidationError(e)),
+                    _ => Ok(subgraph),
+                },
+            ).and_then(
+                // Make sure the data sources agree on the Ethereum network;
+                // those that don't specify one index the default network
+                |subgraph| match subgraph.specified_network_name() {
+                    Err(e) => Err(SubgraphProviderError::NetworkError(e)),
+                    _ => Ok(subgraph),
+                },
+            ).map(|mut subgraph| {
+                subgraph
+                    .schema
+                    .add_subgraph_id_directives(subgraph.id.clone());
+                subgraph
+            })
+    }
+
+    fn send_add_events(
+        &self,
+        subgraph: SubgraphManifest,
+    ) -> impl Future<Item = (), Error = SubgraphProviderError> + Send + 'static {
+        // Push the subgraph and the schema into their streams
+        self.schema_event_sink
+            .clone()
+            .send(SchemaEvent::SchemaAdded(subgraph.schema.clone()))
+            .map_err(|e| panic!("failed to forward subgraph schema: {}", e))
+            .join(
+                self.event_sink
+                    .clone()
+                    .send(SubgraphProviderEvent::SubgraphAdded(subgraph))
+                    .map_err(|e| panic!("failed to forward subgraph: {}", e)),
+            ).map(|_| ())
+    }
+
     fn send_remove_events(
         &self,
         name: String,
@@ -58,113 +164,114 @@ impl<L: LinkResolver> SubgraphProvider<L> {
     /// Clones but forcing receivers to `None`.
     fn clone(&self) -> Self {
         SubgraphProvider {
-            _logger: self._logger.clone(),
+            logger: self.logger.clone(),
             event_stream: None,
             event_sink: self.event_sink.clone(),
             schema_event_stream: None,
             schema_event_sink: self.schema_event_sink.clone(),
             resolver: self.resolver.clone(),
-            subgraphs: self.subgraphs.clone(),
+            store: self.store.clone(),
         }
     }
 }
 
-impl<L: LinkResolver> SubgraphProviderTrait for SubgraphProvider<L> {
+impl<L, S> SubgraphProviderTrait for SubgraphProvider<L, S>
+where
+    L: LinkResolver,
+    S: SubgraphDeploymentStore + 'static,
+{
     fn deploy(
         &self,
         name: String,
         link: String,
     ) -> Box<Future<Item = (), Error = SubgraphProviderError> + Send + 'static> {
-        // Check that the name contains only allowed characters.
-        if !name
-            .chars()
-            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        // Check that the name contains only allowed characters. Names that
+        // start with `_` are reserved for built-in endpoints, such as the
+        // indexing status API.
+        if name.starts_with('_')
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
         {
             return Box::new(Err(SubgraphProviderError::InvalidName(name)).into_future());
         }
 
         let self_clone = self.clone();
-        Box::new(
-            SubgraphManifest::resolve(name.clone(), Link { link }, self_clone.resolver.clone())
-                .map_err(SubgraphProviderError::ResolveError)
-                .and_then(
-                    // Validate the subgraph schema before deploying the subgraph
-                    |subgraph| match validate_schema(&subgraph.schema.document) {
-                        Err(e) => Err(SubgraphProviderError::SchemaValidationError(e)),
-                        _ => Ok(subgraph),
-                    },
-                ).and_then(move |mut subgraph| {
-                    subgraph
-                        .schema
-                        .add_subgraph_id_directives(subgraph.id.clone());
-
-                    let old_id = self_clone
-                        .subgraphs
-                        .lock()
-                        .unwrap()
-                        .insert(name.clone(), subgraph.id.clone());
-
+        Box::new(self.resolve(name.clone(), link).and_then(move |subgraph| {
+            self_clone
+                .store
+                .upsert_subgraph_deployment(name.clone(), subgraph.id.clone())
+                .map_err(SubgraphProviderError::StoreError)
+                .into_future()
+                .and_then(move |old_deployment| {
                     // If a subgraph is being updated, remove the old subgraph.
-                    if let Some(id) = old_id {
-                        Box::new(self_clone.send_remove_events(name, id))
-                    } else {
-                        Box::new(future::ok(()))
-                            as Box<Future<Item = _, Error = _> + Send + 'static>
-                    }.and_then(move |_| {
-                        // Push the subgraph and the schema into their streams
-                        self_clone
-                            .schema_event_sink
-                            .clone()
-                            .send(SchemaEvent::SchemaAdded(subgraph.schema.clone()))
-                            .map_err(|e| panic!("failed to forward subgraph schema: {}", e))
-                            .join(
-                                self_clone
-                                    .event_sink
-                                    .clone()
-                                    .send(SubgraphProviderEvent::SubgraphAdded(subgraph))
-                                    .map_err(|e| panic!("failed to forward subgraph: {}", e)),
-                            ).map(|_| ())
-                    })
-                }),
-        )
+                    // Subgraphs that failed to redeploy were never started, so
+                    // there is nothing to remove for them.
+                    match old_deployment {
+                        Some(ref old) if old.status == SubgraphDeploymentStatus::Deployed => {
+                            Box::new(self_clone.send_remove_events(name, old.subgraph_id.clone()))
+                                as Box<Future<Item = _, Error = _> + Send + 'static>
+                        }
+                        _ => Box::new(future::ok(())),
+                    }.and_then(move |_| self_clone.send_add_events(subgraph))
+                })
+        }))
     }
 
     fn remove(
         &self,
         name_or_id: String,
     ) -> Box<Future<Item = (), Error = SubgraphProviderError> + Send + 'static> {
-        let mut subgraphs = self.subgraphs.lock().unwrap();
+        let deployments = match self.store.subgraph_deployments() {
+            Ok(deployments) => deployments,
+            Err(e) => return Box::new(future::err(SubgraphProviderError::StoreError(e))),
+        };
 
         // Either `name_or_id` is a name,
-        let name = if subgraphs.contains_key(&name_or_id) {
-            name_or_id
-        // or it's an id, so we get the corresponding name.
-        } else if let Some(name) = subgraphs.keys().find(|&name| subgraphs[name] == name_or_id) {
-            name.to_owned()
-        // Otherwise the subgraph is not hosted.
-        } else {
-            return Box::new(future::err(SubgraphProviderError::NotFound(
-                name_or_id.to_owned(),
-            )));
+        let deployment = match deployments
+            .iter()
+            .find(|deployment| deployment.name == name_or_id)
+            // or it's an id, so we get the corresponding name.
+            .or_else(|| {
+                deployments
+                    .iter()
+                    .find(|deployment| deployment.subgraph_id == name_or_id)
+            }) {
+            Some(deployment) => deployment.clone(),
+            // Otherwise the subgraph is not hosted.
+            None => return Box::new(future::err(SubgraphProviderError::NotFound(name_or_id))),
         };
 
-        // Remove the subgraph and signal the removal to the graphql server and
-        // the runtime manager.
-        let id = subgraphs.remove(&name).unwrap();
-        Box::new(self.send_remove_events(name, id))
+        if let Err(e) = self
+            .store
+            .remove_subgraph_deployment(deployment.name.clone())
+        {
+            return Box::new(future::err(SubgraphProviderError::StoreError(e)));
+        }
+
+        // Signal the removal to the graphql server and the runtime manager,
+        // unless the subgraph was never started.
+        match deployment.status {
+            SubgraphDeploymentStatus::Deployed => {
+                Box::new(self.send_remove_events(deployment.name, deployment.subgraph_id))
+            }
+            SubgraphDeploymentStatus::Failed => Box::new(future::ok(())),
+        }
     }
 
-    fn list(&self) -> Vec<(String, String)> {
-        self.subgraphs
-            .lock()
-            .unwrap()
-            .iter()
-            .map(|(name, id)| (name.clone(), id.clone()))
-            .collect()
+    fn list(&self) -> Result<Vec<(String, String)>, SubgraphProviderError> {
+        self.store
+            .subgraph_deployments()
+            .map(|deployments| {
+                deployments
+                    .into_iter()
+                    .map(|deployment| (deployment.name, deployment.subgraph_id))
+                    .collect()
+            }).map_err(SubgraphProviderError::StoreError)
     }
 }
 
-impl<L> EventProducer<SubgraphProviderEvent> for SubgraphProvider<L> {
+impl<L, S> EventProducer<SubgraphProviderEvent> for SubgraphProvider<L, S> {
     fn take_event_stream(
         &mut self,
     ) -> Option<Box<Stream<Item = SubgraphProviderEvent, Error = ()> + Send>> {
@@ -174,7 +281,7 @@ impl<L> EventProducer<SubgraphProviderEvent> for SubgraphProvider<L> {
     }
 }
 
-impl<L> EventProducer<SchemaEvent> for SubgraphProvider<L> {
+impl<L, S> EventProducer<SchemaEvent> for SubgraphProvider<L, S> {
     fn take_event_stream(&mut self) -> Option<Box<Stream<Item = SchemaEvent, Error = ()> + Send>> {
         self.schema_event_stream
             .take()
@@ -193,8 +300,41 @@ fn rejects_name_bad_for_urls() {
             unimplemented!()
         }
     }
+
+    struct FakeDeploymentStore;
+
+    impl SubgraphDeploymentStore for FakeDeploymentStore {
+        fn upsert_subgraph_deployment(
+            &self,
+            _: String,
+            _: String,
+        ) -> Result<Option<SubgraphDeployment>, failure::Error> {
+            unimplemented!()
+        }
+
+        fn set_subgraph_deployment_status(
+            &self,
+            _: String,
+            _: SubgraphDeploymentStatus,
+        ) -> Result<(), failure::Error> {
+            unimplemented!()
+        }
+
+        fn remove_subgraph_deployment(&self, _: String) -> Result<(), failure::Error> {
+            unimplemented!()
+        }
+
+        fn subgraph_deployments(&self) -> Result<Vec<SubgraphDeployment>, failure::Error> {
+            unimplemented!()
+        }
+    }
+
     let logger = slog::Logger::root(slog::Discard, o!());
-    let provider = Arc::new(SubgraphProvider::new(logger, Arc::new(FakeLinkResolver)));
+    let provider = Arc::new(SubgraphProvider::new(
+        logger,
+        Arc::new(FakeLinkResolver),
+        Arc::new(FakeDeploymentStore),
+    ));
     let bad = "/../funky%2F:9001".to_owned();
     let result = provider.deploy(bad.clone(), "".to_owned());
     match result.wait() {
@@ -9,17 +9,21 @@ extern crate walkdir;
 use ipfs_api::IpfsClient;
 use walkdir::WalkDir;
 
+use std::collections::HashMap;
 use std::fs::read_to_string;
 use std::io::Cursor;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 use std::time::Duration;
 use std::time::Instant;
 
 use graph::components::ethereum::*;
 use graph::prelude::*;
+use graph::util::ethereum::string_to_h256;
+use graph::web3::types::{Block, Bytes, Log, Transaction, H160, H2048, H256, U128, U256};
 use graph_core::RuntimeManager;
-use graph_mock::FakeStore;
-use graph_runtime_wasm::RuntimeHostBuilder;
+use graph_mock::MockStore;
+use graph_runtime_wasm::{MappingLimits, RuntimeHostBuilder};
 
 /// Adds subgraph located in `test/subgraphs/`, replacing "link to" placeholders
 /// in the subgraph manifest with links to files just added into a local IPFS
@@ -64,31 +68,163 @@ fn add_subgraph_to_ipfs(
 
 #[test]
 fn multiple_data_sources_per_subgraph() {
-    struct MockEthereumAdapter {
-        received_subscriptions: Vec<String>,
-    }
+    struct MockEthereumAdapter;
 
     impl EthereumAdapter for MockEthereumAdapter {
         fn contract_call(
             &mut self,
             _request: EthereumContractCall,
-        ) -> Box<Future<Item = Vec<ethabi::Token>, Error = EthereumContractCallError>> {
+        ) -> Box<Future<Item = Vec<ethabi::Token>, Error = EthereumContractCallError> + Send>
+        {
+            unimplemented!()
+        }
+
+        fn logs_in_block_range(
+            &mut self,
+            _from: u64,
+            _to: u64,
+            _log_filter: EthereumLogFilter,
+        ) -> Box<Future<Item = Vec<Log>, Error = Error> + Send> {
             unimplemented!()
         }
 
-        fn subscribe_to_event(
+        fn calls_in_block_range(
             &mut self,
-            subscription: EthereumEventSubscription,
-        ) -> Box<Stream<Item = EthereumEvent, Error = EthereumSubscriptionError>> {
-            self.received_subscriptions.push(subscription.event.name);
-            Box::new(stream::iter_ok(vec![]))
+            _from: u64,
+            _to: u64,
+            _call_filter: EthereumCallFilter,
+        ) -> Box<Future<Item = Vec<EthereumCall>, Error = Error> + Send> {
+            unimplemented!()
         }
 
-        fn unsubscribe_from_event(&mut self, _subscription_id: String) -> bool {
+        fn block_ptr_by_number(
+            &mut self,
+            _number: u64,
+        ) -> Box<Future<Item = EthereumBlockPointer, Error = Error> + Send> {
+            unimplemented!()
+        }
+
+        fn block_by_number(
+            &mut self,
+            _number: u64,
+        ) -> Box<Future<Item = Block<Transaction>, Error = Error> + Send> {
             unimplemented!()
         }
     }
 
+    struct MockBlockStream {
+        blocks: Box<Stream<Item = EthereumBlockWithLogs, Error = Error> + Send>,
+    }
+
+    impl Stream for MockBlockStream {
+        type Item = EthereumBlockWithLogs;
+        type Error = Error;
+
+        fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+            self.blocks.poll()
+        }
+    }
+
+    impl BlockStream for MockBlockStream {}
+
+    /// Creates block streams that emit a single block with one log for each
+    /// data source of the subgraph.
+    #[derive(Clone)]
+    struct MockBlockStreamBuilder {
+        log_filters: Arc<Mutex<Vec<EthereumLogFilter>>>,
+        block_processed: Arc<AtomicBool>,
+    }
+
+    impl BlockStreamBuilder for MockBlockStreamBuilder {
+        type Stream = MockBlockStream;
+
+        fn from_subgraph(
+            &self,
+            manifest: &SubgraphManifest,
+            _logger: Logger,
+        ) -> Result<Self::Stream, Error> {
+            let log_filters = manifest
+                .data_sources
+                .iter()
+                .map(EthereumLogFilter::from_data_source)
+                .collect::<Vec<_>>();
+            self.log_filters
+                .lock()
+                .unwrap()
+                .extend(log_filters.iter().cloned());
+
+            let block_ptr = EthereumBlockPointer::from((H256::from(1), 1u64));
+            let transaction = Transaction {
+                hash: H256::from(3),
+                nonce: U256::from(0),
+                block_hash: Some(block_ptr.hash),
+                block_number: Some(block_ptr.number.into()),
+                transaction_index: Some(U128::from(0)),
+                from: H160::default(),
+                to: None,
+                value: U256::from(0),
+                gas_price: U256::from(1),
+                gas: U256::from(100_000),
+                input: Bytes(vec![]),
+            };
+            let logs = log_filters
+                .into_iter()
+                .map(|log_filter| Log {
+                    address: *log_filter.contract_addresses.iter().next().unwrap(),
+                    topics: vec![
+                        *log_filter.event_signatures.iter().next().unwrap(),
+                        H256::from(2),
+                    ],
+                    data: Bytes(vec![]),
+                    block_hash: Some(block_ptr.hash),
+                    block_number: Some(block_ptr.number.into()),
+                    transaction_hash: Some(transaction.hash),
+                    transaction_index: transaction.transaction_index,
+                    log_index: None,
+                    transaction_log_index: None,
+                    log_type: None,
+                    removed: None,
+                }).collect();
+
+            // The stream is only polled again after the block has been
+            // processed; it never ends, just like a real block stream
+            let block_processed = self.block_processed.clone();
+            Ok(MockBlockStream {
+                blocks: Box::new(
+                    stream::iter_ok(vec![EthereumBlockWithLogs {
+                        block_ptr,
+                        block: Block {
+                            hash: Some(block_ptr.hash),
+                            parent_hash: H256::default(),
+                            uncles_hash: H256::default(),
+                            author: H160::default(),
+                            state_root: H256::default(),
+                            transactions_root: H256::default(),
+                            receipts_root: H256::default(),
+                            number: Some(U128::from(block_ptr.number)),
+                            gas_used: U256::from(0),
+                            gas_limit: U256::from(1_000_000),
+                            extra_data: Bytes(vec![]),
+                            logs_bloom: H2048::default(),
+                            timestamp: U256::from(1_538_000_000),
+                            difficulty: U256::from(1),
+                            total_difficulty: U256::from(1),
+                            seal_fields: vec![],
+                            uncles: vec![],
+                            transactions: vec![transaction],
+                            size: None,
+                        },
+                        logs,
+                        calls: vec![],
+                    }]).chain(stream::poll_fn(move || {
+                        block_processed.store(true, Ordering::SeqCst);
+                        Ok(Async::NotReady)
+                    })),
+                ),
+            })
+        }
+    }
+
     let mut runtime = tokio::runtime::Runtime::new().unwrap();
 
     let subgraph_link = runtime
@@ -96,21 +232,37 @@ fn multiple_data_sources_per_subgraph() {
             add_subgraph_to_ipfs(Arc::new(IpfsClient::default()), "two-datasources")
         })).unwrap();
 
+    let block_stream_builder = MockBlockStreamBuilder {
+        log_filters: Arc::new(Mutex::new(vec![])),
+        block_processed: Arc::new(AtomicBool::new(false)),
+    };
+    let log_filters = block_stream_builder.log_filters.clone();
+    let block_processed = block_stream_builder.block_processed.clone();
+
     runtime
         .block_on(future::lazy(|| {
             let resolver = Arc::new(IpfsClient::default());
             let logger = Logger::root(slog::Discard, o!());
-            let eth_adapter = Arc::new(Mutex::new(MockEthereumAdapter {
-                received_subscriptions: vec![],
-            }));
-            let fake_store = Arc::new(Mutex::new(FakeStore));
+            let mut eth_adapters = HashMap::new();
+            eth_adapters.insert(
+                "mainnet".to_owned(),
+                Arc::new(Mutex::new(MockEthereumAdapter)),
+            );
+            let store = Arc::new(MockStore::new());
             let host_builder = RuntimeHostBuilder::new(
                 &logger,
-                eth_adapter.clone(),
+                eth_adapters,
                 resolver.clone(),
-                fake_store.clone(),
+                store.clone(),
+                MappingLimits::default(),
+            );
+            let manager = RuntimeManager::new(
+                &logger,
+                store,
+                Some("mainnet".to_owned()),
+                host_builder,
+                block_stream_builder,
             );
-            let manager = RuntimeManager::new(&logger, fake_store, host_builder);
 
             // Load a subgraph with two data sets, one listening for `ExampleEvent`
             // and the other for `ExampleEvent2`.
@@ -127,22 +279,28 @@ fn multiple_data_sources_per_subgraph() {
                     .event_sink()
                     .send(SubgraphProviderEvent::SubgraphAdded(subgraph))
             }).and_then(move |_| {
-                // If we subscribed to both events, then we're handling multiple data sets.
-                // Wait for thirty seconds for that to happen, otherwise fail the test.
+                // If the logs of both events were processed, then we're handling
+                // multiple data sets. Wait for thirty seconds for that to happen,
+                // otherwise fail the test.
                 let start_time = Instant::now();
                 let max_wait = Duration::from_secs(30);
-                loop {
-                    let subscriptions = &eth_adapter.lock().unwrap().received_subscriptions;
-                    if subscriptions.contains(&"ExampleEvent".to_owned())
-                        && subscriptions.contains(&"ExampleEvent2".to_owned())
-                    {
-                        break;
-                    }
+                while !block_processed.load(Ordering::SeqCst) {
                     if Instant::now().duration_since(start_time) > max_wait {
-                        panic!("Test failed, events subscribed to: {:?}", subscriptions)
+                        panic!("Test failed, block was not processed")
                     }
                     ::std::thread::yield_now();
                 }
+
+                let event_signatures = log_filters
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .cloned()
+                    .collect::<EthereumLogFilter>()
+                    .event_signatures;
+                assert_eq!(event_signatures.len(), 2);
+                assert!(event_signatures.contains(&string_to_h256("ExampleEvent(string)")));
+                assert!(event_signatures.contains(&string_to_h256("ExampleEvent2(string)")));
                 Ok(())
             })
         })).unwrap();
@@ -166,7 +324,11 @@ fn added_schema_name_and_id(event: &SchemaEvent) -> (&str, &str) {
 fn subgraph_provider_events() {
     let mut runtime = tokio::runtime::Runtime::new().unwrap();
     let logger = Logger::root(slog::Discard, o!());
-    let mut provider = graph_core::SubgraphProvider::new(logger, Arc::new(IpfsClient::default()));
+    let mut provider = graph_core::SubgraphProvider::new(
+        logger,
+        Arc::new(IpfsClient::default()),
+        Arc::new(MockStore::new()),
+    );
     let provider_events = provider.take_event_stream().unwrap();
     let schema_events = provider.take_event_stream().unwrap();
     let provider = Arc::new(provider);
@@ -252,6 +414,7 @@ fn subgraph_list() {
     let provider = Arc::new(graph_core::SubgraphProvider::new(
         logger,
         Arc::new(IpfsClient::default()),
+        Arc::new(MockStore::new()),
     ));
 
     let (subgraph1_link, subgraph2_link) = runtime
@@ -263,7 +426,7 @@ fn subgraph_list() {
     let subgraph1_id = subgraph1_link.trim_left_matches("/ipfs/").to_owned();
     let subgraph2_id = subgraph2_link.trim_left_matches("/ipfs/").to_owned();
 
-    assert!(provider.list().is_empty());
+    assert!(provider.list().unwrap().is_empty());
     runtime
         .block_on(provider.deploy("subgraph1".to_owned(), subgraph1_link.clone()))
         .unwrap();
@@ -271,7 +434,7 @@ fn subgraph_list() {
         .block_on(provider.deploy("subgraph2".to_owned(), subgraph2_link.clone()))
         .unwrap();
     assert_eq!(
-        provider.list(),
+        provider.list().unwrap(),
         [
             ("subgraph1".to_owned(), subgraph1_id),
             ("subgraph2".to_owned(), subgraph2_id.clone())
@@ -280,9 +443,89 @@ fn subgraph_list() {
     runtime
         .block_on(provider.remove("subgraph1".to_owned()))
         .unwrap();
-    assert_eq!(provider.list(), [("subgraph2".to_owned(), subgraph2_id)]);
+    assert_eq!(
+        provider.list().unwrap(),
+        [("subgraph2".to_owned(), subgraph2_id)]
+    );
     runtime
         .block_on(provider.remove("subgraph2".to_owned()))
         .unwrap();
-    assert!(provider.list().is_empty());
+    assert!(provider.list().unwrap().is_empty());
+}
+
+#[test]
+fn deployed_subgraphs_are_started_by_a_new_provider() {
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let logger = Logger::root(slog::Discard, o!());
+    let store = Arc::new(MockStore::new());
+
+    let subgraph_link = runtime
+        .block_on(future::lazy(|| {
+            add_subgraph_to_ipfs(Arc::new(IpfsClient::default()), "two-datasources")
+        })).unwrap();
+    let subgraph_id = subgraph_link.trim_left_matches("/ipfs/");
+
+    // Deploy the subgraph with one provider
+    let provider = graph_core::SubgraphProvider::new(
+        logger.clone(),
+        Arc::new(IpfsClient::default()),
+        store.clone(),
+    );
+    runtime
+        .block_on(provider.deploy("subgraph".to_owned(), subgraph_link.clone()))
+        .unwrap();
+    drop(provider);
+
+    // Start the deployed subgraphs with another provider, as if the node
+    // had been restarted
+    let mut provider =
+        graph_core::SubgraphProvider::new(logger, Arc::new(IpfsClient::default()), store);
+    let provider_events = provider.take_event_stream().unwrap();
+    let schema_events = provider.take_event_stream().unwrap();
+    runtime
+        .block_on(provider.start_deployed_subgraphs())
+        .unwrap();
+    assert_eq!(
+        provider.list().unwrap(),
+        [("subgraph".to_owned(), subgraph_id.to_owned())]
+    );
+
+    // Finish the event streams.
+    drop(provider);
+
+    let provider_events = runtime.block_on(provider_events.collect()).unwrap();
+    assert_eq!(provider_events.len(), 1);
+    assert_eq!(
+        added_subgraph_name_and_id(&provider_events[0]),
+        ("subgraph", subgraph_id)
+    );
+
+    let schema_events = runtime.block_on(schema_events.collect()).unwrap();
+    assert_eq!(schema_events.len(), 1);
+    assert_eq!(
+        added_schema_name_and_id(&schema_events[0]),
+        ("subgraph", subgraph_id)
+    );
+}
+
+#[test]
+fn subgraphs_with_data_sources_on_different_networks_are_rejected() {
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let logger = Logger::root(slog::Discard, o!());
+    let provider = graph_core::SubgraphProvider::new(
+        logger,
+        Arc::new(IpfsClient::default()),
+        Arc::new(MockStore::new()),
+    );
+
+    let subgraph_link = runtime
+        .block_on(future::lazy(|| {
+            add_subgraph_to_ipfs(Arc::new(IpfsClient::default()), "two-networks")
+        })).unwrap();
+
+    match runtime.block_on(provider.deploy("subgraph".to_owned(), subgraph_link)) {
+        Err(SubgraphProviderError::NetworkError(_)) => (),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert!(provider.list().unwrap().is_empty());
 }
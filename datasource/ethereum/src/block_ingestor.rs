@@ -1,10 +1,11 @@
 use failure::Error;
 use std::fmt::Debug;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 use std::time::Instant;
 
 use graph::prelude::*;
+use graph::util::metrics::{BLOCK_INGESTOR_HEAD_LAG, BLOCK_INGESTOR_POLL_DURATION};
 use graph::web3::api::Web3;
 use graph::web3::transports::batch::Batch;
 use graph::web3::types::{Block, BlockId, BlockNumber, Transaction, H256};
@@ -13,14 +14,17 @@ use graph::web3::Transport;
 
 pub struct BlockIngestor<S, T>
 where
-    S: BlockStore + Send + 'static,
+    S: BlockStore + Send + Sync + 'static,
     T: BatchTransport + Send + Sync + Debug + Clone + 'static,
     <T as Transport>::Out: Send,
     <T as BatchTransport>::Batch: Send,
 {
-    store: Arc<Mutex<S>>,
+    store: Arc<S>,
     network_name: String,
-    web3_transport: T,
+    /// Transports to redundant providers for the network; blocks are
+    /// ingested from one of them at a time.
+    web3_transports: Vec<T>,
+    current_transport: AtomicUsize,
     ancestor_count: u64,
     logger: slog::Logger,
     polling_interval: Duration,
@@ -28,23 +32,31 @@ where
 
 impl<S, T> BlockIngestor<S, T>
 where
-    S: BlockStore + Send + 'static,
+    S: BlockStore + Send + Sync + 'static,
     T: BatchTransport + Send + Sync + Debug + Clone + 'static,
     <T as Transport>::Out: Send,
     <T as BatchTransport>::Batch: Send,
 {
     pub fn new(
-        store: Arc<Mutex<S>>,
+        store: Arc<S>,
         network_name: String,
-        web3_transport: T,
+        web3_transports: Vec<T>,
         ancestor_count: u64,
         logger: slog::Logger,
         polling_interval: Duration,
     ) -> Result<BlockIngestor<S, T>, Error> {
+        if web3_transports.is_empty() {
+            return Err(format_err!(
+                "no Ethereum providers for network: {}",
+                network_name
+            ));
+        }
+
         Ok(BlockIngestor {
             store,
             network_name,
-            web3_transport,
+            web3_transports,
+            current_transport: AtomicUsize::new(0),
             ancestor_count,
             logger: logger.new(o!("component" => "BlockIngestor")),
             polling_interval,
@@ -66,14 +78,24 @@ where
                     .map_err(move |e| {
                         error!(static_self.logger, "timer::Interval failed: {:?}", e);
                     }).for_each(move |_| {
+                        let timer = BLOCK_INGESTOR_POLL_DURATION
+                            .with_label_values(&[&static_self.network_name])
+                            .start_timer();
+
                         // Attempt to poll
                         static_self.do_poll().then(move |result| {
+                            timer.observe_duration();
+
                             if let Err(e) = result {
                                 // Some polls will fail due to transient issues
                                 warn!(
                                     static_self.logger,
                                     "failed to poll for latest block: {:?}", e
                                 );
+
+                                // Poll the next provider of the network, in
+                                // case the current one is unhealthy
+                                static_self.switch_transport();
                             }
 
                             // Continue polling even if polling failed
@@ -83,40 +105,62 @@ where
             })
     }
 
+    /// The transport to the provider that blocks are currently ingested from.
+    fn web3_transport(&self) -> T {
+        let current = self.current_transport.load(Ordering::SeqCst);
+        self.web3_transports[current % self.web3_transports.len()].clone()
+    }
+
+    /// Ingest blocks from the next provider of the network from now on.
+    fn switch_transport(&self) {
+        if self.web3_transports.len() > 1 {
+            let current = self.current_transport.fetch_add(1, Ordering::SeqCst) + 1;
+            info!(self.logger, "Switching to next Ethereum provider";
+                  "provider" => current % self.web3_transports.len());
+        }
+    }
+
+    /// Adds the network info of every provider to the store, which makes
+    /// sure that all providers are on the same network.
     fn add_network_to_store<'a>(&'a self) -> impl Future<Item = (), Error = Error> + 'a {
-        let web3 = Web3::new(self.web3_transport.clone());
-
-        // Ask Ethereum node for info to identify the network
-        let net_ver_future = web3.net().version();
-        let gen_block_future = web3.eth().block(BlockNumber::Earliest.into());
-        net_ver_future
-            .join(gen_block_future)
-            .map_err(|e| format_err!("could not get network info from Ethereum: {}", e))
-            .and_then(move |(net_version, gen_block)| {
-                let gen_block_hash = gen_block
-                    .expect("Ethereum node could not find genesis block")
-                    .hash
-                    .unwrap();
-
-                // Add Ethereum network info to store
-                self.store.lock().unwrap().add_network_if_missing(
-                    &self.network_name,
-                    &net_version,
-                    gen_block_hash,
-                )
-            })
+        stream::iter_ok(self.web3_transports.iter()).for_each(move |transport| {
+            let web3 = Web3::new(transport.clone());
+
+            // Ask Ethereum node for info to identify the network
+            let net_ver_future = web3.net().version();
+            let gen_block_future = web3.eth().block(BlockNumber::Earliest.into());
+            net_ver_future
+                .join(gen_block_future)
+                .map_err(|e| format_err!("could not get network info from Ethereum: {}", e))
+                .and_then(move |(net_version, gen_block)| {
+                    let gen_block_hash = gen_block
+                        .expect("Ethereum node could not find genesis block")
+                        .hash
+                        .unwrap();
+
+                    // Add Ethereum network info to store
+                    self.store.add_network_if_missing(
+                        &self.network_name,
+                        &net_version,
+                        gen_block_hash,
+                    )
+                })
+        })
     }
 
     fn do_poll<'a>(&'a self) -> impl Future<Item = (), Error = Error> + 'a {
         // Ask for latest block from Ethereum node
         self.get_latest_block()
             .and_then(move |latest_block: Block<Transaction>| {
+                let latest_number = latest_block.number.map(|number| number.as_u64());
+
                 // Store latest block in block store.
                 // Might be a no-op if latest block is one that we have seen.
                 // ingest_blocks will return a (potentially incomplete) list of blocks that are
                 // missing.
                 self.ingest_blocks(stream::once(Ok(latest_block)))
-            }).and_then(move |missing_block_hashes| {
+                    .map(move |missing_block_hashes| (latest_number, missing_block_hashes))
+            }).and_then(move |(latest_number, missing_block_hashes)| {
                 // Repeatedly fetch missing blocks, and ingest them.
                 // ingest_blocks will continue to tell us about more missing blocks until we have
                 // filled in all missing pieces of the blockchain (that we care about).
@@ -135,12 +179,28 @@ where
                             ))
                         }
                     },
-                )
-            })
+                ).map(move |()| latest_number)
+            }).and_then(move |latest_number| self.update_head_lag(latest_number))
+    }
+
+    /// Reports how many blocks the chain head in the store is behind the
+    /// latest block of the Ethereum node.
+    fn update_head_lag(&self, latest_number: Option<u64>) -> Result<(), Error> {
+        let head_number = self
+            .store
+            .chain_head_ptr(&self.network_name)?
+            .map_or(0, |head_ptr| head_ptr.number);
+
+        if let Some(latest_number) = latest_number {
+            BLOCK_INGESTOR_HEAD_LAG
+                .with_label_values(&[&self.network_name])
+                .set(latest_number.saturating_sub(head_number) as f64);
+        }
+        Ok(())
     }
 
     fn get_latest_block(&self) -> impl Future<Item = Block<Transaction>, Error = Error> {
-        let web3 = Web3::new(self.web3_transport.clone());
+        let web3 = Web3::new(self.web3_transport());
         web3.eth()
             .block_with_txs(BlockNumber::Latest.into())
             .map_err(|e| format_err!("could not get latest block from Ethereum: {}", e))
@@ -155,13 +215,9 @@ where
         blocks: B,
     ) -> impl Future<Item = Vec<H256>, Error = Error> + Send + 'a {
         self.store
-            .lock()
-            .unwrap()
             .upsert_blocks(&self.network_name, blocks)
             .and_then(move |()| {
                 self.store
-                    .lock()
-                    .unwrap()
                     .attempt_head_update(&self.network_name, self.ancestor_count)
             })
     }
@@ -177,7 +233,7 @@ where
             return Box::new(stream::empty());
         }
 
-        let web3 = Web3::new(Batch::new(self.web3_transport.clone()));
+        let web3 = Web3::new(Batch::new(self.web3_transport()));
 
         // Add requests to batch
         let block_futures = block_hashes
@@ -0,0 +1,509 @@
+use failure::Error;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use graph::components::store::EventSource;
+use graph::prelude::{
+    BlockStream as BlockStreamTrait, BlockStreamBuilder as BlockStreamBuilderTrait, *,
+};
+use graph::tokio::timer::Delay;
+use graph::web3::types::{Block, Log, Transaction};
+
+use log_scanner::LogScanner;
+
+/// The number of final blocks to load at once for subgraphs with block
+/// handlers, which need every block and not just those with logs.
+const FINAL_BLOCK_RANGE_SIZE_WITH_BLOCK_HANDLERS: u64 = 100;
+
+/// What a block stream needs to do next, based on the block pointer of the
+/// subgraph and the head block pointer of the chain.
+enum NextStep {
+    /// The subgraph has processed all blocks up to the chain head.
+    Wait,
+    /// The subgraph was moved back to the parent of an orphaned block.
+    Reverted,
+    /// Scan final blocks for logs and calls, starting at `from` and ending
+    /// at `to` at the latest.
+    ScanFinalBlocks { from: u64, to: u64 },
+    /// Load the block with the given number on the main chain leading up
+    /// to `head_ptr` from the block store.
+    LoadRecentBlock {
+        head_ptr: EthereumBlockPointer,
+        number: u64,
+    },
+}
+
+/// The outcome of a single step of a block stream.
+enum StepOutcome {
+    /// Blocks for the subgraph to process, in order.
+    Blocks(Vec<EthereumBlockWithLogs>),
+    /// The block pointer of the subgraph changed; take another step.
+    Retry,
+    /// There is nothing to process until the chain head advances.
+    Wait,
+}
+
+struct BlockStreamContext<S, E> {
+    store: Arc<S>,
+    ethereum_adapter: Arc<Mutex<E>>,
+    network_name: String,
+    subgraph_id: String,
+    log_filter: EthereumLogFilter,
+    log_scanner: LogScanner<E>,
+    call_filter: EthereumCallFilter,
+    has_block_handlers: bool,
+    reorg_threshold: u64,
+    /// The health of the subgraph as last recorded in the store; loaded from
+    /// the store on the first step.
+    health: Mutex<Option<SubgraphHealth>>,
+    logger: Logger,
+}
+
+impl<S, E> BlockStreamContext<S, E>
+where
+    S: Store + 'static,
+    E: EthereumAdapter,
+{
+    /// Take one step towards the chain head.
+    fn step(&self) -> Box<Future<Item = StepOutcome, Error = Error> + Send> {
+        match self.next_step() {
+            Ok(NextStep::Wait) => Box::new(future::ok(StepOutcome::Wait)),
+            Ok(NextStep::Reverted) => Box::new(future::ok(StepOutcome::Retry)),
+            Ok(NextStep::ScanFinalBlocks { from, to }) => {
+                Box::new(self.scan_final_blocks(from, to).map(StepOutcome::Blocks))
+            }
+            Ok(NextStep::LoadRecentBlock { head_ptr, number }) => Box::new(
+                self.load_recent_block(head_ptr, number)
+                    .map(StepOutcome::Blocks),
+            ),
+            Err(e) => Box::new(future::err(e)),
+        }
+    }
+
+    /// Compare the block pointer of the subgraph with the chain head to
+    /// decide what to do next. Reverts the most recent block of the subgraph
+    /// if it is no longer on the main chain.
+    fn next_step(&self) -> Result<NextStep, Error> {
+        let head_ptr = match self.store.chain_head_ptr(&self.network_name)? {
+            Some(head_ptr) => head_ptr,
+            // The block ingestor has not picked a head block yet
+            None => return Ok(NextStep::Wait),
+        };
+        let subgraph_ptr = self.store.block_ptr(self.subgraph_id.clone())?;
+
+        if let Some(subgraph_ptr) = subgraph_ptr {
+            // Blocks that are more than `reorg_threshold` blocks behind the
+            // chain head are considered final and are never reverted
+            let is_recent = subgraph_ptr.number <= head_ptr.number
+                && head_ptr.number - subgraph_ptr.number <= self.reorg_threshold;
+
+            if is_recent {
+                let main_chain_block = self
+                    .store
+                    .ancestor_block(head_ptr, head_ptr.number - subgraph_ptr.number)?
+                    .ok_or_else(|| {
+                        format_err!(
+                            "block {} is missing from the block store",
+                            subgraph_ptr.number
+                        )
+                    })?;
+
+                if main_chain_block.hash != Some(subgraph_ptr.hash) {
+                    let orphaned_block = self.store.block(subgraph_ptr.hash)?.ok_or_else(|| {
+                        format_err!(
+                            "orphaned block {:x} is missing from the block store",
+                            subgraph_ptr.hash
+                        )
+                    })?;
+
+                    info!(self.logger, "Reverting orphaned block";
+                          "number" => subgraph_ptr.number,
+                          "hash" => format!("{:x}", subgraph_ptr.hash));
+
+                    self.store.revert_events(
+                        EventSource::EthereumBlock(subgraph_ptr.hash).to_string(),
+                        self.subgraph_id.clone(),
+                    )?;
+                    self.store.set_block_ptr(
+                        self.subgraph_id.clone(),
+                        EthereumBlockPointer::to_parent(&orphaned_block),
+                    )?;
+
+                    return Ok(NextStep::Reverted);
+                }
+            }
+        }
+
+        let next_number = subgraph_ptr.map_or(0, |ptr| ptr.number + 1);
+
+        let next_step = if next_number > head_ptr.number {
+            NextStep::Wait
+        } else if head_ptr.number - next_number > self.reorg_threshold {
+            // The log scanner decides how many of the final blocks to scan,
+            // unless all of them need to be loaded for block handlers
+            let last_final_number = head_ptr.number - self.reorg_threshold - 1;
+            NextStep::ScanFinalBlocks {
+                from: next_number,
+                to: if self.has_block_handlers {
+                    last_final_number
+                        .min(next_number + FINAL_BLOCK_RANGE_SIZE_WITH_BLOCK_HANDLERS - 1)
+                } else {
+                    last_final_number
+                },
+            }
+        } else {
+            NextStep::LoadRecentBlock {
+                head_ptr,
+                number: next_number,
+            }
+        };
+
+        // The subgraph has caught up with the chain head once it no longer
+        // needs to scan final blocks
+        self.update_health(
+            match next_step {
+                NextStep::ScanFinalBlocks { .. } => false,
+                _ => true,
+            },
+            next_number,
+        )?;
+
+        Ok(next_step)
+    }
+
+    /// Record in the store whether the subgraph is synced or still syncing,
+    /// but only when that changes. A subgraph that was recorded as failed,
+    /// also before a restart, stays failed until it has processed the block
+    /// that it failed on; `next_number` is the number of the next block that
+    /// the subgraph will process.
+    fn update_health(&self, synced: bool, next_number: u64) -> Result<(), Error> {
+        let mut last_health = self.health.lock().unwrap();
+
+        if last_health.is_none() {
+            *last_health = Some(self.store.subgraph_health(self.subgraph_id.clone())?);
+        }
+
+        let health = if synced {
+            SubgraphHealth::Synced
+        } else {
+            SubgraphHealth::Syncing
+        };
+        let changed = match *last_health {
+            Some(SubgraphHealth::Failed {
+                block_ptr: Some(ref block_ptr),
+                ..
+            }) => next_number > block_ptr.number,
+            // The error happened before the subgraph processed any blocks,
+            // and it now processes blocks again
+            Some(SubgraphHealth::Failed {
+                block_ptr: None, ..
+            }) => true,
+            ref recorded => *recorded != Some(health.clone()),
+        };
+
+        if changed {
+            self.store
+                .set_subgraph_health(self.subgraph_id.clone(), health.clone())?;
+            *last_health = Some(health);
+        }
+
+        Ok(())
+    }
+
+    /// Find all matching logs and calls in a range of final blocks, starting
+    /// at `from` and ending at `to` at the latest, and load the blocks they
+    /// are from. The last block of the range is always included, so that the
+    /// subgraph advances to the end of the range even if there are no logs
+    /// or calls.
+    ///
+    /// If the subgraph has block handlers, all blocks of the range are
+    /// included.
+    fn scan_final_blocks(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Box<Future<Item = Vec<EthereumBlockWithLogs>, Error = Error> + Send> {
+        let logger = self.logger.clone();
+        let ethereum_adapter = self.ethereum_adapter.clone();
+        let call_filter = self.call_filter.clone();
+        let has_block_handlers = self.has_block_handlers;
+
+        let logs_and_calls = self.log_scanner.scan(from, to).and_then(move |(to, logs)| {
+            debug!(logger, "Found logs in final blocks, scanning them for calls";
+                   "from" => from,
+                   "to" => to,
+                   "logs" => logs.len());
+
+            ethereum_adapter
+                .lock()
+                .unwrap()
+                .calls_in_block_range(from, to, call_filter)
+                .map(move |calls| (to, logs, calls))
+        });
+        let ethereum_adapter = self.ethereum_adapter.clone();
+
+        Box::new(logs_and_calls.and_then(move |(to, logs, calls)| {
+            let mut blocks: BTreeMap<u64, (Vec<Log>, Vec<EthereumCall>)> = BTreeMap::new();
+
+            if has_block_handlers {
+                blocks.extend((from..=to).map(|number| (number, (vec![], vec![]))));
+            }
+
+            // Group logs and calls by the block they are from; both are
+            // returned in the order of the chain
+            for log in logs {
+                let number = log.block_number.unwrap().as_u64();
+                blocks
+                    .entry(number)
+                    .or_insert_with(Default::default)
+                    .0
+                    .push(log);
+            }
+            for call in calls {
+                let number = call.block_number;
+                blocks
+                    .entry(number)
+                    .or_insert_with(Default::default)
+                    .1
+                    .push(call);
+            }
+            blocks.entry(to).or_insert_with(Default::default);
+
+            // Load the full blocks, which provide the block and transaction
+            // data that is passed to the mappings
+            let mut ethereum_adapter = ethereum_adapter.lock().unwrap();
+            let blocks = blocks
+                .into_iter()
+                .map(|(number, (logs, calls))| {
+                    ethereum_adapter
+                        .block_by_number(number)
+                        .and_then(move |block| block_with_logs(block, logs, calls))
+                }).collect::<Vec<_>>();
+
+            stream::futures_ordered(blocks).collect()
+        }))
+    }
+
+    /// Load a block that is not yet final from the block store, along with
+    /// its matching logs and calls.
+    fn load_recent_block(
+        &self,
+        head_ptr: EthereumBlockPointer,
+        number: u64,
+    ) -> Box<Future<Item = Vec<EthereumBlockWithLogs>, Error = Error> + Send> {
+        let block = match self
+            .store
+            .ancestor_block(head_ptr, head_ptr.number - number)
+        {
+            Ok(Some(block)) => block,
+            Ok(None) => {
+                return Box::new(future::err(format_err!(
+                    "block {} is missing from the block store",
+                    number
+                )))
+            }
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        let mut ethereum_adapter = self.ethereum_adapter.lock().unwrap();
+        let logs = ethereum_adapter.logs_in_block_range(number, number, self.log_filter.clone());
+        let calls = ethereum_adapter.calls_in_block_range(number, number, self.call_filter.clone());
+
+        Box::new(
+            logs.join(calls)
+                .and_then(move |(logs, calls)| block_with_logs(block, logs, calls))
+                .map(|block| vec![block]),
+        )
+    }
+}
+
+/// Combines a block with the logs and calls from it. Fails if any of the
+/// logs or calls are from a different block with the same number, which
+/// means that the chain has been reorganized since the block was loaded.
+fn block_with_logs(
+    block: Block<Transaction>,
+    logs: Vec<Log>,
+    calls: Vec<EthereumCall>,
+) -> Result<EthereumBlockWithLogs, Error> {
+    let block_ptr =
+        EthereumBlockPointer::from((block.hash.unwrap(), block.number.unwrap().as_u64()));
+
+    let is_from_other_block = logs
+        .iter()
+        .any(|log| log.block_hash != Some(block_ptr.hash))
+        || calls.iter().any(|call| call.block_hash != block_ptr.hash);
+
+    if is_from_other_block {
+        Err(format_err!(
+            "logs or calls of block {} are from a different branch of the chain",
+            block_ptr.number
+        ))
+    } else {
+        Ok(EthereumBlockWithLogs {
+            block_ptr,
+            block,
+            logs,
+            calls,
+        })
+    }
+}
+
+/// A block stream that walks the chain ingested into the block store.
+pub struct BlockStream {
+    blocks: Box<Stream<Item = EthereumBlockWithLogs, Error = Error> + Send>,
+}
+
+impl BlockStream {
+    fn new<S, E>(context: BlockStreamContext<S, E>, polling_interval: Duration) -> Self
+    where
+        S: Store + 'static,
+        E: EthereumAdapter,
+    {
+        let context = Arc::new(context);
+
+        // Take steps until there are blocks to process, waiting for the
+        // polling interval whenever there is nothing to do or a step fails
+        let next_blocks = move || {
+            let context = context.clone();
+            future::loop_fn((), move |()| {
+                let logger = context.logger.clone();
+                context
+                    .step()
+                    .or_else(move |e| {
+                        warn!(logger, "Failed to load blocks: {}", e);
+                        Ok(StepOutcome::Wait)
+                    }).and_then(
+                        move |outcome| -> Box<Future<Item = _, Error = Error> + Send> {
+                            match outcome {
+                                StepOutcome::Blocks(blocks) => {
+                                    Box::new(future::ok(future::Loop::Break(blocks)))
+                                }
+                                StepOutcome::Retry => {
+                                    Box::new(future::ok(future::Loop::Continue(())))
+                                }
+                                StepOutcome::Wait => Box::new(
+                                    Delay::new(Instant::now() + polling_interval)
+                                        .map_err(Error::from)
+                                        .map(|()| future::Loop::Continue(())),
+                                ),
+                            }
+                        },
+                    )
+            })
+        };
+
+        BlockStream {
+            blocks: Box::new(
+                stream::unfold((), move |()| Some(next_blocks().map(|blocks| (blocks, ()))))
+                    .map(stream::iter_ok::<_, Error>)
+                    .flatten(),
+            ),
+        }
+    }
+}
+
+impl Stream for BlockStream {
+    type Item = EthereumBlockWithLogs;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        self.blocks.poll()
+    }
+}
+
+impl BlockStreamTrait for BlockStream {}
+
+pub struct BlockStreamBuilder<S, E> {
+    store: Arc<S>,
+    ethereum_adapters: HashMap<String, Arc<Mutex<E>>>,
+    reorg_threshold: u64,
+    polling_interval: Duration,
+}
+
+impl<S, E> Clone for BlockStreamBuilder<S, E> {
+    fn clone(&self) -> Self {
+        BlockStreamBuilder {
+            store: self.store.clone(),
+            ethereum_adapters: self.ethereum_adapters.clone(),
+            reorg_threshold: self.reorg_threshold,
+            polling_interval: self.polling_interval,
+        }
+    }
+}
+
+impl<S, E> BlockStreamBuilder<S, E>
+where
+    S: Store + 'static,
+    E: EthereumAdapter,
+{
+    /// Creates a builder for block streams of subgraphs, using the Ethereum
+    /// adapter of the network that each subgraph indexes, by network name.
+    ///
+    /// Blocks that are more than `reorg_threshold` blocks behind the chain
+    /// head are considered final. The threshold must not be larger than the
+    /// number of ancestors that the block ingestor keeps in the block store.
+    pub fn new(
+        store: Arc<S>,
+        ethereum_adapters: HashMap<String, Arc<Mutex<E>>>,
+        reorg_threshold: u64,
+        polling_interval: Duration,
+    ) -> Self {
+        BlockStreamBuilder {
+            store,
+            ethereum_adapters,
+            reorg_threshold,
+            polling_interval,
+        }
+    }
+}
+
+impl<S, E> BlockStreamBuilderTrait for BlockStreamBuilder<S, E>
+where
+    S: Store + 'static,
+    E: EthereumAdapter,
+{
+    type Stream = BlockStream;
+
+    fn from_subgraph(
+        &self,
+        manifest: &SubgraphManifest,
+        logger: Logger,
+    ) -> Result<Self::Stream, Error> {
+        let network_name = manifest.network_name()?;
+        let ethereum_adapter = self
+            .ethereum_adapters
+            .get(&network_name)
+            .ok_or_else(|| format_err!("no Ethereum adapter for network: {}", network_name))?
+            .clone();
+
+        let log_filter: EthereumLogFilter = manifest
+            .data_sources
+            .iter()
+            .map(EthereumLogFilter::from_data_source)
+            .collect();
+
+        let context = BlockStreamContext {
+            store: self.store.clone(),
+            ethereum_adapter: ethereum_adapter.clone(),
+            network_name,
+            subgraph_id: manifest.id.clone(),
+            log_filter: log_filter.clone(),
+            log_scanner: LogScanner::new(&logger, ethereum_adapter.clone(), log_filter),
+            call_filter: manifest
+                .data_sources
+                .iter()
+                .map(EthereumCallFilter::from_data_source)
+                .collect(),
+            has_block_handlers: manifest
+                .data_sources
+                .iter()
+                .any(|data_source| !data_source.mapping.block_handlers.is_empty()),
+            reorg_threshold: self.reorg_threshold,
+            health: Mutex::new(None),
+            logger: logger.new(o!("component" => "BlockStream")),
+        };
+
+        Ok(BlockStream::new(context, self.polling_interval))
+    }
+}
@@ -1,49 +1,155 @@
-use ethabi::{RawLog, Token};
+use ethabi::Token;
 use failure::Error;
 use futures::future;
 use futures::prelude::*;
-use futures::stream::iter_ok;
-use std::sync::Arc;
-use std::time::Duration;
 
 use graph::components::ethereum::{EthereumAdapter as EthereumAdapterTrait, *};
+use graph::prelude::Logger;
 use graph::web3;
-use graph::web3::api::{CreateFilter, Eth, Web3};
-use graph::web3::helpers::CallFuture;
+use graph::web3::api::Eth;
+use graph::web3::helpers::{self, CallFuture};
 use graph::web3::types::*;
 
+use provider_pool::ProviderPool;
+
+/// The parameters of a `trace_filter` request.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TraceFilter {
+    from_block: BlockNumber,
+    to_block: BlockNumber,
+    to_address: Vec<Address>,
+}
+
+/// A transaction trace as returned by `trace_filter`. Only the fields that
+/// are needed to turn call traces into `EthereumCall`s are included.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Trace {
+    #[serde(rename = "type")]
+    trace_type: String,
+    action: TraceAction,
+    result: Option<TraceResult>,
+    block_hash: H256,
+    block_number: u64,
+    transaction_hash: Option<H256>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TraceAction {
+    call_type: Option<String>,
+    from: Option<Address>,
+    to: Option<Address>,
+    value: Option<U256>,
+    input: Option<Bytes>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TraceResult {
+    output: Option<Bytes>,
+}
+
+impl Trace {
+    /// Converts the trace into a call, unless it is not a regular call or
+    /// the call failed (in which case the trace has no result).
+    fn into_call(self) -> Option<EthereumCall> {
+        if self.trace_type != "call"
+            || self.action.call_type.as_ref().map(String::as_str) != Some("call")
+        {
+            return None;
+        }
+
+        let Trace {
+            action,
+            result,
+            block_hash,
+            block_number,
+            transaction_hash,
+            ..
+        } = self;
+
+        Some(EthereumCall {
+            from: action.from?,
+            to: action.to?,
+            value: action.value.unwrap_or_default(),
+            input: action.input.map_or(vec![], |input| input.0),
+            output: result?.output.map_or(vec![], |output| output.0),
+            block_hash,
+            block_number,
+            transaction_hash: transaction_hash?,
+        })
+    }
+}
+
+/// Parts of the error messages with which Ethereum nodes refuse to return
+/// the logs of a block range because there are too many of them.
+const TOO_MANY_LOGS_MESSAGES: &[&str] = &[
+    "query returned more than",
+    "response size exceeded",
+    "block range is too wide",
+    "exceed maximum block range",
+];
+
+fn is_too_many_logs_error(e: &web3::error::Error) -> bool {
+    match *e.kind() {
+        web3::error::ErrorKind::Rpc(ref rpc_error) => {
+            let message = rpc_error.message.to_lowercase();
+            TOO_MANY_LOGS_MESSAGES
+                .iter()
+                .any(|part| message.contains(part))
+        }
+        _ => false,
+    }
+}
+
+/// Whether a request failed because the provider could not be reached or
+/// its connection broke, as opposed to an answer of the provider such as an
+/// RPC error.
+fn is_connection_error(e: &web3::error::Error) -> bool {
+    match *e.kind() {
+        web3::error::ErrorKind::Transport(_)
+        | web3::error::ErrorKind::Io(_)
+        | web3::error::ErrorKind::Unreachable => true,
+        _ => false,
+    }
+}
+
 pub struct EthereumAdapterConfig<T: web3::Transport> {
-    pub transport: T,
+    /// Transports to redundant providers for the same Ethereum network.
+    pub transports: Vec<T>,
 }
 
 pub struct EthereumAdapter<T: web3::Transport> {
-    eth_client: Arc<Web3<T>>,
+    providers: ProviderPool<T>,
 }
 
-impl<T: web3::Transport> EthereumAdapter<T> {
-    pub fn new(config: EthereumAdapterConfig<T>) -> Self {
+impl<T> EthereumAdapter<T>
+where
+    T: web3::Transport + Send + Sync + 'static,
+    T::Out: Send,
+{
+    pub fn new(logger: &Logger, config: EthereumAdapterConfig<T>) -> Self {
         EthereumAdapter {
-            eth_client: Arc::new(Web3::new(config.transport)),
+            providers: ProviderPool::new(logger, config.transports),
         }
     }
 
-    pub fn block_number(&self) -> CallFuture<U256, T::Out> {
-        self.eth_client.eth().block_number()
-    }
-
-    pub fn sha3(&self, data: &str) -> CallFuture<H256, T::Out> {
-        self.eth_client.web3().sha3(Bytes::from(data))
+    pub fn block_number(&self) -> impl Future<Item = U256, Error = Error> + Send {
+        self.providers.request(|web3| {
+            web3.eth()
+                .block_number()
+                .map_err(|e| format_err!("could not get block number from Ethereum: {}", e))
+        })
     }
 
-    pub fn event_filter(&self, subscription: EthereumEventSubscription) -> CreateFilter<T, Log> {
-        let filter_builder = FilterBuilder::default();
-        let eth_filter: Filter = filter_builder
-            .address(vec![subscription.address])
-            .from_block(subscription.range.from)
-            .to_block(subscription.range.to)
-            .topics(Some(vec![subscription.event.signature()]), None, None, None)
-            .build();
-        self.eth_client.eth_filter().create_logs_filter(eth_filter)
+    pub fn sha3(&self, data: &str) -> impl Future<Item = H256, Error = Error> + Send {
+        let data = Bytes::from(data);
+        self.providers.request(move |web3| {
+            web3.web3()
+                .sha3(data.clone())
+                .map_err(|e| format_err!("could not hash data on Ethereum: {}", e))
+        })
     }
 
     pub fn block(eth: Eth<T>, block_id: BlockId) -> impl Future<Item = Block<H256>, Error = Error> {
@@ -52,6 +158,15 @@ impl<T: web3::Transport> EthereumAdapter<T> {
             .and_then(|block| block.ok_or(format_err!("no block returned from Ethereum")))
     }
 
+    pub fn block_with_txs(
+        eth: Eth<T>,
+        block_id: BlockId,
+    ) -> impl Future<Item = Block<Transaction>, Error = Error> {
+        eth.block_with_txs(block_id)
+            .map_err(|e| format_err!("could not get block from Ethereum: {}", e))
+            .and_then(|block| block.ok_or(format_err!("no block returned from Ethereum")))
+    }
+
     fn call(
         eth: Eth<T>,
         contract_address: Address,
@@ -70,11 +185,15 @@ impl<T: web3::Transport> EthereumAdapter<T> {
     }
 }
 
-impl<T: web3::Transport + Send + Sync + 'static> EthereumAdapterTrait for EthereumAdapter<T> {
+impl<T> EthereumAdapterTrait for EthereumAdapter<T>
+where
+    T: web3::Transport + Send + Sync + 'static,
+    T::Out: Send,
+{
     fn contract_call(
         &mut self,
         call: EthereumContractCall,
-    ) -> Box<Future<Item = Vec<Token>, Error = EthereumContractCallError>> {
+    ) -> Box<Future<Item = Vec<Token>, Error = EthereumContractCallError> + Send> {
         // Emit custom error for type mismatches.
         for (token, kind) in call
             .args
@@ -89,30 +208,42 @@ impl<T: web3::Transport + Send + Sync + 'static> EthereumAdapterTrait for Ethere
             }
         }
 
-        // Obtain a handle on the Ethereum client
-        let eth_client = self.eth_client.clone();
-
         // Prepare for the function call, encoding the call parameters according
         // to the ABI
         let call_address = call.address;
         let call_data = call.function.encode_input(&call.args).unwrap();
+        let block_id = call.block_id.clone();
 
         Box::new(
-            // Resolve the block ID into a block number
-            Self::block(eth_client.eth(), call.block_id.clone())
-                .map_err(EthereumContractCallError::from)
-                .and_then(move |block| {
-                    // Make the actual function call
-                    Self::call(
-                        eth_client.eth(),
-                        call_address,
-                        Bytes(call_data),
-                        block
-                            .number
-                            .map(|number| number.as_u64())
-                            .map(BlockNumber::Number),
-                    ).map_err(EthereumContractCallError::from)
-                })
+            self.providers
+                .request(move |web3| {
+                    let eth = web3.eth();
+                    let call_data = call_data.clone();
+
+                    // Resolve the block ID into a block number
+                    Self::block(web3.eth(), block_id.clone()).and_then(move |block| {
+                        // Make the actual function call. Calls are
+                        // deterministic, so reverts and other RPC errors
+                        // are answers of the provider and not failures;
+                        // they are passed on as such
+                        Self::call(
+                            eth,
+                            call_address,
+                            Bytes(call_data),
+                            block
+                                .number
+                                .map(|number| number.as_u64())
+                                .map(BlockNumber::Number),
+                        ).then(|result| match result {
+                            Ok(output) => Ok(Ok(output)),
+                            Err(ref e) if is_connection_error(e) => {
+                                Err(format_err!("could not call contract on Ethereum: {}", e))
+                            }
+                            Err(e) => Ok(Err(EthereumContractCallError::from(e))),
+                        })
+                    })
+                }).map_err(EthereumContractCallError::from)
+                .and_then(|result| result)
                 // Decode the return values according to the ABI
                 .and_then(move |output| {
                     call.function
@@ -122,43 +253,98 @@ impl<T: web3::Transport + Send + Sync + 'static> EthereumAdapterTrait for Ethere
         )
     }
 
-    fn subscribe_to_event(
+    fn logs_in_block_range(
         &mut self,
-        subscription: EthereumEventSubscription,
-    ) -> Box<Stream<Item = EthereumEvent, Error = EthereumSubscriptionError>> {
-        let event = subscription.event.clone();
+        from: u64,
+        to: u64,
+        log_filter: EthereumLogFilter,
+    ) -> Box<Future<Item = Vec<Log>, Error = Error> + Send> {
+        // An empty list of addresses or topics would match all logs
+        if log_filter.is_empty() {
+            return Box::new(future::ok(vec![]));
+        }
+
+        let eth_filter: Filter = FilterBuilder::default()
+            .address(log_filter.contract_addresses.into_iter().collect())
+            .from_block(BlockNumber::Number(from))
+            .to_block(BlockNumber::Number(to))
+            .topics(
+                Some(log_filter.event_signatures.into_iter().collect()),
+                None,
+                None,
+                None,
+            ).build();
+
         Box::new(
-            self.event_filter(subscription)
-                .map_err(EthereumSubscriptionError::from)
-                .map(|base_filter| {
-                    let past_logs_stream = base_filter
-                        .logs()
-                        .map_err(EthereumSubscriptionError::from)
-                        .map(|logs_vec| iter_ok::<_, EthereumSubscriptionError>(logs_vec))
-                        .flatten_stream();
-                    let future_logs_stream = base_filter
-                        .stream(Duration::from_millis(2000))
-                        .map_err(EthereumSubscriptionError::from);
-                    past_logs_stream.chain(future_logs_stream)
-                }).flatten_stream()
-                .and_then(move |log| {
-                    event
-                        .parse_log(RawLog {
-                            topics: log.topics.clone(),
-                            data: log.clone().data.0,
-                        }).map_err(EthereumSubscriptionError::from)
-                        .map(|log_data| (log, log_data))
-                }).map(move |(log, log_data)| EthereumEvent {
-                    address: log.address,
-                    event_signature: log.topics[0],
-                    block_hash: log.block_hash.unwrap(),
-                    params: log_data.params,
-                    removed: log.is_removed(),
+            self.providers
+                .request(move |web3| {
+                    // Refusing to return too many logs is an answer of the
+                    // provider and not a failure, so it is passed on as such
+                    web3.eth()
+                        .logs(eth_filter.clone())
+                        .then(move |result| match result {
+                            Ok(logs) => Ok(Ok(logs)),
+                            Err(ref e) if is_too_many_logs_error(e) => {
+                                Ok(Err(Error::from(TooManyLogsError { from, to })))
+                            }
+                            Err(e) => Err(format_err!("could not get logs from Ethereum: {}", e)),
+                        })
+                }).and_then(|result| result),
+        )
+    }
+
+    fn calls_in_block_range(
+        &mut self,
+        from: u64,
+        to: u64,
+        call_filter: EthereumCallFilter,
+    ) -> Box<Future<Item = Vec<EthereumCall>, Error = Error> + Send> {
+        // An empty list of addresses would match all calls
+        if call_filter.is_empty() {
+            return Box::new(future::ok(vec![]));
+        }
+
+        let trace_filter = helpers::serialize(&TraceFilter {
+            from_block: BlockNumber::Number(from),
+            to_block: BlockNumber::Number(to),
+            to_address: call_filter.contract_addresses.iter().cloned().collect(),
+        });
+
+        Box::new(
+            self.providers
+                .request(move |web3| {
+                    CallFuture::<Vec<Trace>, _>::new(
+                        web3.transport()
+                            .execute("trace_filter", vec![trace_filter.clone()]),
+                    ).map_err(|e| format_err!("could not get traces from Ethereum: {}", e))
+                }).map(move |traces| {
+                    traces
+                        .into_iter()
+                        .filter_map(Trace::into_call)
+                        .filter(|call| call_filter.matches(call))
+                        .collect()
                 }),
         )
     }
 
-    fn unsubscribe_from_event(&mut self, _unique_id: String) -> bool {
-        false
+    fn block_ptr_by_number(
+        &mut self,
+        number: u64,
+    ) -> Box<Future<Item = EthereumBlockPointer, Error = Error> + Send> {
+        Box::new(
+            self.providers
+                .request(move |web3| {
+                    Self::block(web3.eth(), BlockId::Number(BlockNumber::Number(number)))
+                }).map(EthereumBlockPointer::from),
+        )
+    }
+
+    fn block_by_number(
+        &mut self,
+        number: u64,
+    ) -> Box<Future<Item = Block<Transaction>, Error = Error> + Send> {
+        self.providers.request(move |web3| {
+            Self::block_with_txs(web3.eth(), BlockId::Number(BlockNumber::Number(number)))
+        })
     }
 }
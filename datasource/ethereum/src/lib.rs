@@ -4,11 +4,18 @@ extern crate failure;
 extern crate futures;
 extern crate graph;
 extern crate jsonrpc_core;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 
 mod block_ingestor;
+mod block_stream;
 mod ethereum_adapter;
+mod log_scanner;
+mod provider_pool;
 mod transport;
 
 pub use self::block_ingestor::BlockIngestor;
+pub use self::block_stream::{BlockStream, BlockStreamBuilder};
 pub use self::ethereum_adapter::{EthereumAdapter, EthereumAdapterConfig};
 pub use self::transport::{EventLoopHandle, Transport};
@@ -0,0 +1,96 @@
+use failure::Error;
+use futures::future::{self, Loop};
+use std::sync::Mutex;
+
+use graph::components::ethereum::TooManyLogsError;
+use graph::prelude::*;
+use graph::web3::types::Log;
+
+/// The number of blocks to scan for logs at once until the scanner learns
+/// how many logs the Ethereum node is willing to return at once.
+const INITIAL_RANGE_SIZE: u64 = 1000;
+
+/// The largest number of blocks to scan for logs at once.
+const MAX_RANGE_SIZE: u64 = 10_000;
+
+/// Scans ranges of blocks for the logs of all event handlers of all data
+/// sources of a subgraph, with a single `eth_getLogs` request per range.
+///
+/// The size of the ranges adapts to the Ethereum node: it is halved whenever
+/// the node reports that a range has too many logs, and grows again slowly
+/// with every range that is scanned in full.
+pub struct LogScanner<E> {
+    ethereum_adapter: Arc<Mutex<E>>,
+    log_filter: EthereumLogFilter,
+    range_size: Arc<Mutex<u64>>,
+    logger: Logger,
+}
+
+impl<E> LogScanner<E>
+where
+    E: EthereumAdapter,
+{
+    pub fn new(
+        logger: &Logger,
+        ethereum_adapter: Arc<Mutex<E>>,
+        log_filter: EthereumLogFilter,
+    ) -> Self {
+        LogScanner {
+            ethereum_adapter,
+            log_filter,
+            range_size: Arc::new(Mutex::new(INITIAL_RANGE_SIZE)),
+            logger: logger.new(o!("component" => "LogScanner")),
+        }
+    }
+
+    /// Finds the logs in a range of blocks that starts at `from` and ends at
+    /// `to` at the latest. Returns the number of the last block of the range
+    /// that was scanned, along with the logs.
+    pub fn scan(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Box<Future<Item = (u64, Vec<Log>), Error = Error> + Send> {
+        let ethereum_adapter = self.ethereum_adapter.clone();
+        let log_filter = self.log_filter.clone();
+        let range_size = self.range_size.clone();
+        let logger = self.logger.clone();
+
+        Box::new(future::loop_fn((), move |()| {
+            let size = *range_size.lock().unwrap();
+            let range_to = to.min(from + size - 1);
+            let range_size = range_size.clone();
+            let logger = logger.clone();
+
+            let logs = ethereum_adapter.lock().unwrap().logs_in_block_range(
+                from,
+                range_to,
+                log_filter.clone(),
+            );
+
+            logs.then(move |result| match result {
+                Ok(logs) => {
+                    // Only grow the range if it was not cut short by `to`
+                    if range_to - from + 1 == size {
+                        *range_size.lock().unwrap() = (size + size / 4 + 1).min(MAX_RANGE_SIZE);
+                    }
+                    Ok(Loop::Break((range_to, logs)))
+                }
+                Err(e) => match e.downcast::<TooManyLogsError>() {
+                    // A single block cannot be split up any further
+                    Ok(e) if range_to > from => {
+                        let size = (range_to - from + 1) / 2;
+                        debug!(logger, "Too many logs, scanning smaller block ranges";
+                               "from" => e.from,
+                               "to" => e.to,
+                               "range_size" => size);
+                        *range_size.lock().unwrap() = size;
+                        Ok(Loop::Continue(()))
+                    }
+                    Ok(e) => Err(e.into()),
+                    Err(e) => Err(e),
+                },
+            })
+        }))
+    }
+}
@@ -0,0 +1,177 @@
+use failure::Error;
+use futures::future::{self, Loop};
+use futures::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use graph::prelude::*;
+use graph::web3;
+use graph::web3::api::Web3;
+
+/// How long a provider is passed over after its first failed request, in
+/// milliseconds. The backoff doubles with every further failure in a row.
+const INITIAL_BACKOFF_MS: u64 = 500;
+
+/// The longest a provider is passed over after failed requests, in
+/// milliseconds.
+const MAX_BACKOFF_MS: u64 = 60_000;
+
+/// The health of a provider, scored by the number of its requests that
+/// failed in a row.
+#[derive(Default)]
+struct ProviderHealth {
+    failures: u32,
+    /// Until this time, the provider is only used if all other providers
+    /// fail as well.
+    retry_at: Option<Instant>,
+}
+
+impl ProviderHealth {
+    fn is_backing_off(&self, now: Instant) -> bool {
+        self.retry_at.map_or(false, |retry_at| retry_at > now)
+    }
+
+    fn record_success(&mut self) {
+        self.failures = 0;
+        self.retry_at = None;
+    }
+
+    fn record_failure(&mut self, now: Instant) {
+        self.failures = self.failures.saturating_add(1);
+        let backoff = INITIAL_BACKOFF_MS
+            .saturating_mul(1 << (self.failures - 1).min(16))
+            .min(MAX_BACKOFF_MS);
+        self.retry_at = Some(now + Duration::from_millis(backoff));
+    }
+}
+
+struct Provider<T: web3::Transport> {
+    web3: Web3<T>,
+    health: Mutex<ProviderHealth>,
+}
+
+/// Redundant providers for the same Ethereum network.
+///
+/// Requests are spread across the providers round-robin. When a request
+/// fails, it is sent to the next provider, and the failed provider is
+/// passed over for a while, backing off further with every failure in a
+/// row.
+pub struct ProviderPool<T: web3::Transport> {
+    logger: Logger,
+    providers: Arc<Vec<Provider<T>>>,
+    next: Arc<AtomicUsize>,
+}
+
+impl<T: web3::Transport> Clone for ProviderPool<T> {
+    fn clone(&self) -> Self {
+        ProviderPool {
+            logger: self.logger.clone(),
+            providers: self.providers.clone(),
+            next: self.next.clone(),
+        }
+    }
+}
+
+impl<T> ProviderPool<T>
+where
+    T: web3::Transport + Send + Sync + 'static,
+    T::Out: Send,
+{
+    pub fn new(logger: &Logger, transports: Vec<T>) -> Self {
+        assert!(
+            !transports.is_empty(),
+            "At least one Ethereum provider is required"
+        );
+
+        ProviderPool {
+            logger: logger.new(o!("component" => "ProviderPool")),
+            providers: Arc::new(
+                transports
+                    .into_iter()
+                    .map(|transport| Provider {
+                        web3: Web3::new(transport),
+                        health: Mutex::new(ProviderHealth::default()),
+                    }).collect(),
+            ),
+            next: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// The provider that the next request would be sent to first.
+    pub fn web3(&self) -> &Web3<T> {
+        &self.providers[self.order()[0]].web3
+    }
+
+    /// Returns the indices of the providers in the order in which a request
+    /// should try them: the providers that are not backing off, round-robin,
+    /// followed by those that are, healthiest first.
+    fn order(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let count = self.providers.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+
+        let is_backing_off = |i: &usize| {
+            self.providers[*i]
+                .health
+                .lock()
+                .unwrap()
+                .is_backing_off(now)
+        };
+        let (mut backing_off, mut order): (Vec<_>, Vec<_>) = (0..count)
+            .map(|i| (start + i) % count)
+            .partition(is_backing_off);
+        backing_off.sort_by_key(|i| self.providers[*i].health.lock().unwrap().failures);
+        order.extend(backing_off);
+        order
+    }
+
+    /// Sends a request to one provider after another until it succeeds,
+    /// keeping track of the health of each provider on the way. Fails with
+    /// the error of the last provider if the request fails on all of them.
+    pub fn request<F, R>(&self, request: F) -> Box<Future<Item = R::Item, Error = Error> + Send>
+    where
+        F: Fn(&Web3<T>) -> R + Send + 'static,
+        R: IntoFuture<Error = Error>,
+        R::Future: Send + 'static,
+        R::Item: Send + 'static,
+    {
+        let logger = self.logger.clone();
+        let providers = self.providers.clone();
+
+        Box::new(future::loop_fn(
+            (self.order().into_iter(), None),
+            move |(mut order, last_error): (_, Option<Error>)|
+                  -> Box<Future<Item = _, Error = Error> + Send> {
+                let index = match order.next() {
+                    Some(index) => index,
+                    None => return Box::new(future::err(last_error.unwrap())),
+                };
+
+                let logger = logger.clone();
+                let providers = providers.clone();
+                Box::new(
+                    request(&providers[index].web3)
+                        .into_future()
+                        .then(move |result| {
+                            let mut health = providers[index].health.lock().unwrap();
+                            match result {
+                                Ok(item) => {
+                                    health.record_success();
+                                    Ok(Loop::Break(item))
+                                }
+                                Err(e) => {
+                                    health.record_failure(Instant::now());
+                                    warn!(logger, "Ethereum request failed, trying next provider";
+                                          "provider" => index,
+                                          "failures" => health.failures,
+                                          "error" => e.to_string());
+                                    Ok(Loop::Continue((order, Some(e))))
+                                }
+                            }
+                        }),
+                )
+            },
+        ))
+    }
+}
@@ -11,8 +11,10 @@ use std::collections::VecDeque;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
-use graph::components::ethereum::EthereumContractCall;
-use graph::prelude::EthereumAdapter as EthereumAdapterTrait;
+use graph::components::ethereum::{
+    EthereumContractCall, EthereumContractCallError, EthereumLogFilter, TooManyLogsError,
+};
+use graph::prelude::{o, slog, EthereumAdapter as EthereumAdapterTrait, Logger};
 use graph::serde_json;
 use graph::web3::error::{Error, ErrorKind};
 use graph::web3::helpers::*;
@@ -50,7 +52,7 @@ fn mock_block() -> Block<U256> {
 pub struct TestTransport {
     asserted: usize,
     requests: Arc<Mutex<Vec<(String, Vec<jsonrpc_core::Value>)>>>,
-    response: Arc<Mutex<VecDeque<jsonrpc_core::Value>>>,
+    response: Arc<Mutex<VecDeque<jsonrpc_core::Result<jsonrpc_core::Value>>>>,
 }
 
 impl Transport for TestTransport {
@@ -68,7 +70,8 @@ impl Transport for TestTransport {
 
     fn send(&self, id: RequestId, request: jsonrpc_core::Call) -> Result<jsonrpc_core::Value> {
         match self.response.lock().unwrap().pop_front() {
-            Some(response) => Box::new(finished(response)),
+            Some(Ok(response)) => Box::new(finished(response)),
+            Some(Err(error)) => Box::new(failed(ErrorKind::Rpc(error).into())),
             None => {
                 println!("Unexpected request (id: {:?}): {:?}", id, request);
                 Box::new(failed(ErrorKind::Unreachable.into()))
@@ -79,11 +82,15 @@ impl Transport for TestTransport {
 
 impl TestTransport {
     pub fn set_response(&mut self, value: jsonrpc_core::Value) {
-        *self.response.lock().unwrap() = vec![value].into();
+        *self.response.lock().unwrap() = vec![Ok(value)].into();
     }
 
     pub fn add_response(&mut self, value: jsonrpc_core::Value) {
-        self.response.lock().unwrap().push_back(value);
+        self.response.lock().unwrap().push_back(Ok(value));
+    }
+
+    pub fn add_error_response(&mut self, error: jsonrpc_core::Error) {
+        self.response.lock().unwrap().push_back(Err(error));
     }
 
     pub fn assert_request(&mut self, method: &str, params: &[String]) {
@@ -116,17 +123,7 @@ impl TestTransport {
     }
 }
 
-#[test]
-fn contract_call() {
-    let mut transport = TestTransport::default();
-
-    transport.add_response(serde_json::to_value(mock_block()).unwrap());
-    transport.add_response(jsonrpc_core::Value::String(format!(
-        "{:?}",
-        H256::from(100000)
-    )));
-
-    let mut adapter = EthereumAdapter::new(EthereumAdapterConfig { transport });
+fn balance_of_call() -> EthereumContractCall {
     let balance_of = Function {
         name: "balanceOf".to_owned(),
         inputs: vec![Param {
@@ -142,13 +139,137 @@ fn contract_call() {
     let function = Function::from(balance_of);
     let gnt_addr = Address::from_str("eF7FfF64389B814A946f3E92105513705CA6B990").unwrap();
     let holder_addr = Address::from_str("00d04c4b12C4686305bb4F4fC93487CdFBa62580").unwrap();
-    let call = EthereumContractCall {
+    EthereumContractCall {
         address: gnt_addr,
         block_id: BlockId::Number(BlockNumber::Latest),
         function: function,
         args: vec![Token::Address(holder_addr)],
-    };
-    let call_result = adapter.contract_call(call).wait().unwrap();
+    }
+}
+
+#[test]
+fn contract_call() {
+    let mut transport = TestTransport::default();
+
+    transport.add_response(serde_json::to_value(mock_block()).unwrap());
+    transport.add_response(jsonrpc_core::Value::String(format!(
+        "{:?}",
+        H256::from(100000)
+    )));
+
+    let logger = Logger::root(slog::Discard, o!());
+    let mut adapter = EthereumAdapter::new(
+        &logger,
+        EthereumAdapterConfig {
+            transports: vec![transport],
+        },
+    );
+    let call_result = adapter.contract_call(balance_of_call()).wait().unwrap();
 
     assert_eq!(call_result[0], Token::Uint(U256::from(100000)));
 }
+
+#[test]
+fn contract_call_fails_over_to_healthy_provider() {
+    // The first provider fails every request
+    let mut failing_transport = TestTransport::default();
+    let mut healthy_transport = TestTransport::default();
+
+    for balance in 1..4 {
+        healthy_transport.add_response(serde_json::to_value(mock_block()).unwrap());
+        healthy_transport.add_response(jsonrpc_core::Value::String(format!(
+            "{:?}",
+            H256::from(balance)
+        )));
+    }
+
+    let logger = Logger::root(slog::Discard, o!());
+    let mut adapter = EthereumAdapter::new(
+        &logger,
+        EthereumAdapterConfig {
+            transports: vec![failing_transport.clone(), healthy_transport.clone()],
+        },
+    );
+
+    // The failing provider is tried first, then passed over while it is
+    // backing off, even when it is next in line
+    for balance in 1..4 {
+        let call_result = adapter.contract_call(balance_of_call()).wait().unwrap();
+        assert_eq!(call_result[0], Token::Uint(U256::from(balance)));
+    }
+
+    failing_transport.assert_request(
+        "eth_getBlockByNumber",
+        &[r#""latest""#.into(), "false".into()],
+    );
+    failing_transport.assert_no_more_requests();
+}
+
+#[test]
+fn contract_call_does_not_fail_over_on_reverts() {
+    let mut reverting_transport = TestTransport::default();
+    let mut other_transport = TestTransport::default();
+
+    reverting_transport.add_response(serde_json::to_value(mock_block()).unwrap());
+    reverting_transport.add_error_response(jsonrpc_core::Error {
+        code: jsonrpc_core::ErrorCode::ServerError(-32015),
+        message: String::from("VM execution error."),
+        data: Some(jsonrpc_core::Value::String(String::from("Reverted 0x"))),
+    });
+
+    let logger = Logger::root(slog::Discard, o!());
+    let mut adapter = EthereumAdapter::new(
+        &logger,
+        EthereumAdapterConfig {
+            transports: vec![reverting_transport.clone(), other_transport.clone()],
+        },
+    );
+
+    // The revert is returned as is, without trying the other provider
+    match adapter.contract_call(balance_of_call()).wait() {
+        Err(EthereumContractCallError::CallError(_)) => (),
+        result => panic!("Expected a call error, got: {:?}", result),
+    }
+
+    let methods: Vec<_> = reverting_transport
+        .requests
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(method, _)| method.clone())
+        .collect();
+    assert_eq!(methods, vec!["eth_getBlockByNumber", "eth_call"]);
+    other_transport.assert_no_more_requests();
+}
+
+#[test]
+fn logs_in_block_range_with_too_many_logs() {
+    let mut transport = TestTransport::default();
+
+    transport.add_error_response(jsonrpc_core::Error {
+        code: jsonrpc_core::ErrorCode::ServerError(-32005),
+        message: String::from("query returned more than 10000 results"),
+        data: None,
+    });
+
+    let logger = Logger::root(slog::Discard, o!());
+    let mut adapter = EthereumAdapter::new(
+        &logger,
+        EthereumAdapterConfig {
+            transports: vec![transport],
+        },
+    );
+    let log_filter = EthereumLogFilter {
+        contract_addresses: vec![H160::from(1)].into_iter().collect(),
+        event_signatures: vec![H256::from(2)].into_iter().collect(),
+    };
+
+    let error = adapter
+        .logs_in_block_range(0, 999, log_filter)
+        .wait()
+        .unwrap_err()
+        .downcast::<TooManyLogsError>()
+        .unwrap();
+
+    assert_eq!((error.from, error.to), (0, 999));
+}
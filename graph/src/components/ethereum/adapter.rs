@@ -1,8 +1,14 @@
 use ethabi::{Bytes, Error as ABIError, Event, Function, LogParam, ParamType, Token};
 use failure::{Error, SyncFailure};
-use futures::{Future, Stream};
+use futures::Future;
+use std::collections::HashSet;
+use std::iter::FromIterator;
+use std::str::FromStr;
 use web3::error::Error as Web3Error;
-use web3::types::{Address, Block, BlockId, BlockNumber, H256};
+use web3::types::{Address, Block, BlockId, Log, Transaction, H256, U256};
+
+use data::subgraph::DataSource;
+use util::ethereum::{function_selector, string_to_h256};
 
 /// A request for the state of a contract at a specific block hash and address.
 pub struct EthereumContractStateRequest {
@@ -65,53 +71,255 @@ impl From<Error> for EthereumContractCallError {
     }
 }
 
+/// The Ethereum node refused to return the logs of a block range because
+/// there are too many of them. The logs of a smaller range may still be
+/// returned.
 #[derive(Fail, Debug)]
-pub enum EthereumSubscriptionError {
-    #[fail(display = "RPC error: {}", _0)]
-    RpcError(SyncFailure<Web3Error>),
-    #[fail(display = "ABI error: {}", _0)]
-    ABIError(SyncFailure<ABIError>),
+#[fail(display = "too many logs in blocks {} through {}", from, to)]
+pub struct TooManyLogsError {
+    pub from: u64,
+    pub to: u64,
+}
+
+/// A filter for logs emitted by any of a set of contract addresses with any
+/// of a set of event signatures.
+#[derive(Clone, Debug, Default)]
+pub struct EthereumLogFilter {
+    pub contract_addresses: HashSet<Address>,
+    pub event_signatures: HashSet<H256>,
 }
 
-impl From<Web3Error> for EthereumSubscriptionError {
-    fn from(err: Web3Error) -> EthereumSubscriptionError {
-        EthereumSubscriptionError::RpcError(SyncFailure::new(err))
+impl EthereumLogFilter {
+    /// Returns `true` if the log was emitted by one of the contracts and has
+    /// one of the event signatures of the filter.
+    pub fn matches(&self, log: &Log) -> bool {
+        let has_signature = log
+            .topics
+            .first()
+            .map_or(false, |signature| self.event_signatures.contains(signature));
+        self.contract_addresses.contains(&log.address) && has_signature
+    }
+
+    /// Returns `true` if the filter does not match any logs.
+    pub fn is_empty(&self) -> bool {
+        self.contract_addresses.is_empty() || self.event_signatures.is_empty()
+    }
+
+    /// Creates a filter for the logs of all event handlers of a data source.
+    pub fn from_data_source(data_source: &DataSource) -> Self {
+        EthereumLogFilter {
+            contract_addresses: Address::from_str(&data_source.source.address)
+                .into_iter()
+                .collect(),
+            event_signatures: data_source
+                .mapping
+                .event_handlers
+                .iter()
+                .map(|event_handler| string_to_h256(&event_handler.event))
+                .collect(),
+        }
     }
 }
 
-impl From<ABIError> for EthereumSubscriptionError {
-    fn from(err: ABIError) -> EthereumSubscriptionError {
-        EthereumSubscriptionError::ABIError(SyncFailure::new(err))
+impl FromIterator<EthereumLogFilter> for EthereumLogFilter {
+    fn from_iter<I: IntoIterator<Item = EthereumLogFilter>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(EthereumLogFilter::default(), |mut filter, other| {
+                filter.contract_addresses.extend(other.contract_addresses);
+                filter.event_signatures.extend(other.event_signatures);
+                filter
+            })
     }
 }
 
-/// A range to allow event subscriptions to limit the block numbers to consider.
-#[derive(Debug)]
-pub struct BlockNumberRange {
-    pub from: BlockNumber,
-    pub to: BlockNumber,
+/// A call to a contract function, taken from the traces of the transaction
+/// that made the call.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EthereumCall {
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+    pub input: Bytes,
+    pub output: Bytes,
+    pub block_hash: H256,
+    pub block_number: u64,
+    pub transaction_hash: H256,
 }
 
-/// A subscription to a specific contract address, event signature and block range.
-#[derive(Debug)]
-pub struct EthereumEventSubscription {
-    /// An ID that uniquely identifies the subscription (e.g. a GUID).
-    pub subscription_id: String,
-    pub address: Address,
-    pub range: BlockNumberRange,
-    pub event: Event,
+/// A filter for calls to any of a set of contract addresses with any of a
+/// set of function selectors.
+#[derive(Clone, Debug, Default)]
+pub struct EthereumCallFilter {
+    pub contract_addresses: HashSet<Address>,
+    pub function_selectors: HashSet<[u8; 4]>,
 }
 
-/// An event logged for a specific contract address and event signature.
+impl EthereumCallFilter {
+    /// Returns `true` if the call is to one of the contracts and its input
+    /// starts with one of the function selectors of the filter.
+    pub fn matches(&self, call: &EthereumCall) -> bool {
+        let has_selector = call.input.len() >= 4 && {
+            let mut selector = [0u8; 4];
+            selector.copy_from_slice(&call.input[..4]);
+            self.function_selectors.contains(&selector)
+        };
+        self.contract_addresses.contains(&call.to) && has_selector
+    }
+
+    /// Returns `true` if the filter does not match any calls.
+    pub fn is_empty(&self) -> bool {
+        self.contract_addresses.is_empty() || self.function_selectors.is_empty()
+    }
+
+    /// Creates a filter for the calls of all call handlers of a data source.
+    pub fn from_data_source(data_source: &DataSource) -> Self {
+        EthereumCallFilter {
+            contract_addresses: Address::from_str(&data_source.source.address)
+                .into_iter()
+                .collect(),
+            function_selectors: data_source
+                .mapping
+                .call_handlers
+                .iter()
+                .map(|call_handler| function_selector(&call_handler.function))
+                .collect(),
+        }
+    }
+}
+
+impl FromIterator<EthereumCallFilter> for EthereumCallFilter {
+    fn from_iter<I: IntoIterator<Item = EthereumCallFilter>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(EthereumCallFilter::default(), |mut filter, other| {
+                filter.contract_addresses.extend(other.contract_addresses);
+                filter.function_selectors.extend(other.function_selectors);
+                filter
+            })
+    }
+}
+
+/// An event logged for a specific contract address and event signature,
+/// along with the block and transaction that it was logged in.
 #[derive(Debug)]
 pub struct EthereumEvent {
     pub address: Address,
+    pub log_index: U256,
+    pub transaction_log_index: U256,
     pub event_signature: H256,
-    pub block_hash: H256,
+    pub block: EthereumBlockHeader,
+    pub transaction: EthereumTransactionData,
     pub params: Vec<LogParam>,
     pub removed: bool,
 }
 
+impl EthereumEvent {
+    /// Creates an event from a log, the block it was logged in and its
+    /// decoded parameters. Fails if the transaction of the log is not part
+    /// of the block.
+    pub fn from_log(
+        log: &Log,
+        block: &Block<Transaction>,
+        params: Vec<LogParam>,
+    ) -> Result<EthereumEvent, Error> {
+        let event_signature = *log
+            .topics
+            .first()
+            .ok_or_else(|| format_err!("log has no event signature"))?;
+
+        let transaction = log
+            .transaction_hash
+            .and_then(|hash| block.transactions.iter().find(|tx| tx.hash == hash))
+            .ok_or_else(|| {
+                format_err!(
+                    "transaction {:?} of log not found in block {:?}",
+                    log.transaction_hash,
+                    block.hash
+                )
+            })?;
+
+        Ok(EthereumEvent {
+            address: log.address,
+            log_index: log.log_index.unwrap_or_default(),
+            transaction_log_index: log.transaction_log_index.unwrap_or_default(),
+            event_signature,
+            block: EthereumBlockHeader::from(block),
+            transaction: EthereumTransactionData::from(transaction),
+            params,
+            removed: log.is_removed(),
+        })
+    }
+}
+
+/// The fields of an Ethereum transaction that are passed to event handlers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EthereumTransactionData {
+    pub hash: H256,
+    pub index: u64,
+    pub from: Address,
+    pub to: Option<Address>,
+    pub value: U256,
+    pub gas_limit: U256,
+    pub gas_price: U256,
+    pub input: Bytes,
+}
+
+impl<'a> From<&'a Transaction> for EthereumTransactionData {
+    fn from(tx: &'a Transaction) -> EthereumTransactionData {
+        EthereumTransactionData {
+            hash: tx.hash,
+            index: tx.transaction_index.map_or(0, |index| index.as_u64()),
+            from: tx.from,
+            to: tx.to,
+            value: tx.value,
+            gas_limit: tx.gas,
+            gas_price: tx.gas_price,
+            input: tx.input.0.clone(),
+        }
+    }
+}
+
+/// A contract function call with its decoded inputs and outputs, as passed
+/// to call handlers.
+#[derive(Debug)]
+pub struct EthereumCallData {
+    pub from: Address,
+    pub to: Address,
+    pub block_hash: H256,
+    pub block_number: u64,
+    pub transaction_hash: H256,
+    pub inputs: Vec<LogParam>,
+    pub outputs: Vec<LogParam>,
+}
+
+/// The header fields of an Ethereum block that are passed to block handlers
+/// and, as part of events, to event handlers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EthereumBlockHeader {
+    pub hash: H256,
+    pub parent_hash: H256,
+    pub number: u64,
+    pub timestamp: U256,
+    pub author: Address,
+    pub gas_used: U256,
+    pub gas_limit: U256,
+    pub difficulty: U256,
+}
+
+impl<'a, T> From<&'a Block<T>> for EthereumBlockHeader {
+    fn from(b: &'a Block<T>) -> EthereumBlockHeader {
+        EthereumBlockHeader {
+            hash: b.hash.unwrap(),
+            parent_hash: b.parent_hash,
+            number: b.number.unwrap().as_u64(),
+            timestamp: b.timestamp,
+            author: b.author,
+            gas_used: b.gas_used,
+            gas_limit: b.gas_limit,
+            difficulty: b.difficulty,
+        }
+    }
+}
+
 /// A block hash and block number from a specific Ethereum block.
 ///
 /// Maximum block number supported: 2^63 - 1
@@ -172,14 +380,42 @@ pub trait EthereumAdapter: Send + 'static {
     fn contract_call(
         &mut self,
         call: EthereumContractCall,
-    ) -> Box<Future<Item = Vec<Token>, Error = EthereumContractCallError>>;
+    ) -> Box<Future<Item = Vec<Token>, Error = EthereumContractCallError> + Send>;
+
+    /// Find all logs matching the filter in the blocks `from` through `to`
+    /// (inclusive), in the order they appear on the main chain.
+    ///
+    /// Fails with a `TooManyLogsError` if the Ethereum node refuses to
+    /// return that many logs at once.
+    fn logs_in_block_range(
+        &mut self,
+        from: u64,
+        to: u64,
+        log_filter: EthereumLogFilter,
+    ) -> Box<Future<Item = Vec<Log>, Error = Error> + Send>;
 
-    /// Subscribe to an event of a smart contract.
-    fn subscribe_to_event(
+    /// Find all successful calls matching the filter in the blocks `from`
+    /// through `to` (inclusive), in the order they appear on the main chain.
+    ///
+    /// Calls are obtained from transaction traces, which requires an
+    /// Ethereum node with tracing enabled.
+    fn calls_in_block_range(
         &mut self,
-        subscription: EthereumEventSubscription,
-    ) -> Box<Stream<Item = EthereumEvent, Error = EthereumSubscriptionError>>;
+        from: u64,
+        to: u64,
+        call_filter: EthereumCallFilter,
+    ) -> Box<Future<Item = Vec<EthereumCall>, Error = Error> + Send>;
 
-    /// Cancel a specific event subscription. Returns true when the subscription existed before.
-    fn unsubscribe_from_event(&mut self, subscription_id: String) -> bool;
+    /// Get a pointer to the block with the given number on the main chain.
+    fn block_ptr_by_number(
+        &mut self,
+        number: u64,
+    ) -> Box<Future<Item = EthereumBlockPointer, Error = Error> + Send>;
+
+    /// Get the block with the given number on the main chain, including its
+    /// transactions.
+    fn block_by_number(
+        &mut self,
+        number: u64,
+    ) -> Box<Future<Item = Block<Transaction>, Error = Error> + Send>;
 }
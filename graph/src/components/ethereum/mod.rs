@@ -1,11 +1,13 @@
 mod adapter;
+mod stream;
 
 pub use self::adapter::{
-    BlockNumberRange, EthereumAdapter, EthereumBlockPointer, EthereumContractCall,
-    EthereumContractCallError, EthereumContractState, EthereumContractStateError,
-    EthereumContractStateRequest, EthereumEvent, EthereumEventSubscription,
-    EthereumSubscriptionError,
+    EthereumAdapter, EthereumBlockHeader, EthereumBlockPointer, EthereumCall, EthereumCallData,
+    EthereumCallFilter, EthereumContractCall, EthereumContractCallError, EthereumContractState,
+    EthereumContractStateError, EthereumContractStateRequest, EthereumEvent, EthereumLogFilter,
+    EthereumTransactionData, TooManyLogsError,
 };
+pub use self::stream::{BlockStream, BlockStreamBuilder, EthereumBlockWithLogs};
 
 pub use web3::types::BlockNumber;
 
@@ -0,0 +1,38 @@
+use failure::Error;
+use futures::Stream;
+use slog::Logger;
+use web3::types::{Block, Log, Transaction};
+
+use super::{EthereumBlockPointer, EthereumCall};
+use data::subgraph::SubgraphManifest;
+
+/// A block on the main chain along with the logs and calls from it that match
+/// the log and call filters of a subgraph.
+#[derive(Clone, Debug)]
+pub struct EthereumBlockWithLogs {
+    pub block_ptr: EthereumBlockPointer,
+    /// The full block, including its transactions.
+    pub block: Block<Transaction>,
+    pub logs: Vec<Log>,
+    pub calls: Vec<EthereumCall>,
+}
+
+/// A stream of the blocks that a subgraph needs to process, in order.
+///
+/// Before the stream continues with the blocks of a new main chain, it
+/// reverts the entity changes of any blocks that the subgraph processed on
+/// a branch that is no longer part of the main chain.
+pub trait BlockStream: Stream<Item = EthereumBlockWithLogs, Error = Error> {}
+
+pub trait BlockStreamBuilder: Clone + Send + 'static {
+    type Stream: BlockStream + Send + 'static;
+
+    /// Build a block stream for a subgraph, starting after the block that
+    /// the subgraph's block pointer points to. Fails if the Ethereum network
+    /// of the subgraph is not available.
+    fn from_subgraph(
+        &self,
+        manifest: &SubgraphManifest,
+        logger: Logger,
+    ) -> Result<Self::Stream, Error>;
+}
@@ -15,6 +15,10 @@ pub trait GraphQlRunner: Send + Sync {
     /// Runs a GraphQL query and returns its result.
     fn run_query(&self, query: Query) -> QueryResultFuture;
 
+    /// Runs a GraphQL query against the indexing status API and returns its
+    /// result.
+    fn run_indexing_status_query(&self, query: Query) -> QueryResultFuture;
+
     /// Runs a GraphQL subscription and returns a stream of results.
     fn run_subscription(&self, subscription: Subscription) -> SubscriptionResultFuture;
 }
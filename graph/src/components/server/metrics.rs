@@ -0,0 +1,12 @@
+use futures::prelude::*;
+
+/// Common trait for servers that expose metrics of the node to Prometheus.
+pub trait MetricsServer {
+    type ServeError;
+
+    /// Returns a Future that, when spawned, brings up the metrics server.
+    fn serve(
+        &mut self,
+        port: u16,
+    ) -> Result<Box<Future<Item = (), Error = ()> + Send>, Self::ServeError>;
+}
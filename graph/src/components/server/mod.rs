@@ -6,3 +6,6 @@ pub mod subscription;
 
 /// Component for the JSON-RPC admin API.
 pub mod admin;
+
+/// Component for exposing metrics to Prometheus.
+pub mod metrics;
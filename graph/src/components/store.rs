@@ -3,8 +3,15 @@ use futures::Future;
 use futures::Stream;
 use web3::types::{Block, Transaction, H256};
 
+use components::ethereum::EthereumBlockPointer;
+use data::schema::Schema;
 use data::store::*;
+use data::subgraph::DynamicDataSource;
+use std::collections::BTreeMap;
 use std::fmt;
+use std::mem;
+use std::str::FromStr;
+use std::time::SystemTime;
 
 /// Key by which an individual entity in the store can be accessed.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -77,6 +84,22 @@ pub struct StoreQuery {
 
     /// An optional range to limit the size of the result.
     pub range: Option<StoreRange>,
+
+    /// Only return entities that come after the entity with this ID in the
+    /// order of the query.
+    pub after: Option<String>,
+
+    /// Only return entities that come before the entity with this ID in the
+    /// order of the query.
+    pub before: Option<String>,
+
+    /// Only return this many entities from the end of the result, after
+    /// `range` has been applied.
+    pub last: Option<usize>,
+
+    /// Query the entities as they were after the block with this number was
+    /// processed, instead of their current state.
+    pub block: Option<u64>,
 }
 
 /// Operation types that lead to entity changes.
@@ -124,19 +147,266 @@ impl fmt::Display for EventSource {
     }
 }
 
+/// An operation on an entity that is applied to the store as part of a block.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EntityOperation {
+    /// Sets an entity, merging it into the existing entity, if there is one.
+    Set(StoreKey, Entity),
+    /// Removes an entity, if it exists.
+    Remove(StoreKey),
+}
+
+/// A modification of an entity that is pending in an `EntityCache`.
+#[derive(Clone, Debug, PartialEq)]
+enum EntityModification {
+    /// The entity is updated with these attributes; attributes that are
+    /// set to `Value::Null` are removed from the entity.
+    Update(Entity),
+    /// The entity is removed and, if there are attributes, created again
+    /// with only these attributes.
+    Overwrite(Option<Entity>),
+}
+
+/// A write-through cache for the entities of a subgraph that mappings read
+/// and write while a block is processed.
+///
+/// Entities are loaded from the store at most once. Sets and removes are
+/// merged into pending modifications that later reads see, and that are
+/// turned into entity operations once the block has been processed, so
+/// they can be applied to the store in a single transaction.
+#[derive(Debug, Default)]
+pub struct EntityCache {
+    /// Entities as they were loaded from the store; `None` for entities
+    /// that do not exist in the store.
+    current: BTreeMap<StoreKey, Option<Entity>>,
+    /// Modifications of entities that are not in the store yet.
+    updates: BTreeMap<StoreKey, EntityModification>,
+}
+
+impl EntityCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Looks up an entity, including the modifications of it that are
+    /// pending. Loads the entity from the store if it is not in the cache
+    /// yet. Returns `None` if the entity does not exist.
+    pub fn get<S: BasicStore + ?Sized>(
+        &mut self,
+        store: &S,
+        key: StoreKey,
+    ) -> Result<Option<Entity>, Error> {
+        let update = match self.updates.get(&key) {
+            Some(EntityModification::Overwrite(entity)) => return Ok(entity.clone()),
+            Some(EntityModification::Update(update)) => Some(update.clone()),
+            None => None,
+        };
+
+        // Failed lookups are not cached, so the entity is loaded again the
+        // next time it is needed
+        if !self.current.contains_key(&key) {
+            let current = store.get(key.clone())?;
+            self.current.insert(key.clone(), current);
+        }
+        let current = self.current[&key].clone();
+
+        Ok(match (current, update) {
+            (Some(mut entity), Some(update)) => {
+                entity.merge(update);
+                Some(entity)
+            }
+            (None, Some(update)) => {
+                let mut entity = Entity::new();
+                entity.merge(update);
+                Some(entity)
+            }
+            (current, None) => current,
+        })
+    }
+
+    /// Sets an entity, merging it into the entity with the same key.
+    pub fn set(&mut self, key: StoreKey, entity: Entity) {
+        let modification = match self.updates.remove(&key) {
+            None => EntityModification::Update(entity),
+            Some(EntityModification::Update(mut update)) => {
+                // Keep `Value::Null` attributes so they are removed from
+                // the entity in the store as well
+                for (attribute, value) in entity.iter() {
+                    update.insert(attribute.clone(), value.clone());
+                }
+                EntityModification::Update(update)
+            }
+            Some(EntityModification::Overwrite(overwrite)) => {
+                let mut overwrite = overwrite.unwrap_or_else(Entity::new);
+                overwrite.merge(entity);
+                EntityModification::Overwrite(Some(overwrite))
+            }
+        };
+        self.updates.insert(key, modification);
+    }
+
+    /// Removes an entity.
+    pub fn remove(&mut self, key: StoreKey) {
+        self.updates
+            .insert(key, EntityModification::Overwrite(None));
+    }
+
+    /// Returns the entity operations that apply the pending modifications
+    /// to the store, ordered by key, and empties the cache.
+    pub fn take_operations(&mut self) -> Vec<EntityOperation> {
+        self.current.clear();
+
+        let mut operations = vec![];
+        for (key, modification) in mem::replace(&mut self.updates, BTreeMap::new()) {
+            match modification {
+                EntityModification::Update(entity) => {
+                    operations.push(EntityOperation::Set(key, entity))
+                }
+                EntityModification::Overwrite(entity) => {
+                    operations.push(EntityOperation::Remove(key.clone()));
+                    if let Some(entity) = entity {
+                        operations.push(EntityOperation::Set(key, entity));
+                    }
+                }
+            }
+        }
+        operations
+    }
+}
+
+/// The status of a subgraph deployment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubgraphDeploymentStatus {
+    /// The subgraph was resolved and handed to the runtime.
+    Deployed,
+    /// The subgraph could not be resolved when it was redeployed.
+    Failed,
+}
+
+impl fmt::Display for SubgraphDeploymentStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SubgraphDeploymentStatus::Deployed => write!(f, "deployed"),
+            SubgraphDeploymentStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+impl FromStr for SubgraphDeploymentStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "deployed" => Ok(SubgraphDeploymentStatus::Deployed),
+            "failed" => Ok(SubgraphDeploymentStatus::Failed),
+            _ => Err(format_err!("invalid subgraph deployment status: {}", s)),
+        }
+    }
+}
+
+/// A subgraph that is deployed under a name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubgraphDeployment {
+    pub name: String,
+    /// ID of the subgraph, which is the IPFS hash of its manifest.
+    pub subgraph_id: String,
+    pub deployed_at: SystemTime,
+    pub status: SubgraphDeploymentStatus,
+}
+
+/// The health of a subgraph, as far as indexing it is concerned.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SubgraphHealth {
+    /// The subgraph is processing blocks that are already final.
+    Syncing,
+    /// The subgraph has caught up with the chain head and is processing
+    /// recent blocks as they arrive.
+    Synced,
+    /// Indexing the subgraph stopped because of an error. The block pointer
+    /// points to the block that could not be processed, if the error
+    /// happened while processing a block. A failed subgraph that is
+    /// restarted becomes healthy again once it gets past that block.
+    Failed {
+        error: String,
+        block_ptr: Option<EthereumBlockPointer>,
+    },
+}
+
+impl fmt::Display for SubgraphHealth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SubgraphHealth::Syncing => write!(f, "syncing"),
+            SubgraphHealth::Synced => write!(f, "synced"),
+            SubgraphHealth::Failed { .. } => write!(f, "failed"),
+        }
+    }
+}
+
+/// How far indexing a subgraph has progressed, and whether it is healthy.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubgraphIndexingStatus {
+    pub subgraph_id: String,
+    pub network_name: String,
+    /// The most recent block that the subgraph has processed.
+    pub latest_block_ptr: Option<EthereumBlockPointer>,
+    /// The head block of the network that the subgraph indexes.
+    pub chain_head_block_ptr: Option<EthereumBlockPointer>,
+    pub entity_count: u64,
+    pub health: SubgraphHealth,
+}
+
 /// Common trait for store implementations that don't require interaction with the system.
-pub trait BasicStore: Send {
-    /// Looks up an entity using the given store key.
-    fn get(&self, key: StoreKey) -> Result<Entity, ()>;
+pub trait BasicStore: Send + Sync {
+    /// Looks up an entity using the given store key. Returns `None` if the
+    /// entity does not exist.
+    fn get(&self, key: StoreKey) -> Result<Option<Entity>, Error>;
 
     /// Updates an entity using the given store key and entity data.
-    fn set(&mut self, key: StoreKey, entity: Entity, event_source: EventSource) -> Result<(), ()>;
+    fn set(&self, key: StoreKey, entity: Entity, event_source: EventSource) -> Result<(), ()>;
 
     /// Deletes an entity using the given store key.
-    fn delete(&mut self, key: StoreKey, event_source: EventSource) -> Result<(), ()>;
+    fn delete(&self, key: StoreKey, event_source: EventSource) -> Result<(), ()>;
 
     /// Queries the store for entities that match the store query.
     fn find(&self, query: StoreQuery) -> Result<Vec<Entity>, ()>;
+
+    /// Applies the entity operations of a block and moves the block pointer
+    /// of the subgraph to that block in a single transaction.
+    ///
+    /// Either all operations and the block pointer update are persisted or
+    /// none of them are.
+    fn transact_block_operations(
+        &self,
+        subgraph_id: String,
+        block_ptr: EthereumBlockPointer,
+        operations: Vec<EntityOperation>,
+    ) -> Result<(), Error>;
+}
+
+/// Common trait for stores that keep track of which subgraphs are deployed.
+pub trait SubgraphDeploymentStore: Send + Sync {
+    /// Deploys the subgraph with the given ID under a name, replacing the
+    /// subgraph that was previously deployed under that name.
+    ///
+    /// Returns the replaced deployment, if there was one.
+    fn upsert_subgraph_deployment(
+        &self,
+        name: String,
+        subgraph_id: String,
+    ) -> Result<Option<SubgraphDeployment>, Error>;
+
+    /// Updates the status of the deployment with the given name.
+    fn set_subgraph_deployment_status(
+        &self,
+        name: String,
+        status: SubgraphDeploymentStatus,
+    ) -> Result<(), Error>;
+
+    /// Removes the deployment with the given name, if it exists.
+    fn remove_subgraph_deployment(&self, name: String) -> Result<(), Error>;
+
+    /// Lists all subgraph deployments, ordered by name.
+    fn subgraph_deployments(&self) -> Result<Vec<SubgraphDeployment>, Error>;
 }
 
 /// A pair of subgraph ID and entity type name.
@@ -174,12 +444,92 @@ pub trait BlockStore {
         network_name: &str,
         ancestor_count: u64,
     ) -> Result<Vec<H256>, Error>;
+
+    /// Get a pointer to the head block of the network, or `None` if no head
+    /// block has been set yet.
+    fn chain_head_ptr(&self, network_name: &str) -> Result<Option<EthereumBlockPointer>, Error>;
+
+    /// Look up a block by its hash. Returns `None` if the block is not in the store.
+    fn block(&self, block_hash: H256) -> Result<Option<Block<Transaction>>, Error>;
+
+    /// Look up the ancestor of a block that is `offset` blocks before it,
+    /// e.g. an offset of 1 yields the parent block.
+    ///
+    /// Returns `None` if the ancestor or any of the blocks in between are
+    /// not in the store.
+    fn ancestor_block(
+        &self,
+        block_ptr: EthereumBlockPointer,
+        offset: u64,
+    ) -> Result<Option<Block<Transaction>>, Error>;
 }
 
 /// Common trait for store implementations.
-pub trait Store: BasicStore + BlockStore + Send {
+pub trait Store: BasicStore + BlockStore + Send + Sync {
     /// Subscribe to entity changes for specific subgraphs and entities.
     ///
     /// Returns a stream of entity changes that match the input arguments.
-    fn subscribe(&mut self, entities: Vec<SubgraphEntityPair>) -> EntityChangeStream;
+    fn subscribe(&self, entities: Vec<SubgraphEntityPair>) -> EntityChangeStream;
+
+    /// Register a subgraph that indexes the given network in the store,
+    /// unless it is already present.
+    ///
+    /// Newly added subgraphs have no block pointer yet.
+    fn add_subgraph_if_missing(
+        &self,
+        subgraph_id: String,
+        network_name: String,
+    ) -> Result<(), Error>;
+
+    /// Create the tables that store the entities of a subgraph, one per
+    /// entity type in its schema, unless they exist already.
+    ///
+    /// Subgraphs that already have entities in the store keep the storage
+    /// they were indexed with.
+    fn create_entity_tables(&self, subgraph_id: String, schema: &Schema) -> Result<(), Error>;
+
+    /// Get a pointer to the most recent block that events of the subgraph
+    /// have been processed for, or `None` if no events have been processed yet.
+    fn block_ptr(&self, subgraph_id: String) -> Result<Option<EthereumBlockPointer>, Error>;
+
+    /// Update the block pointer of the subgraph after processing events
+    /// from the given block.
+    fn set_block_ptr(
+        &self,
+        subgraph_id: String,
+        block_ptr: EthereumBlockPointer,
+    ) -> Result<(), Error>;
+
+    /// Revert all entity changes that were made to the subgraph while
+    /// processing events from the given block, and remove the data sources
+    /// that its mappings created in that block.
+    fn revert_events(&self, block_hash: String, subgraph_id: String) -> Result<(), Error>;
+
+    /// Persist data sources that the mappings of the subgraph created while
+    /// processing the given block. Data sources that already exist are
+    /// ignored.
+    fn add_dynamic_data_sources(
+        &self,
+        subgraph_id: String,
+        block_ptr: EthereumBlockPointer,
+        data_sources: Vec<DynamicDataSource>,
+    ) -> Result<(), Error>;
+
+    /// Load the data sources that the mappings of the subgraph created, in
+    /// the order they were created in.
+    fn dynamic_data_sources(&self, subgraph_id: String) -> Result<Vec<DynamicDataSource>, Error>;
+
+    /// Get the health of the subgraph. Newly added subgraphs are syncing.
+    fn subgraph_health(&self, subgraph_id: String) -> Result<SubgraphHealth, Error>;
+
+    /// Update the health of the subgraph.
+    fn set_subgraph_health(&self, subgraph_id: String, health: SubgraphHealth)
+        -> Result<(), Error>;
+
+    /// Get the indexing status of the subgraph, or `None` if the subgraph
+    /// has not been added to the store.
+    fn subgraph_indexing_status(
+        &self,
+        subgraph_id: String,
+    ) -> Result<Option<SubgraphIndexingStatus>, Error>;
 }
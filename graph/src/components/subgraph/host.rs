@@ -1,24 +1,84 @@
+use std::sync::Mutex;
+
 use prelude::*;
+use web3::types::{Block, Log, Transaction};
 
-/// Events emitted by a runtime host.
+/// Events emitted by a runtime host. Entity changes are not events; they
+/// are written to the entity cache of the block that is being processed.
 #[derive(Debug, Clone, PartialEq)]
 pub enum RuntimeHostEvent {
-    /// An entity should be created or updated.
-    EntitySet(StoreKey, Entity, EventSource),
-    /// An entity should be removed.
-    EntityRemoved(StoreKey, EventSource),
+    /// A data source should be created from a template of the subgraph.
+    DataSourceCreated(DynamicDataSource),
 }
 
+/// A mapping handler failed because a call of the host, e.g. to an Ethereum
+/// node or IPFS, did not finish in time. This is not caused by the block or
+/// the mapping, so processing the block again may succeed.
+#[derive(Fail, Debug)]
+#[fail(display = "{}", _0)]
+pub struct HostCallTimeoutError(pub String);
+
 /// Common trait for runtime host implementations.
-pub trait RuntimeHost: EventProducer<RuntimeHostEvent> + Send {
+pub trait RuntimeHost: Send + Sync + 'static {
     /// The subgraph definition the runtime is for.
     fn subgraph_manifest(&self) -> &SubgraphManifest;
+
+    /// Returns `true` if the runtime host has a handler for the log.
+    fn matches_log(&self, log: &Log) -> bool;
+
+    /// Process a log by calling the matching event handler in the mappings.
+    /// The block that the log is from provides the block and transaction
+    /// data of the event. The handler reads and writes entities through
+    /// the entity cache of the block.
+    ///
+    /// Resolves to the events emitted by the handler, in the order they
+    /// were emitted.
+    fn process_log(
+        &self,
+        block: Arc<Block<Transaction>>,
+        log: Log,
+        entity_cache: Arc<Mutex<EntityCache>>,
+    ) -> Box<Future<Item = Vec<RuntimeHostEvent>, Error = Error> + Send>;
+
+    /// Returns `true` if the runtime host has a handler for the call.
+    fn matches_call(&self, call: &EthereumCall) -> bool;
+
+    /// Process a call by calling the matching call handler in the mappings.
+    /// The handler reads and writes entities through the entity cache of
+    /// the block.
+    ///
+    /// Resolves to the events emitted by the handler, in the order they
+    /// were emitted.
+    fn process_call(
+        &self,
+        call: EthereumCall,
+        entity_cache: Arc<Mutex<EntityCache>>,
+    ) -> Box<Future<Item = Vec<RuntimeHostEvent>, Error = Error> + Send>;
+
+    /// Returns `true` if the runtime host has handlers for every block.
+    fn has_block_handlers(&self) -> bool;
+
+    /// Process a block by calling all block handlers in the mappings, in
+    /// the order they are declared. The handlers read and write entities
+    /// through the entity cache of the block.
+    ///
+    /// Resolves to the events emitted by the handlers, in the order they
+    /// were emitted.
+    fn process_block(
+        &self,
+        block: EthereumBlockHeader,
+        entity_cache: Arc<Mutex<EntityCache>>,
+    ) -> Box<Future<Item = Vec<RuntimeHostEvent>, Error = Error> + Send>;
 }
 
-pub trait RuntimeHostBuilder: Send + 'static {
+pub trait RuntimeHostBuilder: Clone + Send + 'static {
     type Host: RuntimeHost;
 
-    /// Build a new runtime host for a dataset.
-    fn build(&mut self, subgraph_manifest: SubgraphManifest, data_source: DataSource)
-        -> Self::Host;
+    /// Build a new runtime host for a dataset. Fails if the Ethereum
+    /// network of the subgraph is not available.
+    fn build(
+        &mut self,
+        subgraph_manifest: SubgraphManifest,
+        data_source: DataSource,
+    ) -> Result<Self::Host, Error>;
 }
@@ -3,7 +3,7 @@ mod manager;
 mod provider;
 mod registry;
 
-pub use self::host::{RuntimeHost, RuntimeHostBuilder, RuntimeHostEvent};
+pub use self::host::{HostCallTimeoutError, RuntimeHost, RuntimeHostBuilder, RuntimeHostEvent};
 pub use self::manager::RuntimeManager;
 pub use self::provider::{SchemaEvent, SubgraphProvider, SubgraphProviderEvent};
 pub use self::registry::SubgraphRegistry;
@@ -33,5 +33,6 @@ pub trait SubgraphProvider:
         name_or_id: String,
     ) -> Box<Future<Item = (), Error = SubgraphProviderError> + Send + 'static>;
 
-    fn list(&self) -> Vec<(String, String)>;
+    /// Lists the names and IDs of all deployed subgraphs, ordered by name.
+    fn list(&self) -> Result<Vec<(String, String)>, SubgraphProviderError>;
 }
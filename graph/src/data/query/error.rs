@@ -20,9 +20,21 @@ pub enum QueryExecutionError {
     AbstractTypeError(String),
     InvalidArgumentError(Pos, String, q::Value),
     MissingArgumentError(Pos, String),
+    InvalidVariableError(Pos, String, q::Value),
+    MissingVariableError(Pos, String),
     UnknownField(Pos, String, String),
     EmptyQuery,
     MultipleSubscriptionFields,
+    InvalidBlockArgument(q::Value),
+    UnknownBlock(String),
+    UnknownCursor(String),
+    BlockNotIndexed(u64),
+    TooDeep(Pos, u8),
+    TooComplex(u64, u64),
+    RangeTooLarge(Pos, String, i64, u32),
+    ResultTooBig(usize, usize),
+    Timeout,
+    ValidationError(Pos, String),
 }
 
 impl Error for QueryExecutionError {
@@ -70,6 +82,12 @@ impl fmt::Display for QueryExecutionError {
             QueryExecutionError::MissingArgumentError(_, s) => {
                 write!(f, "No value provided for required argument: {}", s)
             }
+            QueryExecutionError::InvalidVariableError(_, s, v) => {
+                write!(f, "Invalid value provided for variable \"${}\": {:?}", s, v)
+            }
+            QueryExecutionError::MissingVariableError(_, s) => {
+                write!(f, "No value provided for required variable: ${}", s)
+            }
             QueryExecutionError::UnknownField(_, t, s) => {
                 write!(f, "Type \"{}\" has no field \"{}\"", t, s)
             }
@@ -78,6 +96,42 @@ impl fmt::Display for QueryExecutionError {
                 f,
                 "Only a single top-level field is allowed in subscriptions"
             ),
+            QueryExecutionError::InvalidBlockArgument(v) => write!(
+                f,
+                "Invalid block: {:?}; expected either a block number or a block hash",
+                v
+            ),
+            QueryExecutionError::UnknownBlock(s) => write!(f, "Unknown block: {}", s),
+            QueryExecutionError::UnknownCursor(s) => {
+                write!(f, "Unknown cursor: there is no entity with ID {}", s)
+            }
+            QueryExecutionError::BlockNotIndexed(n) => {
+                write!(f, "The subgraph has not indexed block {} yet", n)
+            }
+            QueryExecutionError::TooDeep(_, max_depth) => write!(
+                f,
+                "The query is nested deeper than the limit of {} levels",
+                max_depth
+            ),
+            QueryExecutionError::TooComplex(complexity, max_complexity) => write!(
+                f,
+                "The estimated cost of the query ({}) exceeds the limit of {}",
+                complexity, max_complexity
+            ),
+            QueryExecutionError::RangeTooLarge(_, argument, value, max_value) => write!(
+                f,
+                "The value of argument \"{}\" ({}) exceeds the limit of {}",
+                argument, value, max_value
+            ),
+            QueryExecutionError::ResultTooBig(size, max_size) => write!(
+                f,
+                "The query result contains {} objects, more than the limit of {}",
+                size, max_size
+            ),
+            QueryExecutionError::Timeout => {
+                write!(f, "The query took longer than the time limit to execute")
+            }
+            QueryExecutionError::ValidationError(_, s) => write!(f, "{}", s),
         }
     }
 }
@@ -181,7 +235,12 @@ impl Serialize for QueryError {
             | QueryError::ExecutionError(QueryExecutionError::NonNullError(pos, _))
             | QueryError::ExecutionError(QueryExecutionError::ListValueError(pos, _))
             | QueryError::ExecutionError(QueryExecutionError::InvalidArgumentError(pos, _, _))
-            | QueryError::ExecutionError(QueryExecutionError::MissingArgumentError(pos, _)) => {
+            | QueryError::ExecutionError(QueryExecutionError::MissingArgumentError(pos, _))
+            | QueryError::ExecutionError(QueryExecutionError::InvalidVariableError(pos, _, _))
+            | QueryError::ExecutionError(QueryExecutionError::MissingVariableError(pos, _))
+            | QueryError::ExecutionError(QueryExecutionError::TooDeep(pos, _))
+            | QueryError::ExecutionError(QueryExecutionError::RangeTooLarge(pos, _, _, _))
+            | QueryError::ExecutionError(QueryExecutionError::ValidationError(pos, _)) => {
                 let mut location = HashMap::new();
                 location.insert("line", pos.line);
                 location.insert("column", pos.column);
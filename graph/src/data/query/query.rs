@@ -1,19 +1,57 @@
 use graphql_parser::query as q;
+use serde::de::{Deserialize, Deserializer, Error};
+use serde_json;
 use std::cmp::PartialEq;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ops::{Deref, DerefMut};
 
 use data::schema::Schema;
 
-#[derive(Deserialize)]
-#[serde(untagged, remote = "q::Value")]
-enum GraphQLValue {
-    String(String),
+/// Variable value for a GraphQL query.
+#[derive(Clone, Debug)]
+pub struct QueryVariableValue(q::Value);
+
+impl<'de> Deserialize<'de> for QueryVariableValue {
+    fn deserialize<D>(deserializer: D) -> Result<QueryVariableValue, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        json_to_graphql_value(value)
+            .map(QueryVariableValue)
+            .map_err(D::Error::custom)
+    }
 }
 
-/// Variable value for a GraphQL query.
-#[derive(Clone, Debug, Deserialize)]
-pub struct QueryVariableValue(#[serde(with = "GraphQLValue")] q::Value);
+/// Converts a JSON value into the GraphQL value it represents. Numbers that
+/// fit into 32 bits become `Int`s, all other numbers become `Float`s.
+fn json_to_graphql_value(value: serde_json::Value) -> Result<q::Value, String> {
+    Ok(match value {
+        serde_json::Value::Null => q::Value::Null,
+        serde_json::Value::Bool(b) => q::Value::Boolean(b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) if i >= i32::min_value() as i64 && i <= i32::max_value() as i64 => {
+                q::Value::Int(q::Number::from(i as i32))
+            }
+            _ => match n.as_f64() {
+                Some(f) => q::Value::Float(f),
+                None => return Err(format!("Invalid number in query variables: {}", n)),
+            },
+        },
+        serde_json::Value::String(s) => q::Value::String(s),
+        serde_json::Value::Array(values) => q::Value::List(
+            values
+                .into_iter()
+                .map(json_to_graphql_value)
+                .collect::<Result<_, _>>()?,
+        ),
+        serde_json::Value::Object(map) => q::Value::Object(
+            map.into_iter()
+                .map(|(key, value)| json_to_graphql_value(value).map(|value| (key, value)))
+                .collect::<Result<BTreeMap<_, _>, _>>()?,
+        ),
+    })
+}
 
 impl Deref for QueryVariableValue {
     type Target = q::Value;
@@ -41,6 +79,12 @@ impl<'a> From<&'a str> for QueryVariableValue {
     }
 }
 
+impl From<q::Value> for QueryVariableValue {
+    fn from(value: q::Value) -> Self {
+        QueryVariableValue(value)
+    }
+}
+
 /// Variable values for a GraphQL query.
 #[derive(Clone, Debug, Deserialize)]
 pub struct QueryVariables(HashMap<String, QueryVariableValue>);
@@ -41,3 +41,11 @@ impl From<QueryExecutionError> for QueryResult {
         result
     }
 }
+
+impl From<Vec<QueryExecutionError>> for QueryResult {
+    fn from(e: Vec<QueryExecutionError>) -> Self {
+        let mut result = Self::new(None);
+        result.errors = Some(e.into_iter().map(QueryError::from).collect());
+        result
+    }
+}
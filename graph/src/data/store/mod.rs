@@ -1,3 +1,4 @@
+use failure::Error;
 use graphql_parser::query;
 use graphql_parser::schema;
 
@@ -79,6 +80,51 @@ impl Value {
     }
 }
 
+impl Value {
+    /// Returns `true` if the value can be stored in a field of type `ty`.
+    /// Enum values and references to other entities are strings.
+    fn is_assignable(&self, schema: &schema::Document, ty: &schema::Type) -> bool {
+        use self::schema::Type::{ListType, NamedType, NonNullType};
+
+        match (self, ty) {
+            (Value::Null, NonNullType(_)) => false,
+            (value, NonNullType(ty)) => value.is_assignable(schema, ty),
+            (Value::Null, _) => true,
+
+            (Value::List(values), ListType(ty)) => {
+                values.iter().all(|value| value.is_assignable(schema, ty))
+            }
+            (_, ListType(_)) => false,
+
+            (value, NamedType(name)) => match (value, name.as_str()) {
+                (Value::String(_), ID) | (Value::String(_), "String") => true,
+                (Value::Int(_), "Int") => true,
+                (Value::Float(_), "Float") => true,
+                (Value::Bool(_), "Boolean") => true,
+                (Value::Bytes(_), BYTES_SCALAR) => true,
+                (Value::BigInt(_), BIG_INT_SCALAR) => true,
+                (_, ID) | (_, "String") | (_, "Int") | (_, "Float") | (_, "Boolean") => false,
+                (_, BYTES_SCALAR) | (_, BIG_INT_SCALAR) => false,
+                (value, name) => schema
+                    .definitions
+                    .iter()
+                    .filter_map(|definition| match definition {
+                        schema::Definition::TypeDefinition(type_definition) => {
+                            Some(type_definition)
+                        }
+                        _ => None,
+                    }).any(|type_definition| match (value, type_definition) {
+                        (Value::String(_), schema::TypeDefinition::Enum(t)) => t.name == name,
+                        (Value::String(_), schema::TypeDefinition::Object(t)) => t.name == name,
+                        (Value::String(_), schema::TypeDefinition::Interface(t)) => t.name == name,
+                        (_, schema::TypeDefinition::Scalar(t)) => t.name == name,
+                        _ => false,
+                    }),
+            },
+        }
+    }
+}
+
 impl From<Value> for query::Value {
     fn from(value: Value) -> Self {
         match value {
@@ -136,6 +182,75 @@ impl Entity {
             };
         }
     }
+
+    /// Checks that the entity conforms to the object type `entity_type` of
+    /// the schema: every attribute must be a field of the type that is not
+    /// derived, its value must have the type of the field, and none of the
+    /// non-nullable fields may be missing.
+    pub fn validate(&self, schema: &schema::Document, entity_type: &str) -> Result<(), Error> {
+        let object_type = schema
+            .definitions
+            .iter()
+            .filter_map(|definition| match definition {
+                schema::Definition::TypeDefinition(schema::TypeDefinition::Object(t)) => Some(t),
+                _ => None,
+            }).find(|object_type| object_type.name == entity_type)
+            .ok_or_else(|| format_err!("unknown entity type `{}`", entity_type))?;
+
+        for attribute in self.keys() {
+            if !object_type
+                .fields
+                .iter()
+                .any(|field| &field.name == attribute)
+            {
+                return Err(format_err!(
+                    "entity type `{}` has no field `{}`",
+                    entity_type,
+                    attribute
+                ));
+            }
+        }
+
+        for field in object_type.fields.iter() {
+            let value = self.get(&field.name).unwrap_or(&Value::Null);
+
+            // Derived fields are resolved from other entities at query time
+            if field
+                .directives
+                .iter()
+                .any(|directive| directive.name == "derivedFrom")
+            {
+                if *value != Value::Null {
+                    return Err(format_err!(
+                        "field `{}` of entity type `{}` is derived and cannot be set",
+                        field.name,
+                        entity_type
+                    ));
+                }
+                continue;
+            }
+
+            if value.is_assignable(schema, &field.field_type) {
+                continue;
+            }
+            return Err(match value {
+                Value::Null => format_err!(
+                    "missing value for non-nullable field `{}` of entity type `{}`",
+                    field.name,
+                    entity_type
+                ),
+                _ => format_err!(
+                    "value {:?} of field `{}` of entity type `{}` is not of type `{}`",
+                    value,
+                    field.name,
+                    entity_type,
+                    field.field_type
+                ),
+            });
+        }
+
+        Ok(())
+    }
 }
 
 impl Deref for Entity {
@@ -202,3 +317,63 @@ fn value_bigint() {
     );
     assert_eq!(query::Value::from(from_query), graphql_value);
 }
+
+#[test]
+fn entity_validation() {
+    let schema = ::graphql_parser::parse_schema(
+        "
+        enum Color { red, green }
+        type Thing {
+            id: ID!
+            name: String!
+            count: Int
+            colors: [Color!]
+            owner: User
+            parts: [Thing!] @derivedFrom(field: \"owner\")
+        }
+        type User {
+            id: ID!
+        }
+        ",
+    ).unwrap();
+
+    let thing = |attributes: Vec<(&str, Value)>| {
+        let mut entity = Entity::from(vec![
+            ("id", Value::from("1")),
+            ("name", Value::from("Thing 1")),
+        ]);
+        entity.merge(Entity::from(attributes));
+        entity
+    };
+    let error = |entity: Entity| {
+        entity
+            .validate(&schema, "Thing")
+            .expect_err("entity should be invalid")
+            .to_string()
+    };
+
+    assert!(thing(vec![]).validate(&schema, "Thing").is_ok());
+    let complete_thing = thing(vec![
+        ("count", Value::Int(3)),
+        ("colors", Value::List(vec![Value::from("red")])),
+        ("owner", Value::from("user 1")),
+    ]);
+    assert!(complete_thing.validate(&schema, "Thing").is_ok());
+    assert!(thing(vec![]).validate(&schema, "Unknown").is_err());
+
+    assert_eq!(
+        error(thing(vec![("nmae", Value::from("Thing 1"))])),
+        "entity type `Thing` has no field `nmae`"
+    );
+    assert_eq!(
+        error(thing(vec![("count", Value::from("3"))])),
+        "value String(\"3\") of field `count` of entity type `Thing` is not of type `Int`"
+    );
+    assert_eq!(
+        error(thing(vec![("name", Value::Null)])),
+        "missing value for non-nullable field `name` of entity type `Thing`"
+    );
+    assert!(error(thing(vec![("colors", Value::List(vec![Value::Null]))])).contains("colors"));
+    assert!(error(thing(vec![("colors", Value::from("red"))])).contains("colors"));
+    assert!(error(thing(vec![("parts", Value::List(vec![]))])).contains("derived"));
+}
@@ -9,6 +9,7 @@ use parity_wasm;
 use parity_wasm::elements::Module;
 use serde_yaml;
 use tokio::prelude::*;
+use web3::types::Address;
 
 use std::sync::Arc;
 
@@ -17,7 +18,8 @@ pub enum SubgraphProviderError {
     #[fail(display = "subgraph resolve error: {}", _0)]
     ResolveError(SubgraphManifestResolveError),
     #[fail(
-        display = "name {} is invalid, only ASCII alphanumerics, `-` and `_` are allowed",
+        display = "name {} is invalid, only ASCII alphanumerics, `-` and `_` are allowed, \
+                   and names must not start with `_`",
         _0
     )]
     InvalidName(String),
@@ -27,6 +29,13 @@ pub enum SubgraphProviderError {
     /// Occurs when a subgraph's GraphQL schema is invalid.
     #[fail(display = "GraphQL schema error: {}", _0)]
     SchemaValidationError(failure::Error),
+    /// Occurs when the data sources of a subgraph don't agree on the
+    /// Ethereum network they index.
+    #[fail(display = "Ethereum network error: {}", _0)]
+    NetworkError(failure::Error),
+    /// Occurs when subgraph deployments cannot be loaded from or saved to the store.
+    #[fail(display = "subgraph deployment store error: {}", _0)]
+    StoreError(failure::Error),
 }
 
 #[derive(Fail, Debug)]
@@ -112,6 +121,17 @@ pub struct MappingEventHandler {
     pub handler: String,
 }
 
+#[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
+pub struct MappingBlockHandler {
+    pub handler: String,
+}
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
+pub struct MappingCallHandler {
+    pub function: String,
+    pub handler: String,
+}
+
 #[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
 pub struct BaseMapping<C, W> {
     pub kind: String,
@@ -122,6 +142,10 @@ pub struct BaseMapping<C, W> {
     pub abis: Vec<BaseMappingABI<C>>,
     #[serde(rename = "eventHandlers")]
     pub event_handlers: Vec<MappingEventHandler>,
+    #[serde(rename = "blockHandlers", default)]
+    pub block_handlers: Vec<MappingBlockHandler>,
+    #[serde(rename = "callHandlers", default)]
+    pub call_handlers: Vec<MappingCallHandler>,
     #[serde(rename = "file")]
     pub runtime: W,
 }
@@ -141,6 +165,8 @@ impl UnresolvedMapping {
             entities,
             abis,
             event_handlers,
+            block_handlers,
+            call_handlers,
             runtime,
         } = self;
 
@@ -160,6 +186,8 @@ impl UnresolvedMapping {
             entities,
             abis,
             event_handlers,
+            block_handlers,
+            call_handlers,
             runtime,
         })
     }
@@ -168,6 +196,7 @@ impl UnresolvedMapping {
 #[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
 pub struct BaseDataSource<C, W> {
     pub kind: String,
+    pub network: Option<String>,
     pub name: String,
     pub source: Source,
     pub mapping: BaseMapping<C, W>,
@@ -183,12 +212,14 @@ impl UnresolvedDataSource {
     ) -> impl Future<Item = DataSource, Error = failure::Error> {
         let UnresolvedDataSource {
             kind,
+            network,
             name,
             source,
             mapping,
         } = self;
         mapping.resolve(resolver).map(|mapping| DataSource {
             kind,
+            network,
             name,
             source,
             mapping,
@@ -196,8 +227,73 @@ impl UnresolvedDataSource {
     }
 }
 
+/// The source of a data source template; the contract address is only
+/// known once a data source is created from the template.
+#[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
+pub struct TemplateSource {
+    pub abi: String,
+}
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
+pub struct BaseDataSourceTemplate<C, W> {
+    pub kind: String,
+    pub network: Option<String>,
+    pub name: String,
+    pub source: TemplateSource,
+    pub mapping: BaseMapping<C, W>,
+}
+
+pub type UnresolvedDataSourceTemplate = BaseDataSourceTemplate<Link, Link>;
+pub type DataSourceTemplate = BaseDataSourceTemplate<Contract, Module>;
+
+impl UnresolvedDataSourceTemplate {
+    pub fn resolve(
+        self,
+        resolver: &impl LinkResolver,
+    ) -> impl Future<Item = DataSourceTemplate, Error = failure::Error> {
+        let UnresolvedDataSourceTemplate {
+            kind,
+            network,
+            name,
+            source,
+            mapping,
+        } = self;
+        mapping.resolve(resolver).map(|mapping| DataSourceTemplate {
+            kind,
+            network,
+            name,
+            source,
+            mapping,
+        })
+    }
+}
+
+impl DataSourceTemplate {
+    /// Creates a data source for the contract at `address` from the template.
+    pub fn create_data_source(&self, address: Address) -> DataSource {
+        DataSource {
+            kind: self.kind.clone(),
+            network: self.network.clone(),
+            name: self.name.clone(),
+            source: Source {
+                address: format!("{:x}", address),
+                abi: self.source.abi.clone(),
+            },
+            mapping: self.mapping.clone(),
+        }
+    }
+}
+
+/// A data source that a mapping created at runtime from the template with
+/// the given name, for the contract at the given address.
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct DynamicDataSource {
+    pub template: String,
+    pub address: Address,
+}
+
 #[derive(Clone, Debug, Deserialize)]
-pub struct BaseSubgraphManifest<S, D> {
+pub struct BaseSubgraphManifest<S, D, T> {
     pub id: String,
     pub location: String,
     #[serde(rename = "specVersion")]
@@ -205,17 +301,111 @@ pub struct BaseSubgraphManifest<S, D> {
     pub schema: S,
     #[serde(rename = "dataSources")]
     pub data_sources: Vec<D>,
+    #[serde(default)]
+    pub templates: Vec<T>,
 }
 
 /// Consider two subgraphs to be equal if they come from the same IPLD link.
-impl<S, D> PartialEq for BaseSubgraphManifest<S, D> {
+impl<S, D, T> PartialEq for BaseSubgraphManifest<S, D, T> {
     fn eq(&self, other: &Self) -> bool {
         self.location == other.location
     }
 }
 
-pub type UnresolvedSubgraphManifest = BaseSubgraphManifest<SchemaData, UnresolvedDataSource>;
-pub type SubgraphManifest = BaseSubgraphManifest<Schema, DataSource>;
+pub type UnresolvedSubgraphManifest =
+    BaseSubgraphManifest<SchemaData, UnresolvedDataSource, UnresolvedDataSourceTemplate>;
+pub type SubgraphManifest = BaseSubgraphManifest<Schema, DataSource, DataSourceTemplate>;
+
+impl SubgraphManifest {
+    /// Adds data sources that were created at runtime from the templates of
+    /// the manifest to its data sources. Fails if a template doesn't exist.
+    pub fn with_dynamic_data_sources(
+        mut self,
+        dynamic_data_sources: &[DynamicDataSource],
+    ) -> Result<Self, failure::Error> {
+        for dynamic_data_source in dynamic_data_sources {
+            let data_source = self
+                .templates
+                .iter()
+                .find(|template| template.name == dynamic_data_source.template)
+                .ok_or_else(|| {
+                    format_err!(
+                        "data source template not found: {}",
+                        dynamic_data_source.template
+                    )
+                })?.create_data_source(dynamic_data_source.address);
+            self.data_sources.push(data_source);
+        }
+        Ok(self)
+    }
+
+    /// Returns the name of the Ethereum network that the subgraph indexes.
+    ///
+    /// Fails unless at least one data source or template specifies a
+    /// network and all that do specify the same one; see
+    /// `with_default_network`.
+    pub fn network_name(&self) -> Result<String, failure::Error> {
+        self.specified_network_name()?
+            .ok_or_else(|| format_err!("no data source specifies an Ethereum network"))
+    }
+
+    /// Returns the name of the Ethereum network that the data sources and
+    /// templates specify, or `None` if none of them specify one.
+    ///
+    /// Fails if they specify different networks.
+    pub fn specified_network_name(&self) -> Result<Option<String>, failure::Error> {
+        let mut networks = self
+            .data_sources
+            .iter()
+            .filter_map(|data_source| data_source.network.as_ref())
+            .chain(
+                self.templates
+                    .iter()
+                    .filter_map(|template| template.network.as_ref()),
+            );
+
+        let network = match networks.next() {
+            Some(network) => network,
+            None => return Ok(None),
+        };
+
+        match networks.find(|other| other != &network) {
+            Some(other) => Err(format_err!(
+                "data sources index different Ethereum networks: {}, {}",
+                network,
+                other
+            )),
+            None => Ok(Some(network.clone())),
+        }
+    }
+
+    /// Makes the data sources and templates that don't specify an Ethereum
+    /// network index the network that the others specify, or
+    /// `default_network_name` if none of them specify one.
+    pub fn with_default_network(
+        mut self,
+        default_network_name: Option<&str>,
+    ) -> Result<Self, failure::Error> {
+        let network_name = match self.specified_network_name()? {
+            Some(network_name) => network_name,
+            None => default_network_name.map(String::from).ok_or_else(|| {
+                format_err!(
+                    "no data source specifies an Ethereum network and there is no default network"
+                )
+            })?,
+        };
+
+        for data_source in self.data_sources.iter_mut() {
+            data_source
+                .network
+                .get_or_insert_with(|| network_name.clone());
+        }
+        for template in self.templates.iter_mut() {
+            template.network.get_or_insert_with(|| network_name.clone());
+        }
+        Ok(self)
+    }
+}
 
 impl SubgraphManifest {
     /// Entry point for resolving a subgraph definition.
@@ -275,6 +465,7 @@ impl UnresolvedSubgraphManifest {
             spec_version,
             schema,
             data_sources,
+            templates,
         } = self;
 
         // resolve each data set
@@ -283,13 +474,20 @@ impl UnresolvedSubgraphManifest {
                 .into_iter()
                 .map(|data_set| data_set.resolve(resolver)),
         ).collect()
-        .join(schema.resolve(id.clone(), name, resolver))
-        .map(|(data_sources, schema)| SubgraphManifest {
+        .join3(
+            stream::futures_ordered(
+                templates
+                    .into_iter()
+                    .map(|template| template.resolve(resolver)),
+            ).collect(),
+            schema.resolve(id.clone(), name, resolver),
+        ).map(|(data_sources, templates, schema)| SubgraphManifest {
             id,
             location,
             spec_version,
             schema,
             data_sources,
+            templates,
         })
     }
 }
@@ -13,7 +13,11 @@ pub extern crate slog;
 #[macro_use]
 extern crate failure;
 extern crate ipfs_api;
+#[macro_use]
+extern crate lazy_static;
 extern crate parity_wasm;
+#[macro_use]
+pub extern crate prometheus;
 pub extern crate serde_json;
 extern crate slog_async;
 extern crate slog_term;
@@ -50,20 +54,26 @@ pub mod prelude {
 
     pub use std::sync::Arc;
 
-    pub use components::ethereum::{EthereumAdapter, EthereumBlockPointer};
+    pub use components::ethereum::{
+        BlockStream, BlockStreamBuilder, EthereumAdapter, EthereumBlockHeader,
+        EthereumBlockPointer, EthereumBlockWithLogs, EthereumCall, EthereumCallFilter,
+        EthereumLogFilter,
+    };
     pub use components::graphql::{GraphQlRunner, QueryResultFuture, SubscriptionResultFuture};
     pub use components::link_resolver::LinkResolver;
     pub use components::server::admin::JsonRpcServer;
+    pub use components::server::metrics::MetricsServer;
     pub use components::server::query::GraphQLServer;
     pub use components::server::subscription::SubscriptionServer;
     pub use components::store::{
-        BasicStore, BlockStore, EntityChange, EntityChangeOperation, EntityChangeStream,
-        EventSource, Store, StoreFilter, StoreKey, StoreOrder, StoreQuery, StoreRange,
-        SubgraphEntityPair,
+        BasicStore, BlockStore, EntityCache, EntityChange, EntityChangeOperation,
+        EntityChangeStream, EntityOperation, EventSource, Store, StoreFilter, StoreKey, StoreOrder,
+        StoreQuery, StoreRange, SubgraphDeployment, SubgraphDeploymentStatus,
+        SubgraphDeploymentStore, SubgraphEntityPair, SubgraphHealth, SubgraphIndexingStatus,
     };
     pub use components::subgraph::{
-        RuntimeHost, RuntimeHostBuilder, RuntimeHostEvent, RuntimeManager, SchemaEvent,
-        SubgraphProvider, SubgraphProviderEvent, SubgraphRegistry,
+        HostCallTimeoutError, RuntimeHost, RuntimeHostBuilder, RuntimeHostEvent, RuntimeManager,
+        SchemaEvent, SubgraphProvider, SubgraphProviderEvent, SubgraphRegistry,
     };
     pub use components::{EventConsumer, EventProducer};
 
@@ -74,7 +84,8 @@ pub mod prelude {
     pub use data::schema::Schema;
     pub use data::store::{Attribute, Entity, Value};
     pub use data::subgraph::{
-        DataSource, Link, SubgraphManifest, SubgraphManifestResolveError, SubgraphProviderError,
+        DataSource, DataSourceTemplate, DynamicDataSource, Link, SubgraphManifest,
+        SubgraphManifestResolveError, SubgraphProviderError,
     };
     pub use data::subscription::{
         QueryResultStream, Subscription, SubscriptionError, SubscriptionResult,
@@ -1,4 +1,4 @@
-use ethabi::{Contract, Event};
+use ethabi::{Contract, Event, Function};
 use tiny_keccak::Keccak;
 use web3::types::H256;
 
@@ -13,6 +13,14 @@ pub fn string_to_h256(s: &str) -> H256 {
     H256::from_slice(&result)
 }
 
+/// Returns the 4-byte selector that identifies a contract function with the
+/// given signature (e.g. `transfer(address,uint256)`) in call data.
+pub fn function_selector(signature: &str) -> [u8; 4] {
+    let mut selector = [0u8; 4];
+    selector.copy_from_slice(&string_to_h256(signature)[..4]);
+    selector
+}
+
 /// Returns the contract event with the given signature, if it exists.
 pub fn contract_event_with_signature<'a>(
     contract: &'a Contract,
@@ -23,3 +31,28 @@ pub fn contract_event_with_signature<'a>(
         .filter(|event| event.signature() == string_to_h256(signature))
         .next()
 }
+
+/// Returns the contract function with the given signature, if it exists.
+pub fn contract_function_with_signature<'a>(
+    contract: &'a Contract,
+    signature: &str,
+) -> Option<&'a Function> {
+    contract
+        .functions()
+        .find(|function| function_signature(function) == signature.replace(" ", ""))
+}
+
+/// Builds the signature of a contract function from its name and input types,
+/// e.g. `transfer(address,uint256)`.
+fn function_signature(function: &Function) -> String {
+    format!(
+        "{}({})",
+        function.name,
+        function
+            .inputs
+            .iter()
+            .map(|input| input.kind.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    )
+}
@@ -0,0 +1,73 @@
+use prometheus::{Counter, CounterVec, Gauge, GaugeVec, Histogram, HistogramVec};
+
+lazy_static! {
+    /// How long it takes the block ingestor to poll the Ethereum node of a
+    /// network for the latest block and ingest it.
+    pub static ref BLOCK_INGESTOR_POLL_DURATION: HistogramVec = register_histogram_vec!(
+        "ethereum_block_ingestor_poll_duration_seconds",
+        "Time it takes to poll an Ethereum node for the latest block and ingest it",
+        &["network"]
+    ).unwrap();
+
+    /// How many blocks the chain head in the store is behind the latest
+    /// block of the Ethereum node of a network.
+    pub static ref BLOCK_INGESTOR_HEAD_LAG: GaugeVec = register_gauge_vec!(
+        "ethereum_block_ingestor_head_lag_blocks",
+        "Number of blocks that the stored chain head is behind the Ethereum node",
+        &["network"]
+    ).unwrap();
+
+    /// The number of Ethereum events that the mappings of a subgraph have
+    /// handled successfully.
+    pub static ref SUBGRAPH_EVENTS_PROCESSED: CounterVec = register_counter_vec!(
+        "subgraph_ethereum_events_processed_total",
+        "Number of Ethereum events handled by the mappings of a subgraph",
+        &["subgraph"]
+    ).unwrap();
+
+    /// How long the event handlers of a subgraph take to run.
+    pub static ref SUBGRAPH_EVENT_HANDLER_DURATION: HistogramVec = register_histogram_vec!(
+        "subgraph_ethereum_event_handler_duration_seconds",
+        "Time it takes an event handler of a subgraph to handle an Ethereum event",
+        &["subgraph", "handler"]
+    ).unwrap();
+
+    /// How long contract calls made by mappings through `ethereum.call` take.
+    pub static ref ETHEREUM_CALL_DURATION: Histogram = register_histogram!(
+        "ethereum_call_duration_seconds",
+        "Time it takes to make a contract call with ethereum.call"
+    ).unwrap();
+
+    /// The number of contract calls made through `ethereum.call` that failed.
+    pub static ref ETHEREUM_CALL_ERRORS: Counter = register_counter!(
+        "ethereum_call_errors_total",
+        "Number of contract calls made with ethereum.call that failed"
+    ).unwrap();
+
+    /// How long store operations take, by operation.
+    pub static ref STORE_OPERATION_DURATION: HistogramVec = register_histogram_vec!(
+        "store_operation_duration_seconds",
+        "Time it takes to get, set, delete or find entities in the store",
+        &["operation"]
+    ).unwrap();
+
+    /// The number of GraphQL queries that were run, by whether they
+    /// succeeded or failed.
+    pub static ref GRAPHQL_QUERIES: CounterVec = register_counter_vec!(
+        "graphql_queries_total",
+        "Number of GraphQL queries run against subgraphs",
+        &["status"]
+    ).unwrap();
+
+    /// How long GraphQL queries take to run.
+    pub static ref GRAPHQL_QUERY_DURATION: Histogram = register_histogram!(
+        "graphql_query_duration_seconds",
+        "Time it takes to run a GraphQL query against a subgraph"
+    ).unwrap();
+
+    /// The number of GraphQL subscriptions that are currently active.
+    pub static ref GRAPHQL_SUBSCRIPTIONS_ACTIVE: Gauge = register_gauge!(
+        "graphql_subscriptions_active",
+        "Number of active GraphQL subscriptions over WebSockets"
+    ).unwrap();
+}
@@ -6,3 +6,6 @@ pub mod log;
 
 /// Utils for working with ethereum data types
 pub mod ethereum;
+
+/// Prometheus metrics reported by the components of the node.
+pub mod metrics;
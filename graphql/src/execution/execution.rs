@@ -3,6 +3,8 @@ use graphql_parser::schema as s;
 use indexmap::IndexMap;
 use std::cmp;
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
 
 use graph::prelude::*;
 
@@ -33,6 +35,15 @@ where
     pub fields: Vec<&'a q::Field>,
     /// Whether or not we're executing an introspection query
     pub introspecting: bool,
+    /// The `block` argument of the closest enclosing field that has one;
+    /// nested fields are resolved against the same block.
+    pub block: Option<q::Value>,
+    /// The time by which the execution has to finish, if any.
+    pub deadline: Option<Instant>,
+    /// The number of objects in the result so far, shared by all fields.
+    pub result_size: Arc<AtomicUsize>,
+    /// The number of objects that the result may contain, if limited.
+    pub max_result_size: Option<usize>,
 }
 
 impl<'a, R1, R2> ExecutionContext<'a, R1, R2>
@@ -46,6 +57,41 @@ where
         ctx.fields.push(field);
         ctx
     }
+
+    /// Returns whether the execution has run past its deadline.
+    pub fn is_past_deadline(&self) -> bool {
+        self.deadline
+            .map_or(false, |deadline| Instant::now() > deadline)
+    }
+
+    /// Fails if the result contains more objects than it may.
+    pub fn check_result_size(&self) -> Result<(), QueryExecutionError> {
+        let size = self.result_size.load(Ordering::SeqCst);
+        match self.max_result_size {
+            Some(max_size) if size > max_size => {
+                Err(QueryExecutionError::ResultTooBig(size, max_size))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Adds the objects of a resolved value to the size of the result, and
+    /// fails if the result now contains more objects than it may.
+    fn add_to_result_size(&self, value: &q::Value) -> Result<(), QueryExecutionError> {
+        self.result_size
+            .fetch_add(count_objects(value), Ordering::SeqCst);
+        self.check_result_size()
+    }
+}
+
+/// Returns the number of objects that a resolved value adds to the result:
+/// one for an object, or one per object in a list.
+fn count_objects(value: &q::Value) -> usize {
+    match value {
+        q::Value::Object(_) => 1,
+        q::Value::List(values) => values.iter().map(count_objects).sum(),
+        _ => 0,
+    }
 }
 
 /// Executes the root selection set of a query.
@@ -73,7 +119,7 @@ where
 ///
 /// Allows passing in a parent value during recursive processing of objects and their fields.
 pub fn execute_selection_set<'a, R1, R2>(
-    mut ctx: ExecutionContext<'a, R1, R2>,
+    ctx: ExecutionContext<'a, R1, R2>,
     selection_set: &'a q::SelectionSet,
     object_type: &s::ObjectType,
     object_value: &Option<q::Value>,
@@ -82,27 +128,105 @@ where
     R1: Resolver,
     R2: Resolver,
 {
-    let mut result = QueryResult::new(None);
-    let mut result_map: BTreeMap<String, q::Value> = BTreeMap::new();
-
     // Group fields with the same response key, so we can execute them together
     let grouped_field_set = collect_fields(ctx.clone(), object_type, selection_set, None);
 
+    Ok(execute_fields(
+        ctx,
+        object_type,
+        object_value,
+        &grouped_field_set,
+        HashMap::new(),
+    ))
+}
+
+/// Executes the same selection set for several objects of the given type.
+///
+/// Fields that refer to other objects are resolved for all objects at once
+/// where the resolver supports it, instead of with one lookup per object.
+fn execute_selection_set_for_objects<'a, R1, R2>(
+    ctx: ExecutionContext<'a, R1, R2>,
+    selection_set: &'a q::SelectionSet,
+    object_type: &s::ObjectType,
+    object_values: Vec<q::Value>,
+) -> Vec<q::Value>
+where
+    R1: Resolver,
+    R2: Resolver,
+{
+    let grouped_field_set = collect_fields(ctx.clone(), object_type, selection_set, None);
+
+    // Resolve and complete the fields that can be resolved in batches
+    let mut prefetched_values: Vec<HashMap<&String, Result<q::Value, QueryExecutionError>>> =
+        object_values.iter().map(|_| HashMap::new()).collect();
+    for (response_key, fields) in grouped_field_set.iter() {
+        if let Some(values) = prefetch_field(ctx.clone(), object_type, &object_values, fields) {
+            for (prefetched, value) in prefetched_values.iter_mut().zip(values) {
+                prefetched.insert(*response_key, value);
+            }
+        }
+    }
+
+    object_values
+        .into_iter()
+        .zip(prefetched_values)
+        .map(|(object_value, prefetched)| {
+            execute_fields(
+                ctx.clone(),
+                object_type,
+                &Some(object_value),
+                &grouped_field_set,
+                prefetched,
+            ).data
+            .unwrap_or(q::Value::Null)
+        }).collect()
+}
+
+/// Executes grouped fields against an object value, except for the fields
+/// that have already been resolved and completed.
+fn execute_fields<'a, R1, R2>(
+    mut ctx: ExecutionContext<'a, R1, R2>,
+    object_type: &s::ObjectType,
+    object_value: &Option<q::Value>,
+    grouped_field_set: &IndexMap<&'a String, Vec<&'a q::Field>>,
+    mut prefetched_values: HashMap<&'a String, Result<q::Value, QueryExecutionError>>,
+) -> QueryResult
+where
+    R1: Resolver,
+    R2: Resolver,
+{
+    let mut result = QueryResult::new(None);
+    let mut result_map: BTreeMap<String, q::Value> = BTreeMap::new();
+
     // Process all field groups in order
-    for (response_key, fields) in grouped_field_set {
+    for (response_key, fields) in grouped_field_set.iter() {
         // If the field exists on the object, execute it and add its result to the result map
         if let Some((ref field, introspecting)) =
             get_field_type(ctx.clone(), object_type, &fields[0].name)
         {
-            // Push the new field onto the context's field stack
-            let mut ctx = ctx.for_field(&fields[0]);
+            let value = match prefetched_values.remove(response_key) {
+                Some(value) => value,
+                None => {
+                    // Push the new field onto the context's field stack
+                    let mut ctx = ctx.for_field(fields[0]);
 
-            // Remember whether or not we're introspecting now
-            ctx.introspecting = introspecting;
+                    // Remember whether or not we're introspecting now
+                    ctx.introspecting = introspecting;
+
+                    execute_field(
+                        ctx,
+                        object_type,
+                        object_value,
+                        fields[0],
+                        field,
+                        fields.clone(),
+                    )
+                }
+            };
 
-            match execute_field(ctx, object_type, object_value, &fields[0], field, fields) {
+            match value {
                 Ok(v) => {
-                    result_map.insert(response_key.to_owned(), v);
+                    result_map.insert(response_key.to_string(), v);
                 }
                 Err(e) => {
                     result.add_error(QueryError::from(e));
@@ -116,7 +240,178 @@ where
         result.data = Some(q::Value::Object(result_map));
     }
 
-    Ok(result)
+    result
+}
+
+/// Resolves and completes a field for several objects at once, if the field
+/// refers to other objects and the resolver can resolve it in a batch.
+/// Returns one value per object.
+fn prefetch_field<'a, R1, R2>(
+    mut ctx: ExecutionContext<'a, R1, R2>,
+    object_type: &s::ObjectType,
+    object_values: &Vec<q::Value>,
+    fields: &Vec<&'a q::Field>,
+) -> Option<Vec<Result<q::Value, QueryExecutionError>>>
+where
+    R1: Resolver,
+    R2: Resolver,
+{
+    let field = fields[0];
+    let (field_definition, introspecting) = get_field_type(ctx.clone(), object_type, &field.name)?;
+    if introspecting {
+        return None;
+    }
+
+    // Only fields that refer to other objects require lookups
+    let field_object_type =
+        match sast::get_type_definition_from_field_type(&ctx.schema.document, field_definition) {
+            Some(s::TypeDefinition::Object(t)) => t,
+            _ => return None,
+        };
+
+    let mut ctx = ctx.for_field(field);
+    let block_argument = q::Name::from("block");
+    let mut argument_values = coerce_argument_values(ctx.clone(), object_type, field).ok()?;
+    inherit_block(&mut ctx, &block_argument, &mut argument_values);
+
+    let values = ctx
+        .resolver
+        .resolve_field_batch(
+            object_values,
+            &field.name,
+            field_definition,
+            field_object_type,
+            &argument_values,
+        ).ok()
+        .and_then(|values| values)
+        .filter(|values| values.len() == object_values.len())?;
+
+    // Once the result is too big, the field fails for each object when it
+    // is executed for the objects one by one
+    if values
+        .iter()
+        .any(|value| ctx.add_to_result_size(value).is_err())
+    {
+        return None;
+    }
+
+    Some(complete_values(
+        ctx,
+        field,
+        &field_definition.field_type,
+        field_object_type,
+        fields.clone(),
+        values,
+    ))
+}
+
+/// Completes the values of a field of several objects at once, so that the
+/// fields of the objects they refer to can be resolved in batches as well.
+fn complete_values<'a, R1, R2>(
+    ctx: ExecutionContext<'a, R1, R2>,
+    field: &'a q::Field,
+    field_type: &'a s::Type,
+    object_type: &s::ObjectType,
+    fields: Vec<&'a q::Field>,
+    values: Vec<q::Value>,
+) -> Vec<Result<q::Value, QueryExecutionError>>
+where
+    R1: Resolver,
+    R2: Resolver,
+{
+    let mut objects = vec![];
+    for value in values.iter() {
+        match value {
+            q::Value::List(items) => {
+                objects.extend(items.iter().filter(|item| is_object(item)).cloned())
+            }
+            q::Value::Object(_) => objects.push(value.clone()),
+            _ => (),
+        }
+    }
+
+    let mut completed_objects =
+        execute_selection_set_for_objects(ctx, &merge_selection_sets(fields), object_type, objects)
+            .into_iter();
+
+    // Put the completed objects back in place
+    let mut completed_values = Vec::with_capacity(values.len());
+    for value in values.into_iter() {
+        let value = match value {
+            q::Value::List(items) => {
+                let mut completed_items = Vec::with_capacity(items.len());
+                for item in items.into_iter() {
+                    completed_items.push(match item {
+                        q::Value::Object(_) => completed_objects.next().unwrap(),
+                        item => item,
+                    });
+                }
+                q::Value::List(completed_items)
+            }
+            q::Value::Object(_) => completed_objects.next().unwrap(),
+            value => value,
+        };
+        completed_values.push(check_completed_value(field, field_type, value));
+    }
+    completed_values
+}
+
+/// Checks that a completed value is not null where its type does not allow
+/// that, and that it is a list where its type is a list type.
+fn check_completed_value(
+    field: &q::Field,
+    field_type: &s::Type,
+    value: q::Value,
+) -> Result<q::Value, QueryExecutionError> {
+    match field_type {
+        s::Type::NonNullType(inner_type) => match value {
+            q::Value::Null => Err(QueryExecutionError::NonNullError(
+                field.position,
+                field.name.to_string(),
+            )),
+            value => check_completed_value(field, inner_type, value),
+        },
+        s::Type::ListType(inner_type) => match value {
+            q::Value::Null => Ok(q::Value::Null),
+            q::Value::List(values) => values
+                .into_iter()
+                .map(|value| check_completed_value(field, inner_type, value))
+                .collect::<Result<Vec<q::Value>, QueryExecutionError>>()
+                .map(q::Value::List),
+            _ => Err(QueryExecutionError::ListValueError(
+                field.position,
+                field.name.to_string(),
+            )),
+        },
+        s::Type::NamedType(_) => Ok(value),
+    }
+}
+
+fn is_object(value: &q::Value) -> bool {
+    match value {
+        q::Value::Object(_) => true,
+        _ => false,
+    }
+}
+
+/// Passes the block that a field is resolved against on to its nested
+/// fields, or resolves the field against the block of the closest enclosing
+/// field that has one.
+fn inherit_block<'a, 'b, R1, R2>(
+    ctx: &mut ExecutionContext<'a, R1, R2>,
+    block_argument: &'b q::Name,
+    argument_values: &mut HashMap<&'b q::Name, q::Value>,
+) where
+    R1: Resolver,
+    R2: Resolver,
+{
+    let block = argument_values.get(block_argument).cloned();
+    match block {
+        Some(block) => ctx.block = Some(block),
+        None => if let Some(ref block) = ctx.block {
+            argument_values.insert(block_argument, block.clone());
+        },
+    }
 }
 
 /// Collects fields of a selection set.
@@ -264,7 +559,7 @@ where
 
 /// Executes a field.
 fn execute_field<'a, R1, R2>(
-    ctx: ExecutionContext<'a, R1, R2>,
+    mut ctx: ExecutionContext<'a, R1, R2>,
     object_type: &s::ObjectType,
     object_value: &Option<q::Value>,
     field: &'a q::Field,
@@ -275,18 +570,30 @@ where
     R1: Resolver,
     R2: Resolver,
 {
-    coerce_argument_values(ctx.clone(), object_type, field)
-        .and_then(|argument_values| {
-            resolve_field_value(
-                ctx.clone(),
-                object_type,
-                object_value,
-                field,
-                field_definition,
-                &field_definition.field_type,
-                &argument_values,
-            )
-        }).and_then(|value| complete_value(ctx, field, &field_definition.field_type, fields, value))
+    // Stop resolving fields once the time for the execution is up
+    if ctx.is_past_deadline() {
+        return Err(QueryExecutionError::Timeout);
+    }
+
+    // Stop resolving fields once the result is too big
+    ctx.check_result_size()?;
+
+    let block_argument = q::Name::from("block");
+    let mut argument_values = coerce_argument_values(ctx.clone(), object_type, field)?;
+    inherit_block(&mut ctx, &block_argument, &mut argument_values);
+
+    resolve_field_value(
+        ctx.clone(),
+        object_type,
+        object_value,
+        field,
+        field_definition,
+        &field_definition.field_type,
+        &argument_values,
+    ).and_then(|value| {
+        ctx.add_to_result_size(&value)?;
+        complete_value(ctx, field, &field_definition.field_type, fields, value)
+    })
 }
 
 /// Resolves the value of a field.
@@ -362,21 +669,21 @@ where
         // Let the resolver decide how the field (with the given object type)
         // is resolved into an entity based on the (potential) parent object
         s::TypeDefinition::Object(t) => if ctx.introspecting {
-            Ok(ctx.introspection_resolver.resolve_object(
+            ctx.introspection_resolver.resolve_object(
                 object_value,
                 &field.name,
                 field_definition,
                 t,
                 argument_values,
-            ))
+            )
         } else {
-            Ok(ctx.resolver.resolve_object(
+            ctx.resolver.resolve_object(
                 object_value,
                 &field.name,
                 field_definition,
                 t,
                 argument_values,
-            ))
+            )
         },
 
         // Let the resolver decide how values in the resolved object value
@@ -452,21 +759,21 @@ where
                 // Let the resolver decide how the list field (with the given item object type)
                 // is resolved into a entities based on the (potential) parent object
                 s::TypeDefinition::Object(t) => if ctx.introspecting {
-                    Ok(ctx.introspection_resolver.resolve_objects(
+                    ctx.introspection_resolver.resolve_objects(
                         object_value,
                         &field.name,
                         field_definition,
                         t,
                         argument_values,
-                    ))
+                    )
                 } else {
-                    Ok(ctx.resolver.resolve_objects(
+                    ctx.resolver.resolve_objects(
                         object_value,
                         &field.name,
                         field_definition,
                         t,
                         argument_values,
-                    ))
+                    )
                 },
 
                 // Let the resolver decide how values in the resolved object value
@@ -540,19 +847,51 @@ where
     // Complete list values
     if let s::Type::ListType(inner_type) = field_type {
         return match resolved_value {
-            // Complete list values individually
             q::Value::List(values) => {
-                let mut out = Vec::with_capacity(values.len());
-                for value in values.into_iter() {
-                    out.push(complete_value(
-                        ctx.clone(),
-                        field,
-                        inner_type,
-                        fields.clone(),
-                        value,
-                    )?);
+                let object_type = if ctx.introspecting {
+                    None
+                } else {
+                    get_object_item_type(&ctx.schema.document, inner_type)
+                };
+
+                match object_type {
+                    // Complete lists of objects together, so that the fields
+                    // of all objects can be resolved at once
+                    Some(object_type) if values.iter().all(is_object) => {
+                        let out = execute_selection_set_for_objects(
+                            ctx.clone(),
+                            &merge_selection_sets(fields),
+                            object_type,
+                            values,
+                        );
+
+                        // Objects without any field values complete to null
+                        match inner_type.as_ref() {
+                            s::Type::NonNullType(_) if out.contains(&q::Value::Null) => {
+                                Err(QueryExecutionError::NonNullError(
+                                    field.position,
+                                    field.name.to_string(),
+                                ))
+                            }
+                            _ => Ok(q::Value::List(out)),
+                        }
+                    }
+
+                    // Complete list values individually
+                    _ => {
+                        let mut out = Vec::with_capacity(values.len());
+                        for value in values.into_iter() {
+                            out.push(complete_value(
+                                ctx.clone(),
+                                field,
+                                inner_type,
+                                fields.clone(),
+                                value,
+                            )?);
+                        }
+                        Ok(q::Value::List(out))
+                    }
                 }
-                Ok(q::Value::List(out))
             }
 
             // Return field error if the resolved value for the list is not a list
@@ -634,6 +973,21 @@ where
     }
 }
 
+/// Returns the object type of the items of a list type, if they are objects.
+fn get_object_item_type<'a>(
+    schema: &'a s::Document,
+    item_type: &s::Type,
+) -> Option<&'a s::ObjectType> {
+    match item_type {
+        s::Type::NonNullType(inner_type) => get_object_item_type(schema, inner_type),
+        s::Type::NamedType(name) => match sast::get_named_type(schema, name) {
+            Some(s::TypeDefinition::Object(t)) => Some(t),
+            _ => None,
+        },
+        s::Type::ListType(_) => None,
+    }
+}
+
 /// Resolves an abstract type (interface, union) into an object type based on the given value.
 fn resolve_abstract_type<'a, R1, R2>(
     ctx: ExecutionContext<'a, R1, R2>,
@@ -703,8 +1057,8 @@ where
     if let Some(argument_definitions) = sast::get_argument_definitions(object_type, &field.name) {
         for argument_def in argument_definitions.iter() {
             match qast::get_argument_value(&field.arguments, &argument_def.name) {
-                // We don't support variables yet
-                Some(q::Value::Variable(_)) => unimplemented!(),
+                // Variables are replaced with their values before execution
+                Some(q::Value::Variable(_)) => unreachable!(),
 
                 // There is no value, either use the default or fail
                 None => {
@@ -14,7 +14,7 @@ pub trait Resolver: Clone + Send + Sync {
         field_definition: &s::Field,
         object_type: &s::ObjectType,
         arguments: &HashMap<&q::Name, q::Value>,
-    ) -> q::Value;
+    ) -> Result<q::Value, QueryExecutionError>;
 
     /// Resolves an entity referenced by a parent object.
     fn resolve_object(
@@ -24,7 +24,22 @@ pub trait Resolver: Clone + Send + Sync {
         field_definition: &s::Field,
         object_type: &s::ObjectType,
         arguments: &HashMap<&q::Name, q::Value>,
-    ) -> q::Value;
+    ) -> Result<q::Value, QueryExecutionError>;
+
+    /// Resolves the entities referenced by a field of many parent objects
+    /// at once. Returns the values of the field for the parents, in the same
+    /// order, or `None` if the field cannot be resolved in a batch, in which
+    /// case it is resolved for each parent separately.
+    fn resolve_field_batch(
+        &self,
+        _parents: &[q::Value],
+        _field: &q::Name,
+        _field_definition: &s::Field,
+        _object_type: &s::ObjectType,
+        _arguments: &HashMap<&q::Name, q::Value>,
+    ) -> Result<Option<Vec<q::Value>>, QueryExecutionError> {
+        Ok(None)
+    }
 
     /// Resolves an enum value for a given enum type.
     fn resolve_enum_value(&self, enum_type: &s::EnumType, value: Option<&q::Value>) -> q::Value {
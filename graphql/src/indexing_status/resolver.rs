@@ -0,0 +1,165 @@
+use graphql_parser::{query as q, schema as s};
+use std::collections::HashMap;
+
+use graph::prelude::*;
+
+use prelude::*;
+
+fn object_field<'a>(object: &'a Option<q::Value>, field: &str) -> Option<&'a q::Value> {
+    object
+        .as_ref()
+        .and_then(|object| match object {
+            q::Value::Object(ref data) => Some(data),
+            _ => None,
+        })
+        .and_then(|data| data.get(field))
+}
+
+fn block_object(block_ptr: EthereumBlockPointer) -> q::Value {
+    object_value(vec![
+        ("hash", q::Value::String(format!("{:#x}", block_ptr.hash))),
+        ("number", q::Value::String(block_ptr.number.to_string())),
+    ])
+}
+
+fn indexing_status_object(name: String, status: SubgraphIndexingStatus) -> q::Value {
+    let error = match status.health {
+        SubgraphHealth::Failed {
+            ref error,
+            block_ptr,
+        } => object_value(vec![
+            ("message", q::Value::String(error.clone())),
+            ("block", block_ptr.map_or(q::Value::Null, block_object)),
+        ]),
+        _ => q::Value::Null,
+    };
+
+    object_value(vec![
+        ("name", q::Value::String(name)),
+        ("subgraphId", q::Value::String(status.subgraph_id)),
+        ("network", q::Value::String(status.network_name)),
+        (
+            "latestBlock",
+            status.latest_block_ptr.map_or(q::Value::Null, block_object),
+        ),
+        (
+            "chainHeadBlock",
+            status
+                .chain_head_block_ptr
+                .map_or(q::Value::Null, block_object),
+        ),
+        (
+            "entityCount",
+            q::Value::String(status.entity_count.to_string()),
+        ),
+        ("health", q::Value::Enum(status.health.to_string())),
+        ("error", error),
+    ])
+}
+
+/// A resolver for the indexing status API, which reports how far indexing
+/// the deployed subgraphs has progressed.
+pub struct IndexingStatusResolver<S> {
+    logger: Logger,
+    store: Arc<S>,
+}
+
+impl<S> Clone for IndexingStatusResolver<S>
+where
+    S: Store + SubgraphDeploymentStore,
+{
+    fn clone(&self) -> Self {
+        IndexingStatusResolver {
+            logger: self.logger.clone(),
+            store: self.store.clone(),
+        }
+    }
+}
+
+impl<S> IndexingStatusResolver<S>
+where
+    S: Store + SubgraphDeploymentStore,
+{
+    pub fn new(logger: &Logger, store: Arc<S>) -> Self {
+        IndexingStatusResolver {
+            logger: logger.new(o!("component" => "IndexingStatusResolver")),
+            store,
+        }
+    }
+
+    /// Resolves the indexing status of all deployments, or only of the
+    /// deployment with the name given in the arguments. Deployments whose
+    /// subgraph the runtime has not started indexing yet are left out.
+    fn indexing_statuses(&self, arguments: &HashMap<&q::Name, q::Value>) -> q::Value {
+        let name = match arguments.get(&String::from("name")) {
+            Some(q::Value::String(name)) => Some(name),
+            _ => None,
+        };
+
+        let deployments = match self.store.subgraph_deployments() {
+            Ok(deployments) => deployments,
+            Err(e) => {
+                error!(self.logger, "Failed to load subgraph deployments";
+                       "error" => e.to_string());
+                return q::Value::Null;
+            }
+        };
+
+        q::Value::List(
+            deployments
+                .into_iter()
+                .filter(|deployment| name.map_or(true, |name| &deployment.name == name))
+                .filter_map(|deployment| {
+                    match self
+                        .store
+                        .subgraph_indexing_status(deployment.subgraph_id.clone())
+                    {
+                        Ok(status) => {
+                            status.map(|status| indexing_status_object(deployment.name, status))
+                        }
+                        Err(e) => {
+                            error!(self.logger, "Failed to load subgraph indexing status";
+                                   "subgraph_id" => &deployment.subgraph_id,
+                                   "error" => e.to_string());
+                            None
+                        }
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+impl<S> Resolver for IndexingStatusResolver<S>
+where
+    S: Store + SubgraphDeploymentStore,
+{
+    fn resolve_objects(
+        &self,
+        parent: &Option<q::Value>,
+        field: &q::Name,
+        _field_definition: &s::Field,
+        _object_type: &s::ObjectType,
+        arguments: &HashMap<&q::Name, q::Value>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(match field.as_str() {
+            "subgraphIndexingStatus" => self.indexing_statuses(arguments),
+            _ => object_field(parent, field.as_str())
+                .map(|value| value.clone())
+                .unwrap_or(q::Value::Null),
+        })
+    }
+
+    fn resolve_object(
+        &self,
+        parent: &Option<q::Value>,
+        field: &q::Name,
+        _field_definition: &s::Field,
+        _object_type: &s::ObjectType,
+        _arguments: &HashMap<&q::Name, q::Value>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(object_field(parent, field.as_str())
+            .map(|value| value.clone())
+            .unwrap_or(q::Value::Null))
+    }
+}
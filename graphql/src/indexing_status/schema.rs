@@ -0,0 +1,46 @@
+use graphql_parser;
+
+use graph::prelude::*;
+
+const INDEXING_STATUS_SCHEMA: &'static str = "
+scalar BigInt
+scalar String
+
+type Query {
+  subgraphIndexingStatus(name: String): [SubgraphIndexingStatus!]!
+}
+
+type SubgraphIndexingStatus {
+  name: String!
+  subgraphId: String!
+  network: String!
+  latestBlock: EthereumBlock
+  chainHeadBlock: EthereumBlock
+  entityCount: BigInt!
+  health: SubgraphHealth!
+  error: SubgraphError
+}
+
+type EthereumBlock {
+  hash: String!
+  number: BigInt!
+}
+
+type SubgraphError {
+  message: String!
+  block: EthereumBlock
+}
+
+enum SubgraphHealth {
+  syncing
+  synced
+  failed
+}";
+
+pub fn indexing_status_schema() -> Schema {
+    Schema {
+        name: String::from("indexing-status"),
+        id: String::from("indexing-status"),
+        document: graphql_parser::parse_schema(INDEXING_STATUS_SCHEMA).unwrap(),
+    }
+}
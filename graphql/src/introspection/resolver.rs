@@ -486,8 +486,8 @@ impl<'a> Resolver for IntrospectionResolver<'a> {
         _field_definition: &s::Field,
         _object_type: &s::ObjectType,
         _arguments: &HashMap<&q::Name, q::Value>,
-    ) -> q::Value {
-        match field.as_str() {
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(match field.as_str() {
             "possibleTypes" => {
                 let type_names = object_field(parent, "possibleTypes")
                     .and_then(|value| match value {
@@ -512,7 +512,7 @@ impl<'a> Resolver for IntrospectionResolver<'a> {
             _ => object_field(parent, field.as_str())
                 .map(|value| value.clone())
                 .unwrap_or(q::Value::Null),
-        }
+        })
     }
 
     fn resolve_object(
@@ -522,8 +522,8 @@ impl<'a> Resolver for IntrospectionResolver<'a> {
         _field_definition: &s::Field,
         _object_type: &s::ObjectType,
         arguments: &HashMap<&q::Name, q::Value>,
-    ) -> q::Value {
-        match field.as_str() {
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(match field.as_str() {
             "__schema" => self.schema_object(),
             "__type" => self.type_object(arguments),
             "type" => object_field(parent, "type")
@@ -539,6 +539,6 @@ impl<'a> Resolver for IntrospectionResolver<'a> {
             _ => object_field(parent, field.as_str())
                 .map(|value| value.clone())
                 .unwrap_or(q::Value::Null),
-        }
+        })
     }
 }
@@ -3,6 +3,8 @@ extern crate graph;
 extern crate graphql_parser;
 extern crate indexmap;
 extern crate inflector;
+#[macro_use]
+extern crate lazy_static;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
@@ -15,6 +17,9 @@ pub mod schema;
 /// Utilities for schema introspection.
 pub mod introspection;
 
+/// Utilities for querying the indexing status of subgraphs.
+pub mod indexing_status;
+
 /// Utilities for executing GraphQL.
 mod execution;
 
@@ -33,8 +38,9 @@ mod store;
 /// Prelude that exports the most important traits and types.
 pub mod prelude {
     pub use super::execution::{ExecutionContext, Resolver};
+    pub use super::indexing_status::{indexing_status_schema, IndexingStatusResolver};
     pub use super::introspection::{introspection_schema, IntrospectionResolver};
-    pub use super::query::{execute_query, QueryExecutionOptions};
+    pub use super::query::{execute_query, validate_query, QueryExecutionOptions, QueryLimits};
     pub use super::schema::{api_schema, validate_schema, APISchemaError, SchemaValidationError};
     pub use super::store::{build_query, StoreResolver};
     pub use super::subscription::{execute_subscription, SubscriptionExecutionOptions};
@@ -78,7 +78,7 @@ pub fn skip_selection(selection: &Selection) -> bool {
 /// Returns true if a selection should be included (as per the `@include` directive).
 pub fn include_selection(selection: &Selection) -> bool {
     match get_directive(selection, "include".to_string()) {
-        Some(directive) => match get_argument_value(&directive.arguments, &"include".to_string()) {
+        Some(directive) => match get_argument_value(&directive.arguments, &"if".to_string()) {
             Some(val) => match val {
                 Value::Boolean(include) => *include,
                 _ => false,
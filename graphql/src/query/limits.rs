@@ -0,0 +1,209 @@
+use graphql_parser::{query as q, schema as s};
+use std::time::Duration;
+
+use graph::prelude::QueryExecutionError;
+
+use query::ast as qast;
+use schema::ast as sast;
+use store::DEFAULT_FIRST;
+
+/// Limits on the queries that are executed. Queries that exceed the depth,
+/// complexity or range limits are rejected before they are executed, and
+/// queries whose results get too big or that take too long are aborted.
+#[derive(Clone, Copy, Debug)]
+pub struct QueryLimits {
+    /// How many levels deep fields may be nested in a query.
+    pub max_depth: u8,
+    /// The estimated cost that a query may have; the cost of a field with a
+    /// selection set is the number of entities it may return multiplied
+    /// by one plus the cost of its selection set.
+    pub max_complexity: u64,
+    /// The largest number of entities that a collection field may be asked
+    /// for with its `first` or `last` argument.
+    pub max_first: u32,
+    /// The number of objects that a query result may contain.
+    pub max_result_size: usize,
+    /// How long a query may run.
+    pub timeout: Duration,
+}
+
+impl Default for QueryLimits {
+    fn default() -> Self {
+        QueryLimits {
+            max_depth: 255,
+            max_complexity: 10_000_000,
+            max_first: 1000,
+            max_result_size: 1_000_000,
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Checks the depth, the ranges and the estimated cost of a selection set
+/// against the limits, without executing it.
+pub fn check_selection_set(
+    schema: &s::Document,
+    document: &q::Document,
+    object_type: Option<&s::ObjectType>,
+    selection_set: &q::SelectionSet,
+    limits: &QueryLimits,
+) -> Result<(), QueryExecutionError> {
+    let mut analyzer = Analyzer {
+        schema,
+        document,
+        max_depth: limits.max_depth,
+        max_first: limits.max_first,
+        visited_fragments: vec![],
+    };
+
+    let complexity = analyzer.selection_set_cost(object_type, selection_set, 0)?;
+    if complexity > limits.max_complexity {
+        return Err(QueryExecutionError::TooComplex(
+            complexity,
+            limits.max_complexity,
+        ));
+    }
+    Ok(())
+}
+
+struct Analyzer<'a> {
+    schema: &'a s::Document,
+    document: &'a q::Document,
+    max_depth: u8,
+    max_first: u32,
+    /// The fragments that are being analyzed, used to break fragment cycles.
+    visited_fragments: Vec<&'a q::Name>,
+}
+
+impl<'a> Analyzer<'a> {
+    /// Returns the estimated cost of a selection set that is nested `depth`
+    /// levels deep. The object type is `None` if it is unknown, e.g. for
+    /// introspection fields.
+    fn selection_set_cost(
+        &mut self,
+        object_type: Option<&'a s::ObjectType>,
+        selection_set: &'a q::SelectionSet,
+        depth: u8,
+    ) -> Result<u64, QueryExecutionError> {
+        let mut cost: u64 = 0;
+
+        for selection in selection_set.items.iter() {
+            if qast::skip_selection(selection) || !qast::include_selection(selection) {
+                continue;
+            }
+
+            let selection_cost = match selection {
+                q::Selection::Field(field) => self.field_cost(object_type, field, depth)?,
+
+                q::Selection::FragmentSpread(spread) => {
+                    if self.visited_fragments.contains(&&spread.fragment_name) {
+                        continue;
+                    }
+                    let fragment = match qast::get_fragment(self.document, &spread.fragment_name) {
+                        Some(fragment) => fragment,
+                        None => continue,
+                    };
+
+                    self.visited_fragments.push(&spread.fragment_name);
+                    let fragment_type =
+                        self.type_condition_type(object_type, Some(&fragment.type_condition));
+                    let fragment_cost =
+                        self.selection_set_cost(fragment_type, &fragment.selection_set, depth);
+                    self.visited_fragments.pop();
+                    fragment_cost?
+                }
+
+                q::Selection::InlineFragment(fragment) => {
+                    let fragment_type =
+                        self.type_condition_type(object_type, fragment.type_condition.as_ref());
+                    self.selection_set_cost(fragment_type, &fragment.selection_set, depth)?
+                }
+            };
+
+            cost = cost.saturating_add(selection_cost);
+        }
+
+        Ok(cost)
+    }
+
+    /// Returns the estimated cost of a field. Fields without a selection set
+    /// are returned with their parent object and cost nothing.
+    fn field_cost(
+        &mut self,
+        object_type: Option<&'a s::ObjectType>,
+        field: &'a q::Field,
+        depth: u8,
+    ) -> Result<u64, QueryExecutionError> {
+        if depth >= self.max_depth {
+            return Err(QueryExecutionError::TooDeep(field.position, self.max_depth));
+        }
+
+        let collection_size = self.collection_size(field)?;
+
+        if field.selection_set.items.is_empty() {
+            return Ok(0);
+        }
+
+        let schema = self.schema;
+        let field_definition = object_type.and_then(|t| sast::get_field_type(t, &field.name));
+        let field_type = field_definition.and_then(|field_definition| {
+            match sast::get_type_definition_from_field_type(schema, field_definition) {
+                Some(s::TypeDefinition::Object(t)) => Some(t),
+                _ => None,
+            }
+        });
+
+        // Collections return up to `first` or `last` entities
+        let size = match field_definition {
+            Some(field_definition) if sast::is_list_type(&field_definition.field_type) => {
+                collection_size
+            }
+            _ => 1,
+        };
+
+        let children_cost = self.selection_set_cost(field_type, &field.selection_set, depth + 1)?;
+        Ok(size.saturating_mul(children_cost.saturating_add(1)))
+    }
+
+    /// Returns the number of entities that a collection field may return:
+    /// up to `first` or `last` entities, or as many as the store returns
+    /// by default. Fails if `first` or `last` is larger than the limit.
+    fn collection_size(&self, field: &q::Field) -> Result<u64, QueryExecutionError> {
+        let mut size = None;
+        for name in ["first", "last"].iter() {
+            let value = match qast::get_argument_value(&field.arguments, &name.to_string()) {
+                Some(q::Value::Int(n)) => n.as_i64(),
+                _ => None,
+            };
+            match value {
+                Some(n) if n > self.max_first as i64 => {
+                    return Err(QueryExecutionError::RangeTooLarge(
+                        field.position,
+                        name.to_string(),
+                        n,
+                        self.max_first,
+                    ))
+                }
+                Some(n) if n > 0 && size.is_none() => size = Some(n as u64),
+                _ => (),
+            }
+        }
+        Ok(size.unwrap_or(DEFAULT_FIRST as u64))
+    }
+
+    /// Returns the object type that a type condition refers to, or the
+    /// current object type if there is no type condition.
+    fn type_condition_type(
+        &self,
+        object_type: Option<&'a s::ObjectType>,
+        type_condition: Option<&q::TypeCondition>,
+    ) -> Option<&'a s::ObjectType> {
+        match type_condition {
+            Some(q::TypeCondition::On(name)) => match sast::get_named_type(self.schema, name) {
+                Some(s::TypeDefinition::Object(t)) => Some(t),
+                _ => None,
+            },
+            None => object_type,
+        }
+    }
+}
@@ -1,14 +1,30 @@
 use graphql_parser::query as q;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
 
 use graph::prelude::*;
 
 use execution::*;
 use prelude::*;
 use query::ast as qast;
+use schema::ast as sast;
 
 /// Utilities for working with GraphQL query ASTs.
 pub mod ast;
 
+/// Limits on the depth, cost, result size and execution time of queries.
+mod limits;
+
+/// Utilities for validating GraphQL queries.
+pub mod validation;
+
+/// Substitution of the variable values of a query into its document.
+mod variables;
+
+pub use self::limits::QueryLimits;
+pub use self::validation::validate_query;
+pub(crate) use self::variables::substitute_variables;
+
 /// Options available for query execution.
 pub struct QueryExecutionOptions<R>
 where
@@ -18,6 +34,8 @@ where
     pub logger: slog::Logger,
     /// The resolver to use.
     pub resolver: R,
+    /// The limits that the query has to stay within.
+    pub limits: QueryLimits,
 }
 
 /// Executes a query and returns a result.
@@ -27,17 +45,64 @@ where
 {
     info!(options.logger, "Execute query");
 
+    // Reject queries that break any of the validation rules
+    let introspection_schema = introspection_schema();
+    if let Err(errors) = validate_query(
+        &query.schema.document,
+        &introspection_schema,
+        &query.document,
+    ) {
+        return QueryResult::from(errors);
+    }
+
+    // Replace the variables of the query with their values
+    let document = match substitute_variables(
+        &query.schema.document,
+        &introspection_schema,
+        &query.document,
+        &query.variables,
+    ) {
+        Ok(document) => document,
+        Err(e) => return QueryResult::from(e),
+    };
+
     // Obtain the only operation of the query (fail if there is none or more than one)
-    let operation = match qast::get_operation(&query.document, None) {
+    let operation = match qast::get_operation(&document, None) {
         Ok(op) => op,
         Err(e) => return QueryResult::from(e),
     };
 
-    // Create an introspection type store and resolver
-    let introspection_schema = introspection_schema();
+    let selection_set = match operation {
+        q::OperationDefinition::Query(q::Query { selection_set, .. }) => selection_set,
+        q::OperationDefinition::SelectionSet(selection_set) => selection_set,
+
+        // Everything else (e.g. mutations) is unsupported
+        _ => {
+            return QueryResult::from(QueryExecutionError::NotSupported(
+                "Only queries are supported".to_string(),
+            ))
+        }
+    };
+
+    // Reject queries that are too deep or too expensive before running them
+    let limits = options.limits;
+    if let Err(e) = limits::check_selection_set(
+        &query.schema.document,
+        &document,
+        sast::get_root_query_type(&query.schema.document),
+        selection_set,
+        &limits,
+    ) {
+        return QueryResult::from(e);
+    }
+
+    // Create an introspection resolver
     let introspection_resolver = IntrospectionResolver::new(&options.logger, &query.schema);
 
-    // Create a fresh execution context
+    // Create a fresh execution context; the result contains at least the
+    // query object
+    let deadline = Instant::now() + limits.timeout;
+    let result_size = Arc::new(AtomicUsize::new(1));
     let ctx = ExecutionContext {
         logger: options.logger,
         resolver: Arc::new(options.resolver),
@@ -45,24 +110,31 @@ where
         introspection_resolver: Arc::new(introspection_resolver),
         introspection_schema: &introspection_schema,
         introspecting: false,
-        document: &query.document,
+        document: &document,
         fields: vec![],
+        block: None,
+        deadline: Some(deadline),
+        result_size: result_size.clone(),
+        max_result_size: Some(limits.max_result_size),
     };
 
-    match operation {
-        // Execute top-level `query { ... }` expressions
-        &q::OperationDefinition::Query(q::Query {
-            ref selection_set, ..
-        }) => execute_root_selection_set(ctx, selection_set, &None),
+    // Execute top-level `query { ... }` and `{ ... }` expressions
+    let result = execute_root_selection_set(ctx, selection_set, &None);
 
-        // Execute top-level `{ ... }` expressions
-        &q::OperationDefinition::SelectionSet(ref selection_set) => {
-            execute_root_selection_set(ctx, selection_set, &None)
-        }
+    // Fields that were not resolved in time are missing from the result
+    if Instant::now() > deadline {
+        return QueryResult::from(QueryExecutionError::Timeout);
+    }
 
-        // Everything else (e.g. mutations) is unsupported
-        _ => QueryResult::from(QueryExecutionError::NotSupported(
-            "Only queries are supported".to_string(),
-        )),
+    // Fields that were not resolved because the result got too big are
+    // missing from the result as well
+    let result_size = result_size.load(Ordering::SeqCst);
+    if result_size > limits.max_result_size {
+        return QueryResult::from(QueryExecutionError::ResultTooBig(
+            result_size,
+            limits.max_result_size,
+        ));
     }
+
+    result
 }
@@ -0,0 +1,1192 @@
+use graphql_parser;
+use graphql_parser::{query as q, schema as s, Pos};
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+use graph::prelude::QueryExecutionError;
+
+use query::ast as qast;
+use schema::ast as sast;
+use values::MaybeCoercible;
+
+lazy_static! {
+    /// Directives that every GraphQL server supports.
+    static ref BUILT_IN_DIRECTIVES: s::Document = graphql_parser::parse_schema(
+        "
+        directive @skip(if: Boolean!) on FIELD | FRAGMENT_SPREAD | INLINE_FRAGMENT
+        directive @include(if: Boolean!) on FIELD | FRAGMENT_SPREAD | INLINE_FRAGMENT
+        "
+    ).unwrap();
+
+    /// The definition of the `__typename` field that all composite types have.
+    static ref TYPENAME_FIELD: s::Field = s::Field {
+        position: Pos::default(),
+        description: None,
+        name: "__typename".to_string(),
+        arguments: vec![],
+        field_type: s::Type::NonNullType(Box::new(s::Type::NamedType("String".to_string()))),
+        directives: vec![],
+    };
+}
+
+/// Validates a query document against a schema, following the validation
+/// rules of the GraphQL specification. Fields of the introspection schema,
+/// such as `__schema`, may be queried as well.
+///
+/// Returns all violations of the rules, ordered by their position.
+pub fn validate_query(
+    schema: &s::Document,
+    introspection_schema: &s::Document,
+    document: &q::Document,
+) -> Result<(), Vec<QueryExecutionError>> {
+    let mut validator = Validator {
+        schema,
+        introspection_schema,
+        built_in_directives: &BUILT_IN_DIRECTIVES,
+        typename_field: &TYPENAME_FIELD,
+        document,
+        errors: vec![],
+        scope: Scope::default(),
+        fragment_scopes: HashMap::new(),
+    };
+    validator.validate_document();
+
+    let mut errors = validator.errors;
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        errors.sort_by_key(|(position, _)| *position);
+        Err(errors
+            .into_iter()
+            .map(|(position, message)| QueryExecutionError::ValidationError(position, message))
+            .collect())
+    }
+}
+
+/// An operation of any kind, e.g. a query or a subscription.
+struct Operation<'a> {
+    /// The kind of operation, as written in queries (e.g. `query`).
+    kind: &'static str,
+    /// The directive location of the operation (e.g. `QUERY`).
+    location: &'static str,
+    name: Option<&'a q::Name>,
+    position: Pos,
+    variable_definitions: &'a [q::VariableDefinition],
+    directives: &'a [q::Directive],
+    selection_set: &'a q::SelectionSet,
+}
+
+impl<'a> Operation<'a> {
+    fn from_definition(operation: &'a q::OperationDefinition) -> Self {
+        match operation {
+            q::OperationDefinition::SelectionSet(selection_set) => Operation {
+                kind: "query",
+                location: "QUERY",
+                name: None,
+                position: selection_set.span.0,
+                variable_definitions: &[],
+                directives: &[],
+                selection_set,
+            },
+            q::OperationDefinition::Query(query) => Operation {
+                kind: "query",
+                location: "QUERY",
+                name: query.name.as_ref(),
+                position: query.position,
+                variable_definitions: &query.variable_definitions,
+                directives: &query.directives,
+                selection_set: &query.selection_set,
+            },
+            q::OperationDefinition::Mutation(mutation) => Operation {
+                kind: "mutation",
+                location: "MUTATION",
+                name: mutation.name.as_ref(),
+                position: mutation.position,
+                variable_definitions: &mutation.variable_definitions,
+                directives: &mutation.directives,
+                selection_set: &mutation.selection_set,
+            },
+            q::OperationDefinition::Subscription(subscription) => Operation {
+                kind: "subscription",
+                location: "SUBSCRIPTION",
+                name: subscription.name.as_ref(),
+                position: subscription.position,
+                variable_definitions: &subscription.variable_definitions,
+                directives: &subscription.directives,
+                selection_set: &subscription.selection_set,
+            },
+        }
+    }
+
+    /// Describes the operation in error messages.
+    fn description(&self) -> String {
+        match self.name {
+            Some(name) => format!("operation \"{}\"", name),
+            None => "the anonymous operation".to_string(),
+        }
+    }
+}
+
+/// A place where a variable is used.
+#[derive(Clone)]
+struct VariableUsage<'a> {
+    name: &'a q::Name,
+    position: Pos,
+    /// The type that the variable has to be compatible with at this place.
+    expected_type: &'a s::Type,
+    /// Whether the argument or input field that the variable is passed to
+    /// has a default value.
+    has_default_value: bool,
+}
+
+/// The variable usages and fragment spreads of an operation or a fragment,
+/// not including those of the fragments that it spreads.
+#[derive(Default)]
+struct Scope<'a> {
+    usages: Vec<VariableUsage<'a>>,
+    spreads: Vec<(&'a q::Name, Pos)>,
+}
+
+struct Validator<'a> {
+    schema: &'a s::Document,
+    introspection_schema: &'a s::Document,
+    built_in_directives: &'a s::Document,
+    /// The definition of the `__typename` field that all composite types have.
+    typename_field: &'a s::Field,
+    document: &'a q::Document,
+    errors: Vec<(Pos, String)>,
+    /// The scope of the operation or fragment that is being validated.
+    scope: Scope<'a>,
+    fragment_scopes: HashMap<&'a q::Name, Scope<'a>>,
+}
+
+impl<'a> Validator<'a> {
+    fn error(&mut self, position: Pos, message: String) {
+        self.errors.push((position, message));
+    }
+
+    fn validate_document(&mut self) {
+        let document = self.document;
+        let operations: Vec<_> = qast::get_operations(document)
+            .into_iter()
+            .map(Operation::from_definition)
+            .collect();
+        let fragments: Vec<_> = document
+            .definitions
+            .iter()
+            .filter_map(|definition| match definition {
+                q::Definition::Fragment(fragment) => Some(fragment),
+                _ => None,
+            }).collect();
+
+        // Operation names must be unique, and an anonymous operation must be
+        // the only operation in the document
+        let mut operation_names = HashSet::new();
+        for operation in operations.iter() {
+            match operation.name {
+                Some(name) => {
+                    if !operation_names.insert(name) {
+                        self.error(
+                            operation.position,
+                            format!("There can be only one operation named \"{}\"", name),
+                        );
+                    }
+                }
+                None => {
+                    if operations.len() > 1 {
+                        self.error(
+                            operation.position,
+                            "This anonymous operation must be the only defined operation"
+                                .to_string(),
+                        );
+                    }
+                }
+            }
+        }
+
+        // Fragment names must be unique
+        let mut fragment_names = HashSet::new();
+        for fragment in fragments.iter() {
+            if !fragment_names.insert(&fragment.name) {
+                self.error(
+                    fragment.position,
+                    format!("There can be only one fragment named \"{}\"", fragment.name),
+                );
+            }
+        }
+
+        // Validate fragments on their own, so that their errors are only
+        // reported once, no matter how often they are spread
+        for fragment in fragments.iter() {
+            self.validate_fragment_definition(fragment);
+            let scope = mem::replace(&mut self.scope, Scope::default());
+            self.fragment_scopes.entry(&fragment.name).or_insert(scope);
+        }
+        self.validate_fragment_cycles(&fragments);
+
+        let mut used_fragments = HashSet::new();
+        for operation in operations.iter() {
+            self.validate_operation(operation);
+            let scope = mem::replace(&mut self.scope, Scope::default());
+            self.validate_variables(operation, scope, &mut used_fragments);
+        }
+
+        // All fragments must be used by an operation
+        for fragment in fragments.iter() {
+            if !used_fragments.contains(&fragment.name) {
+                self.error(
+                    fragment.position,
+                    format!("Fragment \"{}\" is never used", fragment.name),
+                );
+            }
+        }
+    }
+
+    fn validate_operation(&mut self, operation: &Operation<'a>) {
+        self.validate_directives(operation.directives, operation.location, operation.position);
+
+        let root_type = match operation.kind {
+            "query" => sast::get_named_type(self.schema, &"Query".to_string()),
+            "mutation" => sast::get_named_type(self.schema, &"Mutation".to_string()),
+            _ => sast::get_named_type(self.schema, &"Subscription".to_string()),
+        };
+        let root_type = match root_type {
+            Some(root_type) => root_type,
+            None => {
+                return self.error(
+                    operation.position,
+                    format!("The schema does not support {} operations", operation.kind),
+                )
+            }
+        };
+
+        // Subscriptions must select exactly one top-level field
+        if operation.kind == "subscription" {
+            let mut fields = vec![];
+            self.collect_fields(root_type, operation.selection_set, &mut fields, &mut vec![]);
+            let response_keys: HashSet<_> = fields.iter().map(|(key, _, _)| *key).collect();
+            if response_keys.len() > 1 {
+                let message = match operation.name {
+                    Some(name) => format!(
+                        "Subscription \"{}\" must select only one top-level field",
+                        name
+                    ),
+                    None => {
+                        "Anonymous subscription must select only one top-level field".to_string()
+                    }
+                };
+                self.error(operation.position, message);
+            }
+        }
+
+        self.validate_selection_set(root_type, operation.selection_set);
+    }
+
+    fn validate_fragment_definition(&mut self, fragment: &'a q::FragmentDefinition) {
+        self.validate_directives(
+            &fragment.directives,
+            "FRAGMENT_DEFINITION",
+            fragment.position,
+        );
+
+        let q::TypeCondition::On(ref type_name) = fragment.type_condition;
+        if let Some(fragment_type) =
+            self.validate_type_condition(type_name, Some(&fragment.name), fragment.position)
+        {
+            self.validate_selection_set(fragment_type, &fragment.selection_set);
+        }
+    }
+
+    /// Checks that the type of a type condition exists and is a composite
+    /// type, and returns it if it is.
+    fn validate_type_condition(
+        &mut self,
+        type_name: &q::Name,
+        fragment_name: Option<&q::Name>,
+        position: Pos,
+    ) -> Option<&'a s::TypeDefinition> {
+        match self.named_type(type_name) {
+            Some(t) if is_composite_type(t) => Some(t),
+            Some(_) => {
+                let message = match fragment_name {
+                    Some(name) => format!(
+                        "Fragment \"{}\" cannot condition on non-composite type \"{}\"",
+                        name, type_name
+                    ),
+                    None => format!(
+                        "Fragment cannot condition on non-composite type \"{}\"",
+                        type_name
+                    ),
+                };
+                self.error(position, message);
+                None
+            }
+            None => {
+                self.error(position, format!("Unknown type \"{}\"", type_name));
+                None
+            }
+        }
+    }
+
+    /// Reports fragments that spread themselves, directly or through other
+    /// fragments.
+    fn validate_fragment_cycles(&mut self, fragments: &[&'a q::FragmentDefinition]) {
+        let mut visited = HashSet::new();
+        for fragment in fragments.iter() {
+            if !visited.contains(&fragment.name) {
+                self.detect_fragment_cycles(&fragment.name, &mut visited, &mut vec![]);
+            }
+        }
+    }
+
+    fn detect_fragment_cycles(
+        &mut self,
+        fragment_name: &'a q::Name,
+        visited: &mut HashSet<&'a q::Name>,
+        path: &mut Vec<&'a q::Name>,
+    ) {
+        visited.insert(fragment_name);
+        path.push(fragment_name);
+
+        let spreads = self
+            .fragment_scopes
+            .get(fragment_name)
+            .map(|scope| scope.spreads.clone())
+            .unwrap_or(vec![]);
+        for (spread_name, position) in spreads {
+            match path.iter().position(|name| *name == spread_name) {
+                Some(index) => {
+                    let via: Vec<_> = path[index + 1..]
+                        .iter()
+                        .map(|name| format!("\"{}\"", name))
+                        .collect();
+                    let message = if via.is_empty() {
+                        format!("Cannot spread fragment \"{}\" within itself", spread_name)
+                    } else {
+                        format!(
+                            "Cannot spread fragment \"{}\" within itself via {}",
+                            spread_name,
+                            via.join(", ")
+                        )
+                    };
+                    self.error(position, message);
+                }
+                None => {
+                    if !visited.contains(spread_name) {
+                        self.detect_fragment_cycles(spread_name, visited, path);
+                    }
+                }
+            }
+        }
+
+        path.pop();
+    }
+
+    fn validate_selection_set(
+        &mut self,
+        parent_type: &'a s::TypeDefinition,
+        selection_set: &'a q::SelectionSet,
+    ) {
+        for selection in selection_set.items.iter() {
+            match selection {
+                q::Selection::Field(field) => self.validate_field(parent_type, field),
+                q::Selection::FragmentSpread(spread) => {
+                    self.validate_fragment_spread(parent_type, spread)
+                }
+                q::Selection::InlineFragment(fragment) => {
+                    self.validate_inline_fragment(parent_type, fragment)
+                }
+            }
+        }
+
+        self.validate_field_merging(parent_type, selection_set);
+    }
+
+    fn validate_field(&mut self, parent_type: &'a s::TypeDefinition, field: &'a q::Field) {
+        let field_definition = match self.selected_field_definition(parent_type, field) {
+            Some(field_definition) => field_definition,
+            None => {
+                return self.error(
+                    field.position,
+                    format!(
+                        "Cannot query field \"{}\" on type \"{}\"",
+                        field.name,
+                        sast::get_type_name(parent_type)
+                    ),
+                )
+            }
+        };
+
+        self.validate_arguments(
+            &field.arguments,
+            &field_definition.arguments,
+            &format!("field \"{}\"", field.name),
+            field.position,
+        );
+        self.validate_directives(&field.directives, "FIELD", field.position);
+
+        // Composite fields need a selection of subfields, leaf fields must
+        // not have one
+        let field_type = sast::get_base_type(&field_definition.field_type);
+        match self.named_type(field_type) {
+            Some(t) if is_composite_type(t) => {
+                if field.selection_set.items.is_empty() {
+                    self.error(
+                        field.position,
+                        format!(
+                            "Field \"{}\" of type \"{}\" must have a selection of subfields",
+                            field.name,
+                            type_to_string(&field_definition.field_type)
+                        ),
+                    );
+                } else {
+                    self.validate_selection_set(t, &field.selection_set);
+                }
+            }
+            _ => {
+                if !field.selection_set.items.is_empty() {
+                    self.error(
+                        field.position,
+                        format!(
+                            "Field \"{}\" must not have a selection since type \"{}\" \
+                             has no subfields",
+                            field.name,
+                            type_to_string(&field_definition.field_type)
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    fn validate_fragment_spread(
+        &mut self,
+        parent_type: &'a s::TypeDefinition,
+        spread: &'a q::FragmentSpread,
+    ) {
+        self.scope
+            .spreads
+            .push((&spread.fragment_name, spread.position));
+        self.validate_directives(&spread.directives, "FRAGMENT_SPREAD", spread.position);
+
+        let fragment = match qast::get_fragment(self.document, &spread.fragment_name) {
+            Some(fragment) => fragment,
+            None => {
+                return self.error(
+                    spread.position,
+                    format!("Unknown fragment \"{}\"", spread.fragment_name),
+                )
+            }
+        };
+
+        // Invalid type conditions are reported with the fragment definition
+        let q::TypeCondition::On(ref type_name) = fragment.type_condition;
+        match self.named_type(type_name) {
+            Some(fragment_type) if is_composite_type(fragment_type) => {
+                if !self.types_overlap(parent_type, fragment_type) {
+                    self.error(
+                        spread.position,
+                        format!(
+                            "Fragment \"{}\" cannot be spread here as objects of type \"{}\" \
+                             can never be of type \"{}\"",
+                            spread.fragment_name,
+                            sast::get_type_name(parent_type),
+                            type_name
+                        ),
+                    );
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn validate_inline_fragment(
+        &mut self,
+        parent_type: &'a s::TypeDefinition,
+        fragment: &'a q::InlineFragment,
+    ) {
+        self.validate_directives(&fragment.directives, "INLINE_FRAGMENT", fragment.position);
+
+        let fragment_type = match fragment.type_condition {
+            Some(q::TypeCondition::On(ref type_name)) => {
+                match self.validate_type_condition(type_name, None, fragment.position) {
+                    Some(fragment_type) => fragment_type,
+                    None => return,
+                }
+            }
+            None => parent_type,
+        };
+
+        if !self.types_overlap(parent_type, fragment_type) {
+            self.error(
+                fragment.position,
+                format!(
+                    "Fragment cannot be spread here as objects of type \"{}\" \
+                     can never be of type \"{}\"",
+                    sast::get_type_name(parent_type),
+                    sast::get_type_name(fragment_type)
+                ),
+            );
+        }
+
+        self.validate_selection_set(fragment_type, &fragment.selection_set);
+    }
+
+    /// Checks that fields with the same response key in a selection set
+    /// select the same field with the same arguments, so that they can be
+    /// merged into one. Fields of different object types may differ, as long
+    /// as their results have the same shape.
+    fn validate_field_merging(
+        &mut self,
+        parent_type: &'a s::TypeDefinition,
+        selection_set: &'a q::SelectionSet,
+    ) {
+        let mut fields = vec![];
+        self.collect_fields(parent_type, selection_set, &mut fields, &mut vec![]);
+
+        for (index, field) in fields.iter().enumerate() {
+            let conflict = fields[..index]
+                .iter()
+                .filter_map(|other| self.find_field_conflict(other, field, false))
+                .next();
+
+            if let Some(reason) = conflict {
+                self.error(
+                    field.2.position,
+                    format!(
+                        "Fields \"{}\" conflict because {}; use different aliases on the \
+                         fields to fetch both",
+                        field.0, reason
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Returns the reason why two fields with the same response key cannot
+    /// be merged, if they cannot. The subfields that the two fields select are
+    /// merged as well and must not conflict either.
+    fn find_field_conflict(
+        &self,
+        &(key_a, parent_type_a, field_a): &(&'a q::Name, &'a s::TypeDefinition, &'a q::Field),
+        &(key_b, parent_type_b, field_b): &(&'a q::Name, &'a s::TypeDefinition, &'a q::Field),
+        parents_exclusive: bool,
+    ) -> Option<String> {
+        if key_a != key_b {
+            return None;
+        }
+
+        // Fields of different object types are never both part of a result
+        let parents_exclusive = parents_exclusive
+            || (sast::get_type_name(parent_type_a) != sast::get_type_name(parent_type_b)
+                && is_object_type(parent_type_a)
+                && is_object_type(parent_type_b));
+
+        if !parents_exclusive {
+            if field_a.name != field_b.name {
+                return Some(format!(
+                    "\"{}\" and \"{}\" are different fields",
+                    field_a.name, field_b.name
+                ));
+            } else if !same_arguments(field_a, field_b) {
+                return Some("they have differing arguments".to_string());
+            }
+        }
+
+        // Unknown fields are reported by `validate_field`
+        let (type_a, type_b) = match (
+            self.field_type(parent_type_a, field_a),
+            self.field_type(parent_type_b, field_b),
+        ) {
+            (Some(type_a), Some(type_b)) => (type_a, type_b),
+            _ => return None,
+        };
+        if !self.same_response_shape(type_a, type_b) {
+            return Some(format!(
+                "they return conflicting types \"{}\" and \"{}\"",
+                type_to_string(type_a),
+                type_to_string(type_b)
+            ));
+        }
+
+        let (subfield_type_a, subfield_type_b) = match (
+            self.named_type(sast::get_base_type(type_a)),
+            self.named_type(sast::get_base_type(type_b)),
+        ) {
+            (Some(a), Some(b)) if is_composite_type(a) && is_composite_type(b) => (a, b),
+            _ => return None,
+        };
+        let mut subfields_a = vec![];
+        self.collect_fields(
+            subfield_type_a,
+            &field_a.selection_set,
+            &mut subfields_a,
+            &mut vec![],
+        );
+        let mut subfields_b = vec![];
+        self.collect_fields(
+            subfield_type_b,
+            &field_b.selection_set,
+            &mut subfields_b,
+            &mut vec![],
+        );
+
+        // Conflicts between the subfields of one field are reported when
+        // validating its selection set
+        subfields_a
+            .iter()
+            .flat_map(|a| subfields_b.iter().map(move |b| (a, b)))
+            .filter_map(|(a, b)| {
+                self.find_field_conflict(a, b, parents_exclusive)
+                    .map(|reason| format!("subfields \"{}\" conflict because {}", a.0, reason))
+            })
+            .next()
+    }
+
+    /// Returns whether two field types produce results of the same shape:
+    /// the same nesting of lists and non-null types, and the same leaf type.
+    fn same_response_shape(&self, a: &s::Type, b: &s::Type) -> bool {
+        match (a, b) {
+            (s::Type::NonNullType(a), s::Type::NonNullType(b))
+            | (s::Type::ListType(a), s::Type::ListType(b)) => self.same_response_shape(a, b),
+            (s::Type::NamedType(a), s::Type::NamedType(b)) => {
+                let is_leaf = |name: &s::Name| match self.named_type(name) {
+                    Some(t) => !is_composite_type(t),
+                    None => false,
+                };
+                a == b || !(is_leaf(a) || is_leaf(b))
+            }
+            _ => false,
+        }
+    }
+
+    /// Collects the fields of a selection set, including those of the
+    /// fragments that it spreads, with their response key and parent type.
+    fn collect_fields(
+        &self,
+        parent_type: &'a s::TypeDefinition,
+        selection_set: &'a q::SelectionSet,
+        fields: &mut Vec<(&'a q::Name, &'a s::TypeDefinition, &'a q::Field)>,
+        visited_fragments: &mut Vec<&'a q::Name>,
+    ) {
+        for selection in selection_set.items.iter() {
+            match selection {
+                q::Selection::Field(field) => {
+                    fields.push((qast::get_response_key(field), parent_type, field))
+                }
+                q::Selection::FragmentSpread(spread) => {
+                    if visited_fragments.contains(&&spread.fragment_name) {
+                        continue;
+                    }
+                    visited_fragments.push(&spread.fragment_name);
+
+                    if let Some(fragment) = qast::get_fragment(self.document, &spread.fragment_name)
+                    {
+                        let q::TypeCondition::On(ref type_name) = fragment.type_condition;
+                        if let Some(fragment_type) = self.named_type(type_name) {
+                            self.collect_fields(
+                                fragment_type,
+                                &fragment.selection_set,
+                                fields,
+                                visited_fragments,
+                            );
+                        }
+                    }
+                }
+                q::Selection::InlineFragment(fragment) => {
+                    let fragment_type = match fragment.type_condition {
+                        Some(q::TypeCondition::On(ref type_name)) => self.named_type(type_name),
+                        None => Some(parent_type),
+                    };
+                    if let Some(fragment_type) = fragment_type {
+                        self.collect_fields(
+                            fragment_type,
+                            &fragment.selection_set,
+                            fields,
+                            visited_fragments,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks that the arguments of a field or directive are defined, unique
+    /// and of the right type, and that all required arguments are provided.
+    fn validate_arguments(
+        &mut self,
+        arguments: &'a [(q::Name, q::Value)],
+        argument_definitions: &'a [s::InputValue],
+        owner: &str,
+        position: Pos,
+    ) {
+        let mut names = HashSet::new();
+        for (name, value) in arguments.iter() {
+            if !names.insert(name) {
+                self.error(
+                    position,
+                    format!("There can be only one argument named \"{}\"", name),
+                );
+            }
+
+            match argument_definitions.iter().find(|def| &def.name == name) {
+                Some(def) => {
+                    let has_default_value = def.default_value.is_some();
+                    if let Err(reason) =
+                        self.validate_value(value, &def.value_type, has_default_value, position)
+                    {
+                        self.error(
+                            position,
+                            format!(
+                                "Invalid value for argument \"{}\" on {}: {}",
+                                name, owner, reason
+                            ),
+                        );
+                    }
+                }
+                None => self.error(
+                    position,
+                    format!("Unknown argument \"{}\" on {}", name, owner),
+                ),
+            }
+        }
+
+        for def in argument_definitions.iter() {
+            if is_required(def) && !names.contains(&def.name) {
+                self.error(
+                    position,
+                    format!(
+                        "Argument \"{}\" of type \"{}\" on {} is required but not provided",
+                        def.name,
+                        type_to_string(&def.value_type),
+                        owner
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Checks that a value can be coerced to a type. Variables are accepted
+    /// here and checked against the type when the variables of the operation
+    /// are validated.
+    fn validate_value(
+        &mut self,
+        value: &'a q::Value,
+        value_type: &'a s::Type,
+        has_default_value: bool,
+        position: Pos,
+    ) -> Result<(), String> {
+        let expected_type = || format!("expected type \"{}\"", type_to_string(value_type));
+
+        match (value_type, value) {
+            (_, q::Value::Variable(name)) => {
+                self.scope.usages.push(VariableUsage {
+                    name,
+                    position,
+                    expected_type: value_type,
+                    has_default_value,
+                });
+                Ok(())
+            }
+
+            (s::Type::NonNullType(_), q::Value::Null) => Err(expected_type()),
+            (s::Type::NonNullType(inner_type), _) => {
+                self.validate_value(value, inner_type, false, position)
+            }
+            (_, q::Value::Null) => Ok(()),
+
+            // Single values are coerced into lists with one item
+            (s::Type::ListType(inner_type), q::Value::List(values)) => {
+                for value in values.iter() {
+                    self.validate_value(value, inner_type, false, position)?;
+                }
+                Ok(())
+            }
+            (s::Type::ListType(inner_type), _) => {
+                self.validate_value(value, inner_type, false, position)
+            }
+
+            (s::Type::NamedType(type_name), _) => match self.named_type(type_name) {
+                Some(s::TypeDefinition::Scalar(t)) => {
+                    value.coerce(t).map(|_| ()).ok_or_else(expected_type)
+                }
+                Some(s::TypeDefinition::Enum(t)) => {
+                    value.coerce(t).map(|_| ()).ok_or_else(expected_type)
+                }
+                Some(s::TypeDefinition::InputObject(t)) => match value {
+                    q::Value::Object(fields) => {
+                        for (name, value) in fields.iter() {
+                            match t.fields.iter().find(|def| &def.name == name) {
+                                Some(def) => {
+                                    self.validate_value(value, &def.value_type, false, position)?
+                                }
+                                None => {
+                                    return Err(format!(
+                                        "field \"{}\" is not defined by type \"{}\"",
+                                        name, t.name
+                                    ))
+                                }
+                            }
+                        }
+                        match t
+                            .fields
+                            .iter()
+                            .find(|def| is_required(def) && !fields.contains_key(&def.name))
+                        {
+                            Some(def) => Err(format!(
+                                "field \"{}\" of required type \"{}\" was not provided",
+                                def.name,
+                                type_to_string(&def.value_type)
+                            )),
+                            None => Ok(()),
+                        }
+                    }
+                    _ => Err(expected_type()),
+                },
+                _ => Err(format!("type \"{}\" is not an input type", type_name)),
+            },
+        }
+    }
+
+    /// Checks that the directives of an operation, fragment or selection
+    /// are defined, allowed at their location and not repeated.
+    fn validate_directives(
+        &mut self,
+        directives: &'a [q::Directive],
+        location: &str,
+        position: Pos,
+    ) {
+        let mut names = HashSet::new();
+        for directive in directives.iter() {
+            let definition = match self.directive_definition(&directive.name) {
+                Some(definition) => definition,
+                None => {
+                    self.error(
+                        position,
+                        format!("Unknown directive \"@{}\"", directive.name),
+                    );
+                    continue;
+                }
+            };
+
+            if !definition
+                .locations
+                .iter()
+                .any(|definition_location| definition_location.as_str() == location)
+            {
+                self.error(
+                    position,
+                    format!(
+                        "Directive \"@{}\" may not be used on {}",
+                        directive.name, location
+                    ),
+                );
+            }
+            if !names.insert(&directive.name) {
+                self.error(
+                    position,
+                    format!(
+                        "The directive \"@{}\" can only be used once at this location",
+                        directive.name
+                    ),
+                );
+            }
+
+            self.validate_arguments(
+                &directive.arguments,
+                &definition.arguments,
+                &format!("directive \"@{}\"", directive.name),
+                position,
+            );
+        }
+    }
+
+    /// Checks the variable definitions of an operation against the
+    /// variables that the operation and the fragments it spreads use.
+    /// Adds the fragments that the operation uses to `used_fragments`.
+    fn validate_variables(
+        &mut self,
+        operation: &Operation<'a>,
+        scope: Scope<'a>,
+        used_fragments: &mut HashSet<&'a q::Name>,
+    ) {
+        let mut definitions = HashMap::new();
+        for def in operation.variable_definitions.iter() {
+            if definitions.insert(&def.name, def).is_some() {
+                self.error(
+                    def.position,
+                    format!("There can be only one variable named \"${}\"", def.name),
+                );
+            }
+
+            let type_name = sast::get_base_type(&def.var_type);
+            match self.named_type(type_name) {
+                Some(s::TypeDefinition::Scalar(_))
+                | Some(s::TypeDefinition::Enum(_))
+                | Some(s::TypeDefinition::InputObject(_)) => (),
+                Some(_) => self.error(
+                    def.position,
+                    format!(
+                        "Variable \"${}\" cannot be non-input type \"{}\"",
+                        def.name,
+                        type_to_string(&def.var_type)
+                    ),
+                ),
+                None => self.error(def.position, format!("Unknown type \"{}\"", type_name)),
+            }
+
+            if let Some(ref default_value) = def.default_value {
+                if let Err(reason) =
+                    self.validate_value(default_value, &def.var_type, false, def.position)
+                {
+                    self.error(
+                        def.position,
+                        format!(
+                            "Invalid default value for variable \"${}\": {}",
+                            def.name, reason
+                        ),
+                    );
+                }
+            }
+        }
+
+        // Default values are constant and must not use variables
+        for usage in mem::replace(&mut self.scope, Scope::default()).usages {
+            self.error(
+                usage.position,
+                format!(
+                    "Variable \"${}\" may not be used in a default value",
+                    usage.name
+                ),
+            );
+        }
+
+        // Gather the variable usages of the operation and all fragments it
+        // spreads, directly or indirectly
+        let mut usages = scope.usages;
+        let mut spreads = scope.spreads;
+        let mut visited_fragments = HashSet::new();
+        while let Some((fragment_name, _)) = spreads.pop() {
+            if !visited_fragments.insert(fragment_name) {
+                continue;
+            }
+            used_fragments.insert(fragment_name);
+            if let Some(fragment_scope) = self.fragment_scopes.get(fragment_name) {
+                usages.extend(fragment_scope.usages.iter().cloned());
+                spreads.extend(fragment_scope.spreads.iter().cloned());
+            }
+        }
+
+        let mut used_variables = HashSet::new();
+        for usage in usages.iter() {
+            match definitions.get(usage.name) {
+                Some(def) => {
+                    used_variables.insert(usage.name);
+                    if !is_variable_usage_allowed(def, usage) {
+                        self.error(
+                            usage.position,
+                            format!(
+                                "Variable \"${}\" of type \"{}\" used in position \
+                                 expecting type \"{}\"",
+                                usage.name,
+                                type_to_string(&def.var_type),
+                                type_to_string(usage.expected_type)
+                            ),
+                        );
+                    }
+                }
+                None => self.error(
+                    usage.position,
+                    format!(
+                        "Variable \"${}\" is not defined by {}",
+                        usage.name,
+                        operation.description()
+                    ),
+                ),
+            }
+        }
+
+        for def in operation.variable_definitions.iter() {
+            if !used_variables.contains(&def.name) {
+                self.error(
+                    def.position,
+                    format!(
+                        "Variable \"${}\" is never used in {}",
+                        def.name,
+                        operation.description()
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Looks up a type in the schema, or in the introspection schema.
+    fn named_type(&self, name: &s::Name) -> Option<&'a s::TypeDefinition> {
+        sast::get_named_type(self.schema, name)
+            .or_else(|| sast::get_named_type(self.introspection_schema, name))
+    }
+
+    /// Looks up a field of an object or interface type. The root query type
+    /// also has the fields of the introspection schema's root query type.
+    fn field_definition(
+        &self,
+        parent_type: &'a s::TypeDefinition,
+        name: &q::Name,
+    ) -> Option<&'a s::Field> {
+        let fields = match parent_type {
+            s::TypeDefinition::Object(t) => &t.fields,
+            s::TypeDefinition::Interface(t) => &t.fields,
+            _ => return None,
+        };
+
+        fields.iter().find(|field| &field.name == name).or_else(|| {
+            if sast::get_type_name(parent_type) == "Query" {
+                sast::get_root_query_type(self.introspection_schema)
+                    .and_then(|query_type| sast::get_field_type(query_type, name))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Looks up the definition of a selected field, which may also be the
+    /// `__typename` field.
+    fn selected_field_definition(
+        &self,
+        parent_type: &'a s::TypeDefinition,
+        field: &q::Field,
+    ) -> Option<&'a s::Field> {
+        if field.name == "__typename" {
+            Some(self.typename_field)
+        } else {
+            self.field_definition(parent_type, &field.name)
+        }
+    }
+
+    /// Looks up the type of a selected field.
+    fn field_type(
+        &self,
+        parent_type: &'a s::TypeDefinition,
+        field: &q::Field,
+    ) -> Option<&'a s::Type> {
+        self.selected_field_definition(parent_type, field)
+            .map(|field_definition| &field_definition.field_type)
+    }
+
+    fn directive_definition(&self, name: &q::Name) -> Option<&'a s::DirectiveDefinition> {
+        self.schema
+            .definitions
+            .iter()
+            .chain(self.built_in_directives.definitions.iter())
+            .filter_map(|definition| match definition {
+                s::Definition::DirectiveDefinition(directive) => Some(directive),
+                _ => None,
+            }).find(|directive| &directive.name == name)
+    }
+
+    /// Returns the names of the object types that a composite type may be.
+    fn possible_types(&self, t: &'a s::TypeDefinition) -> Vec<&'a s::Name> {
+        match t {
+            s::TypeDefinition::Object(t) => vec![&t.name],
+            s::TypeDefinition::Interface(t) => sast::get_object_type_definitions(self.schema)
+                .into_iter()
+                .chain(sast::get_object_type_definitions(self.introspection_schema))
+                .filter(|object_type| object_type.implements_interfaces.contains(&t.name))
+                .map(|object_type| &object_type.name)
+                .collect(),
+            s::TypeDefinition::Union(t) => t.types.iter().collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Returns whether an object can be of both types.
+    fn types_overlap(&self, a: &'a s::TypeDefinition, b: &'a s::TypeDefinition) -> bool {
+        let possible_types = self.possible_types(b);
+        self.possible_types(a)
+            .iter()
+            .any(|name| possible_types.contains(name))
+    }
+}
+
+fn is_composite_type(t: &s::TypeDefinition) -> bool {
+    match t {
+        s::TypeDefinition::Object(_)
+        | s::TypeDefinition::Interface(_)
+        | s::TypeDefinition::Union(_) => true,
+        _ => false,
+    }
+}
+
+fn is_object_type(t: &s::TypeDefinition) -> bool {
+    match t {
+        s::TypeDefinition::Object(_) => true,
+        _ => false,
+    }
+}
+
+/// Returns whether an argument or input field has to be provided.
+fn is_required(def: &s::InputValue) -> bool {
+    match def.value_type {
+        s::Type::NonNullType(_) => def.default_value.is_none(),
+        _ => false,
+    }
+}
+
+/// Returns whether two fields have the same arguments, in any order.
+fn same_arguments(a: &q::Field, b: &q::Field) -> bool {
+    let same_values = a
+        .arguments
+        .iter()
+        .all(|argument| b.arguments.contains(argument));
+    same_values && a.arguments.len() == b.arguments.len()
+}
+
+/// Returns whether a variable may be used where a value of the usage's
+/// expected type is needed. A nullable variable may be used for a non-null
+/// value if a default value is used in its place when it is null.
+fn is_variable_usage_allowed(def: &q::VariableDefinition, usage: &VariableUsage) -> bool {
+    match (usage.expected_type, &def.var_type) {
+        (s::Type::NonNullType(_), s::Type::NonNullType(_)) => {
+            is_type_compatible(&def.var_type, usage.expected_type)
+        }
+        (s::Type::NonNullType(expected_type), _) => {
+            let has_non_null_default_value = match def.default_value {
+                Some(q::Value::Null) | None => false,
+                Some(_) => true,
+            };
+            (has_non_null_default_value || usage.has_default_value)
+                && is_type_compatible(&def.var_type, expected_type)
+        }
+        _ => is_type_compatible(&def.var_type, usage.expected_type),
+    }
+}
+
+/// Returns whether values of a variable type can be used where values of
+/// the expected type are needed.
+fn is_type_compatible(variable_type: &s::Type, expected_type: &s::Type) -> bool {
+    match (variable_type, expected_type) {
+        (s::Type::NonNullType(variable_type), s::Type::NonNullType(expected_type)) => {
+            is_type_compatible(variable_type, expected_type)
+        }
+        (_, s::Type::NonNullType(_)) => false,
+        (s::Type::NonNullType(variable_type), _) => {
+            is_type_compatible(variable_type, expected_type)
+        }
+        (s::Type::ListType(variable_type), s::Type::ListType(expected_type)) => {
+            is_type_compatible(variable_type, expected_type)
+        }
+        (s::Type::NamedType(variable_type), s::Type::NamedType(expected_type)) => {
+            variable_type == expected_type
+        }
+        _ => false,
+    }
+}
+
+/// Formats a type the way it is written in GraphQL, e.g. `[User!]!`.
+fn type_to_string(t: &s::Type) -> String {
+    match t {
+        s::Type::NamedType(name) => name.to_owned(),
+        s::Type::ListType(inner) => format!("[{}]", type_to_string(inner)),
+        s::Type::NonNullType(inner) => format!("{}!", type_to_string(inner)),
+    }
+}
@@ -0,0 +1,142 @@
+use graphql_parser::{query as q, schema as s};
+use std::collections::HashMap;
+
+use graph::prelude::*;
+
+use query::ast as qast;
+use schema::ast as sast;
+use values::coercion::coerce_value;
+
+/// Returns a copy of the document in which all variables used by its
+/// operation and fragments are replaced with their values.
+///
+/// The value of a variable is the one provided with the query, or the
+/// default value of the variable definition if there is none. Values are
+/// coerced to the type of their variable definition, which e.g. turns strings
+/// into enum values.
+pub fn substitute_variables(
+    schema: &s::Document,
+    introspection_schema: &s::Document,
+    document: &q::Document,
+    variables: &Option<QueryVariables>,
+) -> Result<q::Document, QueryExecutionError> {
+    let operation = qast::get_operation(document, None)?;
+    let variable_definitions = match operation {
+        q::OperationDefinition::Query(query) => &query.variable_definitions,
+        q::OperationDefinition::Mutation(mutation) => &mutation.variable_definitions,
+        q::OperationDefinition::Subscription(subscription) => &subscription.variable_definitions,
+        q::OperationDefinition::SelectionSet(_) => return Ok(document.clone()),
+    };
+
+    let resolver = |name: &s::Name| {
+        sast::get_named_type(schema, name)
+            .or_else(|| sast::get_named_type(introspection_schema, name))
+    };
+
+    let mut values = HashMap::new();
+    for def in variable_definitions {
+        let value = variables
+            .as_ref()
+            .and_then(|variables| variables.get(&def.name))
+            .map(|value| (**value).clone())
+            .or_else(|| def.default_value.clone())
+            .unwrap_or(q::Value::Null);
+
+        let value = match (&def.var_type, value) {
+            (s::Type::NonNullType(_), q::Value::Null) => {
+                return Err(QueryExecutionError::MissingVariableError(
+                    def.position,
+                    def.name.to_owned(),
+                ))
+            }
+            (_, q::Value::Null) => q::Value::Null,
+            (_, value) => coerce_value(&value, &def.var_type, &resolver).ok_or_else(|| {
+                QueryExecutionError::InvalidVariableError(
+                    def.position,
+                    def.name.to_owned(),
+                    value.clone(),
+                )
+            })?,
+        };
+        values.insert(def.name.to_owned(), value);
+    }
+
+    let mut document = document.clone();
+    for definition in document.definitions.iter_mut() {
+        match definition {
+            q::Definition::Operation(q::OperationDefinition::Query(query)) => {
+                substitute_in_selection_set(&mut query.selection_set, &values)
+            }
+            q::Definition::Operation(q::OperationDefinition::Mutation(mutation)) => {
+                substitute_in_selection_set(&mut mutation.selection_set, &values)
+            }
+            q::Definition::Operation(q::OperationDefinition::Subscription(subscription)) => {
+                substitute_in_selection_set(&mut subscription.selection_set, &values)
+            }
+            q::Definition::Operation(q::OperationDefinition::SelectionSet(selection_set)) => {
+                substitute_in_selection_set(selection_set, &values)
+            }
+            q::Definition::Fragment(fragment) => {
+                substitute_in_directives(&mut fragment.directives, &values);
+                substitute_in_selection_set(&mut fragment.selection_set, &values)
+            }
+        }
+    }
+    Ok(document)
+}
+
+fn substitute_in_selection_set(
+    selection_set: &mut q::SelectionSet,
+    values: &HashMap<q::Name, q::Value>,
+) {
+    for selection in selection_set.items.iter_mut() {
+        match selection {
+            q::Selection::Field(field) => {
+                for (_, value) in field.arguments.iter_mut() {
+                    substitute_in_value(value, values);
+                }
+                substitute_in_directives(&mut field.directives, values);
+                substitute_in_selection_set(&mut field.selection_set, values);
+            }
+            q::Selection::FragmentSpread(spread) => {
+                substitute_in_directives(&mut spread.directives, values);
+            }
+            q::Selection::InlineFragment(fragment) => {
+                substitute_in_directives(&mut fragment.directives, values);
+                substitute_in_selection_set(&mut fragment.selection_set, values);
+            }
+        }
+    }
+}
+
+fn substitute_in_directives(
+    directives: &mut Vec<q::Directive>,
+    values: &HashMap<q::Name, q::Value>,
+) {
+    for directive in directives.iter_mut() {
+        for (_, value) in directive.arguments.iter_mut() {
+            substitute_in_value(value, values);
+        }
+    }
+}
+
+fn substitute_in_value(value: &mut q::Value, values: &HashMap<q::Name, q::Value>) {
+    let replacement = match value {
+        // Validation guarantees that all variables are defined
+        q::Value::Variable(name) => values.get(name).cloned().unwrap_or(q::Value::Null),
+        q::Value::List(items) => {
+            for item in items.iter_mut() {
+                substitute_in_value(item, values);
+            }
+            return;
+        }
+        q::Value::Object(fields) => {
+            for (_, field) in fields.iter_mut() {
+                substitute_in_value(field, values);
+            }
+            return;
+        }
+        _ => return,
+    };
+    *value = replacement;
+}
@@ -3,7 +3,7 @@ use graphql_parser::Pos;
 use inflector::Inflector;
 use std::error::Error;
 use std::fmt;
-use std::iter::IntoIterator;
+use std::iter::{self, IntoIterator};
 
 use schema::ast;
 
@@ -45,6 +45,8 @@ pub fn api_schema(input_schema: &Document) -> Result<Document, APISchemaError> {
     let mut schema = input_schema.clone();
     add_builtin_scalar_types(&mut schema)?;
     add_order_direction_enum(&mut schema);
+    add_block_height_type(&mut schema)?;
+    add_meta_field_types(&mut schema)?;
     add_types_for_object_types(&mut schema, &object_types)?;
     add_types_for_interface_types(&mut schema, &interface_types)?;
     add_query_type(&mut schema, &object_types, &interface_types)?;
@@ -93,6 +95,101 @@ fn add_order_direction_enum(schema: &mut Document) {
     schema.definitions.push(def);
 }
 
+/// Adds a global `Block_height` input type to the schema, which selects the
+/// block that entities are queried at by either its number or its hash.
+fn add_block_height_type(schema: &mut Document) -> Result<(), APISchemaError> {
+    let type_name = String::from("Block_height");
+
+    if ast::get_named_type(schema, &type_name).is_some() {
+        return Err(APISchemaError::TypeExists(type_name));
+    }
+
+    let typedef = TypeDefinition::InputObject(InputObjectType {
+        position: Pos::default(),
+        description: None,
+        name: type_name,
+        directives: vec![],
+        fields: vec![
+            input_value(
+                &"number".to_string(),
+                "",
+                Type::NamedType("Int".to_string()),
+            ),
+            input_value(
+                &"hash".to_string(),
+                "",
+                Type::NamedType("Bytes".to_string()),
+            ),
+        ],
+    });
+    let def = Definition::TypeDefinition(typedef);
+    schema.definitions.push(def);
+    Ok(())
+}
+
+/// Adds the `_Meta_` and `_Block_` types that the `_meta` field of the root
+/// `Query` type returns. `_Meta_` carries the `@subgraphId` directive of the
+/// input types, so that resolvers know which subgraph to report on.
+fn add_meta_field_types(schema: &mut Document) -> Result<(), APISchemaError> {
+    for type_name in ["_Meta_", "_Block_"].into_iter() {
+        if ast::get_named_type(schema, &type_name.to_string()).is_some() {
+            return Err(APISchemaError::TypeExists(type_name.to_string()));
+        }
+    }
+
+    let subgraph_id_directives = ast::get_object_type_definitions(schema)
+        .into_iter()
+        .flat_map(|object_type| object_type.directives.iter())
+        .find(|directive| directive.name == "subgraphId")
+        .cloned()
+        .into_iter()
+        .collect();
+
+    let block_type = TypeDefinition::Object(ObjectType {
+        position: Pos::default(),
+        description: None,
+        name: "_Block_".to_string(),
+        implements_interfaces: vec![],
+        directives: vec![],
+        fields: vec![
+            meta_field("number", non_null_type("Int")),
+            meta_field("hash", non_null_type("Bytes")),
+        ],
+    });
+    let meta_type = TypeDefinition::Object(ObjectType {
+        position: Pos::default(),
+        description: None,
+        name: "_Meta_".to_string(),
+        implements_interfaces: vec![],
+        directives: subgraph_id_directives,
+        fields: vec![
+            meta_field("block", non_null_type("_Block_")),
+            meta_field("deployment", non_null_type("String")),
+            meta_field("hasIndexingErrors", non_null_type("Boolean")),
+        ],
+    });
+    for typedef in vec![block_type, meta_type] {
+        let def = Definition::TypeDefinition(typedef);
+        schema.definitions.push(def);
+    }
+    Ok(())
+}
+
+fn meta_field(name: &str, field_type: Type) -> Field {
+    Field {
+        position: Pos::default(),
+        description: None,
+        name: name.to_string(),
+        arguments: vec![],
+        field_type,
+        directives: vec![],
+    }
+}
+
+fn non_null_type(type_name: &str) -> Type {
+    Type::NonNullType(Box::new(Type::NamedType(type_name.to_string())))
+}
+
 /// Adds `*_orderBy` and `*_filter` enum types for the given object types to the schema.
 fn add_types_for_object_types(
     schema: &mut Document,
@@ -317,6 +414,10 @@ fn add_query_type(
             .map(|t| &t.name)
             .chain(interface_types.iter().map(|t| &t.name))
             .flat_map(|name| query_fields_for_type(schema, name))
+            .chain(iter::once(meta_field(
+                "_meta",
+                Type::NamedType("_Meta_".to_string()),
+            )))
             .collect(),
     });
     let def = Definition::TypeDefinition(typedef);
@@ -361,14 +462,21 @@ fn query_fields_for_type(_schema: &Document, type_name: &Name) -> Vec<Field> {
             position: Pos::default(),
             description: None,
             name: type_name.as_str().to_camel_case(),
-            arguments: vec![InputValue {
-                position: Pos::default(),
-                description: None,
-                name: "id".to_string(),
-                value_type: Type::NonNullType(Box::new(Type::NamedType("ID".to_string()))),
-                default_value: None,
-                directives: vec![],
-            }],
+            arguments: vec![
+                InputValue {
+                    position: Pos::default(),
+                    description: None,
+                    name: "id".to_string(),
+                    value_type: Type::NonNullType(Box::new(Type::NamedType("ID".to_string()))),
+                    default_value: None,
+                    directives: vec![],
+                },
+                input_value(
+                    &"block".to_string(),
+                    "",
+                    Type::NamedType("Block_height".to_string()),
+                ),
+            ],
             field_type: Type::NamedType(type_name.to_owned()),
             directives: vec![],
         },
@@ -397,6 +505,11 @@ fn query_fields_for_type(_schema: &Document, type_name: &Name) -> Vec<Field> {
                     "",
                     Type::NamedType(format!("{}_filter", type_name)),
                 ),
+                input_value(
+                    &"block".to_string(),
+                    "",
+                    Type::NamedType("Block_height".to_string()),
+                ),
             ],
             field_type: Type::NonNullType(Box::new(Type::ListType(Box::new(Type::NonNullType(
                 Box::new(Type::NamedType(type_name.to_owned())),
@@ -447,6 +560,75 @@ mod tests {
         assert_eq!(values, [&"asc".to_string(), &"desc".to_string()]);
     }
 
+    #[test]
+    fn api_schema_contains_block_height_type() {
+        let input_schema =
+            parse_schema("type User { id: ID! }").expect("Failed to parse input schema");
+        let schema = api_schema(&input_schema).expect("Failed to derive API schema");
+
+        let block_height = ast::get_named_type(&schema, &"Block_height".to_string())
+            .expect("Block_height type is missing in API schema");
+
+        let input_type = match block_height {
+            TypeDefinition::InputObject(t) => Some(t),
+            _ => None,
+        }.expect("Block_height type is not an input object");
+
+        assert_eq!(
+            input_type
+                .fields
+                .iter()
+                .map(|input_value| (input_value.name.to_owned(), input_value.value_type.clone()))
+                .collect::<Vec<(String, Type)>>(),
+            vec![
+                ("number".to_string(), Type::NamedType("Int".to_string())),
+                ("hash".to_string(), Type::NamedType("Bytes".to_string())),
+            ],
+        );
+    }
+
+    #[test]
+    fn api_schema_contains_meta_field_types() {
+        let input_schema = parse_schema("type User @subgraphId(id: \"users\") { id: ID! }")
+            .expect("Failed to parse input schema");
+        let schema = api_schema(&input_schema).expect("Failed to derive API schema");
+
+        let query_type = ast::get_named_type(&schema, &"Query".to_string())
+            .expect("Query type is missing in derived API schema");
+        let meta_field = match query_type {
+            TypeDefinition::Object(t) => ast::get_field_type(t, &"_meta".to_string()),
+            _ => None,
+        }.expect("\"_meta\" field is missing on Query type");
+        assert_eq!(meta_field.field_type, Type::NamedType("_Meta_".to_string()));
+
+        let meta_type = match ast::get_named_type(&schema, &"_Meta_".to_string()) {
+            Some(TypeDefinition::Object(t)) => Some(t),
+            _ => None,
+        }.expect("_Meta_ type is missing in API schema");
+        assert_eq!(
+            meta_type
+                .fields
+                .iter()
+                .map(|field| field.name.to_owned())
+                .collect::<Vec<String>>(),
+            vec!["block", "deployment", "hasIndexingErrors"]
+        );
+        assert_eq!(
+            meta_type.directives,
+            input_schema
+                .definitions
+                .iter()
+                .filter_map(|def| match def {
+                    Definition::TypeDefinition(TypeDefinition::Object(t)) => Some(t),
+                    _ => None,
+                }).flat_map(|t| t.directives.clone())
+                .collect::<Vec<Directive>>()
+        );
+
+        ast::get_named_type(&schema, &"_Block_".to_string())
+            .expect("_Block_ type is missing in API schema");
+    }
+
     #[test]
     fn api_schema_contains_query_type() {
         let input_schema =
@@ -550,7 +732,7 @@ mod tests {
                 .iter()
                 .map(|input_value| input_value.name.to_owned())
                 .collect::<Vec<String>>(),
-            vec!["id".to_string()],
+            vec!["id".to_string(), "block".to_string()],
         );
 
         let user_plural_field = match query_type {
@@ -580,6 +762,7 @@ mod tests {
                 "orderBy",
                 "orderDirection",
                 "where",
+                "block",
             ]
                 .into_iter()
                 .map(|name| name.to_string())
@@ -638,7 +821,7 @@ mod tests {
                 .iter()
                 .map(|input_value| input_value.name.to_owned())
                 .collect::<Vec<String>>(),
-            vec!["id".to_string()],
+            vec!["id".to_string(), "block".to_string()],
         );
 
         let plural_field = match query_type {
@@ -668,6 +851,7 @@ mod tests {
                 "orderBy",
                 "orderDirection",
                 "where",
+                "block",
             ]
                 .into_iter()
                 .map(|name| name.to_string())
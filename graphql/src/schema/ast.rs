@@ -174,6 +174,25 @@ pub fn get_type_definition_from_type<'a>(
     }
 }
 
+/// Returns the name of the named type that a type is made of, e.g. `User`
+/// for `[User!]!`.
+pub fn get_base_type(t: &Type) -> &Name {
+    match t {
+        Type::NamedType(name) => name,
+        Type::ListType(inner) => get_base_type(inner),
+        Type::NonNullType(inner) => get_base_type(inner),
+    }
+}
+
+/// Returns true if the type is a list type or a non-null list type.
+pub fn is_list_type(t: &Type) -> bool {
+    match t {
+        Type::NamedType(_) => false,
+        Type::ListType(_) => true,
+        Type::NonNullType(inner) => is_list_type(inner),
+    }
+}
+
 /// Looks up a directive in a object type, if it is provided.
 pub fn get_object_type_directive(object_type: &ObjectType, name: Name) -> Option<&Directive> {
     object_type
@@ -2,4 +2,5 @@ mod query;
 mod resolver;
 
 pub use self::query::build_query;
+pub(crate) use self::query::DEFAULT_FIRST;
 pub use self::resolver::StoreResolver;
@@ -3,6 +3,10 @@ use graphql_parser::{query as q, schema as s};
 use schema::ast as sast;
 use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 
+/// The number of entities that collections return if neither `first` nor
+/// `last` is provided.
+pub(crate) const DEFAULT_FIRST: usize = 100;
+
 /// Builds a StoreQuery from GraphQL arguments.
 pub fn build_query(entity: &s::ObjectType, arguments: &HashMap<&q::Name, q::Value>) -> StoreQuery {
     StoreQuery {
@@ -13,10 +17,15 @@ pub fn build_query(entity: &s::ObjectType, arguments: &HashMap<&q::Name, q::Valu
         filter: build_filter(entity, arguments),
         order_by: build_order_by(arguments),
         order_direction: build_order_direction(arguments),
+        after: build_cursor(arguments, "after"),
+        before: build_cursor(arguments, "before"),
+        last: build_last(arguments),
+        block: None,
     }
 }
 
-/// Parses GraphQL arguments into a StoreRange, if present.
+/// Parses GraphQL arguments into a StoreRange. Collections are limited to
+/// the first `DEFAULT_FIRST` entities unless the last entities are requested.
 fn build_range(arguments: &HashMap<&q::Name, q::Value>) -> Option<StoreRange> {
     let first = arguments
         .get(&"first".to_string())
@@ -33,13 +42,41 @@ fn build_range(arguments: &HashMap<&q::Name, q::Value>) -> Option<StoreRange> {
         }).and_then(|n| if n >= 0 { Some(n as usize) } else { None });
 
     match (first, skip) {
-        (None, None) => None,
+        // Without a range, the last entities are found by reversing the order
+        (None, None) if build_last(arguments).is_some() => None,
+        (None, None) => Some(StoreRange {
+            first: DEFAULT_FIRST,
+            skip: 0,
+        }),
         (Some(first), None) => Some(StoreRange { first, skip: 0 }),
         (Some(first), Some(skip)) => Some(StoreRange { first, skip }),
-        (None, Some(skip)) => Some(StoreRange { first: 100, skip }),
+        (None, Some(skip)) => Some(StoreRange {
+            first: DEFAULT_FIRST,
+            skip,
+        }),
     }
 }
 
+/// Parses the `last` GraphQL argument, if present.
+fn build_last(arguments: &HashMap<&q::Name, q::Value>) -> Option<usize> {
+    arguments
+        .get(&"last".to_string())
+        .and_then(|value| match value {
+            q::Value::Int(n) => n.as_i64(),
+            _ => None,
+        }).and_then(|n| if n > 0 { Some(n as usize) } else { None })
+}
+
+/// Parses a cursor GraphQL argument (e.g. `after`) into an entity ID, if present.
+fn build_cursor(arguments: &HashMap<&q::Name, q::Value>, name: &str) -> Option<String> {
+    arguments
+        .get(&name.to_string())
+        .and_then(|value| match value {
+            q::Value::String(id) => Some(id.to_owned()),
+            _ => None,
+        })
+}
+
 /// Parses GraphQL arguments into a StoreFilter, if present.
 fn build_filter(
     entity: &s::ObjectType,
@@ -382,8 +419,27 @@ mod tests {
     }
 
     #[test]
-    fn build_query_yields_no_range_if_none_is_present() {
-        assert_eq!(build_query(&default_object(), &HashMap::new()).range, None,);
+    fn build_query_yields_default_range_if_none_is_present() {
+        assert_eq!(
+            build_query(&default_object(), &HashMap::new()).range,
+            Some(StoreRange {
+                first: 100,
+                skip: 0,
+            }),
+        );
+    }
+
+    #[test]
+    fn build_query_yields_no_range_if_only_last_is_present() {
+        assert_eq!(
+            build_query(
+                &default_object(),
+                &HashMap::from_iter(
+                    vec![(&"last".to_string(), q::Value::Int(q::Number::from(20)))].into_iter()
+                )
+            ).range,
+            None,
+        );
     }
 
     #[test]
@@ -439,4 +495,49 @@ mod tests {
             )]))
         )
     }
+
+    #[test]
+    fn build_query_yields_no_cursors_if_none_are_present() {
+        let query = build_query(&default_object(), &HashMap::new());
+        assert_eq!(query.after, None);
+        assert_eq!(query.before, None);
+        assert_eq!(query.last, None);
+    }
+
+    #[test]
+    fn build_query_parses_cursors() {
+        let query = build_query(
+            &default_object(),
+            &HashMap::from_iter(
+                vec![
+                    (&"after".to_string(), q::Value::String("a".to_string())),
+                    (&"before".to_string(), q::Value::String("z".to_string())),
+                ].into_iter(),
+            ),
+        );
+        assert_eq!(query.after, Some("a".to_string()));
+        assert_eq!(query.before, Some("z".to_string()));
+    }
+
+    #[test]
+    fn build_query_parses_last() {
+        assert_eq!(
+            build_query(
+                &default_object(),
+                &HashMap::from_iter(
+                    vec![(&"last".to_string(), q::Value::Int(q::Number::from(20)))].into_iter()
+                )
+            ).last,
+            Some(20),
+        );
+        assert_eq!(
+            build_query(
+                &default_object(),
+                &HashMap::from_iter(
+                    vec![(&"last".to_string(), q::Value::Int(q::Number::from(0)))].into_iter()
+                )
+            ).last,
+            None,
+        );
+    }
 }
@@ -2,10 +2,11 @@ use graphql_parser::{query as q, schema as s};
 use std::collections::HashMap;
 use std::ops::Deref;
 use std::result;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 use graph::components::store::*;
 use graph::prelude::*;
+use graph::web3::types::H256;
 
 use prelude::*;
 use query::ast as qast;
@@ -15,7 +16,7 @@ use store::query::{collect_entities_from_query_field, parse_subgraph_id};
 /// A resolver that fetches entities from a `Store`.
 pub struct StoreResolver<S> {
     logger: Logger,
-    store: Arc<Mutex<S>>,
+    store: Arc<S>,
 }
 
 impl<S> Clone for StoreResolver<S>
@@ -34,7 +35,7 @@ impl<S> StoreResolver<S>
 where
     S: Store,
 {
-    pub fn new(logger: &Logger, store: Arc<Mutex<S>>) -> Self {
+    pub fn new(logger: &Logger, store: Arc<S>) -> Self {
         StoreResolver {
             logger: logger.new(o!("component" => "StoreResolver")),
             store,
@@ -50,6 +51,23 @@ where
             .find(|directive| directive.name == s::Name::from("derivedFrom"))
     }
 
+    /// Obtains the field of the object type that a field with a
+    /// `@derivedFrom(field: "foo")` directive is derived from.
+    fn get_derived_from_field<'a>(
+        field_definition: &s::Field,
+        object_type: &'a s::ObjectType,
+    ) -> Option<&'a s::Field> {
+        Self::get_derived_from_directive(field_definition)
+            .and_then(|directive| {
+                qast::get_argument_value(&directive.arguments, &q::Name::from("field"))
+            }).and_then(|value| match value {
+                q::Value::String(s) => Some(s),
+                _ => None,
+            }).and_then(|derived_from_field_name| {
+                sast::get_field_type(object_type, derived_from_field_name)
+            })
+    }
+
     /// Adds a filter for matching entities that correspond to a derived field.
     ///
     /// Returns true if the field is a derived field (i.e., if it is defined with
@@ -60,15 +78,7 @@ where
         field_definition: &s::Field,
         object_type: &s::ObjectType,
     ) -> bool {
-        let derived_from_field = Self::get_derived_from_directive(field_definition)
-            .and_then(|directive| {
-                qast::get_argument_value(&directive.arguments, &q::Name::from("field"))
-            }).and_then(|value| match value {
-                q::Value::String(s) => Some(s),
-                _ => None,
-            }).and_then(|derived_from_field_name| {
-                sast::get_field_type(object_type, derived_from_field_name)
-            });
+        let derived_from_field = Self::get_derived_from_field(field_definition, object_type);
 
         if let Some(derived_from_field) = derived_from_field {
             // This field is derived from a field in the object type that we're trying
@@ -169,6 +179,181 @@ where
         }
     }
 
+    /// Determines the number of the block that the `block` argument of a
+    /// field asks for, if it has one. Fails if the subgraph of the object
+    /// type has not indexed that block yet.
+    fn block_number(
+        &self,
+        object_type: &s::ObjectType,
+        arguments: &HashMap<&q::Name, q::Value>,
+    ) -> result::Result<Option<u64>, QueryExecutionError> {
+        let block = match arguments.get(&"block".to_string()) {
+            Some(q::Value::Object(block)) => block,
+            Some(q::Value::Null) | None => return Ok(None),
+            Some(value) => return Err(QueryExecutionError::InvalidBlockArgument(value.clone())),
+        };
+        let invalid_block =
+            || QueryExecutionError::InvalidBlockArgument(q::Value::Object(block.clone()));
+        let block_field = |name: &str| block.get(name).filter(|value| **value != q::Value::Null);
+
+        let number = match (block_field("number"), block_field("hash")) {
+            (Some(q::Value::Int(number)), None) => number
+                .as_i64()
+                .filter(|number| *number >= 0)
+                .map(|number| number as u64)
+                .ok_or_else(invalid_block)?,
+            (None, Some(q::Value::String(hash))) => {
+                let unknown_block = || QueryExecutionError::UnknownBlock(hash.to_owned());
+                let block_hash = hash
+                    .trim_left_matches("0x")
+                    .parse::<H256>()
+                    .map_err(|_| unknown_block())?;
+                self.store
+                    .block(block_hash)
+                    .map_err(|e| {
+                        error!(self.logger, "Failed to look up block";
+                               "hash" => hash,
+                               "error" => e.to_string());
+                        unknown_block()
+                    })?.and_then(|block| block.number)
+                    .ok_or_else(unknown_block)?
+                    .as_u64()
+            }
+            _ => return Err(invalid_block()),
+        };
+
+        // Entities cannot be queried at blocks that are yet to be processed
+        let subgraph_id = parse_subgraph_id(object_type)
+            .expect(format!("Failed to get subgraph ID from type: {}", object_type.name).as_str());
+        let block_ptr = self.store.block_ptr(subgraph_id).map_err(|e| {
+            error!(self.logger, "Failed to look up the block pointer of the subgraph";
+                   "error" => e.to_string());
+            QueryExecutionError::BlockNotIndexed(number)
+        })?;
+        match block_ptr {
+            Some(block_ptr) if block_ptr.number >= number => Ok(Some(number)),
+            _ => Err(QueryExecutionError::BlockNotIndexed(number)),
+        }
+    }
+
+    /// Fails if the `after` or `before` cursor of the query refers to an
+    /// entity that does not exist, instead of finding no entities.
+    fn check_cursors(
+        &self,
+        object_type: &s::ObjectType,
+        query: &StoreQuery,
+    ) -> result::Result<(), QueryExecutionError> {
+        for cursor in query.after.iter().chain(query.before.iter()) {
+            if self.get_entity(object_type, cursor, query.block) == q::Value::Null {
+                return Err(QueryExecutionError::UnknownCursor(cursor.to_owned()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Looks up the entity with the given ID, as it was at `block` if
+    /// present.
+    fn get_entity(&self, object_type: &s::ObjectType, id: &String, block: Option<u64>) -> q::Value {
+        let subgraph = parse_subgraph_id(object_type)
+            .expect(format!("Failed to get subgraph ID from type: {}", object_type.name).as_str());
+
+        // Past versions of entities can only be found with queries
+        if block.is_some() {
+            return self
+                .store
+                .find(StoreQuery {
+                    subgraph,
+                    entity: object_type.name.to_owned(),
+                    filter: Some(StoreFilter::And(vec![StoreFilter::Equal(
+                        String::from("id"),
+                        Value::from(id),
+                    )])),
+                    order_by: None,
+                    order_direction: None,
+                    range: Some(StoreRange { first: 1, skip: 0 }),
+                    after: None,
+                    before: None,
+                    last: None,
+                    block,
+                }).ok()
+                .and_then(|entities| entities.into_iter().next())
+                .map(|entity| entity.into())
+                .unwrap_or(q::Value::Null);
+        }
+
+        self.store
+            .get(StoreKey {
+                subgraph,
+                entity: object_type.name.to_owned(),
+                id: id.to_owned(),
+            }).ok()
+            .and_then(|entity| entity)
+            .map(|entity| entity.into())
+            .unwrap_or(q::Value::Null)
+    }
+
+    /// Resolves the `_meta` field, which reports the latest block that the
+    /// subgraph has processed. Resolves to null if the subgraph has not
+    /// processed any blocks yet.
+    fn meta_object(&self, object_type: &s::ObjectType) -> q::Value {
+        let subgraph_id = parse_subgraph_id(object_type)
+            .expect(format!("Failed to get subgraph ID from type: {}", object_type.name).as_str());
+
+        let block_ptr = match self.store.block_ptr(subgraph_id.clone()) {
+            Ok(Some(block_ptr)) => block_ptr,
+            Ok(None) => return q::Value::Null,
+            Err(e) => {
+                error!(self.logger, "Failed to look up the block pointer of the subgraph";
+                       "error" => e.to_string());
+                return q::Value::Null;
+            }
+        };
+
+        let has_indexing_errors = match self.store.subgraph_health(subgraph_id.clone()) {
+            Ok(SubgraphHealth::Failed { .. }) => true,
+            Ok(_) => false,
+            Err(e) => {
+                error!(self.logger, "Failed to look up the health of the subgraph";
+                       "error" => e.to_string());
+                return q::Value::Null;
+            }
+        };
+
+        object_value(vec![
+            (
+                "block",
+                object_value(vec![
+                    (
+                        "number",
+                        q::Value::Int(q::Number::from(block_ptr.number as i32)),
+                    ),
+                    ("hash", q::Value::String(format!("{:#x}", block_ptr.hash))),
+                ]),
+            ),
+            ("deployment", q::Value::String(subgraph_id)),
+            ("hasIndexingErrors", q::Value::Boolean(has_indexing_errors)),
+        ])
+    }
+
+    /// Returns the IDs of the entities that a field of a parent object
+    /// refers to, or `None` if the field does not hold references.
+    fn referenced_ids(parent: &q::Value, field: &q::Name) -> Option<Vec<String>> {
+        match parent {
+            q::Value::Object(object) => match object.get(field) {
+                None | Some(q::Value::Null) => Some(vec![]),
+                Some(q::Value::String(id)) => Some(vec![id.to_owned()]),
+                Some(q::Value::List(ids)) => ids
+                    .iter()
+                    .map(|id| match id {
+                        q::Value::String(id) => Some(id.to_owned()),
+                        _ => None,
+                    }).collect(),
+                Some(_) => None,
+            },
+            _ => None,
+        }
+    }
+
     /// Returns true if the object has no references in the given field.
     fn references_field_is_empty(parent: &Option<q::Value>, field: &q::Name) -> bool {
         parent
@@ -195,8 +380,10 @@ where
         field_definition: &s::Field,
         object_type: &s::ObjectType,
         arguments: &HashMap<&q::Name, q::Value>,
-    ) -> q::Value {
+    ) -> result::Result<q::Value, QueryExecutionError> {
         let mut query = build_query(&object_type, arguments);
+        query.block = self.block_number(object_type, arguments)?;
+        self.check_cursors(object_type, &query)?;
 
         // Add matching filter for derived fields
         let is_derived =
@@ -209,7 +396,7 @@ where
             && parent.is_some()
             && Self::references_field_is_empty(parent, &field_definition.name)
         {
-            return q::Value::List(vec![]);
+            return Ok(q::Value::List(vec![]));
         }
 
         // Add matching filter for reference fields
@@ -217,8 +404,8 @@ where
             Self::add_filter_for_reference_field(&mut query, parent, field_definition, object_type);
         }
 
-        let store = self.store.lock().unwrap();
-        store
+        Ok(self
+            .store
             .find(query)
             .map(|entities| {
                 q::Value::List(
@@ -227,7 +414,7 @@ where
                         .map(|e| e.into())
                         .collect::<Vec<q::Value>>(),
                 )
-            }).unwrap_or(q::Value::Null)
+            }).unwrap_or(q::Value::Null))
     }
 
     fn resolve_object(
@@ -237,45 +424,33 @@ where
         field_definition: &s::Field,
         object_type: &s::ObjectType,
         arguments: &HashMap<&q::Name, q::Value>,
-    ) -> q::Value {
+    ) -> result::Result<q::Value, QueryExecutionError> {
+        if object_type.name == "_Meta_" {
+            return Ok(self.meta_object(object_type));
+        }
+
+        let block = self.block_number(object_type, arguments)?;
+
         let id = arguments.get(&"id".to_string()).and_then(|id| match id {
             q::Value::String(s) => Some(s),
             _ => None,
         });
 
         if let Some(id) = id {
-            let store = self.store.lock().unwrap();
-            return store
-                .get(StoreKey {
-                    subgraph: parse_subgraph_id(object_type).expect(
-                        format!("Failed to get subgraph ID from type: {}", object_type.name)
-                            .as_str(),
-                    ),
-                    entity: object_type.name.to_owned(),
-                    id: id.to_owned(),
-                }).map(|entity| entity.into())
-                .unwrap_or(q::Value::Null);
+            return Ok(self.get_entity(object_type, id, block));
         }
 
-        match parent {
+        Ok(match parent {
             Some(q::Value::Object(parent_object)) => match parent_object.get(field) {
-                Some(q::Value::String(id)) => self
-                    .store
-                    .lock()
-                    .unwrap()
-                    .get(StoreKey {
-                        subgraph: parse_subgraph_id(object_type).expect(
-                            format!("Failed to get subgraph ID from type: {}", object_type.name)
-                                .as_str(),
-                        ),
-                        entity: object_type.name.to_owned(),
-                        id: id.to_owned(),
-                    }).map(|entity| entity.into())
-                    .unwrap_or(q::Value::Null),
+                Some(q::Value::String(id)) => self.get_entity(object_type, id, block),
+                // Objects that are embedded in their parent, like the block
+                // of `_meta`, need no lookup
+                Some(q::Value::Object(object)) => q::Value::Object(object.clone()),
                 _ => q::Value::Null,
             },
             _ => {
                 let mut query = build_query(&object_type, arguments);
+                query.block = block;
 
                 // Add matching filter for derived fields
                 Self::add_filter_for_derived_field(
@@ -288,8 +463,6 @@ where
                 query.range = Some(StoreRange { first: 1, skip: 0 });
 
                 self.store
-                    .lock()
-                    .unwrap()
                     .find(query)
                     .map(|entities| {
                         entities
@@ -299,7 +472,130 @@ where
                             .unwrap_or(q::Value::Null)
                     }).unwrap_or(q::Value::Null)
             }
+        })
+    }
+
+    fn resolve_field_batch(
+        &self,
+        parents: &[q::Value],
+        field: &q::Name,
+        field_definition: &s::Field,
+        object_type: &s::ObjectType,
+        arguments: &HashMap<&q::Name, q::Value>,
+    ) -> result::Result<Option<Vec<q::Value>>, QueryExecutionError> {
+        // Ranges and cursors select a window of the entities of each parent,
+        // which a single query for all parents can only provide by loading
+        // all entities of all parents
+        let has_window = ["first", "skip", "after", "before", "last"]
+            .iter()
+            .any(|name| match arguments.get(&name.to_string()) {
+                Some(q::Value::Null) | None => false,
+                Some(_) => true,
+            });
+        if has_window {
+            return Ok(None);
         }
+
+        let is_list = sast::is_list_type(&field_definition.field_type);
+
+        // Each parent may have any number of entities in a derived field, of
+        // which only the first ones are returned; the only way to return
+        // them for all parents at once would be to load all of them
+        if Self::get_derived_from_field(field_definition, object_type).is_some() {
+            return Ok(None);
+        }
+
+        // Parents are linked to the entities of the field through the IDs
+        // they refer to
+        let parent_keys = match parents
+            .iter()
+            .map(|parent| Self::referenced_ids(parent, field))
+            .collect::<Option<Vec<_>>>()
+        {
+            Some(parent_keys) => parent_keys,
+            None => return Ok(None),
+        };
+
+        let mut keys = parent_keys
+            .iter()
+            .flat_map(|keys| keys.iter())
+            .collect::<Vec<&String>>();
+        keys.sort();
+        keys.dedup();
+
+        // There is no point in querying the store if no parent has entities
+        if keys.is_empty() {
+            return Ok(Some(
+                parents
+                    .iter()
+                    .map(|_| {
+                        if is_list {
+                            q::Value::List(vec![])
+                        } else {
+                            q::Value::Null
+                        }
+                    }).collect(),
+            ));
+        }
+
+        let filter = StoreFilter::In(
+            String::from("id"),
+            keys.iter().map(|key| Value::from(*key)).collect(),
+        );
+
+        // Query the entities of all parents at once; the number of entities
+        // is bounded by the IDs that the parents refer to, and the range
+        // applies to the entities of each parent
+        let mut query = build_query(&object_type, arguments);
+        query.block = self.block_number(object_type, arguments)?;
+        let range = query.range.take();
+        query.filter = Some(match query.filter.take() {
+            Some(StoreFilter::And(mut filters)) => {
+                filters.push(filter);
+                StoreFilter::And(filters)
+            }
+            _ => StoreFilter::And(vec![filter]),
+        });
+
+        let entities = match self.store.find(query) {
+            Ok(entities) => entities,
+            Err(()) => return Ok(Some(parents.iter().map(|_| q::Value::Null).collect())),
+        };
+
+        let entity_indices: HashMap<String, usize> = entities
+            .iter()
+            .enumerate()
+            .filter_map(|(i, entity)| match entity.get("id") {
+                Some(Value::String(id)) => Some((id.to_owned(), i)),
+                _ => None,
+            }).collect();
+        let entities = entities
+            .into_iter()
+            .map(|entity| entity.into())
+            .collect::<Vec<q::Value>>();
+
+        // Hand each parent its entities, in the order of the query
+        Ok(Some(
+            parent_keys
+                .iter()
+                .map(|keys| {
+                    let mut indices = keys
+                        .iter()
+                        .filter_map(|key| entity_indices.get(key).cloned())
+                        .collect::<Vec<usize>>();
+                    indices.sort();
+                    indices.dedup();
+
+                    let mut values = indices.into_iter().map(|i| entities[i].clone());
+                    match (is_list, &range) {
+                        (true, Some(range)) => {
+                            q::Value::List(values.skip(range.skip).take(range.first).collect())
+                        }
+                        (true, None) => q::Value::List(values.collect()),
+                        (false, _) => values.next().unwrap_or(q::Value::Null),
+                    }
+                }).collect(),
+        ))
     }
 
     fn resolve_field_stream<'a, 'b>(
@@ -321,6 +617,6 @@ where
         let entities = collect_entities_from_query_field(schema, object_type, field);
 
         // Subscribe to the store and return the entity change stream
-        Ok(self.store.lock().unwrap().subscribe(entities))
+        Ok(self.store.subscribe(entities))
     }
 }
@@ -1,6 +1,7 @@
 use graphql_parser::{query as q, schema as s};
 use std::collections::HashMap;
 use std::result::Result;
+use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
 
 use graph::prelude::*;
@@ -8,6 +9,7 @@ use graph::prelude::*;
 use execution::*;
 use prelude::*;
 use query::ast as qast;
+use query::substitute_variables;
 use schema::ast as sast;
 
 /// Options available for subscription execution.
@@ -30,11 +32,27 @@ where
 {
     info!(options.logger, "Execute subscription");
 
+    // Reject subscriptions that break any of the validation rules, reporting
+    // the first violation
+    let introspection_schema = introspection_schema();
+    validate_query(
+        &subscription.query.schema.document,
+        &introspection_schema,
+        &subscription.query.document,
+    ).map_err(|mut errors| errors.remove(0))?;
+
+    // Replace the variables of the subscription with their values
+    let document = substitute_variables(
+        &subscription.query.schema.document,
+        &introspection_schema,
+        &subscription.query.document,
+        &subscription.query.variables,
+    )?;
+
     // Obtain the only operation of the subscription (fail if there is none or more than one)
-    let operation = qast::get_operation(&subscription.query.document, None)?;
+    let operation = qast::get_operation(&document, None)?;
 
-    // Create an introspection type store and resolver
-    let introspection_schema = introspection_schema();
+    // Create an introspection resolver
     let introspection_resolver =
         IntrospectionResolver::new(&options.logger, &subscription.query.schema);
 
@@ -46,8 +64,12 @@ where
         introspection_resolver: Arc::new(introspection_resolver),
         introspection_schema: &introspection_schema,
         introspecting: false,
-        document: &subscription.query.document,
+        document: &document,
         fields: vec![],
+        block: None,
+        deadline: None,
+        result_size: Arc::new(AtomicUsize::new(0)),
+        max_result_size: None,
     };
 
     match operation {
@@ -167,6 +189,10 @@ where
         introspecting: false,
         document: &document,
         fields: vec![],
+        block: None,
+        deadline: None,
+        result_size: Arc::new(AtomicUsize::new(0)),
+        max_result_size: None,
     };
 
     // We have established that this exists earlier in the subscription execution
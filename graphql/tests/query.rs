@@ -8,6 +8,7 @@ extern crate graph_graphql;
 
 use graphql_parser::query as q;
 use std::sync::Mutex;
+use std::time::Duration;
 
 use graph::components::store::EventSource;
 use graph::prelude::*;
@@ -51,11 +52,14 @@ fn test_schema() -> Schema {
 #[derive(Clone)]
 struct TestStore {
     entities: Vec<Entity>,
+    /// The entity types that `find` has been called for, in order.
+    finds: Arc<Mutex<Vec<String>>>,
 }
 
 impl TestStore {
     pub fn new() -> Self {
         TestStore {
+            finds: Arc::new(Mutex::new(vec![])),
             entities: vec![
                 Entity::from(vec![
                     ("__typename", Value::from("Musician")),
@@ -131,24 +135,32 @@ impl TestStore {
 }
 
 impl BasicStore for TestStore {
-    fn get(&self, key: StoreKey) -> Result<Entity, ()> {
-        self.entities
+    fn get(&self, key: StoreKey) -> Result<Option<Entity>, Error> {
+        Ok(self
+            .entities
             .iter()
             .find(|entity| {
                 entity.get("id") == Some(&Value::String(key.id.clone()))
                     && entity.get("__typename") == Some(&Value::String(key.entity.clone()))
-            }).map_or(Err(()), |entity| Ok(entity.clone()))
+            }).map(|entity| entity.clone()))
     }
 
-    fn set(&mut self, _key: StoreKey, _entity: Entity, _source: EventSource) -> Result<(), ()> {
+    fn set(&self, _key: StoreKey, _entity: Entity, _source: EventSource) -> Result<(), ()> {
         unimplemented!()
     }
 
-    fn delete(&mut self, _key: StoreKey, _source: EventSource) -> Result<(), ()> {
+    fn delete(&self, _key: StoreKey, _source: EventSource) -> Result<(), ()> {
         unimplemented!()
     }
 
     fn find(&self, query: StoreQuery) -> Result<Vec<Entity>, ()> {
+        self.finds.lock().unwrap().push(query.entity.clone());
+
+        // Bands are only added in block 1 of the test chain
+        if query.entity == "Band" && query.block == Some(0) {
+            return Ok(vec![]);
+        }
+
         let entity_name = Value::String(query.entity.clone());
 
         let entities = self.entities
@@ -159,8 +171,21 @@ impl BasicStore for TestStore {
             //
             // - And(Contains(...))
             // - And(Equal(...))
+            // - And(In(...))
             // - And(Or([Equal(...), ...]))
             .filter(|entity| {
+                let matches = |filter: &StoreFilter| match filter {
+                    StoreFilter::Equal(k, v) => entity.get(k) == Some(&v),
+                    StoreFilter::In(k, values) => {
+                        entity.get(k).map_or(false, |v| values.contains(v))
+                    }
+                    StoreFilter::Contains(k, v) => match entity.get(k) {
+                        Some(Value::List(values)) => values.contains(v),
+                        _ => false,
+                    },
+                    _ => unimplemented!(),
+                };
+
                 query
                     .filter
                     .as_ref()
@@ -169,16 +194,8 @@ impl BasicStore for TestStore {
                         _ => None,
                     })
                     .map(|filter| match filter {
-                        StoreFilter::Equal(k, v) => entity.get(k) == Some(&v),
-                        StoreFilter::Contains(k, v) => match entity.get(k) {
-                            Some(Value::List(values)) => values.contains(v),
-                            _ => false,
-                        },
-                        StoreFilter::Or(filters) => filters.iter().any(|filter| match filter {
-                            StoreFilter::Equal(k,v) => entity.get(k) == Some(&v),
-                            _ => unimplemented!(),
-                        }),
-                        _ => unimplemented!(),
+                        StoreFilter::Or(filters) => filters.iter().any(|filter| matches(filter)),
+                        filter => matches(filter),
                     })
                     .unwrap_or(true)
             })
@@ -187,6 +204,15 @@ impl BasicStore for TestStore {
 
         Ok(entities)
     }
+
+    fn transact_block_operations(
+        &self,
+        _: String,
+        _: EthereumBlockPointer,
+        _: Vec<EntityOperation>,
+    ) -> Result<(), Error> {
+        unimplemented!()
+    }
 }
 
 impl BlockStore for TestStore {
@@ -212,28 +238,116 @@ impl BlockStore for TestStore {
     ) -> Result<Vec<H256>, Error> {
         unimplemented!()
     }
+
+    fn chain_head_ptr(&self, _: &str) -> Result<Option<EthereumBlockPointer>, Error> {
+        unimplemented!()
+    }
+
+    fn block(&self, _: H256) -> Result<Option<Block<Transaction>>, Error> {
+        Ok(None)
+    }
+
+    fn ancestor_block(
+        &self,
+        _: EthereumBlockPointer,
+        _: u64,
+    ) -> Result<Option<Block<Transaction>>, Error> {
+        unimplemented!()
+    }
 }
 
 impl Store for TestStore {
-    fn subscribe(&mut self, _entities: Vec<SubgraphEntityPair>) -> EntityChangeStream {
+    fn subscribe(&self, _entities: Vec<SubgraphEntityPair>) -> EntityChangeStream {
         unimplemented!();
     }
+
+    fn add_subgraph_if_missing(&self, _: String, _: String) -> Result<(), Error> {
+        unimplemented!()
+    }
+
+    fn create_entity_tables(&self, _: String, _: &Schema) -> Result<(), Error> {
+        unimplemented!()
+    }
+
+    fn block_ptr(&self, _: String) -> Result<Option<EthereumBlockPointer>, Error> {
+        Ok(Some(EthereumBlockPointer::from((H256::zero(), 10u64))))
+    }
+
+    fn set_block_ptr(&self, _: String, _: EthereumBlockPointer) -> Result<(), Error> {
+        unimplemented!()
+    }
+
+    fn revert_events(&self, _: String, _: String) -> Result<(), Error> {
+        unimplemented!()
+    }
+
+    fn add_dynamic_data_sources(
+        &self,
+        _: String,
+        _: EthereumBlockPointer,
+        _: Vec<DynamicDataSource>,
+    ) -> Result<(), Error> {
+        unimplemented!()
+    }
+
+    fn dynamic_data_sources(&self, _: String) -> Result<Vec<DynamicDataSource>, Error> {
+        unimplemented!()
+    }
+
+    fn subgraph_health(&self, _: String) -> Result<SubgraphHealth, Error> {
+        Ok(SubgraphHealth::Synced)
+    }
+
+    fn set_subgraph_health(&self, _: String, _: SubgraphHealth) -> Result<(), Error> {
+        unimplemented!()
+    }
+
+    fn subgraph_indexing_status(&self, _: String) -> Result<Option<SubgraphIndexingStatus>, Error> {
+        unimplemented!()
+    }
 }
 
 fn execute_query_document(query: q::Document) -> QueryResult {
+    execute_query_document_with_store(query, Arc::new(TestStore::new()))
+}
+
+fn execute_query_document_with_store(query: q::Document, store: Arc<TestStore>) -> QueryResult {
+    execute_query_document_with_limits(query, store, QueryLimits::default())
+}
+
+fn execute_query_document_with_limits(
+    query: q::Document,
+    store: Arc<TestStore>,
+    limits: QueryLimits,
+) -> QueryResult {
     let query = Query {
         schema: test_schema(),
         document: query,
         variables: None,
     };
+    execute_test_query(query, store, limits)
+}
+
+fn execute_query_document_with_variables(
+    query: q::Document,
+    variables: QueryVariables,
+) -> QueryResult {
+    let query = Query {
+        schema: test_schema(),
+        document: query,
+        variables: Some(variables),
+    };
+    execute_test_query(query, Arc::new(TestStore::new()), QueryLimits::default())
+}
 
+fn execute_test_query(query: Query, store: Arc<TestStore>, limits: QueryLimits) -> QueryResult {
     let logger = Logger::root(slog::Discard, o!());
-    let store = Arc::new(Mutex::new(TestStore::new()));
     let store_resolver = StoreResolver::new(&logger, store);
 
     let options = QueryExecutionOptions {
         logger: logger,
         resolver: store_resolver,
+        limits,
     };
 
     execute_query(&query, options)
@@ -478,3 +592,681 @@ fn can_query_many_to_many_relationship() {
         )]))
     );
 }
+
+#[test]
+fn includes_and_skips_fields_based_on_directives() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+            query {
+                musicians {
+                    name @include(if: true)
+                    id @skip(if: true)
+                    mainBand @include(if: false) {
+                        name
+                    }
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    let musician = |name: &str| object_value(vec![("name", q::Value::String(String::from(name)))]);
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musicians",
+            q::Value::List(vec![
+                musician("John"),
+                musician("Lisa"),
+                musician("Tom"),
+                musician("Valerie"),
+            ]),
+        )]))
+    );
+}
+
+#[test]
+fn includes_and_skips_fields_based_on_directive_variables() {
+    let mut variables = QueryVariables::new();
+    variables.insert(
+        "withName".to_string(),
+        QueryVariableValue::from(q::Value::Boolean(true)),
+    );
+    variables.insert(
+        "withBand".to_string(),
+        QueryVariableValue::from(q::Value::Boolean(false)),
+    );
+
+    let result = execute_query_document_with_variables(
+        graphql_parser::parse_query(
+            "
+            query Musicians($withName: Boolean!, $withBand: Boolean!, $skipId: Boolean = true) {
+                musicians {
+                    name @include(if: $withName)
+                    id @skip(if: $skipId)
+                    mainBand @include(if: $withBand) {
+                        name
+                    }
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+        variables,
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    let musician = |name: &str| object_value(vec![("name", q::Value::String(String::from(name)))]);
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musicians",
+            q::Value::List(vec![
+                musician("John"),
+                musician("Lisa"),
+                musician("Tom"),
+                musician("Valerie"),
+            ]),
+        )]))
+    );
+}
+
+#[test]
+fn rejects_queries_without_values_for_required_variables() {
+    let result = execute_query_document_with_variables(
+        graphql_parser::parse_query(
+            "
+            query Musicians($withName: Boolean!) {
+                musicians {
+                    name @include(if: $withName)
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+        QueryVariables::new(),
+    );
+
+    assert_eq!(result.data, None);
+    assert_eq!(
+        result
+            .errors
+            .expect("Missing variable not rejected")
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>(),
+        vec!["No value provided for required variable: $withName".to_string()]
+    );
+}
+
+#[test]
+fn can_query_entities_at_a_block() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+            query {
+                musicians(block: { number: 0 }) {
+                    name
+                    mainBand {
+                        name
+                    }
+                    bands {
+                        name
+                    }
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    // Nested fields are resolved at the same block, before any bands
+    // were added
+    let musician = |name: &str| {
+        object_value(vec![
+            ("name", q::Value::String(String::from(name))),
+            ("mainBand", q::Value::Null),
+            ("bands", q::Value::List(vec![])),
+        ])
+    };
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musicians",
+            q::Value::List(vec![
+                musician("John"),
+                musician("Lisa"),
+                musician("Tom"),
+                musician("Valerie"),
+            ]),
+        )]))
+    );
+}
+
+#[test]
+fn cannot_query_entities_at_blocks_that_are_not_indexed() {
+    let errors = |query: &str| {
+        execute_query_document(graphql_parser::parse_query(query).expect("Invalid test query"))
+            .errors
+            .expect("Query did not fail")
+            .into_iter()
+            .map(|error| error.to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        errors("query { musicians(block: { number: 11 }) { name } }"),
+        vec!["The subgraph has not indexed block 11 yet"]
+    );
+    assert_eq!(
+        errors("query { musician(id: \"m1\", block: { hash: \"0x01\" }) { name } }"),
+        vec!["Unknown block: 0x01"]
+    );
+    assert!(
+        errors("query { musicians(block: { number: 1, hash: \"0x01\" }) { name } }")[0]
+            .starts_with("Invalid block")
+    );
+}
+
+#[test]
+fn cannot_query_entities_after_unknown_cursors() {
+    let result = execute_query_document(
+        graphql_parser::parse_query("query { musicians(after: \"m9\") { name } }")
+            .expect("Invalid test query"),
+    );
+
+    assert_eq!(
+        result
+            .errors
+            .expect("Query did not fail")
+            .into_iter()
+            .map(|error| error.to_string())
+            .collect::<Vec<_>>(),
+        vec!["Unknown cursor: there is no entity with ID m9"]
+    );
+}
+
+#[test]
+fn can_query_meta() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+            query {
+                _meta {
+                    block {
+                        number
+                        hash
+                    }
+                    deployment
+                    hasIndexingErrors
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "_meta",
+            object_value(vec![
+                (
+                    "block",
+                    object_value(vec![
+                        ("number", q::Value::Int(q::Number::from(10))),
+                        ("hash", q::Value::String(format!("{:#x}", H256::zero()))),
+                    ]),
+                ),
+                (
+                    "deployment",
+                    q::Value::String(String::from("test_subgraph"))
+                ),
+                ("hasIndexingErrors", q::Value::Boolean(false)),
+            ]),
+        )]))
+    );
+}
+
+#[test]
+fn resolves_references_of_all_objects_of_a_list_at_once() {
+    let store = Arc::new(TestStore::new());
+    let result = execute_query_document_with_store(
+        graphql_parser::parse_query(
+            "
+            query {
+                musicians {
+                    name
+                    mainBand { name }
+                    bands {
+                        name
+                        members { name }
+                    }
+                    writtenSongs { title }
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+        store.clone(),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    // One query for the musicians, and one per reference field for all
+    // objects at the same level, rather than one per object; the entities of
+    // derived fields are limited per object and still need one query each
+    assert_eq!(
+        *store.finds.lock().unwrap(),
+        vec![
+            "Musician", "Band", "Band", "Musician", "Musician", "Musician", "Musician", "Musician",
+            "Song", "Song", "Song", "Song",
+        ]
+    );
+
+    let object = |field: &str, value: &str| {
+        object_value(vec![(field, q::Value::String(String::from(value)))])
+    };
+    let band = |name: &str, members: Vec<&str>| {
+        object_value(vec![
+            ("name", q::Value::String(String::from(name))),
+            (
+                "members",
+                q::Value::List(members.into_iter().map(|m| object("name", m)).collect()),
+            ),
+        ])
+    };
+    let musician = |name: &str, main_band: q::Value, bands: Vec<q::Value>, songs: Vec<&str>| {
+        object_value(vec![
+            ("name", q::Value::String(String::from(name))),
+            ("mainBand", main_band),
+            ("bands", q::Value::List(bands)),
+            (
+                "writtenSongs",
+                q::Value::List(songs.into_iter().map(|s| object("title", s)).collect()),
+            ),
+        ])
+    };
+
+    let musicians = vec!["John", "Lisa", "Tom"];
+    let amateurs = vec!["John", "Tom"];
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musicians",
+            q::Value::List(vec![
+                musician(
+                    "John",
+                    object("name", "The Musicians"),
+                    vec![
+                        band("The Musicians", musicians.clone()),
+                        band("The Amateurs", amateurs.clone()),
+                    ],
+                    vec!["Cheesy Tune", "Pop Tune"],
+                ),
+                musician(
+                    "Lisa",
+                    object("name", "The Musicians"),
+                    vec![band("The Musicians", musicians.clone())],
+                    vec!["Rock Tune"],
+                ),
+                musician(
+                    "Tom",
+                    object("name", "The Amateurs"),
+                    vec![
+                        band("The Musicians", musicians.clone()),
+                        band("The Amateurs", amateurs.clone()),
+                    ],
+                    vec!["Folk Tune"],
+                ),
+                musician("Valerie", q::Value::Null, vec![], vec![]),
+            ]),
+        )]))
+    );
+}
+
+#[test]
+fn resolves_ranges_of_references_per_object() {
+    let store = Arc::new(TestStore::new());
+    let result = execute_query_document_with_store(
+        graphql_parser::parse_query(
+            "
+            query {
+                musicians {
+                    name
+                    bands(first: 1) { name }
+                }
+            }
+            ",
+        ).expect("Invalid test query"),
+        store.clone(),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    // The range applies to the bands of each musician, so the store has to
+    // limit them per musician rather than for all musicians at once
+    assert_eq!(
+        *store.finds.lock().unwrap(),
+        vec!["Musician", "Band", "Band", "Band"]
+    );
+}
+
+#[test]
+fn rejects_queries_that_exceed_the_limits_before_executing_them() {
+    let store = Arc::new(TestStore::new());
+    let errors = |query: &str, limits: QueryLimits| {
+        execute_query_document_with_limits(
+            graphql_parser::parse_query(query).expect("Invalid test query"),
+            store.clone(),
+            limits,
+        ).errors
+        .expect("Query did not fail")
+        .into_iter()
+        .map(|error| error.to_string())
+        .collect::<Vec<_>>()
+    };
+
+    let query = "
+        query {
+            musicians(first: 10) {
+                ...MusicianFields
+            }
+        }
+
+        fragment MusicianFields on Musician {
+            name
+            bands { name }
+        }
+    ";
+
+    assert_eq!(
+        errors(
+            query,
+            QueryLimits {
+                max_depth: 2,
+                ..QueryLimits::default()
+            }
+        ),
+        vec!["The query is nested deeper than the limit of 2 levels"]
+    );
+
+    // 10 musicians with up to 100 bands each
+    assert_eq!(
+        errors(
+            query,
+            QueryLimits {
+                max_complexity: 1000,
+                ..QueryLimits::default()
+            }
+        ),
+        vec!["The estimated cost of the query (1010) exceeds the limit of 1000"]
+    );
+
+    assert_eq!(
+        errors(
+            query,
+            QueryLimits {
+                max_first: 5,
+                ..QueryLimits::default()
+            }
+        ),
+        vec!["The value of argument \"first\" (10) exceeds the limit of 5"]
+    );
+
+    assert!(store.finds.lock().unwrap().is_empty());
+}
+
+#[test]
+fn rejects_queries_that_exceed_the_limits_through_variables() {
+    let store = Arc::new(TestStore::new());
+    let errors = |first: i32, limits: QueryLimits| {
+        let mut variables = QueryVariables::new();
+        variables.insert(
+            "first".to_string(),
+            QueryVariableValue::from(q::Value::Int(q::Number::from(first))),
+        );
+        let query = Query {
+            schema: test_schema(),
+            document: graphql_parser::parse_query(
+                "
+                query Musicians($first: Int) {
+                    musicians(first: $first) {
+                        name
+                        bands { name }
+                    }
+                }
+                ",
+            ).expect("Invalid test query"),
+            variables: Some(variables),
+        };
+        execute_test_query(query, store.clone(), limits)
+            .errors
+            .expect("Query did not fail")
+            .into_iter()
+            .map(|error| error.to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // 1000 musicians with up to 100 bands each
+    assert_eq!(
+        errors(
+            1000,
+            QueryLimits {
+                max_complexity: 100_000,
+                ..QueryLimits::default()
+            }
+        ),
+        vec!["The estimated cost of the query (101000) exceeds the limit of 100000"]
+    );
+
+    assert_eq!(
+        errors(
+            2000,
+            QueryLimits {
+                max_first: 1000,
+                ..QueryLimits::default()
+            }
+        ),
+        vec!["The value of argument \"first\" (2000) exceeds the limit of 1000"]
+    );
+
+    assert!(store.finds.lock().unwrap().is_empty());
+}
+
+#[test]
+fn rejects_results_that_exceed_the_limits() {
+    let errors = |limits: QueryLimits| {
+        execute_query_document_with_limits(
+            graphql_parser::parse_query("query { musicians { name } }")
+                .expect("Invalid test query"),
+            Arc::new(TestStore::new()),
+            limits,
+        ).errors
+        .expect("Query did not fail")
+        .into_iter()
+        .map(|error| error.to_string())
+        .collect::<Vec<_>>()
+    };
+
+    // The query object and four musicians
+    assert_eq!(
+        errors(QueryLimits {
+            max_result_size: 4,
+            ..QueryLimits::default()
+        }),
+        vec!["The query result contains 5 objects, more than the limit of 4"]
+    );
+
+    // The execution stops as soon as the result is too big
+    let store = Arc::new(TestStore::new());
+    execute_query_document_with_limits(
+        graphql_parser::parse_query("query { musicians { name bands { name } } }")
+            .expect("Invalid test query"),
+        store.clone(),
+        QueryLimits {
+            max_result_size: 4,
+            ..QueryLimits::default()
+        },
+    );
+    assert_eq!(*store.finds.lock().unwrap(), vec!["Musician"]);
+
+    assert_eq!(
+        errors(QueryLimits {
+            timeout: Duration::from_secs(0),
+            ..QueryLimits::default()
+        }),
+        vec!["The query took longer than the time limit to execute"]
+    );
+}
+
+#[test]
+fn rejects_invalid_queries_before_executing_them() {
+    let store = Arc::new(TestStore::new());
+    let errors = |query: &str| {
+        execute_query_document_with_store(
+            graphql_parser::parse_query(query).expect("Invalid test query"),
+            store.clone(),
+        ).errors
+        .expect("Query did not fail")
+        .into_iter()
+        .map(|error| error.to_string())
+        .collect::<Vec<_>>()
+    };
+
+    // All violations are reported, in the order in which they appear
+    assert_eq!(
+        errors(
+            "
+            query Musicians($first: String, $unused: Int) {
+                musicians(first: $first) {
+                    nam
+                    bands
+                }
+            }
+            "
+        ),
+        vec![
+            "Variable \"$unused\" is never used in operation \"Musicians\"",
+            "Variable \"$first\" of type \"String\" used in position expecting type \"Int\"",
+            "Cannot query field \"nam\" on type \"Musician\"",
+            "Field \"bands\" of type \"[Band!]!\" must have a selection of subfields",
+        ]
+    );
+
+    assert_eq!(
+        errors(
+            "
+            query A { musicians { ...MusicianFields } }
+            query A { musicians { ...BandFields } }
+            fragment MusicianFields on Musician { name ...MusicianFields }
+            fragment BandFields on Band { name }
+            fragment SongFields on Song { title }
+            "
+        ),
+        vec![
+            "There can be only one operation named \"A\"",
+            "Fragment \"BandFields\" cannot be spread here as objects of type \"Musician\" \
+             can never be of type \"Band\"",
+            "Cannot spread fragment \"MusicianFields\" within itself",
+            "Fragment \"SongFields\" is never used",
+        ]
+    );
+
+    assert_eq!(
+        errors(
+            "
+            query {
+                musician(id: 1, id: \"m1\") { name }
+                musicians(where: { nam: \"John\" }, orderBy: age) {
+                    name @include(when: true)
+                }
+            }
+            "
+        ),
+        vec![
+            "Invalid value for argument \"id\" on field \"musician\": expected type \"ID\"",
+            "There can be only one argument named \"id\"",
+            "Invalid value for argument \"where\" on field \"musicians\": \
+             field \"nam\" is not defined by type \"Musician_filter\"",
+            "Invalid value for argument \"orderBy\" on field \"musicians\": \
+             expected type \"Musician_orderBy\"",
+            "Unknown argument \"when\" on directive \"@include\"",
+            "Argument \"if\" of type \"Boolean!\" on directive \"@include\" \
+             is required but not provided",
+        ]
+    );
+
+    assert_eq!(
+        errors("query { musicians(first: $count) { name } }"),
+        vec!["Variable \"$count\" is not defined by the anonymous operation"]
+    );
+
+    assert_eq!(
+        errors(
+            "
+            query {
+                musicians {
+                    mainBand { x: name }
+                    mainBand { x: id }
+                }
+            }
+            "
+        ),
+        vec![
+            "Fields \"mainBand\" conflict because subfields \"x\" conflict because \
+             \"name\" and \"id\" are different fields; use different aliases on the \
+             fields to fetch both",
+        ]
+    );
+
+    assert!(store.finds.lock().unwrap().is_empty());
+}
+
+#[test]
+fn accepts_valid_queries_with_variables_fragments_and_directives() {
+    let document = graphql_parser::parse_query(
+        "
+        query Musicians($first: Int = 10, $withBands: Boolean!) {
+            musicians(first: $first) {
+                ...MusicianFields
+                bands @include(if: $withBands) { name }
+                ... on Musician { id }
+            }
+            __schema { queryType { name } }
+        }
+
+        fragment MusicianFields on Musician {
+            name
+            mainBand { __typename name }
+        }
+        ",
+    ).expect("Invalid test query");
+
+    let result = validate_query(&test_schema().document, &introspection_schema(), &document);
+    assert!(
+        result.is_ok(),
+        format!("Unexpected errors for valid query: {:#?}", result)
+    );
+}
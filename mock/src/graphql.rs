@@ -38,6 +38,13 @@ impl GraphQlRunner for MockGraphQlRunner {
         Box::new(future::ok(QueryResult::new(Some(data))))
     }
 
+    fn run_indexing_status_query(&self, query: Query) -> QueryResultFuture {
+        info!(self.logger, "Run indexing status query"; "query" => format!("{:?}", query));
+        Box::new(future::ok(QueryResult::new(Some(gqlq::Value::Object(
+            BTreeMap::new(),
+        )))))
+    }
+
     fn run_subscription(&self, subscription: Subscription) -> SubscriptionResultFuture {
         info!(self.logger, "Run subscription"; "subscription" => format!("{:?}", subscription));
         let (_, receiver) = channel(2);
@@ -1,10 +1,14 @@
 use graph::components::store::*;
 use graph::prelude::*;
 use graph::web3::types::{Block, Transaction, H256};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::SystemTime;
 
 /// A mock `Store`.
 pub struct MockStore {
     entities: Vec<Entity>,
+    deployments: Mutex<BTreeMap<String, SubgraphDeployment>>,
 }
 
 impl MockStore {
@@ -19,14 +23,18 @@ impl MockStore {
             entities.push(entity);
         }
 
-        MockStore { entities }
+        MockStore {
+            entities,
+            deployments: Mutex::new(BTreeMap::new()),
+        }
     }
 }
 
 impl BasicStore for MockStore {
-    fn get(&self, key: StoreKey) -> Result<Entity, ()> {
+    fn get(&self, key: StoreKey) -> Result<Option<Entity>, Error> {
         if key.entity == "User" {
-            self.entities
+            Ok(self
+                .entities
                 .iter()
                 .find(|entity| {
                     let id = entity.get("id").unwrap();
@@ -34,24 +42,33 @@ impl BasicStore for MockStore {
                         &Value::String(ref s) => s == &key.id,
                         _ => false,
                     }
-                }).map(|entity| entity.clone())
-                .ok_or(())
+                }).map(|entity| entity.clone()))
         } else {
-            unimplemented!()
+            // Entities of other types do not exist
+            Ok(None)
         }
     }
 
-    fn set(&mut self, _key: StoreKey, _entity: Entity, _source: EventSource) -> Result<(), ()> {
+    fn set(&self, _key: StoreKey, _entity: Entity, _source: EventSource) -> Result<(), ()> {
         unimplemented!();
     }
 
-    fn delete(&mut self, _key: StoreKey, _source: EventSource) -> Result<(), ()> {
+    fn delete(&self, _key: StoreKey, _source: EventSource) -> Result<(), ()> {
         unimplemented!();
     }
 
     fn find(&self, _query: StoreQuery) -> Result<Vec<Entity>, ()> {
         Ok(self.entities.clone())
     }
+
+    fn transact_block_operations(
+        &self,
+        _: String,
+        _: EthereumBlockPointer,
+        _: Vec<EntityOperation>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 impl BlockStore for MockStore {
@@ -73,32 +90,138 @@ impl BlockStore for MockStore {
     fn attempt_head_update(&self, _: &str, _: u64) -> Result<Vec<H256>, Error> {
         unimplemented!()
     }
+
+    fn chain_head_ptr(&self, _: &str) -> Result<Option<EthereumBlockPointer>, Error> {
+        unimplemented!()
+    }
+
+    fn block(&self, _: H256) -> Result<Option<Block<Transaction>>, Error> {
+        unimplemented!()
+    }
+
+    fn ancestor_block(
+        &self,
+        _: EthereumBlockPointer,
+        _: u64,
+    ) -> Result<Option<Block<Transaction>>, Error> {
+        unimplemented!()
+    }
 }
 
 impl Store for MockStore {
-    fn subscribe(&mut self, _entities: Vec<SubgraphEntityPair>) -> EntityChangeStream {
+    fn subscribe(&self, _entities: Vec<SubgraphEntityPair>) -> EntityChangeStream {
         unimplemented!();
     }
+
+    fn add_subgraph_if_missing(&self, _: String, _: String) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn create_entity_tables(&self, _: String, _: &Schema) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn block_ptr(&self, _: String) -> Result<Option<EthereumBlockPointer>, Error> {
+        Ok(None)
+    }
+
+    fn set_block_ptr(&self, _: String, _: EthereumBlockPointer) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn revert_events(&self, _: String, _: String) -> Result<(), Error> {
+        unimplemented!()
+    }
+
+    fn add_dynamic_data_sources(
+        &self,
+        _: String,
+        _: EthereumBlockPointer,
+        _: Vec<DynamicDataSource>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn dynamic_data_sources(&self, _: String) -> Result<Vec<DynamicDataSource>, Error> {
+        Ok(vec![])
+    }
+
+    fn subgraph_health(&self, _: String) -> Result<SubgraphHealth, Error> {
+        Ok(SubgraphHealth::Syncing)
+    }
+
+    fn set_subgraph_health(&self, _: String, _: SubgraphHealth) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn subgraph_indexing_status(&self, _: String) -> Result<Option<SubgraphIndexingStatus>, Error> {
+        Ok(None)
+    }
+}
+
+impl SubgraphDeploymentStore for MockStore {
+    fn upsert_subgraph_deployment(
+        &self,
+        name: String,
+        subgraph_id: String,
+    ) -> Result<Option<SubgraphDeployment>, Error> {
+        let deployment = SubgraphDeployment {
+            name: name.clone(),
+            subgraph_id,
+            deployed_at: SystemTime::now(),
+            status: SubgraphDeploymentStatus::Deployed,
+        };
+        Ok(self.deployments.lock().unwrap().insert(name, deployment))
+    }
+
+    fn set_subgraph_deployment_status(
+        &self,
+        name: String,
+        status: SubgraphDeploymentStatus,
+    ) -> Result<(), Error> {
+        if let Some(deployment) = self.deployments.lock().unwrap().get_mut(&name) {
+            deployment.status = status;
+        }
+        Ok(())
+    }
+
+    fn remove_subgraph_deployment(&self, name: String) -> Result<(), Error> {
+        self.deployments.lock().unwrap().remove(&name);
+        Ok(())
+    }
+
+    fn subgraph_deployments(&self) -> Result<Vec<SubgraphDeployment>, Error> {
+        Ok(self.deployments.lock().unwrap().values().cloned().collect())
+    }
 }
 
 pub struct FakeStore;
 
 impl BasicStore for FakeStore {
-    fn get(&self, _: StoreKey) -> Result<Entity, ()> {
+    fn get(&self, _: StoreKey) -> Result<Option<Entity>, Error> {
         panic!("called FakeStore")
     }
 
-    fn set(&mut self, _: StoreKey, _: Entity, _source: EventSource) -> Result<(), ()> {
+    fn set(&self, _: StoreKey, _: Entity, _source: EventSource) -> Result<(), ()> {
         panic!("called FakeStore")
     }
 
-    fn delete(&mut self, _: StoreKey, _source: EventSource) -> Result<(), ()> {
+    fn delete(&self, _: StoreKey, _source: EventSource) -> Result<(), ()> {
         panic!("called FakeStore")
     }
 
     fn find(&self, _: StoreQuery) -> Result<Vec<Entity>, ()> {
         panic!("called FakeStore")
     }
+
+    fn transact_block_operations(
+        &self,
+        _: String,
+        _: EthereumBlockPointer,
+        _: Vec<EntityOperation>,
+    ) -> Result<(), Error> {
+        panic!("called FakeStore")
+    }
 }
 
 impl BlockStore for FakeStore {
@@ -120,10 +243,71 @@ impl BlockStore for FakeStore {
     fn attempt_head_update(&self, _: &str, _: u64) -> Result<Vec<H256>, Error> {
         panic!("called FakeStore")
     }
+
+    fn chain_head_ptr(&self, _: &str) -> Result<Option<EthereumBlockPointer>, Error> {
+        panic!("called FakeStore")
+    }
+
+    fn block(&self, _: H256) -> Result<Option<Block<Transaction>>, Error> {
+        panic!("called FakeStore")
+    }
+
+    fn ancestor_block(
+        &self,
+        _: EthereumBlockPointer,
+        _: u64,
+    ) -> Result<Option<Block<Transaction>>, Error> {
+        panic!("called FakeStore")
+    }
 }
 
 impl Store for FakeStore {
-    fn subscribe(&mut self, _entities: Vec<SubgraphEntityPair>) -> EntityChangeStream {
+    fn subscribe(&self, _entities: Vec<SubgraphEntityPair>) -> EntityChangeStream {
         unimplemented!();
     }
+
+    fn add_subgraph_if_missing(&self, _: String, _: String) -> Result<(), Error> {
+        panic!("called FakeStore")
+    }
+
+    fn create_entity_tables(&self, _: String, _: &Schema) -> Result<(), Error> {
+        panic!("called FakeStore")
+    }
+
+    fn block_ptr(&self, _: String) -> Result<Option<EthereumBlockPointer>, Error> {
+        panic!("called FakeStore")
+    }
+
+    fn set_block_ptr(&self, _: String, _: EthereumBlockPointer) -> Result<(), Error> {
+        panic!("called FakeStore")
+    }
+
+    fn revert_events(&self, _: String, _: String) -> Result<(), Error> {
+        panic!("called FakeStore")
+    }
+
+    fn add_dynamic_data_sources(
+        &self,
+        _: String,
+        _: EthereumBlockPointer,
+        _: Vec<DynamicDataSource>,
+    ) -> Result<(), Error> {
+        panic!("called FakeStore")
+    }
+
+    fn dynamic_data_sources(&self, _: String) -> Result<Vec<DynamicDataSource>, Error> {
+        panic!("called FakeStore")
+    }
+
+    fn subgraph_health(&self, _: String) -> Result<SubgraphHealth, Error> {
+        panic!("called FakeStore")
+    }
+
+    fn set_subgraph_health(&self, _: String, _: SubgraphHealth) -> Result<(), Error> {
+        panic!("called FakeStore")
+    }
+
+    fn subgraph_indexing_status(&self, _: String) -> Result<Option<SubgraphIndexingStatus>, Error> {
+        panic!("called FakeStore")
+    }
 }
@@ -57,6 +57,7 @@ impl MockSubgraphProvider {
                 },
             },
             data_sources: vec![],
+            templates: vec![],
         };
 
         self.event_sink
@@ -12,6 +12,7 @@ extern crate graph_mock;
 extern crate graph_runtime_wasm;
 extern crate graph_server_http;
 extern crate graph_server_json_rpc;
+extern crate graph_server_metrics;
 extern crate graph_server_websocket;
 extern crate graph_store_postgres;
 extern crate http;
@@ -24,6 +25,7 @@ use ipfs_api::IpfsClient;
 use itertools::FoldWhile::{Continue, Done};
 use itertools::Itertools;
 use reqwest::Client;
+use std::collections::HashMap;
 use std::env;
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::sync::Mutex;
@@ -33,11 +35,12 @@ use url::Url;
 use graph::components::forward;
 use graph::prelude::{JsonRpcServer as JsonRpcServerTrait, *};
 use graph::util::log::{guarded_logger, logger, register_panic_hook};
-use graph_core::SubgraphProvider as IpfsSubgraphProvider;
+use graph_core::{QueryLimits, SubgraphProvider as IpfsSubgraphProvider};
 use graph_datasource_ethereum::{EventLoopHandle, Transport};
-use graph_runtime_wasm::RuntimeHostBuilder as WASMRuntimeHostBuilder;
+use graph_runtime_wasm::{MappingLimits, RuntimeHostBuilder as WASMRuntimeHostBuilder};
 use graph_server_http::GraphQLServer as GraphQLQueryServer;
 use graph_server_json_rpc::{subgraph_deploy_request, JsonRpcServer};
+use graph_server_metrics::PrometheusMetricsServer;
 use graph_server_websocket::SubscriptionServer as GraphQLSubscriptionServer;
 use graph_store_postgres::{Store as DieselStore, StoreConfig};
 
@@ -71,32 +74,49 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                 .value_name("URL")
                 .help("Location of the Postgres database used for storing entities"),
         )
+        .arg(
+            Arg::with_name("store-connection-pool-size")
+                .default_value("10")
+                .long("store-connection-pool-size")
+                .value_name("SIZE")
+                .help("Maximum number of Postgres connections used by the store"),
+        )
         .arg(
             Arg::with_name("ethereum-rpc")
                 .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
                 .required_unless_one(&["ethereum-ws", "ethereum-ipc"])
-                .conflicts_with_all(&["ethereum-ws", "ethereum-ipc"])
                 .long("ethereum-rpc")
                 .value_name("NETWORK_NAME:URL")
-                .help("Ethereum network name (e.g. 'mainnet') and Ethereum RPC endpoint URL, separated by a ':'"),
+                .help("Ethereum network name (e.g. 'mainnet') and Ethereum RPC endpoint URL, separated by a ':'; may be repeated for multiple networks and for redundant providers of the same network"),
         )
         .arg(
             Arg::with_name("ethereum-ws")
                 .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
                 .required_unless_one(&["ethereum-rpc", "ethereum-ipc"])
-                .conflicts_with_all(&["ethereum-rpc", "ethereum-ipc"])
                 .long("ethereum-ws")
                 .value_name("NETWORK_NAME:URL")
-                .help("Ethereum network name (e.g. 'mainnet') and Ethereum WebSocket endpoint URL, separated by a ':'"),
+                .help("Ethereum network name (e.g. 'mainnet') and Ethereum WebSocket endpoint URL, separated by a ':'; may be repeated for multiple networks and for redundant providers of the same network"),
         )
         .arg(
             Arg::with_name("ethereum-ipc")
                 .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
                 .required_unless_one(&["ethereum-rpc", "ethereum-ws"])
-                .conflicts_with_all(&["ethereum-rpc", "ethereum-ws"])
                 .long("ethereum-ipc")
                 .value_name("NETWORK_NAME:FILE")
-                .help("Ethereum network name (e.g. 'mainnet') and Ethereum IPC pipe path, separated by a ':'"),
+                .help("Ethereum network name (e.g. 'mainnet') and Ethereum IPC pipe path, separated by a ':'; may be repeated for multiple networks and for redundant providers of the same network"),
+        )
+        .arg(
+            Arg::with_name("default-network")
+                .takes_value(true)
+                .long("default-network")
+                .value_name("NETWORK_NAME")
+                .help("Ethereum network indexed by subgraphs whose data sources don't specify one; defaults to the only network if just one is configured"),
         )
         .arg(
             Arg::with_name("ipfs")
@@ -113,18 +133,88 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                 .value_name("PORT")
                 .help("port for the admin JSON-RPC server"),
         )
+        .arg(
+            Arg::with_name("metrics-port")
+                .default_value("8040")
+                .long("metrics-port")
+                .value_name("PORT")
+                .help("port for the Prometheus metrics server"),
+        )
+        .arg(
+            Arg::with_name("mapping-fuel-limit")
+                .default_value("10000000000")
+                .long("mapping-fuel-limit")
+                .value_name("INSTRUCTIONS")
+                .help("Maximum number of instructions a mapping handler may execute"),
+        )
+        .arg(
+            Arg::with_name("mapping-timeout")
+                .default_value("60")
+                .long("mapping-timeout")
+                .value_name("SECONDS")
+                .help("Maximum time a mapping handler may run, not counting Ethereum calls and IPFS requests; those fail with a retryable error when they take longer"),
+        )
+        .arg(
+            Arg::with_name("mapping-max-memory")
+                .default_value("1024")
+                .long("mapping-max-memory")
+                .value_name("MB")
+                .help("Maximum memory a mapping may use, in MiB"),
+        )
+        .arg(
+            Arg::with_name("query-max-depth")
+                .default_value("255")
+                .long("query-max-depth")
+                .value_name("LEVELS")
+                .help("Maximum number of levels that fields may be nested in a GraphQL query"),
+        )
+        .arg(
+            Arg::with_name("query-max-complexity")
+                .default_value("10000000")
+                .long("query-max-complexity")
+                .value_name("COST")
+                .help("Maximum estimated cost of a GraphQL query"),
+        )
+        .arg(
+            Arg::with_name("query-max-first")
+                .default_value("1000")
+                .long("query-max-first")
+                .value_name("ENTITIES")
+                .help("Maximum value of the `first` and `last` arguments in a GraphQL query"),
+        )
+        .arg(
+            Arg::with_name("query-max-result-size")
+                .default_value("1000000")
+                .long("query-max-result-size")
+                .value_name("OBJECTS")
+                .help("Maximum number of objects in the result of a GraphQL query"),
+        )
+        .arg(
+            Arg::with_name("query-timeout")
+                .default_value("60")
+                .long("query-timeout")
+                .value_name("SECONDS")
+                .help("Maximum time a GraphQL query may run"),
+        )
         .get_matches();
 
     // Safe to unwrap because a value is required by CLI
     let postgres_url = matches.value_of("postgres-url").unwrap().to_string();
 
+    // Parse the store connection pool size
+    let store_connection_pool_size = matches
+        .value_of("store-connection-pool-size")
+        .unwrap()
+        .parse()
+        .expect("invalid store connection pool size");
+
     // Obtain subgraph related command-line arguments
     let subgraph = matches.value_of("subgraph");
 
     // Obtain the Ethereum parameters
-    let ethereum_rpc = matches.value_of("ethereum-rpc");
-    let ethereum_ipc = matches.value_of("ethereum-ipc");
-    let ethereum_ws = matches.value_of("ethereum-ws");
+    let ethereum_rpc = matches.values_of("ethereum-rpc");
+    let ethereum_ipc = matches.values_of("ethereum-ipc");
+    let ethereum_ws = matches.values_of("ethereum-ws");
 
     // Parse rpc port
     let json_rpc_port = matches
@@ -133,6 +223,66 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
         .parse()
         .expect("invalid admin port");
 
+    // Parse metrics port
+    let metrics_port = matches
+        .value_of("metrics-port")
+        .unwrap()
+        .parse()
+        .expect("invalid metrics port");
+
+    // Parse the limits of mapping handlers; a WASM page is 64 KiB
+    let mapping_limits = MappingLimits {
+        fuel: matches
+            .value_of("mapping-fuel-limit")
+            .unwrap()
+            .parse()
+            .expect("invalid mapping fuel limit"),
+        timeout: Duration::from_secs(
+            matches
+                .value_of("mapping-timeout")
+                .unwrap()
+                .parse()
+                .expect("invalid mapping timeout"),
+        ),
+        max_memory_pages: matches
+            .value_of("mapping-max-memory")
+            .unwrap()
+            .parse::<u32>()
+            .expect("invalid mapping memory limit")
+            .saturating_mul(16),
+    };
+
+    // Parse the limits of GraphQL queries
+    let query_limits = QueryLimits {
+        max_depth: matches
+            .value_of("query-max-depth")
+            .unwrap()
+            .parse()
+            .expect("invalid query depth limit"),
+        max_complexity: matches
+            .value_of("query-max-complexity")
+            .unwrap()
+            .parse()
+            .expect("invalid query complexity limit"),
+        max_first: matches
+            .value_of("query-max-first")
+            .unwrap()
+            .parse()
+            .expect("invalid query range limit"),
+        max_result_size: matches
+            .value_of("query-max-result-size")
+            .unwrap()
+            .parse()
+            .expect("invalid query result size limit"),
+        timeout: Duration::from_secs(
+            matches
+                .value_of("query-timeout")
+                .unwrap()
+                .parse()
+                .expect("invalid query timeout"),
+        ),
+    };
+
     debug!(logger, "Setting up Sentry");
 
     // Set up Sentry, with release tracking and panic handling;
@@ -203,61 +353,125 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
             }),
     );
 
-    let mut subgraph_provider = IpfsSubgraphProvider::new(logger.clone(), ipfs_client.clone());
-
     info!(logger, "Connecting to Postgres db...");
-    let store = DieselStore::new(StoreConfig { url: postgres_url }, &logger);
-    let protected_store = Arc::new(Mutex::new(store));
+    let store = Arc::new(DieselStore::new(
+        StoreConfig {
+            url: postgres_url,
+            connection_pool_size: store_connection_pool_size,
+            query_timeout: Some(query_limits.timeout),
+        },
+        &logger,
+    ));
+    let mut subgraph_provider =
+        IpfsSubgraphProvider::new(logger.clone(), ipfs_client.clone(), store.clone());
     let graphql_runner = Arc::new(graph_core::GraphQlRunner::new(
         &logger,
-        protected_store.clone(),
+        store.clone(),
+        query_limits,
     ));
     let mut graphql_server = GraphQLQueryServer::new(&logger, graphql_runner.clone());
     let mut subscription_server = GraphQLSubscriptionServer::new(&logger, graphql_runner.clone());
 
-    // Create Ethereum adapter
-    let (ethereum_network_name, (transport_event_loop, transport)) = ethereum_ipc
+    // Create a transport for each Ethereum provider, grouped by network
+    let mut ethereum_transports = HashMap::new();
+    for (ethereum_network_name, (transport_event_loop, transport)) in ethereum_ipc
+        .into_iter()
+        .flatten()
         .map(|s| new_transport(s, &logger, Transport::new_ipc))
-        .or(ethereum_ws.map(|s| new_transport(s, &logger, Transport::new_ws)))
-        .or(ethereum_rpc.map(|s| new_transport(s, &logger, Transport::new_rpc)))
-        .expect("One of --ethereum-ipc, --ethereum-ws or --ethereum-rpc must be provided");
-
-    // Create Ethereum block ingestor
-    let block_ingestor = graph_datasource_ethereum::BlockIngestor::new(
-        protected_store.clone(),
-        ethereum_network_name.to_owned(),
-        transport.clone(),
-        400, // ancestor count, which we could make configuable
-        logger.clone(),
-        Duration::from_millis(500), // polling interval, which we could make configurable
-    ).expect("failed to create block ingestor");
-    tokio::spawn(block_ingestor.into_polling_stream());
-
-    // If we drop the event loop the transport will stop working. For now it's
-    // fine to just leak it.
-    std::mem::forget(transport_event_loop);
+        .chain(
+            ethereum_ws
+                .into_iter()
+                .flatten()
+                .map(|s| new_transport(s, &logger, Transport::new_ws)),
+        ).chain(
+            ethereum_rpc
+                .into_iter()
+                .flatten()
+                .map(|s| new_transport(s, &logger, Transport::new_rpc)),
+        ) {
+        // If we drop the event loop the transport will stop working. For now it's
+        // fine to just leak it.
+        std::mem::forget(transport_event_loop);
+
+        ethereum_transports
+            .entry(ethereum_network_name)
+            .or_insert_with(Vec::new)
+            .push(transport);
+    }
 
-    let ethereum_watcher = graph_datasource_ethereum::EthereumAdapter::new(
-        graph_datasource_ethereum::EthereumAdapterConfig { transport },
-    );
+    // Subgraphs that don't specify a network index the default network
+    let default_network_name = match matches.value_of("default-network") {
+        Some(network_name) => {
+            if !ethereum_transports.contains_key(network_name) {
+                panic!(
+                    "No Ethereum provider for the default network: {}",
+                    network_name
+                );
+            }
+            Some(network_name.to_owned())
+        }
+        None if ethereum_transports.len() == 1 => ethereum_transports
+            .keys()
+            .next()
+            .map(|network_name| network_name.to_string()),
+        None => None,
+    };
 
-    match ethereum_watcher.block_number().wait() {
-        Ok(number) => info!(logger, "Connected to Ethereum node";
-                            "most_recent_block" => &number.to_string()),
-        Err(e) => {
-            error!(logger, "Was a valid Ethereum node endpoint provided?");
-            panic!("Failed to connect to Ethereum node: {}", e);
+    // Create a block ingestor and an Ethereum adapter for each network
+    let mut ethereum_adapters = HashMap::new();
+    for (ethereum_network_name, transports) in ethereum_transports {
+        let block_ingestor = graph_datasource_ethereum::BlockIngestor::new(
+            store.clone(),
+            ethereum_network_name.to_owned(),
+            transports.clone(),
+            400, // ancestor count, which we could make configuable
+            logger.clone(),
+            Duration::from_millis(500), // polling interval, which we could make configurable
+        ).expect("failed to create block ingestor");
+        tokio::spawn(block_ingestor.into_polling_stream());
+
+        let ethereum_watcher = graph_datasource_ethereum::EthereumAdapter::new(
+            &logger,
+            graph_datasource_ethereum::EthereumAdapterConfig { transports },
+        );
+
+        match ethereum_watcher.block_number().wait() {
+            Ok(number) => info!(logger, "Connected to Ethereum node";
+                                "network" => ethereum_network_name,
+                                "most_recent_block" => &number.to_string()),
+            Err(e) => {
+                error!(logger, "Was a valid Ethereum node endpoint provided?";
+                       "network" => ethereum_network_name);
+                panic!("Failed to connect to Ethereum node: {}", e);
+            }
         }
+
+        ethereum_adapters.insert(
+            ethereum_network_name.to_owned(),
+            Arc::new(Mutex::new(ethereum_watcher)),
+        );
     }
 
     let runtime_host_builder = WASMRuntimeHostBuilder::new(
         &logger,
-        Arc::new(Mutex::new(ethereum_watcher)),
+        ethereum_adapters.clone(),
         ipfs_client,
-        protected_store.clone(),
+        store.clone(),
+        mapping_limits,
+    );
+    let block_stream_builder = graph_datasource_ethereum::BlockStreamBuilder::new(
+        store.clone(),
+        ethereum_adapters,
+        400, // reorg threshold, which must not exceed the ancestor count
+        Duration::from_millis(500), // polling interval, which we could make configurable
+    );
+    let runtime_manager = graph_core::RuntimeManager::new(
+        &logger,
+        store.clone(),
+        default_network_name,
+        runtime_host_builder,
+        block_stream_builder,
     );
-    let runtime_manager =
-        graph_core::RuntimeManager::new(&logger, protected_store.clone(), runtime_host_builder);
 
     // Forward subgraph events from the subgraph provider to the runtime manager
     tokio::spawn(forward(&mut subgraph_provider, &runtime_manager).unwrap());
@@ -275,6 +489,16 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
             )).and_then(|_| Ok(())),
     );
 
+    // Start the subgraphs that were deployed before the node was restarted
+    let redeploy_logger = logger.clone();
+    tokio::spawn(
+        subgraph_provider
+            .start_deployed_subgraphs()
+            .map_err(move |e| {
+                error!(redeploy_logger, "Failed to start deployed subgraphs: {}", e);
+            }),
+    );
+
     // Start admin JSON-RPC server.
     let json_rpc_server =
         JsonRpcServer::serve(json_rpc_port, Arc::new(subgraph_provider), logger.clone())
@@ -326,6 +550,14 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
             .expect("Failed to start GraphQL subscription server"),
     );
 
+    // Serve Prometheus metrics over HTTP.
+    let mut metrics_server = PrometheusMetricsServer::new(&logger);
+    tokio::spawn(
+        metrics_server
+            .serve(metrics_port)
+            .expect("Failed to start metrics server"),
+    );
+
     future::empty()
 }
 
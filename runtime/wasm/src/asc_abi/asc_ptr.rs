@@ -1,7 +1,7 @@
 use super::{class::EnumPayload, AscHeap, AscType};
 use std::marker::PhantomData;
 use std::mem::{self, size_of};
-use wasmi::{FromRuntimeValue, RuntimeValue};
+use wasmi::{self, FromRuntimeValue, RuntimeValue};
 
 /// A pointer to an object in the Asc heap.
 pub struct AscPtr<C>(u32, PhantomData<C>);
@@ -27,8 +27,9 @@ impl<C: AscType> AscPtr<C> {
     }
 
     /// Allocate `asc_obj` as an Asc object of class `C`.
-    pub(super) fn alloc_obj<H: AscHeap>(asc_obj: &C, heap: &H) -> AscPtr<C> {
-        AscPtr(heap.raw_new(&asc_obj.to_asc_bytes()).unwrap(), PhantomData)
+    pub(super) fn alloc_obj<H: AscHeap>(asc_obj: &C, heap: &H) -> Result<AscPtr<C>, wasmi::Error> {
+        let address = heap.raw_new(&asc_obj.to_asc_bytes())?;
+        Ok(AscPtr(address, PhantomData))
     }
 
     /// Helper used by arrays and strings to read their length.
@@ -3,6 +3,7 @@ use ethabi;
 use graph::data::store;
 use graph::serde_json;
 use std::mem::{self, size_of, size_of_val};
+use wasmi;
 
 ///! Rust types that have with a direct correspondence to an Asc class,
 ///! with their `AscType` implementations.
@@ -92,13 +93,13 @@ pub(crate) struct TypedArray<T> {
 }
 
 impl<T: AscValue> TypedArray<T> {
-    pub(crate) fn new<H: AscHeap>(content: &[T], heap: &H) -> Self {
+    pub(crate) fn new<H: AscHeap>(content: &[T], heap: &H) -> Result<Self, wasmi::Error> {
         let buffer = ArrayBuffer::new(content);
-        TypedArray {
-            buffer: AscPtr::alloc_obj(&buffer, heap),
+        Ok(TypedArray {
+            buffer: AscPtr::alloc_obj(&buffer, heap)?,
             byte_offset: 0,
             byte_length: buffer.byte_length,
-        }
+        })
     }
 
     pub(crate) fn get_buffer<H: AscHeap>(&self, heap: &H) -> ArrayBuffer<T> {
@@ -190,11 +191,11 @@ pub(crate) struct Array<T> {
 }
 
 impl<T: AscValue> Array<T> {
-    pub fn new<H: AscHeap>(content: &[T], heap: &H) -> Self {
-        Array {
-            buffer: heap.asc_new(content),
+    pub fn new<H: AscHeap>(content: &[T], heap: &H) -> Result<Self, wasmi::Error> {
+        Ok(Array {
+            buffer: heap.asc_new(content)?,
             length: content.len() as u32,
-        }
+        })
     }
 
     pub fn get_buffer<H: AscHeap>(&self, heap: &H) -> ArrayBuffer<T> {
@@ -372,10 +373,55 @@ pub(crate) struct AscEthereumEvent {
     pub event_signature: AscPtr<AscH256>,
     pub block_hash: AscPtr<AscH256>,
     pub params: AscPtr<AscLogParamArray>,
+    pub log_index: AscPtr<AscU256>,
+    pub transaction_log_index: AscPtr<AscU256>,
+    pub block: AscPtr<AscEthereumBlock>,
+    pub transaction: AscPtr<AscEthereumTransaction>,
 }
 
 impl AscType for AscEthereumEvent {}
 
+#[repr(C)]
+pub(crate) struct AscEthereumTransaction {
+    pub hash: AscPtr<AscH256>,
+    pub index: AscPtr<AscU256>,
+    pub from: AscPtr<AscAddress>,
+    /// Null for contract creation transactions.
+    pub to: AscPtr<AscAddress>,
+    pub value: AscPtr<AscU256>,
+    pub gas_limit: AscPtr<AscU256>,
+    pub gas_price: AscPtr<AscU256>,
+    pub input: AscPtr<Bytes>,
+}
+
+impl AscType for AscEthereumTransaction {}
+
+#[repr(C)]
+pub(crate) struct AscEthereumCall {
+    pub from: AscPtr<AscAddress>,
+    pub to: AscPtr<AscAddress>,
+    pub block_hash: AscPtr<AscH256>,
+    pub transaction_hash: AscPtr<AscH256>,
+    pub inputs: AscPtr<AscLogParamArray>,
+    pub outputs: AscPtr<AscLogParamArray>,
+}
+
+impl AscType for AscEthereumCall {}
+
+#[repr(C)]
+pub(crate) struct AscEthereumBlock {
+    pub hash: AscPtr<AscH256>,
+    pub parent_hash: AscPtr<AscH256>,
+    pub number: AscPtr<AscU256>,
+    pub timestamp: AscPtr<AscU256>,
+    pub author: AscPtr<AscAddress>,
+    pub gas_used: AscPtr<AscU256>,
+    pub gas_limit: AscPtr<AscU256>,
+    pub difficulty: AscPtr<AscU256>,
+}
+
+impl AscType for AscEthereumBlock {}
+
 #[repr(C)]
 pub(crate) struct AscTypedMapEntry<K, V> {
     pub key: AscPtr<K>,
@@ -33,16 +33,17 @@ pub trait AscHeap: Sized {
     fn get(&self, offset: u32, size: u32) -> Result<Vec<u8>, wasmi::Error>;
 
     /// Instatiate `rust_obj` as an Asc object of class `C`.
-    /// Returns a pointer to the Asc heap.
+    /// Returns a pointer to the Asc heap, or fails if the memory for the
+    /// object or any of its nested objects could not be allocated.
     ///
     /// This operation is expensive as it requires a call to `raw_new` for every
     /// nested object.
-    fn asc_new<C, T: ?Sized>(&self, rust_obj: &T) -> AscPtr<C>
+    fn asc_new<C, T: ?Sized>(&self, rust_obj: &T) -> Result<AscPtr<C>, wasmi::Error>
     where
         C: AscType,
         T: ToAscObj<C>,
     {
-        AscPtr::alloc_obj(&rust_obj.to_asc_obj(self), self)
+        AscPtr::alloc_obj(&rust_obj.to_asc_obj(self)?, self)
     }
 
     ///  Read the rust representation of an Asc object of class `C`.
@@ -60,7 +61,7 @@ pub trait AscHeap: Sized {
 
 /// Type that can be converted to an Asc object of class `C`.
 pub trait ToAscObj<C: AscType> {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> C;
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<C, wasmi::Error>;
 }
 
 /// Type that can be converted from an Asc object of class `C`.
@@ -136,7 +136,7 @@ fn abi_h160() {
     let address = H160::zero();
 
     // As an `ArrayBuffer`
-    let array_buffer: AscPtr<ArrayBuffer<u8>> = module.asc_new(&address);
+    let array_buffer: AscPtr<ArrayBuffer<u8>> = module.asc_new(&address).unwrap();
     let new_address_obj: AscPtr<ArrayBuffer<u8>> =
         module.takes_ptr_returns_ptr("test_address", array_buffer);
 
@@ -149,7 +149,7 @@ fn abi_h160() {
     );
 
     // As an `Uint8Array`
-    let array_buffer: AscPtr<Uint8Array> = module.asc_new(&address);
+    let array_buffer: AscPtr<Uint8Array> = module.asc_new(&address).unwrap();
     let new_address_obj: AscPtr<Uint8Array> =
         module.takes_ptr_returns_ptr("test_typed_array_address", array_buffer);
 
@@ -167,7 +167,7 @@ fn string() {
     let module = TestModule::new("wasm_test/abi_classes.wasm");
     let string = "    漢字Double_Me🇧🇷  ";
     let trimmed_string_obj: AscPtr<AscString> =
-        module.takes_ptr_returns_ptr("repeat_twice", module.asc_new(string));
+        module.takes_ptr_returns_ptr("repeat_twice", module.asc_new(string).unwrap());
     let doubled_string: String = module.asc_get(trimmed_string_obj);
     assert_eq!(doubled_string, string.repeat(2))
 }
@@ -178,7 +178,7 @@ fn abi_u256() {
     let address = U256::zero();
 
     // As an `ArrayBuffer`
-    let array_buffer: AscPtr<ArrayBuffer<u64>> = module.asc_new(&address);
+    let array_buffer: AscPtr<ArrayBuffer<u64>> = module.asc_new(&address).unwrap();
     let new_uint_obj: AscPtr<ArrayBuffer<u64>> =
         module.takes_ptr_returns_ptr("test_uint", array_buffer);
 
@@ -188,7 +188,7 @@ fn abi_u256() {
     assert_eq!(new_uint, U256([1, 0, 0, 1]));
 
     // As an `Uint64Array`
-    let array_buffer: AscPtr<Uint64Array> = module.asc_new(&address);
+    let array_buffer: AscPtr<Uint64Array> = module.asc_new(&address).unwrap();
     let new_uint_obj: AscPtr<Uint64Array> =
         module.takes_ptr_returns_ptr("test_typed_array_uint", array_buffer);
 
@@ -207,8 +207,8 @@ fn abi_bytes_and_fixed_bytes() {
     // As an `ArrayBuffer`
     let new_vec_obj: AscPtr<ArrayBuffer<u8>> = module.takes_ptr_ptr_returns_ptr(
         "concat",
-        module.asc_new::<ArrayBuffer<u8>, _>(&*bytes1),
-        module.asc_new::<ArrayBuffer<u8>, _>(&*bytes2),
+        module.asc_new::<ArrayBuffer<u8>, _>(&*bytes1).unwrap(),
+        module.asc_new::<ArrayBuffer<u8>, _>(&*bytes2).unwrap(),
     );
 
     // This should be bytes1 and bytes2 concatenated.
@@ -221,8 +221,8 @@ fn abi_bytes_and_fixed_bytes() {
     // As an `Uint8Array`
     let new_vec_obj: AscPtr<Uint8Array> = module.takes_ptr_ptr_returns_ptr(
         "concat_typed_array",
-        module.asc_new::<Uint8Array, _>(&*bytes1),
-        module.asc_new::<Uint8Array, _>(&*bytes2),
+        module.asc_new::<Uint8Array, _>(&*bytes1).unwrap(),
+        module.asc_new::<Uint8Array, _>(&*bytes2).unwrap(),
     );
 
     // This should be bytes1 and bytes2 concatenated.
@@ -242,7 +242,7 @@ fn abi_ethabi_token_identity() {
     let token_address = Token::Address(address);
 
     let new_address_obj: AscPtr<ArrayBuffer<u8>> =
-        module.takes_ptr_returns_ptr("token_to_address", module.asc_new(&token_address));
+        module.takes_ptr_returns_ptr("token_to_address", module.asc_new(&token_address).unwrap());
 
     let new_token =
         module.asc_get(module.takes_ptr_returns_ptr("token_from_address", new_address_obj));
@@ -253,7 +253,7 @@ fn abi_ethabi_token_identity() {
     let token_bytes = Token::Bytes(vec![42, 45, 7, 245, 45]);
 
     let new_bytes_obj: AscPtr<ArrayBuffer<u8>> =
-        module.takes_ptr_returns_ptr("token_to_bytes", module.asc_new(&token_bytes));
+        module.takes_ptr_returns_ptr("token_to_bytes", module.asc_new(&token_bytes).unwrap());
 
     let new_token = module.asc_get(module.takes_ptr_returns_ptr("token_from_bytes", new_bytes_obj));
 
@@ -263,7 +263,7 @@ fn abi_ethabi_token_identity() {
     let int_token = Token::Int(U256([256, 453452345, 0, 42]));
 
     let new_int_obj: AscPtr<ArrayBuffer<u8>> =
-        module.takes_ptr_returns_ptr("token_to_int", module.asc_new(&int_token));
+        module.takes_ptr_returns_ptr("token_to_int", module.asc_new(&int_token).unwrap());
 
     let new_token = module.asc_get(module.takes_ptr_returns_ptr("token_from_int", new_int_obj));
 
@@ -273,7 +273,7 @@ fn abi_ethabi_token_identity() {
     let uint_token = Token::Uint(U256([256, 453452345, 0, 42]));
 
     let new_uint_obj: AscPtr<ArrayBuffer<u8>> =
-        module.takes_ptr_returns_ptr("token_to_uint", module.asc_new(&uint_token));
+        module.takes_ptr_returns_ptr("token_to_uint", module.asc_new(&uint_token).unwrap());
 
     let new_token = module.asc_get(module.takes_ptr_returns_ptr("token_from_uint", new_uint_obj));
 
@@ -287,7 +287,7 @@ fn abi_ethabi_token_identity() {
         .module
         .invoke_export(
             "token_to_bool",
-            &[RuntimeValue::from(module.asc_new(&token_bool))],
+            &[RuntimeValue::from(module.asc_new(&token_bool).unwrap())],
             &mut NopExternals,
         ).expect("call failed")
         .expect("call returned nothing")
@@ -304,7 +304,7 @@ fn abi_ethabi_token_identity() {
     let token_string = Token::String("漢字Go🇧🇷".into());
 
     let new_string_obj: AscPtr<AscString> =
-        module.takes_ptr_returns_ptr("token_to_string", module.asc_new(&token_string));
+        module.takes_ptr_returns_ptr("token_to_string", module.asc_new(&token_string).unwrap());
 
     let new_token =
         module.asc_get(module.takes_ptr_returns_ptr("token_from_string", new_string_obj));
@@ -315,8 +315,10 @@ fn abi_ethabi_token_identity() {
     let token_array = Token::Array(vec![token_address, token_bytes, token_bool]);
     let token_array_nested = Token::Array(vec![token_string, token_array]);
 
-    let new_array_obj: AscEnumArray<EthereumValueKind> =
-        module.takes_ptr_returns_ptr("token_to_array", module.asc_new(&token_array_nested));
+    let new_array_obj: AscEnumArray<EthereumValueKind> = module.takes_ptr_returns_ptr(
+        "token_to_array",
+        module.asc_new(&token_array_nested).unwrap(),
+    );
 
     let new_token: Token =
         module.asc_get(module.takes_ptr_returns_ptr("token_from_array", new_array_obj));
@@ -343,8 +345,9 @@ fn abi_store_value() {
 
     // Value::String
     let string = "some string";
-    let new_value: Value =
-        module.asc_get(module.takes_ptr_returns_ptr("value_from_string", module.asc_new(string)));
+    let new_value: Value = module.asc_get(
+        module.takes_ptr_returns_ptr("value_from_string", module.asc_new(string).unwrap()),
+    );
     assert_eq!(new_value, Value::from(string));
 
     // Value::Int
@@ -374,7 +377,10 @@ fn abi_store_value() {
             .module
             .invoke_export(
                 "array_from_values",
-                &[RuntimeValue::from(module.asc_new(string)), float_runtime],
+                &[
+                    RuntimeValue::from(module.asc_new(string).unwrap()),
+                    float_runtime,
+                ],
                 &mut NopExternals,
             ).expect("call failed")
             .expect("call returned nothing")
@@ -390,7 +396,7 @@ fn abi_store_value() {
         Value::String("foo".to_owned()),
         Value::String("bar".to_owned()),
     ];
-    let array_ptr = module.asc_new(array);
+    let array_ptr = module.asc_new(array).unwrap();
     let new_value: Value =
         module.asc_get(module.takes_ptr_returns_ptr("value_from_array", array_ptr));
     assert_eq!(
@@ -403,14 +409,14 @@ fn abi_store_value() {
 
     // Value::Bytes
     let bytes: &[u8] = &[0, 2, 5];
-    let bytes_ptr: AscPtr<Bytes> = module.asc_new(bytes);
+    let bytes_ptr: AscPtr<Bytes> = module.asc_new(bytes).unwrap();
     let new_value: Value =
         module.asc_get(module.takes_ptr_returns_ptr("value_from_bytes", bytes_ptr));
     assert_eq!(new_value, Value::Bytes(bytes.into()));
 
     // Value::BigInt
     let bytes: &[u8] = &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
-    let bytes_ptr: AscPtr<Uint8Array> = module.asc_new(bytes);
+    let bytes_ptr: AscPtr<Uint8Array> = module.asc_new(bytes).unwrap();
     let new_value: Value =
         module.asc_get(module.takes_ptr_returns_ptr("value_from_bigint", bytes_ptr));
     assert_eq!(
@@ -1,9 +1,10 @@
-use futures::sync::mpsc::{channel, Receiver};
+use ethabi::{self, LogParam, Param, RawLog};
+use futures::sync::mpsc::{channel, Sender};
 use futures::sync::oneshot;
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Mutex;
 use std::thread;
-use uuid::Uuid;
 
 use graph::components::ethereum::*;
 use graph::components::store::Store;
@@ -13,21 +14,35 @@ use graph::prelude::{
     RuntimeHost as RuntimeHostTrait, RuntimeHostBuilder as RuntimeHostBuilderTrait, *,
 };
 use graph::util;
-use graph::web3::types::Address;
+use graph::web3::types::{Address, Block, Log, Transaction};
 
-use module::{WasmiModule, WasmiModuleConfig};
+use module::{MappingLimits, WasmiModule, WasmiModuleConfig};
 
 #[derive(Clone)]
 pub struct RuntimeHostConfig {
     subgraph_manifest: SubgraphManifest,
     data_source: DataSource,
+    limits: MappingLimits,
 }
 
 pub struct RuntimeHostBuilder<T, L, S> {
     logger: Logger,
-    ethereum_adapter: Arc<Mutex<T>>,
+    ethereum_adapters: HashMap<String, Arc<Mutex<T>>>,
     link_resolver: Arc<L>,
-    store: Arc<Mutex<S>>,
+    store: Arc<S>,
+    limits: MappingLimits,
+}
+
+impl<T, L, S> Clone for RuntimeHostBuilder<T, L, S> {
+    fn clone(&self) -> Self {
+        RuntimeHostBuilder {
+            logger: self.logger.clone(),
+            ethereum_adapters: self.ethereum_adapters.clone(),
+            link_resolver: self.link_resolver.clone(),
+            store: self.store.clone(),
+            limits: self.limits,
+        }
+    }
 }
 
 impl<T, L, S> RuntimeHostBuilder<T, L, S>
@@ -36,17 +51,22 @@ where
     L: LinkResolver,
     S: Store,
 {
+    /// Creates a builder for runtime hosts that use the Ethereum adapter
+    /// of the network that their subgraph indexes, by network name. The
+    /// mappings of the hosts are bound by the given limits.
     pub fn new(
         logger: &Logger,
-        ethereum_adapter: Arc<Mutex<T>>,
+        ethereum_adapters: HashMap<String, Arc<Mutex<T>>>,
         link_resolver: Arc<L>,
-        store: Arc<Mutex<S>>,
+        store: Arc<S>,
+        limits: MappingLimits,
     ) -> Self {
         RuntimeHostBuilder {
             logger: logger.new(o!("component" => "RuntimeHostBuilder")),
-            ethereum_adapter,
+            ethereum_adapters,
             link_resolver,
             store,
+            limits,
         }
     }
 }
@@ -63,35 +83,58 @@ where
         &mut self,
         subgraph_manifest: SubgraphManifest,
         data_source: DataSource,
-    ) -> Self::Host {
+    ) -> Result<Self::Host, Error> {
+        let network_name = subgraph_manifest.network_name()?;
+        let ethereum_adapter = self
+            .ethereum_adapters
+            .get(&network_name)
+            .ok_or_else(|| format_err!("no Ethereum adapter for network: {}", network_name))?
+            .clone();
+
         RuntimeHost::new(
             &self.logger,
-            self.ethereum_adapter.clone(),
+            ethereum_adapter,
             self.link_resolver.clone(),
             self.store.clone(),
             RuntimeHostConfig {
                 subgraph_manifest,
                 data_source,
+                limits: self.limits,
             },
         )
     }
 }
 
+/// Something on the chain that the mappings of a data source handle.
+enum MappingTrigger {
+    Log(Arc<Block<Transaction>>, Log),
+    Call(EthereumCall),
+    Block(EthereumBlockHeader),
+}
+
+type MappingResultSender = oneshot::Sender<Result<Vec<RuntimeHostEvent>, Error>>;
+
+/// A trigger for the mappings, together with the entity cache of the block
+/// it is from and a sender for the result of handling it.
+type MappingRequest = (MappingTrigger, Arc<Mutex<EntityCache>>, MappingResultSender);
+
 pub struct RuntimeHost {
     config: RuntimeHostConfig,
-    output: Option<Receiver<RuntimeHostEvent>>,
-    // Never accessed, it's purpose is to signal cancelation on drop.
-    _guard: oneshot::Sender<()>,
+    log_filter: EthereumLogFilter,
+    call_filter: EthereumCallFilter,
+    trigger_sender: Sender<MappingRequest>,
 }
 
 impl RuntimeHost {
+    /// Creates a runtime host for a data source and loads its mappings.
+    /// Fails if the data source is invalid or its mappings cannot be loaded.
     pub fn new<T, L, S>(
         logger: &Logger,
         ethereum_adapter: Arc<Mutex<T>>,
         link_resolver: Arc<L>,
-        store: Arc<Mutex<S>>,
+        store: Arc<S>,
         config: RuntimeHostConfig,
-    ) -> Self
+    ) -> Result<Self, Error>
     where
         T: EthereumAdapter,
         L: LinkResolver,
@@ -99,23 +142,66 @@ impl RuntimeHost {
     {
         let logger = logger.new(o!("component" => "RuntimeHost"));
 
-        // Create channel for sending runtime host events
-        let (event_sender, event_receiver) = channel(100);
-
         let wasmi_config = WasmiModuleConfig {
             subgraph: config.subgraph_manifest.clone(),
             data_source: config.data_source.clone(),
-            event_sink: event_sender,
             ethereum_adapter: ethereum_adapter.clone(),
             link_resolver: link_resolver.clone(),
             store: store.clone(),
+            limits: config.limits,
         };
 
+        // Load the main dataset contract.
+        let contract = config
+            .data_source
+            .mapping
+            .abis
+            .iter()
+            .find(|abi| abi.name == config.data_source.source.abi)
+            .ok_or_else(|| {
+                format_err!(
+                    "no ABI entry found for the main contract of data source: {}",
+                    config.data_source.name
+                )
+            })?
+            .contract
+            .clone();
+
+        // Make sure all event handlers refer to events of the contract.
+        for event_handler in config.data_source.mapping.event_handlers.iter() {
+            util::ethereum::contract_event_with_signature(&contract, &event_handler.event)
+                .ok_or_else(|| {
+                    format_err!("event not found in contract: {}", event_handler.event)
+                })?;
+        }
+
+        // Make sure all call handlers refer to functions of the contract.
+        for call_handler in config.data_source.mapping.call_handlers.iter() {
+            util::ethereum::contract_function_with_signature(&contract, &call_handler.function)
+                .ok_or_else(|| {
+                    format_err!("function not found in contract: {}", call_handler.function)
+                })?;
+        }
+
+        // Make sure the contract address of the data set is valid.
+        Address::from_str(config.data_source.source.address.as_str()).map_err(|e| {
+            format_err!(
+                "invalid contract address {}: {}",
+                config.data_source.source.address,
+                e
+            )
+        })?;
+
+        let log_filter = EthereumLogFilter::from_data_source(&config.data_source);
+        let call_filter = EthereumCallFilter::from_data_source(&config.data_source);
+
         let name = config.data_source.name.clone();
         info!(logger, "Loading WASM runtime"; "data_source" => &name);
 
-        // Create channel as cancelation guard.
-        let (cancel_sender, cancel_receiver) = oneshot::channel();
+        // Create channel for sending triggers to the mappings; each trigger
+        // comes with the entity cache of its block and a oneshot sender for
+        // returning the result of handling it.
+        let (trigger_sender, trigger_receiver) = channel(100);
 
         // wasmi modules are not `Send` therefore they cannot be scheduled by
         // the regular tokio executor, so we create a dedicated thread.
@@ -124,155 +210,248 @@ impl RuntimeHost {
         // `task_receiver`.
         let (task_sender, task_receiver) = channel(100);
         tokio::spawn(task_receiver.for_each(tokio::spawn));
+
+        // The thread reports whether the mappings could be loaded
+        let (module_result_sender, module_result_receiver) = oneshot::channel();
+
         thread::spawn(move || {
             let data_source = wasmi_config.data_source.clone();
 
             // Load the mappings as a WASM module
-            let module = WasmiModule::new(&logger, wasmi_config, task_sender);
-
-            // Process one event at a time, blocking the thread when waiting for
-            // the next event. Also check for a cancelation signal.
-            Self::subscribe_to_events(&logger, data_source, module, ethereum_adapter)
-                .select(
-                    cancel_receiver
-                        .into_stream()
-                        .map(|_| panic!("sent into cancel guard"))
-                        .map_err(|_| ()),
-                ).for_each(|_| Ok(()))
-                .wait()
+            let mut module = match WasmiModule::new(&logger, wasmi_config, task_sender) {
+                Ok(module) => {
+                    module_result_sender.send(Ok(())).ok();
+                    module
+                }
+                Err(e) => {
+                    module_result_sender.send(Err(e)).ok();
+                    return;
+                }
+            };
+
+            // Process one trigger at a time, blocking the thread when waiting
+            // for the next trigger. The receiver terminates when the runtime
+            // host, and with it the trigger sender, is dropped.
+            let trigger_logger = logger.clone();
+            trigger_receiver
+                .for_each(move |(trigger, entity_cache, result_sender)| {
+                    let result = match trigger {
+                        MappingTrigger::Log(block, log) => Self::handle_log(
+                            &trigger_logger,
+                            &data_source,
+                            &contract,
+                            &mut module,
+                            &block,
+                            log,
+                            entity_cache,
+                        ),
+                        MappingTrigger::Call(call) => Self::handle_call(
+                            &trigger_logger,
+                            &data_source,
+                            &contract,
+                            &mut module,
+                            call,
+                            entity_cache,
+                        ),
+                        MappingTrigger::Block(block) => Self::handle_block(
+                            &trigger_logger,
+                            &data_source,
+                            &mut module,
+                            block,
+                            entity_cache,
+                        ),
+                    };
+
+                    // The receiver may have been dropped if processing of the
+                    // block was aborted; there is nothing to do about that
+                    result_sender.send(result).ok();
+                    Ok(())
+                }).wait()
                 .ok();
 
             info!(logger, "shutting down WASM runtime"; "data_source" => name);
         });
 
-        RuntimeHost {
+        module_result_receiver
+            .wait()
+            .map_err(|_| format_err!("WASM runtime thread terminated"))
+            .and_then(|result| result)
+            .map_err(|e| {
+                format_err!(
+                    "failed to load mappings of data source {}: {}",
+                    config.data_source.name,
+                    e
+                )
+            })?;
+
+        Ok(RuntimeHost {
             config,
-            output: Some(event_receiver),
-            _guard: cancel_sender,
-        }
+            log_filter,
+            call_filter,
+            trigger_sender,
+        })
     }
 
-    /// Subscribe to all smart contract events of `data_source` contained in
-    /// `subgraph`.
-    fn subscribe_to_events<T, L, S, U>(
+    /// Call the event handler of `data_source` that matches the log.
+    fn handle_log<T, L, S, U>(
         logger: &Logger,
-        data_source: DataSource,
-        mut module: WasmiModule<T, L, S, U>,
-        ethereum_adapter: Arc<Mutex<T>>,
-    ) -> impl Stream<Item = (), Error = ()> + 'static
+        data_source: &DataSource,
+        contract: &Contract,
+        module: &mut WasmiModule<T, L, S, U>,
+        block: &Block<Transaction>,
+        log: Log,
+        entity_cache: Arc<Mutex<EntityCache>>,
+    ) -> Result<Vec<RuntimeHostEvent>, Error>
     where
-        T: EthereumAdapter + 'static,
-        L: LinkResolver + 'static,
-        S: Store + 'static,
-        U: Sink<SinkItem = Box<Future<Item = (), Error = ()> + Send>> + Clone + 'static,
+        T: EthereumAdapter,
+        L: LinkResolver,
+        S: Store,
+        U: Sink<SinkItem = Box<Future<Item = (), Error = ()> + Send>> + Clone,
     {
-        info!(logger, "Subscribe to events");
+        let event_signature = *log
+            .topics
+            .first()
+            .ok_or_else(|| format_err!("log has no event signature"))?;
+
+        let event_handler = data_source
+            .mapping
+            .event_handlers
+            .iter()
+            .find(|event_handler| {
+                util::ethereum::string_to_h256(event_handler.event.as_str()) == event_signature
+            }).ok_or_else(|| format_err!("no event handler found for log: {:?}", log))?;
 
-        // Obtain the contract address of the data set.
-        let address = Address::from_str(data_source.source.address.as_str())
-            .expect("Failed to parse contract address");
+        let event = util::ethereum::contract_event_with_signature(contract, &event_handler.event)
+            .ok_or_else(|| format_err!("event not found in contract: {}", event_handler.event))?;
 
-        // Load the main dataset contract.
-        let contract = data_source
+        let params = event
+            .parse_log(RawLog {
+                topics: log.topics.clone(),
+                data: log.data.0.clone(),
+            }).map_err(|e| format_err!("failed to parse log: {}", e))?
+            .params;
+
+        info!(logger, "Ethereum event received";
+              "signature" => event_signature.to_string());
+
+        debug!(logger, "  Call event handler";
+               "name" => &event_handler.handler,
+               "signature" => &event_handler.event);
+
+        module.handle_ethereum_event(
+            event_handler.handler.as_str(),
+            EthereumEvent::from_log(&log, block, params)?,
+            entity_cache,
+        )
+    }
+
+    /// Call the call handler of `data_source` that matches the call.
+    fn handle_call<T, L, S, U>(
+        logger: &Logger,
+        data_source: &DataSource,
+        contract: &Contract,
+        module: &mut WasmiModule<T, L, S, U>,
+        call: EthereumCall,
+        entity_cache: Arc<Mutex<EntityCache>>,
+    ) -> Result<Vec<RuntimeHostEvent>, Error>
+    where
+        T: EthereumAdapter,
+        L: LinkResolver,
+        S: Store,
+        U: Sink<SinkItem = Box<Future<Item = (), Error = ()> + Send>> + Clone,
+    {
+        if call.input.len() < 4 {
+            return Err(format_err!("call has no function selector"));
+        }
+
+        let call_handler = data_source
             .mapping
-            .abis
+            .call_handlers
             .iter()
-            .find(|abi| abi.name == data_source.source.abi)
-            .expect("No ABI entry found for the main contract of the dataset")
-            .contract
-            .clone();
+            .find(|call_handler| {
+                util::ethereum::function_selector(call_handler.function.as_str())[..]
+                    == call.input[..4]
+            }).ok_or_else(|| format_err!("no call handler found for call: {:?}", call))?;
 
-        // Prepare subscriptions for all events.
-        let event_stream = {
-            let subscription_results = {
-                data_source
-                    .mapping
-                    .event_handlers
-                    .iter()
-                    .map(|event_handler| {
-                        debug!(logger, "Prepare subscription";
-                                "event" => &event_handler.event,
-                                "handler" => &event_handler.handler,
-                            );
-                        let subscription_id = Uuid::new_v4().simple().to_string();
-                        let event = util::ethereum::contract_event_with_signature(
-                            &contract,
-                            event_handler.event.as_str(),
-                        ).expect(
-                            format!("Event not found in contract: {}", event_handler.event)
-                                .as_str(),
-                        );
-
-                        EthereumEventSubscription {
-                            address,
-                            event: event.clone(),
-                            range: BlockNumberRange {
-                                from: BlockNumber::Number(0),
-                                to: BlockNumber::Latest,
-                            },
-                            subscription_id: subscription_id.clone(),
-                        }
-                    })
-            };
+        let function =
+            util::ethereum::contract_function_with_signature(contract, &call_handler.function)
+                .ok_or_else(|| {
+                    format_err!("function not found in contract: {}", call_handler.function)
+                })?;
 
-            // Merge all event streams.
-            let mut event_stream: Box<Stream<Item = _, Error = _>> = Box::new(stream::empty());
-            for subscription in subscription_results {
-                info!(logger, "Subscribe to event";
-                      "name" => &subscription.event.name,
-                      "subscription_id" => &subscription.subscription_id);
-                event_stream = Box::new(
-                    event_stream.select(
-                        ethereum_adapter
-                            .lock()
-                            .unwrap()
-                            .subscribe_to_event(subscription),
-                    ),
-                );
-            }
-            event_stream
-        };
+        let inputs = decode_params(&function.inputs, &call.input[4..])
+            .map_err(|e| format_err!("failed to decode call inputs: {}", e))?;
+        let outputs = decode_params(&function.outputs, &call.output)
+            .map_err(|e| format_err!("failed to decode call outputs: {}", e))?;
 
-        let event_logger = logger.clone();
-        let error_logger = logger.clone();
-
-        event_stream
-            .map(move |event| {
-                info!(event_logger, "Ethereum event received";
-                      "signature" => event.event_signature.to_string(),
-                    );
-
-                if event.removed {
-                    info!(event_logger, "Event removed";
-                          "block" => event.block_hash.to_string());
-                } else {
-                    let event_handler = data_source
-                        .mapping
-                        .event_handlers
-                        .iter()
-                        .find(|event_handler| {
-                            util::ethereum::string_to_h256(event_handler.event.as_str())
-                                == event.event_signature
-                        }).expect("Received an Ethereum event not mentioned in the data set")
-                        .to_owned();
-
-                    debug!(event_logger, "  Call event handler";
-                           "name" => &event_handler.handler,
-                           "signature" => &event_handler.event);
-
-                    module.handle_ethereum_event(event_handler.handler.as_str(), event);
-                }
-            }).map_err(move |e| error!(error_logger, "Event subscription failed: {}", e))
+        debug!(logger, "  Call call handler";
+               "name" => &call_handler.handler,
+               "function" => &call_handler.function);
+
+        module.handle_ethereum_call(
+            call_handler.handler.as_str(),
+            EthereumCallData {
+                from: call.from,
+                to: call.to,
+                block_hash: call.block_hash,
+                block_number: call.block_number,
+                transaction_hash: call.transaction_hash,
+                inputs,
+                outputs,
+            },
+            entity_cache,
+        )
     }
-}
 
-impl EventProducer<RuntimeHostEvent> for RuntimeHost {
-    fn take_event_stream(
-        &mut self,
-    ) -> Option<Box<Stream<Item = RuntimeHostEvent, Error = ()> + Send>> {
-        self.output
-            .take()
-            .map(|s| Box::new(s) as Box<Stream<Item = RuntimeHostEvent, Error = ()> + Send>)
+    /// Call all block handlers of `data_source` for the block.
+    fn handle_block<T, L, S, U>(
+        logger: &Logger,
+        data_source: &DataSource,
+        module: &mut WasmiModule<T, L, S, U>,
+        block: EthereumBlockHeader,
+        entity_cache: Arc<Mutex<EntityCache>>,
+    ) -> Result<Vec<RuntimeHostEvent>, Error>
+    where
+        T: EthereumAdapter,
+        L: LinkResolver,
+        S: Store,
+        U: Sink<SinkItem = Box<Future<Item = (), Error = ()> + Send>> + Clone,
+    {
+        let mut events = vec![];
+
+        for block_handler in data_source.mapping.block_handlers.iter() {
+            debug!(logger, "  Call block handler";
+                   "name" => &block_handler.handler,
+                   "number" => block.number);
+
+            events.extend(module.handle_ethereum_block(
+                &block_handler.handler,
+                block.clone(),
+                entity_cache.clone(),
+            )?);
+        }
+
+        Ok(events)
+    }
+
+    /// Send a trigger to the WASM runtime thread and wait for the result of
+    /// handling it.
+    fn send_trigger(
+        &self,
+        trigger: MappingTrigger,
+        entity_cache: Arc<Mutex<EntityCache>>,
+    ) -> Box<Future<Item = Vec<RuntimeHostEvent>, Error = Error> + Send> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        Box::new(
+            self.trigger_sender
+                .clone()
+                .send((trigger, entity_cache, result_sender))
+                .map_err(|_| format_err!("WASM runtime thread terminated"))
+                .and_then(|_| {
+                    result_receiver.map_err(|_| format_err!("WASM runtime thread terminated"))
+                }).and_then(|result| result),
+        )
     }
 }
 
@@ -280,4 +459,57 @@ impl RuntimeHostTrait for RuntimeHost {
     fn subgraph_manifest(&self) -> &SubgraphManifest {
         &self.config.subgraph_manifest
     }
+
+    fn matches_log(&self, log: &Log) -> bool {
+        self.log_filter.matches(log)
+    }
+
+    fn process_log(
+        &self,
+        block: Arc<Block<Transaction>>,
+        log: Log,
+        entity_cache: Arc<Mutex<EntityCache>>,
+    ) -> Box<Future<Item = Vec<RuntimeHostEvent>, Error = Error> + Send> {
+        self.send_trigger(MappingTrigger::Log(block, log), entity_cache)
+    }
+
+    fn matches_call(&self, call: &EthereumCall) -> bool {
+        self.call_filter.matches(call)
+    }
+
+    fn process_call(
+        &self,
+        call: EthereumCall,
+        entity_cache: Arc<Mutex<EntityCache>>,
+    ) -> Box<Future<Item = Vec<RuntimeHostEvent>, Error = Error> + Send> {
+        self.send_trigger(MappingTrigger::Call(call), entity_cache)
+    }
+
+    fn has_block_handlers(&self) -> bool {
+        !self.config.data_source.mapping.block_handlers.is_empty()
+    }
+
+    fn process_block(
+        &self,
+        block: EthereumBlockHeader,
+        entity_cache: Arc<Mutex<EntityCache>>,
+    ) -> Box<Future<Item = Vec<RuntimeHostEvent>, Error = Error> + Send> {
+        self.send_trigger(MappingTrigger::Block(block), entity_cache)
+    }
+}
+
+/// Decodes ABI-encoded function parameters into named values.
+fn decode_params(params: &[Param], data: &[u8]) -> Result<Vec<LogParam>, ethabi::Error> {
+    let types = params
+        .iter()
+        .map(|param| param.kind.clone())
+        .collect::<Vec<_>>();
+
+    Ok(params
+        .iter()
+        .zip(ethabi::decode(&types, data)?)
+        .map(|(param, value)| LogParam {
+            name: param.name.clone(),
+            value,
+        }).collect())
 }
@@ -1,9 +1,12 @@
 extern crate ethabi;
+#[macro_use]
+extern crate failure;
 extern crate futures;
 extern crate graph;
 extern crate hex;
 extern crate nan_preserving_float;
-extern crate uuid;
+extern crate parity_wasm;
+extern crate pwasm_utils;
 extern crate wasmi;
 
 mod asc_abi;
@@ -14,6 +17,7 @@ mod to_from;
 use self::graph::web3::types::Address;
 
 pub use self::host::{RuntimeHost, RuntimeHostBuilder, RuntimeHostConfig};
+pub use self::module::MappingLimits;
 
 #[derive(Clone, Debug)]
 pub(crate) struct UnresolvedContractCall {
@@ -1,24 +1,32 @@
-use futures::sync::mpsc::Sender;
 use nan_preserving_float::F64;
 use std::collections::HashMap;
 use std::fmt;
 use std::str::FromStr;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use wasmi::{
     Error, Externals, FuncInstance, FuncRef, HostError, ImportsBuilder, MemoryRef, Module,
-    ModuleImportResolver, ModuleInstance, ModuleRef, NopExternals, RuntimeArgs, RuntimeValue,
-    Signature, Trap, TrapKind, ValueType,
+    ModuleImportResolver, ModuleInstance, ModuleRef, RuntimeArgs, RuntimeValue, Signature, Trap,
+    TrapKind, ValueType,
 };
 
+use failure;
 use futures::sync::oneshot;
 use graph::components::ethereum::*;
-use graph::components::store::{EventSource, StoreKey};
+use graph::components::store::StoreKey;
 use graph::components::subgraph::RuntimeHostEvent;
 use graph::data::store::scalar;
 use graph::data::subgraph::DataSource;
 use graph::prelude::*;
 use graph::serde_json;
+use graph::tokio::timer::Deadline;
+use graph::util::metrics::{
+    ETHEREUM_CALL_DURATION, ETHEREUM_CALL_ERRORS, SUBGRAPH_EVENTS_PROCESSED,
+    SUBGRAPH_EVENT_HANDLER_DURATION,
+};
 use graph::web3::types::{BlockId, H160, H256, U256};
+use parity_wasm::elements::{self as wasm, MemoryType};
+use pwasm_utils;
 
 use super::UnresolvedContractCall;
 use asc_abi::asc_ptr::*;
@@ -41,16 +49,20 @@ impl WasmiAscHeap {
 
 impl AscHeap for WasmiAscHeap {
     fn raw_new(&self, bytes: &[u8]) -> Result<u32, Error> {
+        // The allocation function traps if the memory cannot grow any further
         let address = self
             .module
             .invoke_export(
                 "allocate_memory",
                 &[RuntimeValue::I32(bytes.len() as i32)],
-                &mut NopExternals,
-            ).expect("Failed to invoke memory allocation function")
-            .expect("Function did not return a value")
-            .try_into::<u32>()
-            .expect("Function did not return u32");
+                &mut UnmeteredExternals,
+            )?
+            .and_then(|address| address.try_into::<u32>())
+            .ok_or_else(|| {
+                Error::Function(String::from(
+                    "Memory allocation function did not return an address",
+                ))
+            })?;
 
         self.memory.set(address, bytes)?;
 
@@ -85,14 +97,83 @@ const JSON_TO_BIG_INT_FUNC_INDEX: usize = 18;
 const IPFS_CAT_FUNC_INDEX: usize = 19;
 const STORE_GET_FUNC_INDEX: usize = 20;
 const TYPE_CONVERSION_BIG_INT_FUNC_TO_INT256_INDEX: usize = 21;
+const DATA_SOURCE_CREATE_FUNC_INDEX: usize = 22;
+const GAS_FUNC_INDEX: usize = 23;
+
+/// Externals for calling into the module outside of handlers, e.g. to
+/// allocate memory. Only the gas metering function may be called, and the
+/// fuel it charges is not counted.
+struct UnmeteredExternals;
+
+impl Externals for UnmeteredExternals {
+    fn invoke_index(
+        &mut self,
+        index: usize,
+        _args: RuntimeArgs,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        match index {
+            GAS_FUNC_INDEX => Ok(None),
+            _ => Err(Trap::new(TrapKind::Unreachable)),
+        }
+    }
+}
+
+/// Limits on the resources that the mappings of a data source may use.
+/// Exceeding any of them fails the handler that exceeded it.
+#[derive(Clone, Copy, Debug)]
+pub struct MappingLimits {
+    /// The number of WASM instructions that a single handler may execute.
+    pub fuel: u64,
+    /// How long a single handler may run, not counting the time it waits
+    /// for host calls. A host call that takes longer than this fails the
+    /// handler with a `HostCallTimeoutError`, which is not the fault of the
+    /// block that is being processed.
+    pub timeout: Duration,
+    /// The size that the WASM memory may grow to, in 64 KiB pages.
+    pub max_memory_pages: u32,
+}
+
+impl Default for MappingLimits {
+    fn default() -> Self {
+        MappingLimits {
+            fuel: 10_000_000_000,
+            timeout: Duration::from_secs(60),
+            // 1 GiB
+            max_memory_pages: 16_384,
+        }
+    }
+}
+
+/// Caps the size of the memory that a WASM module defines.
+fn limit_memory(module: &mut wasm::Module, max_pages: u32) -> Result<(), failure::Error> {
+    if let Some(section) = module.memory_section_mut() {
+        for memory in section.entries_mut().iter_mut() {
+            let initial = memory.limits().initial();
+            if initial > max_pages {
+                return Err(format_err!(
+                    "WASM module needs {} pages of memory, more than the limit of {} pages",
+                    initial,
+                    max_pages
+                ));
+            }
+
+            let maximum = memory
+                .limits()
+                .maximum()
+                .map_or(max_pages, |maximum| maximum.min(max_pages));
+            *memory = MemoryType::new(initial, Some(maximum));
+        }
+    }
+    Ok(())
+}
 
 pub struct WasmiModuleConfig<T, L, S> {
     pub subgraph: SubgraphManifest,
     pub data_source: DataSource,
-    pub event_sink: Sender<RuntimeHostEvent>,
     pub ethereum_adapter: Arc<Mutex<T>>,
     pub link_resolver: Arc<L>,
-    pub store: Arc<Mutex<S>>,
+    pub store: Arc<S>,
+    pub limits: MappingLimits,
 }
 
 impl<T, L, S> Clone for WasmiModuleConfig<T, L, S> {
@@ -100,10 +181,10 @@ impl<T, L, S> Clone for WasmiModuleConfig<T, L, S> {
         WasmiModuleConfig {
             subgraph: self.subgraph.clone(),
             data_source: self.data_source.clone(),
-            event_sink: self.event_sink.clone(),
             ethereum_adapter: self.ethereum_adapter.clone(),
             link_resolver: self.link_resolver.clone(),
             store: self.store.clone(),
+            limits: self.limits,
         }
     }
 }
@@ -124,16 +205,34 @@ where
     U: Sink<SinkItem = Box<Future<Item = (), Error = ()> + Send>> + Clone,
 {
     /// Creates a new wasmi module
-    pub fn new(logger: &Logger, config: WasmiModuleConfig<T, L, S>, task_sink: U) -> Self {
+    pub fn new(
+        logger: &Logger,
+        config: WasmiModuleConfig<T, L, S>,
+        task_sink: U,
+    ) -> Result<Self, failure::Error> {
         let logger = logger.new(o!("component" => "WasmiModule"));
 
-        let module = Module::from_parity_wasm_module(config.data_source.mapping.runtime.clone())
-            .expect(
-                format!(
-                    "Wasmi could not interpret module of data source: {}",
-                    config.data_source.name
-                ).as_str(),
-            );
+        // Meter the instructions that the module executes, so that handlers
+        // that run for too long can be stopped
+        let mut runtime = pwasm_utils::inject_gas_counter(
+            config.data_source.mapping.runtime.clone(),
+            &pwasm_utils::rules::Set::default(),
+        )
+        .map_err(|_| {
+            format_err!(
+                "failed to inject gas metering into WASM module of data source {}",
+                config.data_source.name
+            )
+        })?;
+        limit_memory(&mut runtime, config.limits.max_memory_pages)?;
+
+        let module = Module::from_parity_wasm_module(runtime).map_err(|e| {
+            format_err!(
+                "wasmi could not interpret module of data source {}: {}",
+                config.data_source.name,
+                e
+            )
+        })?;
 
         // Build import resolver
         let mut imports = ImportsBuilder::new();
@@ -143,18 +242,19 @@ where
         imports.push_resolver("typeConversion", &TypeConversionModuleResolver);
         imports.push_resolver("json", &JsonModuleResolver);
         imports.push_resolver("ipfs", &IpfsModuleResolver);
+        imports.push_resolver("dataSource", &DataSourceModuleResolver);
 
         // Instantiate the runtime module using hosted functions and import resolver
-        let module =
-            ModuleInstance::new(&module, &imports).expect("Failed to instantiate WASM module");
+        let module = ModuleInstance::new(&module, &imports)
+            .map_err(|e| format_err!("failed to instantiate WASM module: {}", e))?;
 
         // Provide access to the WASM runtime linear memory
         let not_started_module = module.not_started_instance().clone();
         let memory = not_started_module
             .export_by_name("memory")
-            .expect("Failed to find memory export in the WASM module")
+            .ok_or_else(|| format_err!("failed to find memory export in the WASM module"))?
             .as_memory()
-            .expect("Export \"memory\" has an invalid type")
+            .ok_or_else(|| format_err!("export \"memory\" has an invalid type"))?
             .clone();
 
         // Create a AssemblyScript-compatible WASM memory heap
@@ -165,40 +265,165 @@ where
             subgraph: config.subgraph,
             data_source: config.data_source,
             logger: logger.clone(),
-            event_sink: config.event_sink.clone(),
+            events: vec![],
             heap: heap.clone(),
             ethereum_adapter: config.ethereum_adapter.clone(),
             link_resolver: config.link_resolver.clone(),
             block_hash: H256::zero(),
             store: config.store.clone(),
+            entity_cache: Arc::new(Mutex::new(EntityCache::new())),
             task_sink,
+            limits: config.limits,
+            fuel_used: 0,
+            deadline: Instant::now() + config.limits.timeout,
         };
 
         let module = module
             .run_start(&mut externals)
-            .expect("Failed to start WASM module instance");
+            .map_err(|e| format_err!("failed to start WASM module instance: {}", e))?;
 
-        WasmiModule {
+        Ok(WasmiModule {
             logger,
             module,
             externals,
             heap,
+        })
+    }
+
+    /// Call the event handler with the given name for the event. The
+    /// handler reads and writes entities through `entity_cache`.
+    ///
+    /// Returns the events emitted by the handler while processing the
+    /// event. Fails if the handler does not exist or traps.
+    pub fn handle_ethereum_event(
+        &mut self,
+        handler_name: &str,
+        event: EthereumEvent,
+        entity_cache: Arc<Mutex<EntityCache>>,
+    ) -> Result<Vec<RuntimeHostEvent>, failure::Error> {
+        self.externals.block_hash = event.block.hash;
+        self.externals.entity_cache = entity_cache;
+        let arg = RuntimeValue::from(self.heap.asc_new(&event).map_err(|e| {
+            format_err!(
+                "failed to pass Ethereum event to handler {}: {}",
+                handler_name,
+                e
+            )
+        })?);
+
+        let subgraph_id = self.externals.subgraph.id.clone();
+        let timer = SUBGRAPH_EVENT_HANDLER_DURATION
+            .with_label_values(&[&subgraph_id, handler_name])
+            .start_timer();
+        let result = self.invoke_handler("event", handler_name, arg);
+        timer.observe_duration();
+
+        if result.is_ok() {
+            SUBGRAPH_EVENTS_PROCESSED
+                .with_label_values(&[&subgraph_id])
+                .inc();
         }
+        result
     }
 
-    pub fn handle_ethereum_event(&mut self, handler_name: &str, event: EthereumEvent) {
-        self.externals.block_hash = event.block_hash.clone();
-        self.module
-            .invoke_export(
+    /// Call the call handler with the given name for the contract function
+    /// call. The handler reads and writes entities through `entity_cache`.
+    ///
+    /// Returns the events emitted by the handler while processing the call.
+    /// Fails if the handler does not exist or traps.
+    pub fn handle_ethereum_call(
+        &mut self,
+        handler_name: &str,
+        call: EthereumCallData,
+        entity_cache: Arc<Mutex<EntityCache>>,
+    ) -> Result<Vec<RuntimeHostEvent>, failure::Error> {
+        self.externals.block_hash = call.block_hash;
+        self.externals.entity_cache = entity_cache;
+        let arg = RuntimeValue::from(self.heap.asc_new(&call).map_err(|e| {
+            format_err!(
+                "failed to pass Ethereum call to handler {}: {}",
                 handler_name,
-                &[RuntimeValue::from(self.heap.asc_new(&event))],
-                &mut self.externals,
-            ).unwrap_or_else(|e| {
-                warn!(self.logger, "Failed to handle Ethereum event";
-                      "handler" => &handler_name,
-                      "error" => format!("{}", e));
-                None
-            });
+                e
+            )
+        })?);
+        self.invoke_handler("call", handler_name, arg)
+    }
+
+    /// Call the block handler with the given name for the block. The
+    /// handler reads and writes entities through `entity_cache`.
+    ///
+    /// Returns the events emitted by the handler while processing the
+    /// block. Fails if the handler does not exist or traps.
+    pub fn handle_ethereum_block(
+        &mut self,
+        handler_name: &str,
+        block: EthereumBlockHeader,
+        entity_cache: Arc<Mutex<EntityCache>>,
+    ) -> Result<Vec<RuntimeHostEvent>, failure::Error> {
+        self.externals.block_hash = block.hash;
+        self.externals.entity_cache = entity_cache;
+        let arg = RuntimeValue::from(self.heap.asc_new(&block).map_err(|e| {
+            format_err!(
+                "failed to pass Ethereum block to handler {}: {}",
+                handler_name,
+                e
+            )
+        })?);
+        self.invoke_handler("block", handler_name, arg)
+    }
+
+    /// Invoke a handler for an Ethereum trigger and collect the events it
+    /// emitted.
+    fn invoke_handler(
+        &mut self,
+        trigger: &str,
+        handler_name: &str,
+        arg: RuntimeValue,
+    ) -> Result<Vec<RuntimeHostEvent>, failure::Error> {
+        // Every handler gets the full fuel and time budget
+        self.externals.fuel_used = 0;
+        self.externals.deadline = Instant::now() + self.externals.limits.timeout;
+
+        let result = self
+            .module
+            .invoke_export(handler_name, &[arg], &mut self.externals);
+
+        // The events of a handler that failed are discarded, since the
+        // handler did not finish
+        let events = self.externals.events.drain(..).collect();
+
+        result.map(|_| events).map_err(|e| {
+            // Host calls that timed out are reported as such, so the block
+            // can be processed again
+            if let Some(timeout) = host_call_timeout(&e) {
+                return HostCallTimeoutError(format!(
+                    "failed to handle Ethereum {} with handler {}: {}",
+                    trigger, handler_name, timeout
+                )).into();
+            }
+
+            // A handler that runs out of memory usually traps in the
+            // allocator, so report the memory limit instead of the trap
+            let memory = &self.heap.memory;
+            let e = if memory
+                .maximum()
+                .map_or(false, |maximum| memory.current_size() >= maximum)
+            {
+                format!(
+                    "Mapping exceeded the memory limit of {} pages",
+                    self.externals.limits.max_memory_pages
+                )
+            } else {
+                e.to_string()
+            };
+
+            format_err!(
+                "failed to handle Ethereum {} with handler {}: {}",
+                trigger,
+                handler_name,
+                e
+            )
+        })
     }
 }
 
@@ -220,19 +445,52 @@ fn host_error(message: String) -> Trap {
     Trap::new(TrapKind::Host(Box::new(HostExternalsError(message))))
 }
 
+/// Returns the timeout of a host call that made a handler trap, if that is
+/// why it trapped.
+fn host_call_timeout(e: &Error) -> Option<&HostCallTimeoutError> {
+    match e {
+        Error::Trap(trap) => match trap.kind() {
+            TrapKind::Host(host_error) => host_error
+                .downcast_ref::<HostExternalsError<HostCallTimeoutError>>()
+                .map(|e| &e.0),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Turns a failure to allocate an object on the heap of the module into a
+/// trap. Traps of the allocation function, e.g. because the memory limit was
+/// reached, are passed on as they are.
+fn allocation_error(e: Error) -> Trap {
+    match e {
+        Error::Trap(trap) => trap,
+        e => host_error(format!("Failed to allocate memory: {}", e)),
+    }
+}
+
 /// Hosted functions for external use by wasm module
 pub struct HostExternals<T, L, S, U> {
     logger: Logger,
     subgraph: SubgraphManifest,
     data_source: DataSource,
-    event_sink: Sender<RuntimeHostEvent>,
+    // Events emitted by the event handler that is currently running.
+    events: Vec<RuntimeHostEvent>,
     heap: WasmiAscHeap,
     ethereum_adapter: Arc<Mutex<T>>,
     link_resolver: Arc<L>,
     // Block hash of the event being mapped.
     block_hash: H256,
-    store: Arc<Mutex<S>>,
+    store: Arc<S>,
+    // Entity cache of the block that is being processed.
+    entity_cache: Arc<Mutex<EntityCache>>,
     task_sink: U,
+    limits: MappingLimits,
+    // Fuel used by the handler that is currently running.
+    fuel_used: u64,
+    // Time by which the handler that is currently running must finish; it
+    // moves by the time that the handler waits for host calls.
+    deadline: Instant,
 }
 
 impl<T, L, S, U> HostExternals<T, L, S, U>
@@ -242,14 +500,32 @@ where
     S: Store,
     U: Sink<SinkItem = Box<Future<Item = (), Error = ()> + Send>> + Clone,
 {
+    /// Charges the fuel for a block of instructions that is about to be
+    /// executed; calls to this are injected by gas metering.
+    fn gas(&mut self, amount: u32) -> Result<Option<RuntimeValue>, Trap> {
+        self.fuel_used = self.fuel_used.saturating_add(amount as u64);
+        if self.fuel_used > self.limits.fuel {
+            return Err(host_error(format!(
+                "Mapping exceeded the fuel limit of {} instructions",
+                self.limits.fuel
+            )));
+        }
+        if Instant::now() > self.deadline {
+            return Err(host_error(format!(
+                "Mapping exceeded the time limit of {} seconds",
+                self.limits.timeout.as_secs()
+            )));
+        }
+        Ok(None)
+    }
+
     /// function store.set(entity: string, id: string, data: Entity): void
     fn store_set(
-        &self,
+        &mut self,
         entity_ptr: AscPtr<AscString>,
         id_ptr: AscPtr<AscString>,
         data_ptr: AscPtr<AscEntity>,
     ) -> Result<Option<RuntimeValue>, Trap> {
-        let block_hash: H256 = self.block_hash.clone();
         let entity: String = self.heap.asc_get(entity_ptr);
         let id: String = self.heap.asc_get(id_ptr);
         let data: HashMap<String, Value> = self.heap.asc_get(data_ptr);
@@ -258,33 +534,43 @@ where
             entity,
             id,
         };
+        let entity = Entity::from(data);
 
-        let entity_data = Entity::from(data);
+        // Validate the entity as it will be stored, i.e. merged into the
+        // existing entity and identified by its key
+        let mut entity_cache = self.entity_cache.lock().unwrap();
+        let mut stored_entity = entity_cache
+            .get(&*self.store, store_key.clone())
+            .map_err(|e| {
+                host_error(format!(
+                    "Failed to load entity {}[{}]: {}",
+                    store_key.entity, store_key.id, e
+                ))
+            })?.unwrap_or_else(Entity::new);
+        stored_entity.merge(entity.clone());
+        stored_entity
+            .entry(String::from("id"))
+            .or_insert_with(|| Value::String(store_key.id.clone()));
+        stored_entity
+            .validate(&self.subgraph.schema.document, &store_key.entity)
+            .map_err(|e| {
+                host_error(format!(
+                    "Invalid entity {}[{}]: {}",
+                    store_key.entity, store_key.id, e
+                ))
+            })?;
 
-        // Send an entity set event
-        let logger = self.logger.clone();
-        self.event_sink
-            .clone()
-            .send(RuntimeHostEvent::EntitySet(
-                store_key,
-                entity_data,
-                EventSource::EthereumBlock(block_hash),
-            )).map_err(move |e| {
-                error!(logger, "Failed to forward runtime host event";
-                        "error" => format!("{}", e));
-            }).wait()
-            .ok();
+        entity_cache.set(store_key, entity);
 
         Ok(None)
     }
 
     /// function store.remove(entity: string, id: string): void
     fn store_remove(
-        &self,
+        &mut self,
         entity_ptr: AscPtr<AscString>,
         id_ptr: AscPtr<AscString>,
     ) -> Result<Option<RuntimeValue>, Trap> {
-        let block_hash: H256 = self.block_hash.clone();
         let entity: String = self.heap.asc_get(entity_ptr);
         let id: String = self.heap.asc_get(id_ptr);
         let store_key = StoreKey {
@@ -293,19 +579,7 @@ where
             id,
         };
 
-        // Send an entity removed event
-        let logger = self.logger.clone();
-        self.event_sink
-            .clone()
-            .send(RuntimeHostEvent::EntityRemoved(
-                store_key,
-                EventSource::EthereumBlock(block_hash),
-            )).map_err(move |e| {
-                error!(logger, "Failed to forward runtime host event";
-                        "error" => format!("{}", e));
-            }).and_then(|_| Ok(()))
-            .wait()
-            .ok();
+        self.entity_cache.lock().unwrap().remove(store_key);
 
         Ok(None)
     }
@@ -322,18 +596,26 @@ where
             id: self.heap.asc_get(id_ptr),
         };
 
-        // Retrieve an Entity from the store
-        self.store
+        // Retrieve an Entity from the cache, which falls back to the store;
+        // entities that don't exist are returned as null
+        let entity = self
+            .entity_cache
             .lock()
             .unwrap()
-            .get(store_key)
-            .map_err(|_| host_error("Error getting entity".to_string()))
-            .and_then(|result| Ok(Some(RuntimeValue::from(self.heap.asc_new(&result)))))
+            .get(&*self.store, store_key)
+            .map_err(|e| host_error(format!("Error getting entity: {}", e)))?;
+        match entity {
+            Some(entity) => {
+                let entity_ptr = self.heap.asc_new(&entity).map_err(allocation_error)?;
+                Ok(Some(RuntimeValue::from(entity_ptr)))
+            }
+            None => Ok(Some(RuntimeValue::I32(0))),
+        }
     }
 
     /// function ethereum.call(call: SmartContractCall): Array<Token>
     fn ethereum_call(
-        &self,
+        &mut self,
         call_ptr: AscPtr<AscUnresolvedContractCall>,
     ) -> Result<Option<RuntimeValue>, Trap> {
         let unresolved_call: UnresolvedContractCall = self.heap.asc_get(call_ptr);
@@ -372,18 +654,27 @@ where
             args: unresolved_call.function_args.clone(),
         };
 
-        self.ethereum_adapter
+        let function_name = unresolved_call.function_name.clone();
+        let contract_name = unresolved_call.contract_name.clone();
+        let call = self
+            .ethereum_adapter
             .lock()
             .unwrap()
             .contract_call(call)
-            .wait()
-            .map(|result| Some(RuntimeValue::from(self.heap.asc_new(&*result))))
-            .map_err(|e| {
-                host_error(format!(
+            .map_err(move |e| {
+                ETHEREUM_CALL_ERRORS.inc();
+                format!(
                     "Failed to call function \"{}\" of contract \"{}\": {}",
-                    unresolved_call.function_name, unresolved_call.contract_name, e
-                ))
-            })
+                    function_name, contract_name, e
+                )
+            });
+
+        let timer = ETHEREUM_CALL_DURATION.start_timer();
+        let result = self.block_on(call);
+        timer.observe_duration();
+
+        let result_ptr = self.heap.asc_new(&*result?).map_err(allocation_error)?;
+        Ok(Some(RuntimeValue::from(result_ptr)))
     }
 
     /// function typeConversion.bytesToString(bytes: Bytes): string
@@ -397,7 +688,9 @@ where
         // buffer and padded with null characters, so trim
         // trailing nulls.
         let trimmed_s = s.trim_right_matches('\u{0000}');
-        Ok(Some(RuntimeValue::from(self.heap.asc_new(trimmed_s))))
+        let string_obj: AscPtr<AscString> =
+            self.heap.asc_new(trimmed_s).map_err(allocation_error)?;
+        Ok(Some(RuntimeValue::from(string_obj)))
     }
 
     /// function typeConversion.u64ArrayToString(u64_array: U64Array): string
@@ -415,7 +708,9 @@ where
 
         let s = String::from_utf8_lossy(&*bytes);
         let trimmed_s = s.trim_right_matches('\u{0000}');
-        Ok(Some(RuntimeValue::from(self.heap.asc_new(trimmed_s))))
+        let string_obj: AscPtr<AscString> =
+            self.heap.asc_new(trimmed_s).map_err(allocation_error)?;
+        Ok(Some(RuntimeValue::from(string_obj)))
     }
 
     /// function typeConversion.u64ArrayToHex(u64_array: U64Array): string
@@ -434,7 +729,10 @@ where
         // Even an empty string must be prefixed with `0x`.
         // Encodes each byte as a two hex digits.
         let hex_string = format!("0x{}", hex::encode(bytes));
-        let hex_string_obj = self.heap.asc_new(hex_string.as_str());
+        let hex_string_obj: AscPtr<AscString> = self
+            .heap
+            .asc_new(hex_string.as_str())
+            .map_err(allocation_error)?;
         Ok(Some(RuntimeValue::from(hex_string_obj)))
     }
 
@@ -442,7 +740,7 @@ where
     fn h256_to_h160(&self, h256_ptr: AscPtr<AscH256>) -> Result<Option<RuntimeValue>, Trap> {
         let h256: H256 = self.heap.asc_get(h256_ptr);
         let h160 = H160::from(h256);
-        let h160_obj: AscPtr<AscH160> = self.heap.asc_new(&h160);
+        let h160_obj: AscPtr<AscH160> = self.heap.asc_new(&h160).map_err(allocation_error)?;
         Ok(Some(RuntimeValue::from(h160_obj)))
     }
 
@@ -450,7 +748,7 @@ where
     fn h160_to_h256(&self, h160_ptr: AscPtr<AscH160>) -> Result<Option<RuntimeValue>, Trap> {
         let h160: H160 = self.heap.asc_get(h160_ptr);
         let h256 = H256::from(h160);
-        let h256_obj: AscPtr<AscH256> = self.heap.asc_new(&h256);
+        let h256_obj: AscPtr<AscH256> = self.heap.asc_new(&h256).map_err(allocation_error)?;
         Ok(Some(RuntimeValue::from(h256_obj)))
     }
 
@@ -459,7 +757,7 @@ where
         let u256: U256 = self.heap.asc_get(u256_ptr);
         let h256 = H256::from(u256);
         let h160 = H160::from(h256);
-        let h160_obj: AscPtr<AscH160> = self.heap.asc_new(&h160);
+        let h160_obj: AscPtr<AscH160> = self.heap.asc_new(&h160).map_err(allocation_error)?;
         Ok(Some(RuntimeValue::from(h160_obj)))
     }
 
@@ -467,7 +765,7 @@ where
     fn u256_to_h256(&self, u256_ptr: AscPtr<AscU256>) -> Result<Option<RuntimeValue>, Trap> {
         let u256: U256 = self.heap.asc_get(u256_ptr);
         let h256 = H256::from(u256);
-        let h256_obj: AscPtr<AscH256> = self.heap.asc_new(&h256);
+        let h256_obj: AscPtr<AscH256> = self.heap.asc_new(&h256).map_err(allocation_error)?;
         Ok(Some(RuntimeValue::from(h256_obj)))
     }
 
@@ -476,7 +774,7 @@ where
         let s: String = self.heap.asc_get(str_ptr);
         let h160 = H160::from_str(s.as_str())
             .map_err(|e| host_error(format!("Failed to convert string to Address/H160: {}", e)))?;
-        let h160_obj: AscPtr<AscH160> = self.heap.asc_new(&h160);
+        let h160_obj: AscPtr<AscH160> = self.heap.asc_new(&h160).map_err(allocation_error)?;
         Ok(Some(RuntimeValue::from(h160_obj)))
     }
 
@@ -490,7 +788,8 @@ where
         let int256: U256 = self.heap.asc_get(int256_ptr);
         let mut buffer = [0; 32];
         int256.to_little_endian(&mut buffer);
-        let big_int_obj: AscPtr<BigInt> = self.heap.asc_new(&buffer[..]);
+        let big_int_obj: AscPtr<BigInt> =
+            self.heap.asc_new(&buffer[..]).map_err(allocation_error)?;
         Ok(Some(RuntimeValue::from(big_int_obj)))
     }
 
@@ -505,7 +804,8 @@ where
             )))
         } else {
             let int256 = U256::from_little_endian(&*bytes);
-            let int256_ptr: AscPtr<Uint64Array> = self.heap.asc_new(&int256);
+            let int256_ptr: AscPtr<Uint64Array> =
+                self.heap.asc_new(&int256).map_err(allocation_error)?;
             Ok(Some(RuntimeValue::from(int256_ptr)))
         }
     }
@@ -522,7 +822,10 @@ where
         // Even an empty string must be prefixed with `0x`.
         // Encodes each byte as a two hex digits.
         let hex_string = format!("0x{}", hex::encode(bytes));
-        let hex_string_obj = self.heap.asc_new(hex_string.as_str());
+        let hex_string_obj: AscPtr<AscString> = self
+            .heap
+            .asc_new(hex_string.as_str())
+            .map_err(allocation_error)?;
 
         Ok(Some(RuntimeValue::from(hex_string_obj)))
     }
@@ -532,19 +835,16 @@ where
         let bytes: Vec<u8> = self.heap.asc_get(bytes_ptr);
         let json: serde_json::Value =
             serde_json::from_reader(&*bytes).map_err(HostExternalsError)?;
-        let json_obj = self.heap.asc_new(&json);
+        let json_obj = self.heap.asc_new(&json).map_err(allocation_error)?;
         Ok(Some(RuntimeValue::from(json_obj)))
     }
 
     /// function ipfs.cat(link: String): Bytes
-    fn ipfs_cat(&self, link_ptr: AscPtr<AscString>) -> Result<Option<RuntimeValue>, Trap> {
+    fn ipfs_cat(&mut self, link_ptr: AscPtr<AscString>) -> Result<Option<RuntimeValue>, Trap> {
         let link = self.heap.asc_get(link_ptr);
-        let bytes = self.block_on(
-            self.link_resolver
-                .cat(&Link { link })
-                .map_err(|e| HostExternalsError(e.to_string())),
-        )?;
-        let bytes_obj: AscPtr<Uint8Array> = self.heap.asc_new(&*bytes);
+        let cat = self.link_resolver.cat(&Link { link });
+        let bytes = self.block_on(cat)?;
+        let bytes_obj: AscPtr<Uint8Array> = self.heap.asc_new(&*bytes).map_err(allocation_error)?;
         Ok(Some(RuntimeValue::from(bytes_obj)))
     }
 
@@ -581,14 +881,77 @@ where
         let json: String = self.heap.asc_get(json);
         let big_int = scalar::BigInt::from_str(&json)
             .map_err(|_| host_error(format!("JSON `{}` is not a decimal string", json)))?;
-        let big_int_ptr: AscPtr<BigInt> = self.heap.asc_new(&*big_int.to_signed_bytes_le());
+        let big_int_ptr: AscPtr<BigInt> = self
+            .heap
+            .asc_new(&*big_int.to_signed_bytes_le())
+            .map_err(allocation_error)?;
         Ok(Some(RuntimeValue::from(big_int_ptr)))
     }
 
-    fn block_on<I: Send + 'static, E: Send + 'static>(
-        &self,
+    /// function dataSource.create(name: string, address: Address): void
+    fn data_source_create(
+        &mut self,
+        name_ptr: AscPtr<AscString>,
+        address_ptr: AscPtr<AscAddress>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let name: String = self.heap.asc_get(name_ptr);
+        let address: H160 = self.heap.asc_get(address_ptr);
+
+        // Make sure the data source template exists
+        if !self
+            .subgraph
+            .templates
+            .iter()
+            .any(|template| template.name == name)
+        {
+            return Err(host_error(format!(
+                "Unknown data source template \"{}\" used in WASM runtime",
+                name
+            )));
+        }
+
+        info!(self.logger, "Create data source";
+              "template" => &name,
+              "address" => format!("{:x}", address));
+
+        self.events
+            .push(RuntimeHostEvent::DataSourceCreated(DynamicDataSource {
+                template: name,
+                address,
+            }));
+
+        Ok(None)
+    }
+
+    /// Runs a future on the task executor and waits for its result. Fails
+    /// if the future fails or if it does not finish within the time limit
+    /// of the handler. The time spent waiting does not count towards the
+    /// time limit of the handler itself.
+    fn block_on<I, E>(
+        &mut self,
         future: impl Future<Item = I, Error = E> + Send + 'static,
-    ) -> Result<I, E> {
+    ) -> Result<I, Trap>
+    where
+        I: Send + 'static,
+        E: fmt::Display + Send + 'static,
+    {
+        let start = Instant::now();
+        let timeout = self.limits.timeout;
+        let future = Deadline::new(future, start + timeout).map_err(move |e| {
+            if e.is_elapsed() {
+                return Trap::new(TrapKind::Host(Box::new(HostExternalsError(
+                    HostCallTimeoutError(format!(
+                        "Host call exceeded the time limit of {} seconds",
+                        timeout.as_secs()
+                    )),
+                ))));
+            }
+            host_error(match e.into_inner() {
+                Some(e) => e.to_string(),
+                None => String::from("Timer failed"),
+            })
+        });
+
         let (return_sender, return_receiver) = oneshot::channel();
         self.task_sink
             .clone()
@@ -597,7 +960,10 @@ where
             }))).wait()
             .map_err(|_| panic!("task receiver dropped"))
             .unwrap();
-        return_receiver.wait().expect("`return_sender` dropped")
+        let result = return_receiver.wait().expect("`return_sender` dropped");
+
+        self.deadline += start.elapsed();
+        result
     }
 }
 
@@ -651,6 +1017,10 @@ where
             TYPE_CONVERSION_BIG_INT_FUNC_TO_INT256_INDEX => {
                 self.big_int_to_int256(args.nth_checked(0)?)
             }
+            DATA_SOURCE_CREATE_FUNC_INDEX => {
+                self.data_source_create(args.nth_checked(0)?, args.nth_checked(1)?)
+            }
+            GAS_FUNC_INDEX => self.gas(args.nth_checked(0)?),
             _ => panic!("Unimplemented function at {}", index),
         }
     }
@@ -674,6 +1044,10 @@ impl ModuleImportResolver for EnvModuleResolver {
                 ),
                 ABORT_FUNC_INDEX,
             ),
+            "gas" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32][..], None),
+                GAS_FUNC_INDEX,
+            ),
             _ => {
                 return Err(Error::Instantiation(format!(
                     "Export '{}' not found",
@@ -846,6 +1220,26 @@ impl ModuleImportResolver for IpfsModuleResolver {
     }
 }
 
+/// Data source module resolver
+pub struct DataSourceModuleResolver;
+
+impl ModuleImportResolver for DataSourceModuleResolver {
+    fn resolve_func(&self, field_name: &str, _signature: &Signature) -> Result<FuncRef, Error> {
+        Ok(match field_name {
+            "create" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], None),
+                DATA_SOURCE_CREATE_FUNC_INDEX,
+            ),
+            _ => {
+                return Err(Error::Instantiation(format!(
+                    "Export '{}' not found",
+                    field_name
+                )))
+            }
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     extern crate failure;
@@ -855,24 +1249,22 @@ mod tests {
     extern crate parity_wasm;
 
     use ethabi::{LogParam, Token};
-    use futures::sync::mpsc::{channel, Receiver};
+    use futures::sync::mpsc::{channel, Sender};
     use std::collections::HashMap;
     use std::io::Cursor;
     use std::iter::FromIterator;
     use std::sync::Mutex;
 
-    use self::graph_mock::FakeStore;
+    use self::graph_mock::MockStore;
     use graph::components::ethereum::*;
     use graph::components::store::*;
     use graph::components::subgraph::*;
     use graph::data::subgraph::*;
     use graph::util;
-    use graph::web3::types::Address;
+    use graph::web3::types::{Address, Block, Log, Transaction};
 
     use super::*;
 
-    use self::graphql_parser::schema::Document;
-
     #[derive(Default)]
     struct MockEthereumAdapter {}
 
@@ -880,55 +1272,81 @@ mod tests {
         fn contract_call(
             &mut self,
             _call: EthereumContractCall,
-        ) -> Box<Future<Item = Vec<Token>, Error = EthereumContractCallError>> {
+        ) -> Box<Future<Item = Vec<Token>, Error = EthereumContractCallError> + Send> {
             unimplemented!()
         }
 
-        fn subscribe_to_event(
+        fn logs_in_block_range(
             &mut self,
-            _subscription: EthereumEventSubscription,
-        ) -> Box<Stream<Item = EthereumEvent, Error = EthereumSubscriptionError>> {
+            _from: u64,
+            _to: u64,
+            _log_filter: EthereumLogFilter,
+        ) -> Box<Future<Item = Vec<Log>, Error = Error> + Send> {
             unimplemented!()
         }
 
-        fn unsubscribe_from_event(&mut self, _subscription_id: String) -> bool {
-            false
+        fn calls_in_block_range(
+            &mut self,
+            _from: u64,
+            _to: u64,
+            _call_filter: EthereumCallFilter,
+        ) -> Box<Future<Item = Vec<EthereumCall>, Error = Error> + Send> {
+            unimplemented!()
+        }
+
+        fn block_ptr_by_number(
+            &mut self,
+            _number: u64,
+        ) -> Box<Future<Item = EthereumBlockPointer, Error = Error> + Send> {
+            unimplemented!()
+        }
+
+        fn block_by_number(
+            &mut self,
+            _number: u64,
+        ) -> Box<Future<Item = Block<Transaction>, Error = Error> + Send> {
+            unimplemented!()
         }
     }
 
     fn test_module(
         data_source: DataSource,
-    ) -> (
-        WasmiModule<
-            MockEthereumAdapter,
-            ipfs_api::IpfsClient,
-            FakeStore,
-            Sender<Box<Future<Item = (), Error = ()> + Send>>,
-        >,
-        Receiver<RuntimeHostEvent>,
-    ) {
+    ) -> WasmiModule<
+        MockEthereumAdapter,
+        ipfs_api::IpfsClient,
+        MockStore,
+        Sender<Box<Future<Item = (), Error = ()> + Send>>,
+    > {
+        test_module_with_limits(data_source, MappingLimits::default())
+    }
+
+    fn test_module_with_limits(
+        data_source: DataSource,
+        limits: MappingLimits,
+    ) -> WasmiModule<
+        MockEthereumAdapter,
+        ipfs_api::IpfsClient,
+        MockStore,
+        Sender<Box<Future<Item = (), Error = ()> + Send>>,
+    > {
         let logger = slog::Logger::root(slog::Discard, o!());
-        let (sender, receiver) = channel(100);
         let mock_ethereum_adapter = Arc::new(Mutex::new(MockEthereumAdapter::default()));
         let (task_sender, task_receiver) = channel(100);
         let mut runtime = tokio::runtime::Runtime::new().unwrap();
         runtime.spawn(task_receiver.for_each(tokio::spawn));
         ::std::mem::forget(runtime);
-        (
-            WasmiModule::new(
-                &logger,
-                WasmiModuleConfig {
-                    subgraph: mock_subgraph(),
-                    data_source,
-                    event_sink: sender,
-                    ethereum_adapter: mock_ethereum_adapter,
-                    link_resolver: Arc::new(ipfs_api::IpfsClient::default()),
-                    store: Arc::new(Mutex::new(FakeStore)),
-                },
-                task_sender,
-            ),
-            receiver,
-        )
+        WasmiModule::new(
+            &logger,
+            WasmiModuleConfig {
+                subgraph: mock_subgraph(),
+                data_source,
+                ethereum_adapter: mock_ethereum_adapter,
+                link_resolver: Arc::new(ipfs_api::IpfsClient::default()),
+                store: Arc::new(MockStore::new()),
+                limits,
+            },
+            task_sender,
+        ).expect("Failed to create WASM module")
     }
 
     fn mock_subgraph() -> SubgraphManifest {
@@ -939,11 +1357,17 @@ mod tests {
             schema: Schema {
                 name: String::from("exampled name"),
                 id: String::from("exampled id"),
-                document: Document {
-                    definitions: vec![],
-                },
+                document: graphql_parser::parse_schema(
+                    "
+                    type ExampleEntity {
+                        id: ID!
+                        exampleAttribute: String!
+                    }
+                    ",
+                ).expect("Failed to parse schema"),
             },
             data_sources: vec![],
+            templates: vec![],
         }
     }
 
@@ -952,6 +1376,7 @@ mod tests {
 
         DataSource {
             kind: String::from("ethereum/contract"),
+            network: Some(String::from("mainnet")),
             name: String::from("example data source"),
             source: Source {
                 address: String::from("0123123123"),
@@ -964,36 +1389,185 @@ mod tests {
                 entities: vec![],
                 abis: vec![],
                 event_handlers: vec![],
+                block_handlers: vec![],
+                call_handlers: vec![],
                 runtime,
             },
         }
     }
 
-    #[test]
-    fn call_invalid_event_handler_and_dont_crash() {
-        // This test passing means the module doesn't crash when an invalid
-        // event handler is called or when the event handler execution fails.
-
-        let (mut module, _) = test_module(mock_data_source("wasm_test/example_event_handler.wasm"));
+    fn mock_block_header() -> EthereumBlockHeader {
+        EthereumBlockHeader {
+            hash: util::ethereum::string_to_h256("example block hash"),
+            parent_hash: util::ethereum::string_to_h256("example parent hash"),
+            number: 1,
+            timestamp: U256::from(1_538_000_000),
+            author: Address::from("0b9a6b4eb0b6d4a1a4c2ad2b8e4a7d3ac2b3f4e9"),
+            gas_used: U256::from(21_000),
+            gas_limit: U256::from(8_000_000),
+            difficulty: U256::from(1_000),
+        }
+    }
 
-        // Create a mock Ethereum event
-        let ethereum_event = EthereumEvent {
+    fn mock_ethereum_event() -> EthereumEvent {
+        EthereumEvent {
             address: Address::from("22843e74c59580b3eaf6c233fa67d8b7c561a835"),
+            log_index: U256::from(0),
+            transaction_log_index: U256::from(0),
             event_signature: util::ethereum::string_to_h256("ExampleEvent(string)"),
-            block_hash: util::ethereum::string_to_h256("example block hash"),
+            block: mock_block_header(),
+            transaction: EthereumTransactionData {
+                hash: util::ethereum::string_to_h256("example transaction hash"),
+                index: 0,
+                from: Address::from("0b9a6b4eb0b6d4a1a4c2ad2b8e4a7d3ac2b3f4e9"),
+                to: Some(Address::from("22843e74c59580b3eaf6c233fa67d8b7c561a835")),
+                value: U256::from(0),
+                gas_limit: U256::from(100_000),
+                gas_price: U256::from(1_000_000_000),
+                input: vec![],
+            },
             params: vec![LogParam {
                 name: String::from("exampleParam"),
                 value: Token::String(String::from("some data")),
             }],
             removed: false,
-        };
+        }
+    }
+
+    #[test]
+    fn call_invalid_event_handler_and_dont_crash() {
+        // This test passing means the module doesn't crash when an invalid
+        // event handler is called or when the event handler execution fails,
+        // but reports the failure instead.
+
+        let mut module = test_module(mock_data_source("wasm_test/example_event_handler.wasm"));
+
+        // Create a mock Ethereum event
+        let ethereum_event = mock_ethereum_event();
 
         // Call a non-existent event handler in the test module; if the test hasn't
         // crashed until now, it means it survives Ethereum event handler errors
-        assert_eq!(
-            module.handle_ethereum_event("handleNonExistentExampleEvent", ethereum_event),
-            ()
+        let result = module.handle_ethereum_event(
+            "handleNonExistentExampleEvent",
+            ethereum_event,
+            Arc::new(Mutex::new(EntityCache::new())),
         );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn call_invalid_block_handler_and_dont_crash() {
+        // This test passing means the module doesn't crash when an invalid
+        // block handler is called.
+
+        let mut module = test_module(mock_data_source("wasm_test/example_event_handler.wasm"));
+
+        let result = module.handle_ethereum_block(
+            "handleNonExistentBlock",
+            mock_block_header(),
+            Arc::new(Mutex::new(EntityCache::new())),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn block_handler_exceeding_fuel_limit_fails() {
+        let mut module = test_module_with_limits(
+            mock_data_source("wasm_test/infinite_loop.wasm"),
+            MappingLimits {
+                fuel: 1_000_000,
+                ..MappingLimits::default()
+            },
+        );
+
+        let result = module.handle_ethereum_block(
+            "handleBlock",
+            mock_block_header(),
+            Arc::new(Mutex::new(EntityCache::new())),
+        );
+        let error = result.expect_err("handler should run out of fuel");
+        assert!(error.to_string().contains("fuel limit"), "{}", error);
+    }
+
+    #[test]
+    fn block_handler_exceeding_time_limit_fails() {
+        let mut module = test_module_with_limits(
+            mock_data_source("wasm_test/infinite_loop.wasm"),
+            MappingLimits {
+                fuel: u64::max_value(),
+                timeout: Duration::from_secs(0),
+                ..MappingLimits::default()
+            },
+        );
+
+        let result = module.handle_ethereum_block(
+            "handleBlock",
+            mock_block_header(),
+            Arc::new(Mutex::new(EntityCache::new())),
+        );
+        let error = result.expect_err("handler should run out of time");
+        assert!(error.to_string().contains("time limit"), "{}", error);
+    }
+
+    #[test]
+    fn host_call_exceeding_time_limit_times_out() {
+        let mut module = test_module_with_limits(
+            mock_data_source("wasm_test/ipfs_cat.wasm"),
+            MappingLimits {
+                timeout: Duration::from_millis(10),
+                ..MappingLimits::default()
+            },
+        );
+
+        let trap = module
+            .externals
+            .block_on(future::empty::<(), String>())
+            .expect_err("host call should time out");
+        assert!(host_call_timeout(&Error::Trap(trap)).is_some());
+    }
+
+    #[test]
+    fn allocating_beyond_the_memory_limit_fails() {
+        let module = test_module_with_limits(
+            mock_data_source("wasm_test/string_to_number.wasm"),
+            MappingLimits {
+                max_memory_pages: 1,
+                ..MappingLimits::default()
+            },
+        );
+
+        // 1 MiB does not fit into a single 64 KiB page
+        let bytes = vec![0u8; 1 << 20];
+        let result: Result<AscPtr<Uint8Array>, _> = module.heap.asc_new(&*bytes);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn call_invalid_call_handler_and_dont_crash() {
+        // This test passing means the module doesn't crash when an invalid
+        // call handler is called.
+
+        let mut module = test_module(mock_data_source("wasm_test/example_event_handler.wasm"));
+
+        let call = EthereumCallData {
+            from: Address::from("0b9a6b4eb0b6d4a1a4c2ad2b8e4a7d3ac2b3f4e9"),
+            to: Address::from("22843e74c59580b3eaf6c233fa67d8b7c561a835"),
+            block_hash: util::ethereum::string_to_h256("example block hash"),
+            block_number: 1,
+            transaction_hash: util::ethereum::string_to_h256("example transaction hash"),
+            inputs: vec![LogParam {
+                name: "exampleParam".to_owned(),
+                value: Token::String("some data".to_owned()),
+            }],
+            outputs: vec![],
+        };
+
+        let result = module.handle_ethereum_call(
+            "handleNonExistentCall",
+            call,
+            Arc::new(Mutex::new(EntityCache::new())),
+        );
+        assert!(result.is_err());
     }
 
     #[test]
@@ -1003,39 +1577,27 @@ mod tests {
         // with sample data taken from the event parameters.
         //
         // This test verifies that the event is delivered and the example data
-        // is returned to the RuntimeHostEvent stream.
+        // is written to the entity cache of the block.
 
-        let (mut module, receiver) =
-            test_module(mock_data_source("wasm_test/example_event_handler.wasm"));
+        let mut module = test_module(mock_data_source("wasm_test/example_event_handler.wasm"));
 
         // Create a mock Ethereum event
-        let ethereum_event = EthereumEvent {
-            address: Address::from("22843e74c59580b3eaf6c233fa67d8b7c561a835"),
-            event_signature: util::ethereum::string_to_h256("ExampleEvent(string)"),
-            block_hash: util::ethereum::string_to_h256("example block hash"),
-            params: vec![LogParam {
-                name: String::from("exampleParam"),
-                value: Token::String(String::from("some data")),
-            }],
-            removed: false,
-        };
+        let ethereum_event = mock_ethereum_event();
 
         // Call the event handler in the test module and pass the event to it
-        module.handle_ethereum_event("handleExampleEvent", ethereum_event);
-
-        // Expect a store set call to be made by the handler and a
-        // RuntimeHostEvent::EntitySet event to be written to the event stream
-        let work = receiver.take(1).into_future();
-        let store_event = work
-            .wait()
-            .expect("No store event received from runtime")
-            .0
-            .expect("Store event must not be None");
-
-        // Verify that this event matches what the test module is sending
+        let entity_cache = Arc::new(Mutex::new(EntityCache::new()));
+        let events = module
+            .handle_ethereum_event("handleExampleEvent", ethereum_event, entity_cache.clone())
+            .expect("Failed to handle event");
+
+        // Entity changes are not emitted as events
+        assert!(events.is_empty());
+
+        // Expect a store set call to be made by the handler, resulting in a
+        // single entity operation that matches what the test module is sending
         assert_eq!(
-            store_event,
-            RuntimeHostEvent::EntitySet(
+            entity_cache.lock().unwrap().take_operations(),
+            vec![EntityOperation::Set(
                 StoreKey {
                     subgraph: String::from("example subgraph"),
                     entity: String::from("ExampleEntity"),
@@ -1044,14 +1606,13 @@ mod tests {
                 Entity::from(HashMap::from_iter(
                     vec![(String::from("exampleAttribute"), Value::from("some data"))].into_iter()
                 )),
-                EventSource::EthereumBlock(util::ethereum::string_to_h256("example block hash",)),
-            )
+            )]
         );
     }
 
     #[test]
     fn json_conversions() {
-        let (mut module, _) = test_module(mock_data_source("wasm_test/string_to_number.wasm"));
+        let mut module = test_module(mock_data_source("wasm_test/string_to_number.wasm"));
 
         // test u64 conversion
         let number = 9223372036850770800;
@@ -1059,7 +1620,9 @@ mod tests {
             .module
             .invoke_export(
                 "testToU64",
-                &[RuntimeValue::from(module.heap.asc_new(&number.to_string()))],
+                &[RuntimeValue::from(
+                    module.heap.asc_new(&number.to_string()).unwrap(),
+                )],
                 &mut module.externals,
             ).expect("call failed")
             .expect("call returned nothing")
@@ -1073,7 +1636,9 @@ mod tests {
             .module
             .invoke_export(
                 "testToI64",
-                &[RuntimeValue::from(module.heap.asc_new(&number.to_string()))],
+                &[RuntimeValue::from(
+                    module.heap.asc_new(&number.to_string()).unwrap(),
+                )],
                 &mut module.externals,
             ).expect("call failed")
             .expect("call returned nothing")
@@ -1088,7 +1653,7 @@ mod tests {
             .invoke_export(
                 "testToF64",
                 &[RuntimeValue::from(
-                    module.heap.asc_new(&number.to_float().to_string()),
+                    module.heap.asc_new(&number.to_float().to_string()).unwrap(),
                 )],
                 &mut module.externals,
             ).expect("call failed")
@@ -1103,7 +1668,7 @@ mod tests {
             .module
             .invoke_export(
                 "testToBigInt",
-                &[RuntimeValue::from(module.heap.asc_new(number))],
+                &[RuntimeValue::from(module.heap.asc_new(number).unwrap())],
                 &mut module.externals,
             ).expect("call failed")
             .expect("call returned nothing")
@@ -1118,7 +1683,7 @@ mod tests {
 
     #[test]
     fn ipfs_cat() {
-        let (mut module, _) = test_module(mock_data_source("wasm_test/ipfs_cat.wasm"));
+        let mut module = test_module(mock_data_source("wasm_test/ipfs_cat.wasm"));
         let ipfs = Arc::new(ipfs_api::IpfsClient::default());
 
         let hash = module
@@ -1130,7 +1695,7 @@ mod tests {
             .module
             .invoke_export(
                 "ipfsCat",
-                &[RuntimeValue::from(module.heap.asc_new(&hash))],
+                &[RuntimeValue::from(module.heap.asc_new(&hash).unwrap())],
                 &mut module.externals,
             ).expect("call failed")
             .expect("call returned nothing")
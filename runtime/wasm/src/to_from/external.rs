@@ -1,18 +1,21 @@
 use ethabi;
 use graph::serde_json;
 
-use graph::components::ethereum::EthereumEvent;
+use graph::components::ethereum::{
+    EthereumBlockHeader, EthereumCallData, EthereumEvent, EthereumTransactionData,
+};
 use graph::data::store;
 use graph::web3::types as web3;
 
 use asc_abi::class::*;
 use asc_abi::{AscHeap, AscPtr, FromAscObj, ToAscObj};
 use std::collections::HashMap;
+use wasmi;
 
 use UnresolvedContractCall;
 
 impl ToAscObj<ArrayBuffer<u8>> for web3::H160 {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> ArrayBuffer<u8> {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<ArrayBuffer<u8>, wasmi::Error> {
         self.0.to_asc_obj(heap)
     }
 }
@@ -24,7 +27,7 @@ impl FromAscObj<ArrayBuffer<u8>> for web3::H160 {
 }
 
 impl ToAscObj<Uint8Array> for web3::H160 {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Uint8Array {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<Uint8Array, wasmi::Error> {
         self.0.to_asc_obj(heap)
     }
 }
@@ -42,19 +45,19 @@ impl FromAscObj<Uint8Array> for web3::H256 {
 }
 
 impl ToAscObj<ArrayBuffer<u8>> for web3::H256 {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> ArrayBuffer<u8> {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<ArrayBuffer<u8>, wasmi::Error> {
         self.0.to_asc_obj(heap)
     }
 }
 
 impl ToAscObj<Uint8Array> for web3::H256 {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Uint8Array {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<Uint8Array, wasmi::Error> {
         self.0.to_asc_obj(heap)
     }
 }
 
 impl ToAscObj<ArrayBuffer<u64>> for web3::U256 {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> ArrayBuffer<u64> {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<ArrayBuffer<u64>, wasmi::Error> {
         self.0.to_asc_obj(heap)
     }
 }
@@ -66,7 +69,7 @@ impl FromAscObj<ArrayBuffer<u64>> for web3::U256 {
 }
 
 impl ToAscObj<Uint64Array> for web3::U256 {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Uint64Array {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<Uint64Array, wasmi::Error> {
         self.0.to_asc_obj(heap)
     }
 }
@@ -78,25 +81,25 @@ impl FromAscObj<Uint64Array> for web3::U256 {
 }
 
 impl ToAscObj<AscEnum<EthereumValueKind>> for ethabi::Token {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> AscEnum<EthereumValueKind> {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<AscEnum<EthereumValueKind>, wasmi::Error> {
         use ethabi::Token::*;
 
         let kind = EthereumValueKind::get_kind(self);
         let payload = match self {
-            Address(address) => heap.asc_new::<AscAddress, _>(address).to_payload(),
+            Address(address) => heap.asc_new::<AscAddress, _>(address)?.to_payload(),
             FixedBytes(bytes) | Bytes(bytes) => {
-                heap.asc_new::<Uint8Array, _>(&**bytes).to_payload()
+                heap.asc_new::<Uint8Array, _>(&**bytes)?.to_payload()
             }
-            Int(uint) | Uint(uint) => heap.asc_new::<AscU256, _>(uint).to_payload(),
+            Int(uint) | Uint(uint) => heap.asc_new::<AscU256, _>(uint)?.to_payload(),
             Bool(b) => *b as u64,
-            String(string) => heap.asc_new(&**string).to_payload(),
-            FixedArray(tokens) | Array(tokens) => heap.asc_new(&**tokens).to_payload(),
+            String(string) => heap.asc_new(&**string)?.to_payload(),
+            FixedArray(tokens) | Array(tokens) => heap.asc_new(&**tokens)?.to_payload(),
         };
 
-        AscEnum {
+        Ok(AscEnum {
             kind,
             payload: EnumPayload(payload),
-        }
+        })
     }
 }
 
@@ -177,94 +180,144 @@ impl FromAscObj<AscEnum<StoreValueKind>> for store::Value {
 }
 
 impl ToAscObj<AscEnum<StoreValueKind>> for store::Value {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> AscEnum<StoreValueKind> {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<AscEnum<StoreValueKind>, wasmi::Error> {
         use self::store::Value;
 
         let payload = match self {
-            Value::String(string) => heap.asc_new(string.as_str()).into(),
+            Value::String(string) => heap.asc_new(string.as_str())?.into(),
             Value::Int(n) => EnumPayload::from(*n),
             Value::Float(n) => EnumPayload::from(*n),
             Value::Bool(b) => EnumPayload::from(*b),
-            Value::List(array) => heap.asc_new(array.as_slice()).into(),
+            Value::List(array) => heap.asc_new(array.as_slice())?.into(),
             Value::Null => EnumPayload(0),
             Value::Bytes(bytes) => {
-                let bytes_obj: AscPtr<Uint8Array> = heap.asc_new(bytes.as_slice());
+                let bytes_obj: AscPtr<Uint8Array> = heap.asc_new(bytes.as_slice())?;
                 bytes_obj.into()
             }
             Value::BigInt(big_int) => {
-                let bytes_obj: AscPtr<Uint8Array> = heap.asc_new(&*big_int.to_signed_bytes_le());
+                let bytes_obj: AscPtr<Uint8Array> = heap.asc_new(&*big_int.to_signed_bytes_le())?;
                 bytes_obj.into()
             }
         };
 
-        AscEnum {
+        Ok(AscEnum {
             kind: StoreValueKind::get_kind(self),
             payload,
-        }
+        })
     }
 }
 
 impl ToAscObj<AscLogParam> for ethabi::LogParam {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> AscLogParam {
-        AscLogParam {
-            name: heap.asc_new(self.name.as_str()),
-            value: heap.asc_new(&self.value),
-        }
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<AscLogParam, wasmi::Error> {
+        Ok(AscLogParam {
+            name: heap.asc_new(self.name.as_str())?,
+            value: heap.asc_new(&self.value)?,
+        })
     }
 }
 
 impl ToAscObj<AscJson> for serde_json::Map<String, serde_json::Value> {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> AscJson {
-        AscTypedMap {
-            entries: heap.asc_new(&*self.iter().collect::<Vec<_>>()),
-        }
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<AscJson, wasmi::Error> {
+        Ok(AscTypedMap {
+            entries: heap.asc_new(&*self.iter().collect::<Vec<_>>())?,
+        })
     }
 }
 
 impl ToAscObj<AscEntity> for HashMap<String, store::Value> {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> AscEntity {
-        AscTypedMap {
-            entries: heap.asc_new(&*self.iter().collect::<Vec<_>>()),
-        }
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<AscEntity, wasmi::Error> {
+        Ok(AscTypedMap {
+            entries: heap.asc_new(&*self.iter().collect::<Vec<_>>())?,
+        })
     }
 }
 
 impl ToAscObj<AscEntity> for store::Entity {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> AscEntity {
-        AscTypedMap {
-            entries: heap.asc_new(&*self.iter().collect::<Vec<_>>()),
-        }
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<AscEntity, wasmi::Error> {
+        Ok(AscTypedMap {
+            entries: heap.asc_new(&*self.iter().collect::<Vec<_>>())?,
+        })
     }
 }
 
 impl ToAscObj<AscEnum<JsonValueKind>> for serde_json::Value {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> AscEnum<JsonValueKind> {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<AscEnum<JsonValueKind>, wasmi::Error> {
         use graph::serde_json::Value;
 
         let payload = match self {
             Value::Null => EnumPayload(0),
             Value::Bool(b) => EnumPayload::from(*b),
-            Value::Number(number) => heap.asc_new(&*number.to_string()).into(),
-            Value::String(string) => heap.asc_new(string.as_str()).into(),
-            Value::Array(array) => heap.asc_new(array.as_slice()).into(),
-            Value::Object(object) => heap.asc_new(object).into(),
+            Value::Number(number) => heap.asc_new(&*number.to_string())?.into(),
+            Value::String(string) => heap.asc_new(string.as_str())?.into(),
+            Value::Array(array) => heap.asc_new(array.as_slice())?.into(),
+            Value::Object(object) => heap.asc_new(object)?.into(),
         };
 
-        AscEnum {
+        Ok(AscEnum {
             kind: JsonValueKind::get_kind(self),
             payload,
-        }
+        })
     }
 }
 
 impl ToAscObj<AscEthereumEvent> for EthereumEvent {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> AscEthereumEvent {
-        AscEthereumEvent {
-            address: heap.asc_new(&self.address),
-            event_signature: heap.asc_new(&self.event_signature),
-            block_hash: heap.asc_new(&self.block_hash),
-            params: heap.asc_new(self.params.as_slice()),
-        }
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<AscEthereumEvent, wasmi::Error> {
+        Ok(AscEthereumEvent {
+            address: heap.asc_new(&self.address)?,
+            event_signature: heap.asc_new(&self.event_signature)?,
+            block_hash: heap.asc_new(&self.block.hash)?,
+            params: heap.asc_new(self.params.as_slice())?,
+            log_index: heap.asc_new(&self.log_index)?,
+            transaction_log_index: heap.asc_new(&self.transaction_log_index)?,
+            block: heap.asc_new(&self.block)?,
+            transaction: heap.asc_new(&self.transaction)?,
+        })
+    }
+}
+
+impl ToAscObj<AscEthereumTransaction> for EthereumTransactionData {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<AscEthereumTransaction, wasmi::Error> {
+        Ok(AscEthereumTransaction {
+            hash: heap.asc_new(&self.hash)?,
+            index: heap.asc_new(&web3::U256::from(self.index))?,
+            from: heap.asc_new(&self.from)?,
+            to: match self.to {
+                Some(to) => heap.asc_new(&to)?,
+                None => AscPtr::default(),
+            },
+            value: heap.asc_new(&self.value)?,
+            gas_limit: heap.asc_new(&self.gas_limit)?,
+            gas_price: heap.asc_new(&self.gas_price)?,
+            input: heap.asc_new(self.input.as_slice())?,
+        })
+    }
+}
+
+impl ToAscObj<AscEthereumCall> for EthereumCallData {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<AscEthereumCall, wasmi::Error> {
+        Ok(AscEthereumCall {
+            from: heap.asc_new(&self.from)?,
+            to: heap.asc_new(&self.to)?,
+            block_hash: heap.asc_new(&self.block_hash)?,
+            transaction_hash: heap.asc_new(&self.transaction_hash)?,
+            inputs: heap.asc_new(self.inputs.as_slice())?,
+            outputs: heap.asc_new(self.outputs.as_slice())?,
+        })
+    }
+}
+
+impl ToAscObj<AscEthereumBlock> for EthereumBlockHeader {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<AscEthereumBlock, wasmi::Error> {
+        Ok(AscEthereumBlock {
+            hash: heap.asc_new(&self.hash)?,
+            parent_hash: heap.asc_new(&self.parent_hash)?,
+            number: heap.asc_new(&web3::U256::from(self.number))?,
+            timestamp: heap.asc_new(&self.timestamp)?,
+            author: heap.asc_new(&self.author)?,
+            gas_used: heap.asc_new(&self.gas_used)?,
+            gas_limit: heap.asc_new(&self.gas_limit)?,
+            difficulty: heap.asc_new(&self.difficulty)?,
+        })
     }
 }
 
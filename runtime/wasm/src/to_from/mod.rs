@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::hash::Hash;
 use std::iter::FromIterator;
+use wasmi;
 
 use asc_abi::class::*;
 use asc_abi::{AscHeap, AscPtr, AscType, AscValue, FromAscObj, ToAscObj};
@@ -10,8 +11,8 @@ use asc_abi::{AscHeap, AscPtr, AscType, AscValue, FromAscObj, ToAscObj};
 mod external;
 
 impl<T: AscValue> ToAscObj<ArrayBuffer<T>> for [T] {
-    fn to_asc_obj<H: AscHeap>(&self, _: &H) -> ArrayBuffer<T> {
-        ArrayBuffer::new(self)
+    fn to_asc_obj<H: AscHeap>(&self, _: &H) -> Result<ArrayBuffer<T>, wasmi::Error> {
+        Ok(ArrayBuffer::new(self))
     }
 }
 
@@ -46,7 +47,7 @@ impl<T: AscValue> FromAscObj<ArrayBuffer<T>> for [T; 4] {
 }
 
 impl<T: AscValue> ToAscObj<TypedArray<T>> for [T] {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> TypedArray<T> {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<TypedArray<T>, wasmi::Error> {
         TypedArray::new(self, heap)
     }
 }
@@ -82,13 +83,13 @@ impl<T: AscValue> FromAscObj<TypedArray<T>> for [T; 4] {
 }
 
 impl ToAscObj<AscString> for str {
-    fn to_asc_obj<H: AscHeap>(&self, _: &H) -> AscString {
-        AscString::new(&self.encode_utf16().collect::<Vec<_>>())
+    fn to_asc_obj<H: AscHeap>(&self, _: &H) -> Result<AscString, wasmi::Error> {
+        Ok(AscString::new(&self.encode_utf16().collect::<Vec<_>>()))
     }
 }
 
 impl ToAscObj<AscString> for String {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> AscString {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<AscString, wasmi::Error> {
         self.as_str().to_asc_obj(heap)
     }
 }
@@ -100,8 +101,11 @@ impl FromAscObj<AscString> for String {
 }
 
 impl<C: AscType, T: ToAscObj<C>> ToAscObj<Array<AscPtr<C>>> for [T] {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Array<AscPtr<C>> {
-        let content: Vec<_> = self.iter().map(|x| heap.asc_new(x)).collect();
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<Array<AscPtr<C>>, wasmi::Error> {
+        let content = self
+            .iter()
+            .map(|x| heap.asc_new(x))
+            .collect::<Result<Vec<_>, _>>()?;
         Array::new(&*content, heap)
     }
 }
@@ -128,11 +132,11 @@ impl<K: AscType, V: AscType, T: FromAscObj<K>, U: FromAscObj<V>> FromAscObj<AscT
 impl<'a, 'b, K: AscType, V: AscType, T: ToAscObj<K>, U: ToAscObj<V>>
     ToAscObj<AscTypedMapEntry<K, V>> for (&'a T, &'b U)
 {
-    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> AscTypedMapEntry<K, V> {
-        AscTypedMapEntry {
-            key: heap.asc_new(self.0),
-            value: heap.asc_new(self.1),
-        }
+    fn to_asc_obj<H: AscHeap>(&self, heap: &H) -> Result<AscTypedMapEntry<K, V>, wasmi::Error> {
+        Ok(AscTypedMapEntry {
+            key: heap.asc_new(self.0)?,
+            value: heap.asc_new(self.1)?,
+        })
     }
 }
 
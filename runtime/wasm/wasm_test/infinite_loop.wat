@@ -0,0 +1,7 @@
+(module
+  (memory (export "memory") 1)
+  (func (export "handleBlock") (param i32)
+    (loop
+      (br 0)))
+  (func (export "allocate_memory") (param i32) (result i32)
+    (i32.const 8)))
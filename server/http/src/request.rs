@@ -80,6 +80,7 @@ impl Future for GraphQLRequest {
 #[cfg(test)]
 mod tests {
     use graphql_parser;
+    use graphql_parser::query as q;
     use hyper;
 
     use graph::prelude::*;
@@ -224,4 +225,40 @@ mod tests {
         assert_eq!(query.document, expected_query);
         assert_eq!(query.variables, Some(expected_variables));
     }
+
+    #[test]
+    fn parses_non_string_variables() {
+        let schema = Schema {
+            name: "test".to_string(),
+            id: "test".to_string(),
+            document: graphql_parser::parse_schema(EXAMPLE_SCHEMA).unwrap(),
+        };
+        let request = GraphQLRequest::new(
+            hyper::Chunk::from(
+                "\
+                 {\
+                 \"query\": \"{ user { name } }\", \
+                 \"variables\": { \"flag\": true, \"count\": 5, \"ids\": [\"a\"] } \
+                 }",
+            ),
+            schema,
+        );
+        let query = request.wait().expect("Should accept valid queries");
+
+        let mut expected_variables = QueryVariables::new();
+        expected_variables.insert(
+            "flag".to_string(),
+            QueryVariableValue::from(q::Value::Boolean(true)),
+        );
+        expected_variables.insert(
+            "count".to_string(),
+            QueryVariableValue::from(q::Value::Int(q::Number::from(5))),
+        );
+        expected_variables.insert(
+            "ids".to_string(),
+            QueryVariableValue::from(q::Value::List(vec![q::Value::String("a".to_string())])),
+        );
+
+        assert_eq!(query.variables, Some(expected_variables));
+    }
 }
@@ -7,6 +7,7 @@ use std::sync::RwLock;
 
 use graph::components::server::query::GraphQLServerError;
 use graph::prelude::*;
+use graph_graphql::prelude::indexing_status_schema;
 
 use request::GraphQLRequest;
 use response::GraphQLResponse;
@@ -105,6 +106,25 @@ where
         )
     }
 
+    /// Handles indexing status queries received via POST /_indexing-status/graphql.
+    fn handle_indexing_status_query(&self, request: Request<Body>) -> GraphQLServiceResponse {
+        let graphql_runner = self.graphql_runner.clone();
+
+        Box::new(
+            request
+                .into_body()
+                .concat2()
+                .map_err(|_| GraphQLServerError::from("Failed to read request body"))
+                .and_then(|body| GraphQLRequest::new(body, indexing_status_schema()))
+                .and_then(move |query| {
+                    graphql_runner
+                        .run_indexing_status_query(query)
+                        .map_err(|e| GraphQLServerError::from(e))
+                })
+                .then(|result| GraphQLResponse::new(result)),
+        )
+    }
+
     // Handles OPTIONS requests
     fn handle_graphql_options(&self, _request: Request<Body>) -> GraphQLServiceResponse {
         Box::new(future::ok(
@@ -163,6 +183,11 @@ where
                         self.serve_file(include_str!("../assets/index.html"))
                     }
 
+                    // POST /_indexing-status/graphql receives indexing status queries
+                    (Method::POST, Some("_indexing-status"), "graphql") => {
+                        self.handle_indexing_status_query(req)
+                    }
+
                     // POST / receives GraphQL queries
                     (Method::POST, Some(name_or_id), "graphql") => {
                         self.handle_graphql_query(&name_or_id, req)
@@ -211,6 +236,17 @@ mod tests {
             )))))
         }
 
+        fn run_indexing_status_query(&self, _query: Query) -> QueryResultFuture {
+            Box::new(future::ok(QueryResult::new(Some(q::Value::Object(
+                BTreeMap::from_iter(
+                    vec![(
+                        String::from("subgraphIndexingStatus"),
+                        q::Value::List(vec![]),
+                    )].into_iter(),
+                ),
+            )))))
+        }
+
         fn run_subscription(&self, _subscription: Subscription) -> SubscriptionResultFuture {
             unimplemented!();
         }
@@ -307,4 +343,41 @@ mod tests {
                 res
             })).unwrap()
     }
+
+    #[test]
+    fn posting_indexing_status_queries_yields_result_response() {
+        let graphql_runner = Arc::new(TestGraphQlRunner);
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime
+            .block_on(future::lazy(|| {
+                let res: Result<_, ()> = Ok({
+                    let mut service =
+                        GraphQLService::new(Default::default(), Default::default(), graphql_runner);
+
+                    let request = Request::builder()
+                        .method(Method::POST)
+                        .uri("http://localhost:8000/_indexing-status/graphql")
+                        .body(Body::from(
+                            "{\"query\": \"{ subgraphIndexingStatus { name } }\"}",
+                        ))
+                        .unwrap();
+
+                    // The response must be a 200, even though no subgraphs are deployed
+                    let response = service
+                        .call(request)
+                        .wait()
+                        .expect("Should return a response");
+                    let data = test_utils::assert_successful_response(response);
+
+                    // The body should match the simulated query result
+                    let statuses = data
+                        .get("subgraphIndexingStatus")
+                        .expect("Query result data has no \"subgraphIndexingStatus\" field")
+                        .as_array()
+                        .expect("Query result field \"subgraphIndexingStatus\" is not a list");
+                    assert!(statuses.is_empty());
+                });
+                res
+            })).unwrap()
+    }
 }
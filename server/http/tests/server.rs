@@ -31,6 +31,10 @@ impl GraphQlRunner for TestGraphQlRunner {
         )))))
     }
 
+    fn run_indexing_status_query(&self, _query: Query) -> QueryResultFuture {
+        unimplemented!();
+    }
+
     fn run_subscription(&self, _subscription: Subscription) -> SubscriptionResultFuture {
         unimplemented!();
     }
@@ -24,6 +24,7 @@ const GRAPH_MASTER_TOKEN_VAR: &str = "GRAPH_MASTER_TOKEN";
 const JSON_RPC_DEPLOY_ERROR: i64 = 0;
 const JSON_RPC_REMOVE_ERROR: i64 = 1;
 const JSON_RPC_UNAUTHORIZED_ERROR: i64 = 2;
+const JSON_RPC_LIST_ERROR: i64 = 3;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct SubgraphDeployParams {
@@ -174,6 +175,7 @@ impl<T: SubgraphProvider> JsonRpcServer<T> {
         let list = self
             .provider
             .list()
+            .map_err(|e| json_rpc_error(JSON_RPC_LIST_ERROR, e.to_string()))?
             .into_iter()
             .map(|(name, id)| (name, Value::from(id)));
         Ok(Value::from(serde_json::Map::from_iter(list)))
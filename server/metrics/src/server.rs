@@ -0,0 +1,105 @@
+use hyper;
+use hyper::header::CONTENT_TYPE;
+use hyper::service::service_fn_ok;
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use std::error::Error;
+use std::fmt;
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+use graph::prelude::{MetricsServer as MetricsServerTrait, *};
+use graph::prometheus::{self, Encoder, TextEncoder};
+
+/// Errors that may occur when starting the server.
+#[derive(Debug)]
+pub enum PrometheusMetricsServeError {
+    BindError(hyper::Error),
+}
+
+impl Error for PrometheusMetricsServeError {
+    fn description(&self) -> &str {
+        "Failed to start the server"
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        None
+    }
+}
+
+impl fmt::Display for PrometheusMetricsServeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PrometheusMetricsServeError::BindError(e) => {
+                write!(f, "Failed to bind metrics server: {}", e)
+            }
+        }
+    }
+}
+
+impl From<hyper::Error> for PrometheusMetricsServeError {
+    fn from(err: hyper::Error) -> Self {
+        PrometheusMetricsServeError::BindError(err)
+    }
+}
+
+/// A server that exposes the metrics of the node to Prometheus via GET /metrics.
+pub struct PrometheusMetricsServer {
+    logger: Logger,
+}
+
+impl PrometheusMetricsServer {
+    /// Creates a new metrics server.
+    pub fn new(logger: &Logger) -> Self {
+        PrometheusMetricsServer {
+            logger: logger.new(o!("component" => "MetricsServer")),
+        }
+    }
+}
+
+/// Encodes all metrics gathered so far in the Prometheus text format.
+fn metrics_response() -> Response<Body> {
+    let encoder = TextEncoder::new();
+    let mut buffer = vec![];
+    match encoder.encode(&prometheus::gather(), &mut buffer) {
+        Ok(()) => Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, encoder.format_type())
+            .body(Body::from(buffer))
+            .unwrap(),
+        Err(e) => Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Body::from(format!("Failed to encode metrics: {}", e)))
+            .unwrap(),
+    }
+}
+
+impl MetricsServerTrait for PrometheusMetricsServer {
+    type ServeError = PrometheusMetricsServeError;
+
+    fn serve(
+        &mut self,
+        port: u16,
+    ) -> Result<Box<Future<Item = (), Error = ()> + Send>, Self::ServeError> {
+        let logger = self.logger.clone();
+
+        let addr = SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), port);
+
+        let new_service = || {
+            service_fn_ok(
+                |request: Request<Body>| match (request.method(), request.uri().path()) {
+                    (&Method::GET, "/metrics") => metrics_response(),
+                    _ => Response::builder()
+                        .status(StatusCode::NOT_FOUND)
+                        .body(Body::from("Not found"))
+                        .unwrap(),
+                },
+            )
+        };
+
+        // Create a task to run the server and handle HTTP requests
+        let task = Server::try_bind(&addr.into())?
+            .serve(new_service)
+            .map_err(move |e| error!(logger, "Server error"; "error" => format!("{}", e)));
+
+        Ok(Box::new(task))
+    }
+}
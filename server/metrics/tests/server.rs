@@ -0,0 +1,46 @@
+extern crate futures;
+extern crate graph;
+extern crate graph_server_metrics;
+extern crate hyper;
+
+use hyper::{Body, Client, Request, StatusCode};
+
+use graph::prelude::*;
+use graph::util::metrics::GRAPHQL_SUBSCRIPTIONS_ACTIVE;
+
+use graph_server_metrics::PrometheusMetricsServer;
+
+#[test]
+fn serves_metrics_in_prometheus_text_format() {
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime
+        .block_on(futures::lazy(|| {
+            let logger = Logger::root(slog::Discard, o!());
+
+            let mut server = PrometheusMetricsServer::new(&logger);
+            let http_server = server.serve(8041).expect("Failed to start metrics server");
+
+            // Launch the server to handle a single request
+            tokio::spawn(http_server.fuse());
+
+            // Metrics only show up once they have been used
+            GRAPHQL_SUBSCRIPTIONS_ACTIVE.set(0.0);
+
+            let client = Client::new();
+            let request = Request::get("http://localhost:8041/metrics")
+                .body(Body::empty())
+                .unwrap();
+
+            client
+                .request(request)
+                .and_then(|response| {
+                    assert_eq!(response.status(), StatusCode::OK);
+                    response.into_body().concat2()
+                })
+                .map(|body| {
+                    let body = String::from_utf8(body.to_vec()).unwrap();
+                    assert!(body.contains("graphql_subscriptions_active 0"));
+                })
+        }))
+        .unwrap()
+}
@@ -10,6 +10,7 @@ use uuid::Uuid;
 
 use graph::prelude::*;
 use graph::serde_json;
+use graph::util::metrics::GRAPHQL_SUBSCRIPTIONS_ACTIVE;
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -230,7 +231,9 @@ where
                            "connection" => &connection_id,
                            "id" => &id);
 
-                    // Execute the GraphQL subscription
+                    // Execute the GraphQL subscription; it counts as active
+                    // until it is stopped or its results end
+                    GRAPHQL_SUBSCRIPTIONS_ACTIVE.inc();
                     let graphql_runner = graphql_runner.clone();
                     let error_sink = msg_sink.clone();
                     let result_sink = msg_sink.clone();
@@ -272,7 +275,10 @@ where
                                             }).map(WsMessage::from)
                                             .forward(result_sink.sink_map_err(|_| ()))
                                     }).and_then(|_| Ok(())),
-                            ).then(|_| Ok(())),
+                            ).then(|_| {
+                                GRAPHQL_SUBSCRIPTIONS_ACTIVE.dec();
+                                Ok(())
+                            }),
                     );
 
                     Ok(())
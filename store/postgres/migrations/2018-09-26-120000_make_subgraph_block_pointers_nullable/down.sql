@@ -0,0 +1,7 @@
+/**************************************************************
+* REQUIRE SUBGRAPH BLOCK POINTERS
+**************************************************************/
+ALTER TABLE subgraphs
+    DROP CONSTRAINT subgraphs_latest_block_check,
+    ALTER COLUMN latest_block_hash SET NOT NULL,
+    ALTER COLUMN latest_block_number SET NOT NULL;
@@ -0,0 +1,9 @@
+/**************************************************************
+* ALLOW SUBGRAPHS WITHOUT A BLOCK POINTER
+**************************************************************/
+-- Subgraphs are added before any of their events have been processed
+ALTER TABLE subgraphs
+    ALTER COLUMN latest_block_hash DROP NOT NULL,
+    ALTER COLUMN latest_block_number DROP NOT NULL,
+    ADD CONSTRAINT subgraphs_latest_block_check
+        CHECK ((latest_block_hash IS NULL) = (latest_block_number IS NULL));
@@ -0,0 +1,11 @@
+/**************************************************************
+* CREATE TABLE
+**************************************************************/
+-- Stores the subgraphs deployed under each name, so that they can be
+-- redeployed when the node restarts
+CREATE TABLE IF NOT EXISTS subgraph_deployments (
+    name VARCHAR PRIMARY KEY,
+    subgraph_id VARCHAR NOT NULL,
+    deployed_at TIMESTAMP NOT NULL DEFAULT now(),
+    status VARCHAR NOT NULL
+);
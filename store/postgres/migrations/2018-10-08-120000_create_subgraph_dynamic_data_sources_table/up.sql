@@ -0,0 +1,14 @@
+/**************************************************************
+* CREATE TABLE
+**************************************************************/
+-- Stores the data sources that the mappings of subgraphs created from
+-- templates, so that they can be restored when the node restarts
+CREATE TABLE IF NOT EXISTS subgraph_dynamic_data_sources (
+    id SERIAL PRIMARY KEY,
+    subgraph_id VARCHAR NOT NULL,
+    template VARCHAR NOT NULL,
+    address VARCHAR NOT NULL,
+    block_hash VARCHAR NOT NULL,
+    block_number BIGINT NOT NULL,
+    UNIQUE (subgraph_id, template, address)
+);
@@ -0,0 +1,9 @@
+/**************************************************************
+* REMOVE subgraphs COLUMNS
+**************************************************************/
+ALTER TABLE subgraphs
+    DROP CONSTRAINT subgraphs_health_error_block_check,
+    DROP COLUMN health,
+    DROP COLUMN health_error,
+    DROP COLUMN health_error_block_hash,
+    DROP COLUMN health_error_block_number;
@@ -0,0 +1,12 @@
+/**************************************************************
+* ADD subgraphs COLUMNS
+**************************************************************/
+-- Tracks whether indexing a subgraph is syncing, synced or has failed,
+-- and with which error at which block if it has failed
+ALTER TABLE subgraphs
+    ADD COLUMN health VARCHAR NOT NULL DEFAULT 'syncing',
+    ADD COLUMN health_error VARCHAR,
+    ADD COLUMN health_error_block_hash VARCHAR,
+    ADD COLUMN health_error_block_number BIGINT,
+    ADD CONSTRAINT subgraphs_health_error_block_check
+        CHECK ((health_error_block_hash IS NULL) = (health_error_block_number IS NULL));
@@ -0,0 +1,85 @@
+/**************************************************************
+* RESTORE REVERT ROW EVENT
+**************************************************************/
+CREATE OR REPLACE FUNCTION revert_entity_event(entity_history_id INTEGER, operation_id INTEGER)
+    RETURNS VOID AS
+$$
+DECLARE
+    target_entity_id VARCHAR;
+    target_subgraph VARCHAR;
+    target_entity VARCHAR;
+    target_data_before JSONB;
+    reversion_identifier VARCHAR;
+BEGIN
+    -- Get entity history event information and save into the declared variables
+    SELECT
+        entity_id,
+        subgraph,
+        entity,
+        data_before
+    INTO
+        target_entity_id,
+        target_subgraph,
+        target_entity,
+        target_data_before
+    FROM entity_history
+    WHERE entity_history.id = entity_history_id;
+
+    reversion_identifier := 'REVERSION';
+
+    CASE
+        -- INSERT case
+        WHEN operation_id = 0 THEN
+            -- Delete inserted row
+            BEGIN
+                PERFORM set_config('vars.current_event_source', 'REVERSION', FALSE);
+                EXECUTE
+                    'DELETE FROM entities WHERE (
+                        subgraph = $1 AND
+                        entity = $2 AND
+                        id = $3)'
+                USING target_subgraph, target_entity, target_entity_id;
+
+                -- Row was already updated
+                EXCEPTION
+                    WHEN no_data_found THEN
+                        NULL;
+            END;
+
+        -- UPDATE or DELETE case
+        WHEN operation_id IN (1,2) THEN
+            -- Insert deleted row if not exists
+            -- If row exists perform update
+            BEGIN
+                EXECUTE
+                    'INSERT INTO entities (id, subgraph, entity, data, event_source)
+                        VALUES ($1, $2, $3, $4, $5)
+                        ON CONFLICT (id, subgraph, entity) DO UPDATE
+                        SET data = $4, event_source = $5'
+                USING
+                    target_entity_id,
+                    target_subgraph,
+                    target_entity,
+                    target_data_before,
+                    reversion_identifier;
+            END;
+    END CASE;
+END;
+$$ LANGUAGE plpgsql;
+
+/**************************************************************
+* DROP ENTITY TABLES, FUNCTIONS AND LAYOUTS
+**************************************************************/
+DO $$
+DECLARE
+    layout_id INTEGER;
+BEGIN
+    FOR layout_id IN SELECT id FROM subgraph_layouts LOOP
+        EXECUTE format('DROP SCHEMA IF EXISTS %I CASCADE', 'sgd' || layout_id);
+    END LOOP;
+END
+$$;
+
+DROP FUNCTION IF EXISTS notify_relational_change();
+DROP FUNCTION IF EXISTS log_relational_change();
+DROP TABLE IF EXISTS subgraph_layouts;
@@ -0,0 +1,215 @@
+/**************************************************************
+* CREATE TABLES
+**************************************************************/
+-- Tracks the subgraphs whose entities are stored in a table per
+-- entity type rather than in the entities table. The tables of a
+-- subgraph live in the Postgres schema "sgd<id>" and are derived
+-- from its GraphQL schema
+CREATE TABLE IF NOT EXISTS subgraph_layouts (
+    id SERIAL PRIMARY KEY,
+    subgraph_id VARCHAR UNIQUE NOT NULL,
+    graphql_schema TEXT NOT NULL
+);
+
+/**************************************************************
+* LOG RELATIONAL CHANGE
+*
+* Writes row level metadata and before & after state of an entity
+* in an entity table to entity_history
+* Called when the entity_history trigger of an entity table is fired
+* Parameters: subgraph ID (trigger argument)
+**************************************************************/
+CREATE OR REPLACE FUNCTION log_relational_change()
+    RETURNS trigger AS
+$$
+DECLARE
+    event_id INTEGER;
+    new_event_id INTEGER;
+    current_event_source VARCHAR;
+    target_entity_id VARCHAR;
+    target_data_before JSONB;
+    target_data_after JSONB;
+    operation_id SMALLINT;
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        operation_id := 0;
+        current_event_source := NEW."__event_source";
+        target_entity_id := NEW.id;
+        target_data_before := NULL;
+        target_data_after := to_jsonb(NEW);
+    ELSIF TG_OP = 'UPDATE' THEN
+        operation_id := 1;
+        current_event_source := NEW."__event_source";
+        target_entity_id := NEW.id;
+        target_data_before := to_jsonb(OLD);
+        target_data_after := to_jsonb(NEW);
+    ELSE
+        -- Use session level setting to get the event_source for the current transaction
+        operation_id := 2;
+        current_event_source := current_setting('vars.current_event_source', TRUE);
+        target_entity_id := OLD.id;
+        target_data_before := to_jsonb(OLD);
+        target_data_after := NULL;
+    END IF;
+
+    SELECT id INTO event_id
+    FROM event_meta_data
+    WHERE db_transaction_id = txid_current();
+
+    new_event_id := null;
+
+    IF event_id IS NULL THEN
+        -- Log information on the postgres transaction for later use in revert operations
+        INSERT INTO event_meta_data
+            (db_transaction_id, db_transaction_time, op_id, source)
+        VALUES
+            (txid_current(), statement_timestamp(), operation_id, current_event_source)
+        RETURNING event_meta_data.id INTO new_event_id;
+    END IF;
+
+    -- Log row metadata and changes, specify whether event was an original ethereum event or a reversion
+    INSERT INTO entity_history
+        (event_id, entity_id, subgraph, entity, data_before, data_after, reversion)
+    VALUES
+        (COALESCE(new_event_id, event_id), target_entity_id, TG_ARGV[0], TG_TABLE_NAME,
+         target_data_before, target_data_after, current_event_source = 'REVERSION');
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+/**************************************************************
+* NOTIFY RELATIONAL CHANGE
+*
+* Emits an entity added, updated or removed notification for an
+* entity in an entity table over the "entity_changes" notification
+* channel.
+* Parameters: subgraph ID (trigger argument)
+**************************************************************/
+CREATE OR REPLACE FUNCTION notify_relational_change()
+    RETURNS trigger AS
+$$
+DECLARE
+    target_entity_id VARCHAR;
+    operation VARCHAR;
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        target_entity_id := NEW.id;
+        operation := 'added';
+    ELSIF TG_OP = 'UPDATE' THEN
+        target_entity_id := NEW.id;
+        operation := 'updated';
+    ELSE
+        target_entity_id := OLD.id;
+        operation := 'removed';
+    END IF;
+
+    PERFORM pg_notify('entity_changes', json_build_object(
+        'subgraph', TG_ARGV[0],
+        'entity', TG_TABLE_NAME,
+        'id', target_entity_id,
+        'operation', operation
+    )::text);
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+/**************************************************************
+* REVERT ROW EVENT
+*
+* Revert a specific row level event
+* Entities of subgraphs with a layout in subgraph_layouts are
+* reverted in their entity table
+* Parameters: entity_history.id (primary key)
+*             operation_id
+**************************************************************/
+CREATE OR REPLACE FUNCTION revert_entity_event(entity_history_id INTEGER, operation_id INTEGER)
+    RETURNS VOID AS
+$$
+DECLARE
+    target_entity_id VARCHAR;
+    target_subgraph VARCHAR;
+    target_entity VARCHAR;
+    target_data_before JSONB;
+    target_layout_id INTEGER;
+    target_schema VARCHAR;
+    reversion_identifier VARCHAR;
+BEGIN
+    -- Get entity history event information and save into the declared variables
+    SELECT
+        entity_id,
+        subgraph,
+        entity,
+        data_before
+    INTO
+        target_entity_id,
+        target_subgraph,
+        target_entity,
+        target_data_before
+    FROM entity_history
+    WHERE entity_history.id = entity_history_id;
+
+    reversion_identifier := 'REVERSION';
+
+    SELECT id INTO target_layout_id
+    FROM subgraph_layouts
+    WHERE subgraph_id = target_subgraph;
+
+    IF target_layout_id IS NOT NULL THEN
+        -- Remove the entity and restore the row it had before the event,
+        -- if there was one
+        target_schema := 'sgd' || target_layout_id;
+
+        PERFORM set_config('vars.current_event_source', reversion_identifier, TRUE);
+        EXECUTE format('DELETE FROM %I.%I WHERE id = $1', target_schema, target_entity)
+        USING target_entity_id;
+
+        IF target_data_before IS NOT NULL THEN
+            EXECUTE format(
+                'INSERT INTO %I.%I SELECT * FROM jsonb_populate_record(NULL::%I.%I, $1)',
+                target_schema, target_entity, target_schema, target_entity)
+            USING jsonb_set(target_data_before, '{__event_source}', to_jsonb(reversion_identifier));
+        END IF;
+
+        RETURN;
+    END IF;
+
+    CASE
+        -- INSERT case
+        WHEN operation_id = 0 THEN
+            -- Delete inserted row
+            BEGIN
+                PERFORM set_config('vars.current_event_source', 'REVERSION', TRUE);
+                EXECUTE
+                    'DELETE FROM entities WHERE (
+                        subgraph = $1 AND
+                        entity = $2 AND
+                        id = $3)'
+                USING target_subgraph, target_entity, target_entity_id;
+
+                -- Row was already updated
+                EXCEPTION
+                    WHEN no_data_found THEN
+                        NULL;
+            END;
+
+        -- UPDATE or DELETE case
+        WHEN operation_id IN (1,2) THEN
+            -- Insert deleted row if not exists
+            -- If row exists perform update
+            BEGIN
+                EXECUTE
+                    'INSERT INTO entities (id, subgraph, entity, data, event_source)
+                        VALUES ($1, $2, $3, $4, $5)
+                        ON CONFLICT (id, subgraph, entity) DO UPDATE
+                        SET data = $4, event_source = $5'
+                USING
+                    target_entity_id,
+                    target_subgraph,
+                    target_entity,
+                    target_data_before,
+                    reversion_identifier;
+            END;
+    END CASE;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,15 @@
+/**************************************************************
+* CREATE TABLE
+**************************************************************/
+-- Stores the blocks in which the entities of subgraphs were changed, so
+-- that the entity history can be read as of any block the subgraph has
+-- processed; rows of reverted blocks are removed along with their changes
+CREATE TABLE IF NOT EXISTS subgraph_entity_blocks (
+    subgraph_id VARCHAR NOT NULL,
+    block_hash VARCHAR NOT NULL,
+    block_number BIGINT NOT NULL,
+    PRIMARY KEY (subgraph_id, block_hash)
+);
+
+CREATE INDEX subgraph_entity_blocks_number
+    ON subgraph_entity_blocks (subgraph_id, block_number);
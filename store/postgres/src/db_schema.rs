@@ -32,8 +32,48 @@ table! {
     subgraphs (id) {
         id -> Varchar,
         network_name -> Varchar,
-        latest_block_hash -> Varchar,
-        latest_block_number -> BigInt,
+        latest_block_hash -> Nullable<Varchar>,
+        latest_block_number -> Nullable<BigInt>,
+        health -> Varchar,
+        health_error -> Nullable<Varchar>,
+        health_error_block_hash -> Nullable<Varchar>,
+        health_error_block_number -> Nullable<BigInt>,
+    }
+}
+
+table! {
+    subgraph_deployments (name) {
+        name -> Varchar,
+        subgraph_id -> Varchar,
+        deployed_at -> Timestamp,
+        status -> Varchar,
+    }
+}
+
+table! {
+    subgraph_dynamic_data_sources (id) {
+        id -> Integer,
+        subgraph_id -> Varchar,
+        template -> Varchar,
+        address -> Varchar,
+        block_hash -> Varchar,
+        block_number -> BigInt,
+    }
+}
+
+table! {
+    subgraph_entity_blocks (subgraph_id, block_hash) {
+        subgraph_id -> Varchar,
+        block_hash -> Varchar,
+        block_number -> BigInt,
+    }
+}
+
+table! {
+    subgraph_layouts (id) {
+        id -> Integer,
+        subgraph_id -> Varchar,
+        graphql_schema -> Text,
     }
 }
 allow_tables_to_appear_in_same_query!(entities, subgraphs);
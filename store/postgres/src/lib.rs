@@ -4,10 +4,12 @@ extern crate diesel;
 extern crate diesel_dynamic_schema;
 #[macro_use]
 extern crate diesel_migrations;
+#[macro_use]
 extern crate failure;
 extern crate fallible_iterator;
 extern crate futures;
 extern crate graph;
+extern crate graphql_parser;
 extern crate postgres;
 extern crate serde;
 extern crate uuid;
@@ -17,6 +19,7 @@ mod entity_changes;
 mod filter;
 pub mod functions;
 pub mod models;
+mod relational;
 pub mod store;
 
 pub use self::store::{Store, StoreConfig};
@@ -28,6 +28,10 @@ pub struct EntityTable {
 pub struct SqlValue(Value);
 
 impl SqlValue {
+    pub fn new(value: Value) -> Self {
+        SqlValue(value)
+    }
+
     pub fn new_array(values: Vec<Value>) -> Vec<Self> {
         values.into_iter().map(|value| SqlValue(value)).collect()
     }
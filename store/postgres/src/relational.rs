@@ -0,0 +1,1129 @@
+//! A storage layout for the entities of a subgraph with one table per
+//! entity type. The tables of a subgraph live in their own Postgres schema
+//! and have a typed column for every field of the entity type in the
+//! GraphQL schema of the subgraph, so that filters and ordering can use
+//! native comparisons and indexes.
+use diesel::pg::Pg;
+use diesel::query_builder::{AstPass, Query, QueryFragment, QueryId};
+use diesel::query_dsl::RunQueryDsl;
+use diesel::result::QueryResult;
+use diesel::sql_types::{Array, BigInt, Bool, Float, Integer, Jsonb, Numeric, Text};
+use graphql_parser::schema;
+use std::collections::BTreeMap;
+
+use graph::data::store::{BIG_INT_SCALAR, BYTES_SCALAR, ID};
+use graph::prelude::*;
+use graph::serde_json;
+
+use filter::UnsupportedFilter;
+use models::SqlValue;
+
+/// The column that holds the source of the last change of an entity, which
+/// the history triggers record. GraphQL field names cannot start with `__`.
+const EVENT_SOURCE_COLUMN: &str = "__event_source";
+
+/// The number of characters of string columns that their B-tree indexes
+/// cover; full values can exceed the size limit of B-tree index entries.
+const STRING_PREFIX_SIZE: usize = 256;
+
+/// The Postgres type of a column, derived from the GraphQL type of a field.
+/// Enum values and references to other entities are stored as strings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ColumnType {
+    Boolean,
+    BigInt,
+    Bytes,
+    Float,
+    Int,
+    String,
+}
+
+impl ColumnType {
+    fn from_type_name(document: &schema::Document, name: &str) -> Result<ColumnType, Error> {
+        match name {
+            "Boolean" => return Ok(ColumnType::Boolean),
+            "Float" => return Ok(ColumnType::Float),
+            "Int" => return Ok(ColumnType::Int),
+            "String" | ID => return Ok(ColumnType::String),
+            BIG_INT_SCALAR => return Ok(ColumnType::BigInt),
+            BYTES_SCALAR => return Ok(ColumnType::Bytes),
+            _ => (),
+        }
+
+        let type_definition = document
+            .definitions
+            .iter()
+            .filter_map(|definition| match definition {
+                schema::Definition::TypeDefinition(type_definition) => Some(type_definition),
+                _ => None,
+            }).find(|type_definition| match type_definition {
+                schema::TypeDefinition::Scalar(t) => t.name == name,
+                schema::TypeDefinition::Object(t) => t.name == name,
+                schema::TypeDefinition::Interface(t) => t.name == name,
+                schema::TypeDefinition::Union(t) => t.name == name,
+                schema::TypeDefinition::Enum(t) => t.name == name,
+                schema::TypeDefinition::InputObject(t) => t.name == name,
+            }).ok_or_else(|| format_err!("unknown type `{}`", name))?;
+
+        match type_definition {
+            schema::TypeDefinition::Enum(_)
+            | schema::TypeDefinition::Object(_)
+            | schema::TypeDefinition::Interface(_)
+            | schema::TypeDefinition::Union(_) => Ok(ColumnType::String),
+            schema::TypeDefinition::Scalar(_) | schema::TypeDefinition::InputObject(_) => Err(
+                format_err!("type `{}` cannot be stored in an entity table", name),
+            ),
+        }
+    }
+
+    fn sql_type(self) -> &'static str {
+        match self {
+            ColumnType::Boolean => "boolean",
+            ColumnType::BigInt => "numeric",
+            ColumnType::Bytes => "text",
+            ColumnType::Float => "real",
+            ColumnType::Int => "integer",
+            ColumnType::String => "text",
+        }
+    }
+
+    /// Returns `true` if `value` is a single value of this type.
+    fn accepts(self, value: &Value) -> bool {
+        match (self, value) {
+            (ColumnType::Boolean, Value::Bool(_))
+            | (ColumnType::BigInt, Value::BigInt(_))
+            | (ColumnType::Bytes, Value::Bytes(_))
+            | (ColumnType::Float, Value::Float(_))
+            | (ColumnType::Int, Value::Int(_))
+            | (ColumnType::String, Value::String(_)) => true,
+            _ => false,
+        }
+    }
+
+    fn accepts_all(self, values: &[Value]) -> bool {
+        values.iter().all(|value| self.accepts(value))
+    }
+
+    /// Converts a single value of this type, as `to_jsonb` returns it for
+    /// the columns selected by `push_select_list`, back into a `Value`.
+    fn value_from_json(self, json: serde_json::Value) -> Result<Value, Error> {
+        use self::serde_json::Value as Json;
+
+        Ok(match (self, json) {
+            (_, Json::Null) => Value::Null,
+            (ColumnType::Boolean, Json::Bool(b)) => Value::Bool(b),
+            (ColumnType::BigInt, Json::String(s)) => Value::BigInt(s.parse()?),
+            (ColumnType::Bytes, Json::String(s)) => Value::Bytes(s.parse()?),
+            (ColumnType::Float, Json::Number(ref n)) if n.as_f64().is_some() => {
+                Value::Float(n.as_f64().unwrap() as f32)
+            }
+            (ColumnType::Int, Json::Number(ref n)) if n.is_i64() => {
+                Value::Int(n.as_i64().unwrap() as i32)
+            }
+            (ColumnType::String, Json::String(s)) => Value::String(s),
+            (column_type, json) => {
+                return Err(format_err!(
+                    "unexpected value {} in a column of type {:?}",
+                    json,
+                    column_type
+                ))
+            }
+        })
+    }
+}
+
+/// A column of an entity table, which stores the values of one field.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Column {
+    pub name: String,
+    pub column_type: ColumnType,
+    /// Whether the field is a list of values of `column_type`.
+    pub list: bool,
+}
+
+impl Column {
+    fn new(document: &schema::Document, field: &schema::Field) -> Result<Column, Error> {
+        fn strip_non_null(ty: &schema::Type) -> &schema::Type {
+            match ty {
+                schema::Type::NonNullType(ty) => &**ty,
+                ty => ty,
+            }
+        }
+
+        let (type_name, list) = match strip_non_null(&field.field_type) {
+            schema::Type::NamedType(name) => (name, false),
+            schema::Type::ListType(ty) => match strip_non_null(ty) {
+                schema::Type::NamedType(name) => (name, true),
+                _ => {
+                    return Err(format_err!(
+                        "field `{}` is a nested list, which cannot be stored in an entity table",
+                        field.name
+                    ))
+                }
+            },
+            schema::Type::NonNullType(_) => unreachable!("non-null types cannot be nested"),
+        };
+
+        Ok(Column {
+            name: field.name.clone(),
+            column_type: ColumnType::from_type_name(document, type_name)?,
+            list,
+        })
+    }
+
+    /// Returns `true` if `value` can be stored in this column.
+    fn accepts(&self, value: &Value) -> bool {
+        match value {
+            Value::Null => true,
+            Value::List(values) => self.list && self.column_type.accepts_all(values),
+            value => !self.list && self.column_type.accepts(value),
+        }
+    }
+
+    fn value_from_json(&self, json: serde_json::Value) -> Result<Value, Error> {
+        if !self.list {
+            return self.column_type.value_from_json(json);
+        }
+
+        match json {
+            serde_json::Value::Array(values) => values
+                .into_iter()
+                .map(|value| self.column_type.value_from_json(value))
+                .collect::<Result<_, _>>()
+                .map(Value::List),
+            json => self.column_type.value_from_json(json),
+        }
+    }
+
+    /// Whether the B-tree index of the column only covers a prefix of each
+    /// value, which is the case for strings.
+    fn has_prefix_index(&self) -> bool {
+        !self.list
+            && match self.column_type {
+                ColumnType::Bytes | ColumnType::String => true,
+                _ => false,
+            }
+    }
+
+    fn sql_type(&self) -> String {
+        if self.list {
+            format!("{}[]", self.column_type.sql_type())
+        } else {
+            self.column_type.sql_type().to_owned()
+        }
+    }
+}
+
+/// The table that stores the entities of one entity type.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Table {
+    pub name: String,
+    pub columns: Vec<Column>,
+}
+
+impl Table {
+    fn new(document: &schema::Document, object_type: &schema::ObjectType) -> Result<Table, Error> {
+        // Derived fields are resolved from other entities at query time
+        let columns = object_type
+            .fields
+            .iter()
+            .filter(|field| {
+                !field
+                    .directives
+                    .iter()
+                    .any(|directive| directive.name == "derivedFrom")
+            }).map(|field| Column::new(document, field))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format_err!("entity type `{}`: {}", object_type.name, e))?;
+
+        match columns.iter().find(|column| column.name == "id") {
+            Some(column) if column.column_type == ColumnType::String && !column.list => (),
+            _ => {
+                return Err(format_err!(
+                    "entity type `{}` needs an `id` field of type `ID` or `String`",
+                    object_type.name
+                ))
+            }
+        }
+
+        Ok(Table {
+            name: object_type.name.clone(),
+            columns,
+        })
+    }
+
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    /// Converts a row of this table, as `to_jsonb` returns it for the
+    /// columns selected by `push_select_list`, into an entity. Columns that
+    /// are `NULL` are left out of the entity.
+    pub fn entity_from_json(&self, json: serde_json::Value) -> Result<Entity, Error> {
+        let mut row = match json {
+            serde_json::Value::Object(row) => row,
+            json => return Err(format_err!("entity row is not an object: {}", json)),
+        };
+
+        let mut entity = Entity::new();
+        for column in self.columns.iter() {
+            match row.remove(&column.name) {
+                None | Some(serde_json::Value::Null) => (),
+                Some(json) => {
+                    entity.insert(column.name.clone(), column.value_from_json(json)?);
+                }
+            }
+        }
+        Ok(entity)
+    }
+}
+
+/// The tables of a subgraph, one for each object type in its schema.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Layout {
+    /// The ID of the subgraph.
+    pub subgraph: String,
+    /// The Postgres schema that contains the tables.
+    pub schema: String,
+    pub tables: BTreeMap<String, Table>,
+}
+
+impl Layout {
+    pub fn new(subgraph: &str, schema: &str, document: &schema::Document) -> Result<Layout, Error> {
+        let tables = document
+            .definitions
+            .iter()
+            .filter_map(|definition| match definition {
+                schema::Definition::TypeDefinition(schema::TypeDefinition::Object(t)) => Some(t),
+                _ => None,
+            }).map(|object_type| {
+                Table::new(document, object_type).map(|table| (table.name.clone(), table))
+            }).collect::<Result<_, _>>()?;
+
+        Ok(Layout {
+            subgraph: subgraph.to_owned(),
+            schema: schema.to_owned(),
+            tables,
+        })
+    }
+
+    pub fn table(&self, entity: &str) -> Result<&Table, Error> {
+        self.tables
+            .get(entity)
+            .ok_or_else(|| format_err!("unknown entity type `{}`", entity))
+    }
+
+    /// Generates the DDL that creates the Postgres schema of the subgraph
+    /// with its tables, indexes and the triggers that keep the entity
+    /// history and emit entity changes.
+    pub fn create_sql(&self) -> String {
+        let mut sql = format!("CREATE SCHEMA {};\n", quote_identifier(&self.schema));
+
+        for table in self.tables.values() {
+            let table_name = format!(
+                "{}.{}",
+                quote_identifier(&self.schema),
+                quote_identifier(&table.name)
+            );
+
+            sql.push_str(&format!("\nCREATE TABLE {} (\n", table_name));
+            for column in table.columns.iter() {
+                sql.push_str(&format!(
+                    "    {} {}{},\n",
+                    quote_identifier(&column.name),
+                    column.sql_type(),
+                    if column.name == "id" {
+                        " PRIMARY KEY"
+                    } else {
+                        ""
+                    }
+                ));
+            }
+            sql.push_str(&format!(
+                "    {} varchar NOT NULL\n);\n",
+                quote_identifier(EVENT_SOURCE_COLUMN)
+            ));
+
+            // Strings can be too long for B-tree index entries, so their
+            // B-tree indexes only cover a prefix of each value; queries sort
+            // and compare strings by that prefix first (see `sort_key`) so
+            // that the index can be used. Lookups by equality of the whole
+            // value get an additional hash index
+            for column in table.columns.iter().filter(|column| column.name != "id") {
+                let column_name = quote_identifier(&column.name);
+                match column.column_type {
+                    _ if column.list => sql.push_str(&format!(
+                        "CREATE INDEX ON {} USING gin ({});\n",
+                        table_name, column_name
+                    )),
+                    ColumnType::Bytes | ColumnType::String => {
+                        sql.push_str(&format!(
+                            "CREATE INDEX ON {} USING btree (left({}, {}));\n",
+                            table_name, column_name, STRING_PREFIX_SIZE
+                        ));
+                        sql.push_str(&format!(
+                            "CREATE INDEX ON {} USING hash ({});\n",
+                            table_name, column_name
+                        ));
+                    }
+                    _ => sql.push_str(&format!(
+                        "CREATE INDEX ON {} USING btree ({});\n",
+                        table_name, column_name
+                    )),
+                }
+            }
+
+            sql.push_str(&format!(
+                "CREATE TRIGGER entity_history\n    \
+                 AFTER INSERT OR UPDATE OR DELETE ON {}\n    \
+                 FOR EACH ROW EXECUTE PROCEDURE log_relational_change({});\n",
+                table_name,
+                quote_literal(&self.subgraph)
+            ));
+            sql.push_str(&format!(
+                "CREATE TRIGGER entity_changes\n    \
+                 AFTER INSERT OR UPDATE OR DELETE ON {}\n    \
+                 FOR EACH ROW EXECUTE PROCEDURE notify_relational_change({});\n",
+                table_name,
+                quote_literal(&self.subgraph)
+            ));
+        }
+
+        sql
+    }
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace("\"", "\"\""))
+}
+
+fn quote_literal(literal: &str) -> String {
+    format!("'{}'", literal.replace("'", "''"))
+}
+
+fn push_table(out: &mut AstPass<Pg>, layout: &Layout, table: &Table) -> QueryResult<()> {
+    out.push_identifier(&layout.schema)?;
+    out.push_sql(".");
+    out.push_identifier(&table.name)
+}
+
+/// Pushes `table`, or a subquery with the rows that `table` had after the
+/// changes of the block with number `block` were applied. Past rows are the
+/// latest versions in the entity history that were written in blocks up to
+/// `block` that have not been reverted.
+fn push_table_at(
+    out: &mut AstPass<Pg>,
+    layout: &Layout,
+    table: &Table,
+    block: Option<&i64>,
+) -> QueryResult<()> {
+    let block = match block {
+        Some(block) => block,
+        None => return push_table(out, layout, table),
+    };
+
+    out.push_sql("(SELECT (jsonb_populate_record(NULL::");
+    push_table(out, layout, table)?;
+    out.push_sql(", h.data_after)).* FROM (SELECT DISTINCT ON (h.entity_id) h.data_after");
+    out.push_sql(" FROM entity_history AS h");
+    out.push_sql(" JOIN event_meta_data AS m ON m.id = h.event_id");
+    out.push_sql(" JOIN subgraph_entity_blocks AS b");
+    out.push_sql(" ON b.subgraph_id = h.subgraph AND b.block_hash = m.source");
+    out.push_sql(" WHERE h.subgraph = ");
+    out.push_bind_param::<Text, _>(&layout.subgraph)?;
+    out.push_sql(" AND h.entity = ");
+    out.push_bind_param::<Text, _>(&table.name)?;
+    out.push_sql(" AND b.block_number <= ");
+    out.push_bind_param::<BigInt, _>(block)?;
+    out.push_sql(" ORDER BY h.entity_id, h.id DESC) AS h WHERE h.data_after IS NOT NULL)");
+    Ok(())
+}
+
+/// Pushes the columns of `table` in a form that `to_jsonb` converts without
+/// losing precision; `Table::entity_from_json` converts them back.
+fn push_select_list(out: &mut AstPass<Pg>, table: &Table) -> QueryResult<()> {
+    for (i, column) in table.columns.iter().enumerate() {
+        if i > 0 {
+            out.push_sql(", ");
+        }
+        out.push_sql("c.");
+        out.push_identifier(&column.name)?;
+        if column.column_type == ColumnType::BigInt {
+            out.push_sql(if column.list { "::text[]" } else { "::text" });
+        }
+        out.push_sql(" AS ");
+        out.push_identifier(&column.name)?;
+    }
+    Ok(())
+}
+
+fn push_column(out: &mut AstPass<Pg>, column: &Column) -> QueryResult<()> {
+    out.push_sql("c.");
+    out.push_identifier(&column.name)
+}
+
+/// The expressions that entities are sorted by when they are ordered by
+/// `column` of the table with the alias `alias`. Columns with a prefix index
+/// are sorted by the indexed prefix first, and by the whole value to break
+/// ties between values with the same prefix.
+fn sort_key(column: &Column, alias: &str) -> Vec<String> {
+    let expr = format!("{}.{}", alias, quote_identifier(&column.name));
+    if column.has_prefix_index() {
+        vec![format!("left({}, {})", expr, STRING_PREFIX_SIZE), expr]
+    } else {
+        vec![expr]
+    }
+}
+
+fn push_scalar(out: &mut AstPass<Pg>, column_type: ColumnType, value: &Value) -> QueryResult<()> {
+    let value = SqlValue::new(value.clone());
+    match column_type {
+        ColumnType::Boolean => out.push_bind_param::<Bool, _>(&value),
+        ColumnType::BigInt => out.push_bind_param::<Numeric, _>(&value),
+        ColumnType::Bytes | ColumnType::String => out.push_bind_param::<Text, _>(&value),
+        ColumnType::Float => out.push_bind_param::<Float, _>(&value),
+        ColumnType::Int => out.push_bind_param::<Integer, _>(&value),
+    }
+}
+
+fn push_array(out: &mut AstPass<Pg>, column_type: ColumnType, values: &[Value]) -> QueryResult<()> {
+    let values = SqlValue::new_array(values.to_vec());
+    match column_type {
+        ColumnType::Boolean => out.push_bind_param::<Array<Bool>, _>(&values),
+        ColumnType::BigInt => out.push_bind_param::<Array<Numeric>, _>(&values),
+        ColumnType::Bytes | ColumnType::String => out.push_bind_param::<Array<Text>, _>(&values),
+        ColumnType::Float => out.push_bind_param::<Array<Float>, _>(&values),
+        ColumnType::Int => out.push_bind_param::<Array<Integer>, _>(&values),
+    }
+}
+
+/// Pushes a value that `column.accepts`.
+fn push_value(out: &mut AstPass<Pg>, column: &Column, value: &Value) -> QueryResult<()> {
+    match value {
+        Value::Null => {
+            out.push_sql("NULL");
+            Ok(())
+        }
+        Value::List(values) => push_array(out, column.column_type, values),
+        value => push_scalar(out, column.column_type, value),
+    }
+}
+
+/// Looks up an entity by its ID.
+pub(crate) struct GetQuery<'a> {
+    layout: &'a Layout,
+    table: &'a Table,
+    id: &'a str,
+}
+
+impl<'a> GetQuery<'a> {
+    pub fn new(layout: &'a Layout, table: &'a Table, id: &'a str) -> Self {
+        GetQuery { layout, table, id }
+    }
+}
+
+impl<'a> QueryFragment<Pg> for GetQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        out.push_sql("SELECT to_jsonb(e) FROM (SELECT ");
+        push_select_list(&mut out, self.table)?;
+        out.push_sql(" FROM ");
+        push_table(&mut out, self.layout, self.table)?;
+        out.push_sql(" AS c WHERE c.\"id\" = ");
+        out.push_bind_param::<Text, _>(&self.id)?;
+        out.push_sql(") AS e");
+        Ok(())
+    }
+}
+
+/// Inserts an entity or replaces all of its columns if it exists.
+pub(crate) struct InsertQuery<'a> {
+    layout: &'a Layout,
+    table: &'a Table,
+    id: &'a str,
+    entity: &'a Entity,
+    event_source: String,
+}
+
+impl<'a> InsertQuery<'a> {
+    /// Fails if `entity` has attributes that are not columns of `table` or
+    /// values that do not fit their columns.
+    pub fn new(
+        layout: &'a Layout,
+        table: &'a Table,
+        id: &'a str,
+        entity: &'a Entity,
+        event_source: String,
+    ) -> Result<Self, Error> {
+        for (attribute, value) in entity.iter() {
+            match table.column(attribute) {
+                Some(column) if column.accepts(value) => (),
+                Some(column) => {
+                    return Err(format_err!(
+                        "value {:?} of field `{}` of entity type `{}` is not of type {}",
+                        value,
+                        attribute,
+                        table.name,
+                        column.sql_type()
+                    ))
+                }
+                None => {
+                    return Err(format_err!(
+                        "entity type `{}` has no field `{}`",
+                        table.name,
+                        attribute
+                    ))
+                }
+            }
+        }
+
+        Ok(InsertQuery {
+            layout,
+            table,
+            id,
+            entity,
+            event_source,
+        })
+    }
+}
+
+impl<'a> QueryFragment<Pg> for InsertQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        out.push_sql("INSERT INTO ");
+        push_table(&mut out, self.layout, self.table)?;
+        out.push_sql(" (");
+        for column in self.table.columns.iter() {
+            out.push_identifier(&column.name)?;
+            out.push_sql(", ");
+        }
+        out.push_identifier(EVENT_SOURCE_COLUMN)?;
+
+        out.push_sql(") VALUES (");
+        for column in self.table.columns.iter() {
+            if column.name == "id" {
+                out.push_bind_param::<Text, _>(&self.id)?;
+            } else {
+                push_value(
+                    &mut out,
+                    column,
+                    self.entity.get(&column.name).unwrap_or(&Value::Null),
+                )?;
+            }
+            out.push_sql(", ");
+        }
+        out.push_bind_param::<Text, _>(&self.event_source)?;
+
+        out.push_sql(") ON CONFLICT (\"id\") DO UPDATE SET ");
+        for column in self
+            .table
+            .columns
+            .iter()
+            .filter(|column| column.name != "id")
+        {
+            out.push_identifier(&column.name)?;
+            out.push_sql(" = EXCLUDED.");
+            out.push_identifier(&column.name)?;
+            out.push_sql(", ");
+        }
+        out.push_identifier(EVENT_SOURCE_COLUMN)?;
+        out.push_sql(" = EXCLUDED.");
+        out.push_identifier(EVENT_SOURCE_COLUMN)
+    }
+}
+
+/// Deletes an entity by its ID.
+pub(crate) struct DeleteQuery<'a> {
+    layout: &'a Layout,
+    table: &'a Table,
+    id: &'a str,
+}
+
+impl<'a> DeleteQuery<'a> {
+    pub fn new(layout: &'a Layout, table: &'a Table, id: &'a str) -> Self {
+        DeleteQuery { layout, table, id }
+    }
+}
+
+impl<'a> QueryFragment<Pg> for DeleteQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        out.push_sql("DELETE FROM ");
+        push_table(&mut out, self.layout, self.table)?;
+        out.push_sql(" WHERE \"id\" = ");
+        out.push_bind_param::<Text, _>(&self.id)
+    }
+}
+
+/// A `StoreFilter` translated to a condition on the columns of a table.
+enum Clause<'a> {
+    And(Vec<Clause<'a>>),
+    Or(Vec<Clause<'a>>),
+    /// `column IS [NOT] NULL`
+    Null(&'a Column, bool),
+    /// `column op value`
+    Compare(&'a Column, &'static str, Value),
+    /// `column = ANY(values)` or `column != ALL(values)`
+    In(&'a Column, Vec<Value>, bool),
+    /// `column [NOT] LIKE pattern`
+    Like(&'a Column, String, bool),
+    /// `[NOT] column @> values` for list columns
+    ArrayContains(&'a Column, Vec<Value>, bool),
+}
+
+fn unsupported(filter: &str, value: Value) -> UnsupportedFilter {
+    UnsupportedFilter {
+        filter: filter.to_owned(),
+        value,
+    }
+}
+
+impl<'a> Clause<'a> {
+    fn new(table: &'a Table, filter: StoreFilter) -> Result<Clause<'a>, UnsupportedFilter> {
+        use graph::components::store::StoreFilter::*;
+
+        Ok(match filter {
+            And(filters) => Clause::And(
+                filters
+                    .into_iter()
+                    .map(|filter| Clause::new(table, filter))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Or(filters) => Clause::Or(
+                filters
+                    .into_iter()
+                    .map(|filter| Clause::new(table, filter))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Contains(..) | NotContains(..) => {
+                let (attribute, name, not, value) = match filter {
+                    Contains(attribute, value) => (attribute, "contains", false, value),
+                    NotContains(attribute, value) => (attribute, "not_contains", true, value),
+                    _ => unreachable!(),
+                };
+                let column = match table.column(&attribute) {
+                    Some(column) => column,
+                    None => return Err(unsupported(name, value)),
+                };
+                if column.list {
+                    // Does the list contain all of the values?
+                    let values = match value {
+                        Value::List(values) => values,
+                        value => vec![value],
+                    };
+                    if !column.column_type.accepts_all(&values) {
+                        return Err(unsupported(name, Value::List(values)));
+                    }
+                    Clause::ArrayContains(column, values, not)
+                } else {
+                    match (column.column_type, value) {
+                        (ColumnType::String, Value::String(s)) => Clause::Like(column, s, not),
+                        (ColumnType::Bytes, Value::Bytes(b)) => {
+                            Clause::Like(column, b.to_string(), not)
+                        }
+                        (_, value) => return Err(unsupported(name, value)),
+                    }
+                }
+            }
+            Equal(..) | Not(..) => {
+                let (attribute, name, not, value) = match filter {
+                    Equal(attribute, value) => (attribute, "equal", false, value),
+                    Not(attribute, value) => (attribute, "not", true, value),
+                    _ => unreachable!(),
+                };
+                match table.column(&attribute) {
+                    Some(column) if value == Value::Null => Clause::Null(column, not),
+                    Some(column) if column.accepts(&value) => {
+                        Clause::Compare(column, if not { "!=" } else { "=" }, value)
+                    }
+                    _ => return Err(unsupported(name, value)),
+                }
+            }
+            GreaterThan(..) | LessThan(..) | GreaterOrEqual(..) | LessOrEqual(..) => {
+                let (attribute, name, op, value) = match filter {
+                    GreaterThan(attribute, value) => (attribute, "gt", ">", value),
+                    LessThan(attribute, value) => (attribute, "lt", "<", value),
+                    GreaterOrEqual(attribute, value) => (attribute, "gte", ">=", value),
+                    LessOrEqual(attribute, value) => (attribute, "lte", "<=", value),
+                    _ => unreachable!(),
+                };
+                let comparable = match value {
+                    Value::Null | Value::List(_) | Value::Bool(_) => false,
+                    _ => true,
+                };
+                match table.column(&attribute) {
+                    Some(column) if comparable && column.accepts(&value) => {
+                        Clause::Compare(column, op, value)
+                    }
+                    _ => return Err(unsupported(name, value)),
+                }
+            }
+            In(..) | NotIn(..) => {
+                let (attribute, name, not, values) = match filter {
+                    In(attribute, values) => (attribute, "in", false, values),
+                    NotIn(attribute, values) => (attribute, "not_in", true, values),
+                    _ => unreachable!(),
+                };
+                match table.column(&attribute) {
+                    Some(column) if !column.list && column.column_type.accepts_all(&values) => {
+                        Clause::In(column, values, not)
+                    }
+                    _ => return Err(unsupported(name, Value::List(values))),
+                }
+            }
+            StartsWith(..) | NotStartsWith(..) | EndsWith(..) | NotEndsWith(..) => {
+                let (attribute, name, not, prefix, value) = match filter {
+                    StartsWith(attribute, value) => (attribute, "starts_with", false, true, value),
+                    NotStartsWith(attribute, value) => {
+                        (attribute, "not_starts_with", true, true, value)
+                    }
+                    EndsWith(attribute, value) => (attribute, "ends_with", false, false, value),
+                    NotEndsWith(attribute, value) => {
+                        (attribute, "not_ends_with", true, false, value)
+                    }
+                    _ => unreachable!(),
+                };
+                let column = match table.column(&attribute) {
+                    Some(column) if column.column_type == ColumnType::String && !column.list => {
+                        column
+                    }
+                    _ => return Err(unsupported(name, value)),
+                };
+                match value {
+                    Value::String(s) => {
+                        let pattern = if prefix {
+                            format!("{}%", s)
+                        } else {
+                            format!("%{}", s)
+                        };
+                        Clause::Like(column, pattern, not)
+                    }
+                    value => return Err(unsupported(name, value)),
+                }
+            }
+        })
+    }
+
+    fn walk_ast(&self, out: &mut AstPass<Pg>) -> QueryResult<()> {
+        match self {
+            Clause::And(clauses) => Self::walk_all(out, clauses, " AND ", "TRUE"),
+            Clause::Or(clauses) => Self::walk_all(out, clauses, " OR ", "FALSE"),
+            Clause::Null(column, not) => {
+                push_column(out, column)?;
+                out.push_sql(if *not { " IS NOT NULL" } else { " IS NULL" });
+                Ok(())
+            }
+            // Compare the prefix of strings first, in the same way that they
+            // are sorted, so that their prefix index can be used
+            Clause::Compare(column, op, value)
+                if column.has_prefix_index() && *op != "=" && *op != "!=" =>
+            {
+                out.push_sql("(");
+                out.push_sql(&sort_key(column, "c").join(", "));
+                out.push_sql(") ");
+                out.push_sql(op);
+                out.push_sql(" (left(");
+                push_value(out, column, value)?;
+                out.push_sql(&format!(", {}), ", STRING_PREFIX_SIZE));
+                push_value(out, column, value)?;
+                out.push_sql(")");
+                Ok(())
+            }
+            Clause::Compare(column, op, value) => {
+                push_column(out, column)?;
+                out.push_sql(" ");
+                out.push_sql(op);
+                out.push_sql(" ");
+                push_value(out, column, value)
+            }
+            Clause::In(column, values, not) => {
+                push_column(out, column)?;
+                out.push_sql(if *not { " != ALL(" } else { " = ANY(" });
+                push_array(out, column.column_type, values)?;
+                out.push_sql(")");
+                Ok(())
+            }
+            Clause::Like(column, pattern, not) => {
+                push_column(out, column)?;
+                out.push_sql(if *not { " NOT LIKE " } else { " LIKE " });
+                out.push_bind_param::<Text, _>(pattern)
+            }
+            Clause::ArrayContains(column, values, not) => {
+                out.push_sql(if *not { "NOT " } else { "" });
+                push_column(out, column)?;
+                out.push_sql(" @> ");
+                push_array(out, column.column_type, values)
+            }
+        }
+    }
+
+    fn walk_all(
+        out: &mut AstPass<Pg>,
+        clauses: &[Clause],
+        separator: &str,
+        empty: &str,
+    ) -> QueryResult<()> {
+        if clauses.is_empty() {
+            out.push_sql(empty);
+            return Ok(());
+        }
+
+        out.push_sql("(");
+        for (i, clause) in clauses.iter().enumerate() {
+            if i > 0 {
+                out.push_sql(separator);
+            }
+            clause.walk_ast(out)?;
+        }
+        out.push_sql(")");
+        Ok(())
+    }
+}
+
+/// Finds the entities of one type that match a `StoreQuery`.
+pub(crate) struct FindQuery<'a> {
+    layout: &'a Layout,
+    table: &'a Table,
+    filter: Option<Clause<'a>>,
+    order: Option<&'a Column>,
+    direction: &'static str,
+    /// Entities must come after (`true`) or before the entity with this ID.
+    cursors: Vec<(String, bool)>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    /// Find the entities as they were at the block with this number.
+    block: Option<i64>,
+}
+
+impl<'a> FindQuery<'a> {
+    /// Translates `query`; `ascending` is the direction of the query and
+    /// `reverse` tells whether to load the entities in the opposite order.
+    pub fn new(
+        layout: &'a Layout,
+        table: &'a Table,
+        query: StoreQuery,
+        ascending: bool,
+        reverse: bool,
+    ) -> Result<Self, UnsupportedFilter> {
+        let filter = match query.filter {
+            Some(filter) => Some(Clause::new(table, filter)?),
+            None => None,
+        };
+
+        let order = match query.order_by {
+            Some(attribute) => Some(
+                table
+                    .column(&attribute)
+                    .filter(|column| !column.list)
+                    .ok_or_else(|| UnsupportedFilter {
+                        filter: "order_by".to_owned(),
+                        value: Value::String(attribute),
+                    })?,
+            ),
+            None => None,
+        };
+
+        let cursors = vec![(query.after, ascending), (query.before, !ascending)]
+            .into_iter()
+            .filter_map(|(cursor, greater)| cursor.map(|cursor| (cursor, greater)))
+            .collect();
+
+        let (limit, offset) = match (query.range, query.last) {
+            (Some(range), _) => (Some(range.first), Some(range.skip)),
+            (None, Some(last)) => (Some(last), None),
+            (None, None) => (None, None),
+        };
+
+        Ok(FindQuery {
+            layout,
+            table,
+            filter,
+            order,
+            direction: if ascending != reverse { "ASC" } else { "DESC" },
+            cursors,
+            limit,
+            offset,
+            block: query.block.map(|block| block as i64),
+        })
+    }
+
+    /// Where entities whose order column is NULL go in the order; NULL
+    /// counts as greater than all values, which is also how Postgres and
+    /// its B-tree indexes sort it by default.
+    fn nulls(&self) -> &'static str {
+        if self.direction == "ASC" {
+            " NULLS LAST"
+        } else {
+            " NULLS FIRST"
+        }
+    }
+
+    /// Pushes a predicate that compares the sort key and ID of entities
+    /// with those of the cursor entity, so entities with the same value as
+    /// the cursor entity are not skipped. NULL counts as greater than all
+    /// values; if the value of the cursor entity is NULL, only the IDs of
+    /// entities whose value is NULL are compared, and otherwise entities
+    /// whose value is NULL make the row comparison NULL.
+    fn push_cursor(&self, out: &mut AstPass<Pg>, cursor: &str, greater: bool) -> QueryResult<()> {
+        let op = if greater { " > " } else { " < " };
+        let column = match self.order {
+            Some(column) => column,
+            None => {
+                out.push_sql("c.\"id\"");
+                out.push_sql(op);
+                return out.push_bind_param::<Text, _>(&cursor);
+            }
+        };
+
+        let cursor_column = format!("p.{}", quote_identifier(&column.name));
+        out.push_sql("CASE WHEN ");
+        self.push_cursor_value(out, &cursor_column, cursor)?;
+        out.push_sql(" IS NULL THEN ");
+        push_column(out, column)?;
+        out.push_sql(if greater {
+            " IS NULL AND c.\"id\" > "
+        } else {
+            " IS NOT NULL OR c.\"id\" < "
+        });
+        out.push_bind_param::<Text, _>(&cursor)?;
+        out.push_sql(" ELSE COALESCE((");
+        out.push_sql(&sort_key(column, "c").join(", "));
+        out.push_sql(", c.\"id\")");
+        out.push_sql(op);
+        out.push_sql("(");
+        for expr in sort_key(column, "p") {
+            self.push_cursor_value(out, &expr, cursor)?;
+            out.push_sql(", ");
+        }
+        out.push_bind_param::<Text, _>(&cursor)?;
+        out.push_sql(if greater {
+            "), TRUE) END"
+        } else {
+            "), FALSE) END"
+        });
+        Ok(())
+    }
+
+    /// Pushes a subquery that selects `expr` of the cursor entity, whose
+    /// table has the alias `p`.
+    fn push_cursor_value(
+        &self,
+        out: &mut AstPass<Pg>,
+        expr: &str,
+        cursor: &str,
+    ) -> QueryResult<()> {
+        out.push_sql("(SELECT ");
+        out.push_sql(expr);
+        out.push_sql(" FROM ");
+        push_table_at(out, self.layout, self.table, self.block.as_ref())?;
+        out.push_sql(" AS p WHERE \"id\" = ");
+        out.push_bind_param::<Text, _>(&cursor)?;
+        out.push_sql(")");
+        Ok(())
+    }
+}
+
+impl<'a> QueryFragment<Pg> for FindQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // The outer query converts rows to JSON; it has to repeat the order
+        // since the order of a subquery is not kept otherwise
+        out.push_sql("SELECT to_jsonb(e) FROM (SELECT ");
+        push_select_list(&mut out, self.table)?;
+        if let Some(column) = self.order {
+            for (i, expr) in sort_key(column, "c").iter().enumerate() {
+                out.push_sql(&format!(", {} AS \"__order{}\"", expr, i));
+            }
+        }
+        out.push_sql(" FROM ");
+        push_table_at(&mut out, self.layout, self.table, self.block.as_ref())?;
+        out.push_sql(" AS c");
+
+        let mut conditions = 0;
+        if let Some(ref filter) = self.filter {
+            out.push_sql(" WHERE ");
+            filter.walk_ast(&mut out)?;
+            conditions += 1;
+        }
+        for (cursor, greater) in self.cursors.iter() {
+            out.push_sql(if conditions == 0 { " WHERE " } else { " AND " });
+            self.push_cursor(&mut out, cursor, *greater)?;
+            conditions += 1;
+        }
+
+        // Order by the order column, if present, and by ID; ordering by ID
+        // makes the order unique, which is required for cursors to work.
+        // Entities whose order column is NULL come last in ascending order
+        out.push_sql(" ORDER BY ");
+        if let Some(column) = self.order {
+            for expr in sort_key(column, "c") {
+                out.push_sql(&expr);
+                out.push_sql(" ");
+                out.push_sql(self.direction);
+                out.push_sql(self.nulls());
+                out.push_sql(", ");
+            }
+        }
+        out.push_sql("c.\"id\" ");
+        out.push_sql(self.direction);
+
+        if let Some(limit) = self.limit {
+            out.push_sql(&format!(" LIMIT {}", limit));
+        }
+        if let Some(offset) = self.offset {
+            out.push_sql(&format!(" OFFSET {}", offset));
+        }
+
+        out.push_sql(") AS e ORDER BY ");
+        if let Some(column) = self.order {
+            for i in 0..sort_key(column, "c").len() {
+                out.push_sql(&format!("e.\"__order{}\" ", i));
+                out.push_sql(self.direction);
+                out.push_sql(self.nulls());
+                out.push_sql(", ");
+            }
+        }
+        out.push_sql("e.\"id\" ");
+        out.push_sql(self.direction);
+        Ok(())
+    }
+}
+
+macro_rules! impl_query {
+    ($query:ident, $sql_type:ty) => {
+        impl<'a> QueryId for $query<'a> {
+            type QueryId = ();
+
+            const HAS_STATIC_QUERY_ID: bool = false;
+        }
+
+        impl<'a> Query for $query<'a> {
+            type SqlType = $sql_type;
+        }
+
+        impl<'a, Conn> RunQueryDsl<Conn> for $query<'a> {}
+    };
+}
+
+impl_query!(GetQuery, Jsonb);
+impl_query!(FindQuery, Jsonb);
+
+impl<'a> QueryId for InsertQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for InsertQuery<'a> {}
+
+impl<'a> QueryId for DeleteQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for DeleteQuery<'a> {}
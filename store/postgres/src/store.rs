@@ -1,24 +1,31 @@
-use diesel::dsl::sql;
+use db_schema::entities;
+use diesel::connection::SimpleConnection;
+use diesel::dsl::{exists, sql};
+use diesel::expression::BoxableExpression;
 use diesel::pg::Pg;
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
-use diesel::sql_types::Text;
-use diesel::{debug_query, delete, insert_into, result, select, update};
+use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
+use diesel::sql_types::{BigInt, Bool, Text};
+use diesel::{debug_query, delete, insert_into, select, update};
 use filter::store_filter;
 use futures::sync::mpsc::{channel, Sender};
+use graphql_parser;
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex, RwLock};
-use std::time::{Duration, Instant};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
 use uuid::Uuid;
 
 use graph::components::store::{EventSource, Store as StoreTrait};
 use graph::prelude::*;
 use graph::serde_json;
+use graph::util::metrics::STORE_OPERATION_DURATION;
 use graph::web3::types::{Block, Transaction, H256};
 use graph::{tokio, tokio::timer::Interval};
 
 use entity_changes::EntityChangeListener;
-use functions::{attempt_head_update, revert_block, set_config};
+use functions::{attempt_head_update, lookup_ancestor_block, revert_block, set_config};
+use relational::{DeleteQuery, FindQuery, GetQuery, InsertQuery, Layout};
 
 embed_migrations!("./migrations");
 
@@ -50,6 +57,12 @@ fn initiate_schema(logger: &slog::Logger, conn: &PgConnection) {
 /// Configuration for the Diesel/Postgres store.
 pub struct StoreConfig {
     pub url: String,
+
+    /// The maximum number of Postgres connections the store keeps open.
+    pub connection_pool_size: u32,
+
+    /// The maximum time that Postgres may spend on a query of entities.
+    pub query_timeout: Option<Duration>,
 }
 
 /// A Store based on Diesel and Postgres.
@@ -57,7 +70,103 @@ pub struct Store {
     logger: slog::Logger,
     subscriptions: Arc<RwLock<HashMap<String, Subscription>>>,
     change_listener: EntityChangeListener,
-    pub conn: Arc<Mutex<PgConnection>>,
+    pub conn: Pool<ConnectionManager<PgConnection>>,
+
+    /// The table layouts of the subgraphs that store their entities in a
+    /// table per entity type, by subgraph ID.
+    layouts: RwLock<HashMap<String, Arc<Layout>>>,
+
+    /// Postgres cancels queries of entities that take longer than this.
+    query_timeout: Option<Duration>,
+}
+
+/// Builds a predicate that matches entities that come after (`greater`) or
+/// before the cursor entity in the order of a query. This is only used for
+/// subgraphs without entity tables; `FindQuery` compares the typed order
+/// column of subgraphs that have them.
+fn cursor_predicate(
+    subgraph_id: &str,
+    entity_name: &str,
+    order_by: &Option<String>,
+    cursor: String,
+    greater: bool,
+) -> Box<BoxableExpression<entities::table, Pg, SqlType = Bool>> {
+    let op = if greater { ">" } else { "<" };
+    match order_by {
+        // Compare `(data ->> attribute, id)` rows so entities with the same
+        // attribute value as the cursor entity are not skipped. NULL counts
+        // as greater than all values, as in the order of the query; if the
+        // attribute of the cursor entity is NULL, only the IDs of entities
+        // whose attribute is NULL are compared, and otherwise entities whose
+        // attribute is NULL make the row comparison NULL. No index covers
+        // this expression, so Postgres compares all entities of the type
+        // with the cursor entity
+        Some(order_attribute) => Box::new(
+            sql::<Bool>("CASE WHEN (SELECT data ->> ")
+                .bind::<Text, _>(order_attribute.to_owned())
+                .sql(" FROM entities WHERE subgraph = ")
+                .bind::<Text, _>(subgraph_id.to_owned())
+                .sql(" AND entity = ")
+                .bind::<Text, _>(entity_name.to_owned())
+                .sql(" AND id = ")
+                .bind::<Text, _>(cursor.clone())
+                .sql(") IS NULL THEN data ->> ")
+                .bind::<Text, _>(order_attribute.to_owned())
+                .sql(if greater {
+                    " IS NULL AND id > "
+                } else {
+                    " IS NOT NULL OR id < "
+                }).bind::<Text, _>(cursor.clone())
+                .sql(" ELSE COALESCE((data ->> ")
+                .bind::<Text, _>(order_attribute.to_owned())
+                .sql(&format!(", id) {} ((SELECT data ->> ", op))
+                .bind::<Text, _>(order_attribute.to_owned())
+                .sql(" FROM entities WHERE subgraph = ")
+                .bind::<Text, _>(subgraph_id.to_owned())
+                .sql(" AND entity = ")
+                .bind::<Text, _>(entity_name.to_owned())
+                .sql(" AND id = ")
+                .bind::<Text, _>(cursor.clone())
+                .sql("), ")
+                .bind::<Text, _>(cursor)
+                .sql(&format!(
+                    "), {}) END",
+                    if greater { "TRUE" } else { "FALSE" }
+                )),
+        ),
+        None => Box::new(sql::<Bool>(&format!("id {} ", op)).bind::<Text, _>(cursor)),
+    }
+}
+
+/// Restores the order of a query whose entities were loaded in reverse to
+/// find the last ones, or drops everything but the last entities.
+fn restore_order(mut results: Vec<Entity>, reverse: bool, last: Option<usize>) -> Vec<Entity> {
+    if reverse {
+        results.reverse();
+    } else if let Some(last) = last {
+        let excess = results.len().saturating_sub(last);
+        results.drain(..excess);
+    }
+    results
+}
+
+/// The name of the Postgres schema that holds the tables of a layout.
+fn layout_schema_name(layout_id: i32) -> String {
+    format!("sgd{}", layout_id)
+}
+
+/// The name, subgraph ID, deployment time and status of a subgraph deployment.
+type SubgraphDeploymentRow = (String, String, SystemTime, String);
+
+fn subgraph_deployment_from_row(
+    (name, subgraph_id, deployed_at, status): SubgraphDeploymentRow,
+) -> Result<SubgraphDeployment, Error> {
+    Ok(SubgraphDeployment {
+        name,
+        subgraph_id,
+        deployed_at,
+        status: status.parse()?,
+    })
 }
 
 impl Store {
@@ -65,14 +174,21 @@ impl Store {
         // Create a store-specific logger
         let logger = logger.new(o!("component" => "Store"));
 
-        // Connect to Postgres
-        let conn =
-            PgConnection::establish(config.url.as_str()).expect("failed to connect to Postgres");
+        // Create a pool of connections to Postgres
+        let conn = Pool::builder()
+            .max_size(config.connection_pool_size)
+            .build(ConnectionManager::<PgConnection>::new(config.url.as_str()))
+            .expect("failed to connect to Postgres");
 
-        info!(logger, "Connected to Postgres"; "url" => &config.url);
+        info!(logger, "Connected to Postgres";
+              "url" => &config.url,
+              "pool_size" => config.connection_pool_size);
 
         // Create the entities table (if necessary)
-        initiate_schema(&logger, &conn);
+        initiate_schema(
+            &logger,
+            &*conn.get().expect("failed to get a Postgres connection"),
+        );
 
         // Listen to entity changes in Postgres
         let mut change_listener = EntityChangeListener::new(config.url.clone());
@@ -85,7 +201,9 @@ impl Store {
             logger: logger.clone(),
             subscriptions: Arc::new(RwLock::new(HashMap::new())),
             change_listener,
-            conn: Arc::new(Mutex::new(conn)),
+            conn,
+            layouts: RwLock::new(HashMap::new()),
+            query_timeout: config.query_timeout,
         };
 
         // Deal with store subscriptions
@@ -174,53 +292,113 @@ impl Store {
         );
     }
 
-    /// Handles block reorganizations.
-    /// Revert all store events related to the given block
-    pub fn revert_events(&self, block_hash: String, subgraph_id: String) {
-        select(revert_block(block_hash, subgraph_id))
-            .execute(&*self.conn.lock().unwrap())
-            .unwrap();
+    /// Takes a connection from the pool, waiting for one to become available
+    /// if all of them are in use.
+    fn get_conn(&self) -> Result<PooledConnection<ConnectionManager<PgConnection>>, Error> {
+        self.conn.get().map_err(Error::from)
     }
-}
 
-impl BasicStore for Store {
-    fn get(&self, key: StoreKey) -> Result<Entity, ()> {
-        debug!(self.logger, "get"; "key" => format!("{:?}", key));
+    /// Looks up the table layout of a subgraph using the given connection.
+    /// Returns `None` if the entities of the subgraph are stored in the
+    /// `entities` table.
+    fn layout(&self, conn: &PgConnection, subgraph: &str) -> Result<Option<Arc<Layout>>, Error> {
+        use db_schema::subgraph_layouts::dsl::*;
+
+        if let Some(layout) = self.layouts.read().unwrap().get(subgraph) {
+            return Ok(Some(layout.clone()));
+        }
+
+        let row = subgraph_layouts
+            .select((id, graphql_schema))
+            .filter(subgraph_id.eq(subgraph))
+            .first::<(i32, String)>(conn)
+            .optional()?;
 
+        match row {
+            Some((layout_id, schema)) => {
+                let document = graphql_parser::parse_schema(&schema)?;
+                let layout = Arc::new(Layout::new(
+                    subgraph,
+                    &layout_schema_name(layout_id),
+                    &document,
+                )?);
+                self.layouts
+                    .write()
+                    .unwrap()
+                    .insert(subgraph.to_owned(), layout.clone());
+                Ok(Some(layout))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Looks up an entity using the given connection. Returns `None` if the
+    /// entity does not exist.
+    fn get_entity(
+        conn: &PgConnection,
+        layout: Option<&Layout>,
+        key: &StoreKey,
+    ) -> Result<Option<Entity>, Error> {
         use db_schema::entities::dsl::*;
 
+        if let Some(layout) = layout {
+            let table = layout.table(&key.entity)?;
+            return match GetQuery::new(layout, table, &key.id)
+                .get_result::<serde_json::Value>(conn)
+                .optional()?
+            {
+                Some(value) => Ok(Some(table.entity_from_json(value)?)),
+                None => Ok(None),
+            };
+        }
+
         // Use primary key fields to get the entity; deserialize the result JSON
-        entities
-            .find((key.id, key.subgraph, key.entity))
+        let value = entities
+            .find((&key.id, &key.subgraph, &key.entity))
             .select(data)
-            .first::<serde_json::Value>(&*self.conn.lock().unwrap())
-            .map(|value| {
-                serde_json::from_value::<Entity>(value).expect("Failed to deserialize entity")
-            }).map_err(|_| ())
+            .first::<serde_json::Value>(conn)
+            .optional()?;
+
+        match value {
+            Some(value) => Ok(Some(serde_json::from_value::<Entity>(value)?)),
+            None => Ok(None),
+        }
     }
 
-    fn set(
-        &mut self,
+    /// Inserts or updates an entity using the given connection.
+    fn set_entity(
+        conn: &PgConnection,
+        layout: Option<&Layout>,
         key: StoreKey,
         input_entity: Entity,
         input_event_source: EventSource,
-    ) -> Result<(), ()> {
-        debug!(self.logger, "set"; "key" => format!("{:?}", key));
-
+    ) -> Result<(), Error> {
         use db_schema::entities::dsl::*;
 
         // Update the existing entity, if necessary
-        let updated_entity = match self.get(key.clone()) {
-            Ok(mut existing_entity) => {
+        let updated_entity = match Self::get_entity(conn, layout, &key)? {
+            Some(mut existing_entity) => {
                 existing_entity.merge(input_entity);
                 existing_entity
             }
-            Err(_) => input_entity,
+            None => input_entity,
         };
 
+        if let Some(layout) = layout {
+            let table = layout.table(&key.entity)?;
+            return InsertQuery::new(
+                layout,
+                table,
+                &key.id,
+                &updated_entity,
+                input_event_source.to_string(),
+            )?.execute(conn)
+            .map(|_| ())
+            .map_err(Error::from);
+        }
+
         // Convert Entity hashmap to serde_json::Value for insert
-        let entity_json: serde_json::Value =
-            serde_json::to_value(&updated_entity).expect("Failed to serialize entity");
+        let entity_json: serde_json::Value = serde_json::to_value(&updated_entity)?;
 
         // Insert entity, perform an update in case of a primary key conflict
         insert_into(entities)
@@ -238,45 +416,75 @@ impl BasicStore for Store {
                 subgraph.eq(&key.subgraph),
                 data.eq(&entity_json),
                 event_source.eq(&input_event_source.to_string()),
-            )).execute(&*self.conn.lock().unwrap())
+            )).execute(conn)
             .map(|_| ())
-            .map_err(|_| ())
+            .map_err(Error::from)
     }
 
-    fn delete(&mut self, key: StoreKey, input_event_source: EventSource) -> Result<(), ()> {
-        debug!(self.logger, "delete"; "key" => format!("{:?}", key));
-
+    /// Deletes an entity using the given connection. Must be called inside
+    /// a transaction, as the event source is passed to the entity history
+    /// triggers through a session variable.
+    fn delete_entity(
+        conn: &PgConnection,
+        layout: Option<&Layout>,
+        key: StoreKey,
+        input_event_source: EventSource,
+    ) -> Result<(), Error> {
         use db_schema::entities::dsl::*;
 
-        let conn = self.conn.lock().unwrap();
-        conn.transaction::<usize, result::Error, _>(|| {
-            // Set session variable to store the source of the event
-            select(set_config(
-                "vars.current_event_source",
-                input_event_source.to_string(),
-                false,
-            )).execute(&*conn)
-            .unwrap();
+        // Set a variable for the history triggers to store the source of
+        // the event; it is local to the transaction, so it does not leak
+        // into later uses of the pooled connection
+        select(set_config(
+            "vars.current_event_source",
+            input_event_source.to_string(),
+            true,
+        )).execute(conn)?;
+
+        if let Some(layout) = layout {
+            let table = layout.table(&key.entity)?;
+            return DeleteQuery::new(layout, table, &key.id)
+                .execute(conn)
+                .map(|_| ())
+                .map_err(Error::from);
+        }
 
-            // Delete from DB where rows match the subgraph ID, entity name and ID
-            delete(
-                entities
-                    .filter(subgraph.eq(&key.subgraph))
-                    .filter(entity.eq(&key.entity))
-                    .filter(id.eq(&key.id)),
-            ).execute(&*conn)
-        }).map(|_| ())
-        .map_err(|_| ())
+        // Delete from DB where rows match the subgraph ID, entity name and ID
+        delete(
+            entities
+                .filter(subgraph.eq(&key.subgraph))
+                .filter(entity.eq(&key.entity))
+                .filter(id.eq(&key.id)),
+        ).execute(conn)
+        .map(|_| ())
+        .map_err(Error::from)
     }
 
-    fn find(&self, query: StoreQuery) -> Result<Vec<Entity>, ()> {
+    /// Finds entities using the given connection.
+    fn find_with_conn(&self, conn: &PgConnection, query: StoreQuery) -> Result<Vec<Entity>, ()> {
         use db_schema::entities::dsl::*;
 
+        let layout = self.layout(conn, &query.subgraph).map_err(|e| {
+            error!(self.logger, "Failed to look up the entity tables of the subgraph";
+                   "subgraph" => &query.subgraph,
+                   "error" => e.to_string())
+        })?;
+        if let Some(layout) = layout {
+            return self.find_in_table(conn, &layout, query);
+        }
+
+        // The history of entities in the `entities` table cannot be queried
+        if query.block.is_some() {
+            error!(self.logger, "Querying entities at a block requires entity tables";
+                   "subgraph" => &query.subgraph);
+            return Err(());
+        }
+
         // Create base boxed query; this will be added to based on the
         // query parameters provided
         let mut diesel_query = entities
-            .filter(entity.eq(query.entity))
-            .filter(subgraph.eq(query.subgraph))
+            .filter(entity.eq(query.entity.clone()))
+            .filter(subgraph.eq(query.subgraph.clone()))
             .select(data)
             .into_boxed::<Pg>();
 
@@ -289,43 +497,311 @@ impl BasicStore for Store {
             })?;
         }
 
-        // Add order by filters to query
-        if let Some(order_attribute) = query.order_by {
-            let direction = query
-                .order_direction
-                .map(|direction| match direction {
-                    StoreOrder::Ascending => String::from("ASC"),
-                    StoreOrder::Descending => String::from("DESC"),
-                }).unwrap_or(String::from("ASC"));
+        // Add cursor bounds to query; these compare the order attribute and
+        // ID of entities with those of the cursor entities
+        let ascending = query.order_direction != Some(StoreOrder::Descending);
+        let cursors = vec![(query.after, ascending), (query.before, !ascending)];
+        for (cursor, greater) in cursors {
+            if let Some(cursor) = cursor {
+                diesel_query = diesel_query.filter(cursor_predicate(
+                    &query.subgraph,
+                    &query.entity,
+                    &query.order_by,
+                    cursor,
+                    greater,
+                ));
+            }
+        }
 
-            diesel_query = diesel_query.order(
+        // Without a range, the last entities can be found by reversing the
+        // order and reversing the results again after loading them
+        let reverse = query.last.is_some() && query.range.is_none();
+
+        // Order by the order attribute, if present, and by ID; ordering by
+        // ID makes the order unique, which is required for cursors to work.
+        // Entities without the attribute come last in ascending order
+        let direction = if ascending != reverse { "ASC" } else { "DESC" };
+        let nulls = if ascending != reverse {
+            "NULLS LAST"
+        } else {
+            "NULLS FIRST"
+        };
+        diesel_query = match query.order_by {
+            Some(order_attribute) => diesel_query.order(
                 sql::<Text>("data ->> ")
                     .bind::<Text, _>(order_attribute)
-                    .sql(&format!(" {} ", direction)),
-            )
-        }
+                    .sql(&format!(" {} {}, id {} ", direction, nulls, direction)),
+            ),
+            None => diesel_query.order(sql::<Text>(&format!("id {} ", direction))),
+        };
 
         // Add range filter to query
         if let Some(range) = query.range {
             diesel_query = diesel_query
                 .limit(range.first as i64)
                 .offset(range.skip as i64);
+        } else if let Some(last) = query.last {
+            diesel_query = diesel_query.limit(last as i64);
         }
 
         debug!(self.logger, "find";
                 "sql" => format!("{:?}", debug_query::<Pg, _>(&diesel_query)));
 
         // Process results; deserialize JSON data
-        diesel_query
-            .load::<serde_json::Value>(&*self.conn.lock().unwrap())
-            .map(|values| {
+        let values = diesel_query
+            .load::<serde_json::Value>(conn)
+            .map_err(|_| ())?;
+        let results = values
+            .into_iter()
+            .map(|value| {
+                serde_json::from_value::<Entity>(value).expect("Error to deserialize entity")
+            }).collect::<Vec<_>>();
+
+        Ok(restore_order(results, reverse, query.last))
+    }
+
+    /// Finds entities in the table of their entity type.
+    fn find_in_table(
+        &self,
+        conn: &PgConnection,
+        layout: &Layout,
+        query: StoreQuery,
+    ) -> Result<Vec<Entity>, ()> {
+        let table = layout.table(&query.entity).map_err(|e| {
+            error!(self.logger, "Failed to find entities";
+                   "error" => e.to_string())
+        })?;
+
+        // Without a range, the last entities can be found by reversing the
+        // order and reversing the results again after loading them
+        let ascending = query.order_direction != Some(StoreOrder::Descending);
+        let reverse = query.last.is_some() && query.range.is_none();
+        let last = query.last;
+
+        let find_query = FindQuery::new(layout, table, query, ascending, reverse).map_err(|e| {
+            error!(self.logger, "value does not support this filter";
+                                "value" => format!("{:?}", e.value),
+                                "filter" => e.filter)
+        })?;
+
+        debug!(self.logger, "find";
+                "sql" => format!("{:?}", debug_query::<Pg, _>(&find_query)));
+
+        let results = find_query
+            .load::<serde_json::Value>(conn)
+            .map_err(Error::from)
+            .and_then(|values| {
                 values
                     .into_iter()
-                    .map(|value| {
-                        serde_json::from_value::<Entity>(value)
-                            .expect("Error to deserialize entity")
-                    }).collect()
-            }).map_err(|_| ())
+                    .map(|value| table.entity_from_json(value))
+                    .collect::<Result<Vec<_>, _>>()
+            }).map_err(|e| {
+                error!(self.logger, "Failed to find entities";
+                       "error" => e.to_string())
+            })?;
+
+        Ok(restore_order(results, reverse, last))
+    }
+
+    /// Counts the entities of a subgraph.
+    fn entity_count(&self, subgraph_id: &str) -> Result<i64, Error> {
+        let conn = self.get_conn()?;
+        Ok(match self.layout(&*conn, subgraph_id)? {
+            // Sum up the row counts of all entity tables of the subgraph
+            Some(layout) => {
+                let counts = layout
+                    .tables
+                    .values()
+                    .map(|table| {
+                        format!(
+                            "(SELECT count(*) FROM \"{}\".\"{}\")",
+                            layout.schema, table.name
+                        )
+                    }).collect::<Vec<_>>();
+                if counts.is_empty() {
+                    0
+                } else {
+                    select(sql::<BigInt>(&counts.join(" + "))).get_result::<i64>(&*conn)?
+                }
+            }
+            None => entities::table
+                .filter(entities::subgraph.eq(subgraph_id))
+                .count()
+                .get_result::<i64>(&*conn)?,
+        })
+    }
+
+    /// Looks up the subgraph deployment with the given name using the
+    /// given connection.
+    fn get_subgraph_deployment(
+        conn: &PgConnection,
+        deployment_name: &str,
+    ) -> Result<Option<SubgraphDeployment>, Error> {
+        use db_schema::subgraph_deployments::dsl::*;
+
+        let row = subgraph_deployments
+            .select((name, subgraph_id, deployed_at, status))
+            .filter(name.eq(deployment_name))
+            .first::<SubgraphDeploymentRow>(conn)
+            .optional()?;
+
+        match row {
+            Some(row) => subgraph_deployment_from_row(row).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Records that the entities of the subgraph were changed in the given
+    /// block, which makes the changes visible to queries at later blocks.
+    fn add_entity_block(
+        conn: &PgConnection,
+        subgraph_id: &str,
+        block_ptr: &EthereumBlockPointer,
+    ) -> Result<(), Error> {
+        use db_schema::subgraph_entity_blocks::dsl;
+
+        insert_into(dsl::subgraph_entity_blocks)
+            .values((
+                dsl::subgraph_id.eq(subgraph_id),
+                dsl::block_hash.eq(EventSource::EthereumBlock(block_ptr.hash).to_string()),
+                dsl::block_number.eq(block_ptr.number as i64),
+            )).on_conflict_do_nothing()
+            .execute(conn)
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+
+    /// Moves the block pointer of a subgraph using the given connection.
+    fn update_block_ptr(
+        conn: &PgConnection,
+        subgraph_id: String,
+        block_ptr: EthereumBlockPointer,
+    ) -> Result<(), Error> {
+        use db_schema::subgraphs::dsl::*;
+
+        update(subgraphs)
+            .set((
+                latest_block_hash.eq::<Option<String>>(Some(format!("{:x}", block_ptr.hash))),
+                latest_block_number.eq::<Option<i64>>(Some(block_ptr.number as i64)),
+            )).filter(id.eq(&subgraph_id))
+            .execute(conn)
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+}
+
+impl BasicStore for Store {
+    fn get(&self, key: StoreKey) -> Result<Option<Entity>, Error> {
+        debug!(self.logger, "get"; "key" => format!("{:?}", key));
+        let _timer = STORE_OPERATION_DURATION
+            .with_label_values(&["get"])
+            .start_timer();
+
+        let conn = self.get_conn()?;
+        let layout = self.layout(&*conn, &key.subgraph)?;
+        Self::get_entity(&*conn, layout.as_ref().map(Arc::as_ref), &key)
+    }
+
+    fn set(
+        &self,
+        key: StoreKey,
+        input_entity: Entity,
+        input_event_source: EventSource,
+    ) -> Result<(), ()> {
+        debug!(self.logger, "set"; "key" => format!("{:?}", key));
+        let _timer = STORE_OPERATION_DURATION
+            .with_label_values(&["set"])
+            .start_timer();
+
+        let conn = self.get_conn().map_err(|_| ())?;
+        let layout = self.layout(&*conn, &key.subgraph).map_err(|_| ())?;
+        Self::set_entity(
+            &*conn,
+            layout.as_ref().map(Arc::as_ref),
+            key,
+            input_entity,
+            input_event_source,
+        ).map_err(|_| ())
+    }
+
+    fn delete(&self, key: StoreKey, input_event_source: EventSource) -> Result<(), ()> {
+        debug!(self.logger, "delete"; "key" => format!("{:?}", key));
+        let _timer = STORE_OPERATION_DURATION
+            .with_label_values(&["delete"])
+            .start_timer();
+
+        let conn = self.get_conn().map_err(|_| ())?;
+        let layout = self.layout(&*conn, &key.subgraph).map_err(|_| ())?;
+        conn.transaction(|| {
+            Self::delete_entity(
+                &*conn,
+                layout.as_ref().map(Arc::as_ref),
+                key,
+                input_event_source,
+            )
+        }).map_err(|_| ())
+    }
+
+    fn find(&self, query: StoreQuery) -> Result<Vec<Entity>, ()> {
+        let _timer = STORE_OPERATION_DURATION
+            .with_label_values(&["find"])
+            .start_timer();
+
+        let conn = self.get_conn().map_err(|_| ())?;
+
+        // The timeout only applies to the transaction of the query, not to
+        // later uses of the pooled connection
+        conn.transaction::<_, Error, _>(|| {
+            if let Some(timeout) = self.query_timeout {
+                let timeout_ms = timeout.as_secs() * 1000 + u64::from(timeout.subsec_millis());
+                conn.batch_execute(&format!("SET LOCAL statement_timeout = {}", timeout_ms))?;
+            }
+            Ok(self.find_with_conn(&*conn, query))
+        }).unwrap_or_else(|e| {
+            error!(self.logger, "Failed to set the timeout of a query";
+                   "error" => e.to_string());
+            Err(())
+        })
+    }
+
+    fn transact_block_operations(
+        &self,
+        subgraph_id: String,
+        block_ptr: EthereumBlockPointer,
+        operations: Vec<EntityOperation>,
+    ) -> Result<(), Error> {
+        debug!(self.logger, "transact block operations";
+               "subgraph" => &subgraph_id,
+               "block_number" => block_ptr.number,
+               "operations" => operations.len());
+
+        let event_source = EventSource::EthereumBlock(block_ptr.hash);
+
+        let conn = self.get_conn()?;
+        let layout = self.layout(&*conn, &subgraph_id)?;
+        let layout = layout.as_ref().map(Arc::as_ref);
+        conn.transaction(|| {
+            if !operations.is_empty() {
+                Self::add_entity_block(&*conn, &subgraph_id, &block_ptr)?;
+            }
+            for operation in operations {
+                match operation {
+                    EntityOperation::Set(key, entity) => {
+                        let _timer = STORE_OPERATION_DURATION
+                            .with_label_values(&["set"])
+                            .start_timer();
+                        Self::set_entity(&*conn, layout, key, entity, event_source.clone())?
+                    }
+                    EntityOperation::Remove(key) => {
+                        let _timer = STORE_OPERATION_DURATION
+                            .with_label_values(&["delete"])
+                            .start_timer();
+                        Self::delete_entity(&*conn, layout, key, event_source.clone())?
+                    }
+                }
+            }
+            Self::update_block_ptr(&*conn, subgraph_id, block_ptr)
+        })
     }
 }
 
@@ -341,7 +817,7 @@ impl BlockStore for Store {
         let network_identifiers_opt = ethereum_networks
             .select((net_version, genesis_block_hash))
             .filter(name.eq(new_network_name))
-            .first::<(Option<String>, Option<String>)>(&*self.conn.lock().unwrap())
+            .first::<(Option<String>, Option<String>)>(&*self.get_conn()?)
             .optional()?;
 
         match network_identifiers_opt {
@@ -357,7 +833,7 @@ impl BlockStore for Store {
                             .eq::<Option<String>>(Some(format!("{:x}", new_genesis_block_hash))),
                     )).on_conflict(name)
                     .do_nothing()
-                    .execute(&*self.conn.lock().unwrap())?;
+                    .execute(&*self.get_conn()?)?;
             }
 
             // Network is in database and has identifiers
@@ -389,7 +865,7 @@ impl BlockStore for Store {
                         genesis_block_hash
                             .eq::<Option<String>>(Some(format!("{:x}", new_genesis_block_hash))),
                     )).filter(name.eq(new_network_name))
-                    .execute(&*self.conn.lock().unwrap())?;
+                    .execute(&*self.get_conn()?)?;
             }
         }
 
@@ -420,7 +896,7 @@ impl BlockStore for Store {
                 .on_conflict(hash)
                 .do_update()
                 .set(values)
-                .execute(&*conn.lock().unwrap())
+                .execute(&*conn.get()?)
                 .map_err(Error::from)
                 .map(|_| ())
         }))
@@ -433,7 +909,7 @@ impl BlockStore for Store {
     ) -> Result<Vec<H256>, Error> {
         // Call attempt_head_update SQL function
         select(attempt_head_update(network_name, ancestor_count as i64))
-            .load(&*self.conn.lock().unwrap())
+            .load(&*self.get_conn()?)
             .map_err(Error::from)
 
             // We got a single return value, but it's returned generically as a set of rows
@@ -450,10 +926,60 @@ impl BlockStore for Store {
             })
             .and_then(|r| r.map_err(Error::from))
     }
+
+    fn chain_head_ptr(&self, net_name: &str) -> Result<Option<EthereumBlockPointer>, Error> {
+        use db_schema::ethereum_networks::dsl::*;
+
+        let head_opt = ethereum_networks
+            .select((head_block_hash, head_block_number))
+            .filter(name.eq(net_name))
+            .first::<(Option<String>, Option<i64>)>(&*self.get_conn()?)
+            .optional()?;
+
+        match head_opt {
+            Some((Some(block_hash), Some(block_number))) => {
+                // Block hashes in the block store are prefixed with 0x
+                let block_hash: H256 = block_hash.trim_left_matches("0x").parse()?;
+                Ok(Some(EthereumBlockPointer::from((block_hash, block_number))))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn block(&self, block_hash: H256) -> Result<Option<Block<Transaction>>, Error> {
+        use db_schema::ethereum_blocks::dsl::*;
+
+        let json_opt = ethereum_blocks
+            .select(data)
+            .filter(hash.eq(format!("{:#x}", block_hash)))
+            .first::<serde_json::Value>(&*self.get_conn()?)
+            .optional()?;
+
+        match json_opt {
+            Some(json) => Ok(Some(serde_json::from_value(json)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn ancestor_block(
+        &self,
+        block_ptr: EthereumBlockPointer,
+        offset: u64,
+    ) -> Result<Option<Block<Transaction>>, Error> {
+        let json_opt = select(lookup_ancestor_block(
+            format!("{:#x}", block_ptr.hash),
+            offset as i64,
+        )).first::<Option<serde_json::Value>>(&*self.get_conn()?)?;
+
+        match json_opt {
+            Some(json) => Ok(Some(serde_json::from_value(json)?)),
+            None => Ok(None),
+        }
+    }
 }
 
 impl StoreTrait for Store {
-    fn subscribe(&mut self, entities: Vec<SubgraphEntityPair>) -> EntityChangeStream {
+    fn subscribe(&self, entities: Vec<SubgraphEntityPair>) -> EntityChangeStream {
         let subscriptions = self.subscriptions.clone();
 
         // Generate a new (unique) UUID; we're looping just to be sure we avoid collisions
@@ -477,4 +1003,335 @@ impl StoreTrait for Store {
         // Return the subscription ID and entity change stream
         Box::new(receiver)
     }
+
+    fn add_subgraph_if_missing(&self, subgraph_id: String, net_name: String) -> Result<(), Error> {
+        use db_schema::subgraphs::dsl::*;
+
+        insert_into(subgraphs)
+            .values((
+                id.eq(&subgraph_id),
+                network_name.eq(&net_name),
+                latest_block_hash.eq::<Option<String>>(None),
+                latest_block_number.eq::<Option<i64>>(None),
+            )).on_conflict(id)
+            .do_nothing()
+            .execute(&*self.get_conn()?)
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+
+    fn create_entity_tables(&self, subgraph_id: String, schema: &Schema) -> Result<(), Error> {
+        use db_schema::subgraph_layouts::dsl;
+
+        let conn = self.get_conn()?;
+        let layout = conn.transaction(|| -> Result<Option<Layout>, Error> {
+            // Switching the storage of a subgraph that has already indexed
+            // entities would lose them
+            let has_entities = select(exists(
+                entities::table.filter(entities::subgraph.eq(&subgraph_id)),
+            )).get_result::<bool>(&*conn)?;
+            if has_entities {
+                return Ok(None);
+            }
+
+            // Schemas that can't be mapped to tables keep storing their
+            // entities in the `entities` table
+            let layout = match Layout::new(&subgraph_id, "", &schema.document) {
+                Ok(layout) => layout,
+                Err(e) => {
+                    warn!(self.logger, "Storing entities in the entities table, \
+                                        since the schema cannot be mapped to tables";
+                          "subgraph" => &subgraph_id,
+                          "error" => format!("{}", e));
+                    return Ok(None);
+                }
+            };
+
+            let layout_id = insert_into(dsl::subgraph_layouts)
+                .values((
+                    dsl::subgraph_id.eq(&subgraph_id),
+                    dsl::graphql_schema.eq(schema.document.to_string()),
+                )).on_conflict(dsl::subgraph_id)
+                .do_nothing()
+                .returning(dsl::id)
+                .get_result::<i32>(&*conn)
+                .optional()?;
+
+            // The tables exist already if the subgraph had a layout
+            match layout_id {
+                Some(layout_id) => {
+                    let layout = Layout {
+                        schema: layout_schema_name(layout_id),
+                        ..layout
+                    };
+                    conn.batch_execute(&layout.create_sql())?;
+                    Ok(Some(layout))
+                }
+                None => Ok(None),
+            }
+        })?;
+
+        if let Some(layout) = layout {
+            info!(self.logger, "Created entity tables";
+                  "subgraph" => &subgraph_id,
+                  "schema" => &layout.schema,
+                  "tables" => layout.tables.len());
+            self.layouts
+                .write()
+                .unwrap()
+                .insert(subgraph_id, Arc::new(layout));
+        }
+        Ok(())
+    }
+
+    fn block_ptr(&self, subgraph_id: String) -> Result<Option<EthereumBlockPointer>, Error> {
+        use db_schema::subgraphs::dsl::*;
+
+        let (block_hash, block_number) = subgraphs
+            .select((latest_block_hash, latest_block_number))
+            .filter(id.eq(&subgraph_id))
+            .first::<(Option<String>, Option<i64>)>(&*self.get_conn()?)?;
+
+        match (block_hash, block_number) {
+            (Some(block_hash), Some(block_number)) => {
+                let block_hash: H256 = block_hash.parse()?;
+                Ok(Some(EthereumBlockPointer::from((block_hash, block_number))))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn set_block_ptr(
+        &self,
+        subgraph_id: String,
+        block_ptr: EthereumBlockPointer,
+    ) -> Result<(), Error> {
+        Self::update_block_ptr(&*self.get_conn()?, subgraph_id, block_ptr)
+    }
+
+    fn revert_events(&self, block_hash: String, subgraph_id: String) -> Result<(), Error> {
+        use db_schema::subgraph_dynamic_data_sources::dsl;
+        use db_schema::subgraph_entity_blocks::dsl as blocks;
+
+        let conn = self.get_conn()?;
+        conn.transaction(|| {
+            select(revert_block(block_hash.clone(), subgraph_id.clone())).execute(&*conn)?;
+
+            // Hide the reverted changes from queries at past blocks
+            delete(
+                blocks::subgraph_entity_blocks
+                    .filter(blocks::subgraph_id.eq(&subgraph_id))
+                    .filter(blocks::block_hash.eq(&block_hash)),
+            ).execute(&*conn)?;
+
+            // Remove the data sources that were created in the block
+            delete(
+                dsl::subgraph_dynamic_data_sources
+                    .filter(dsl::subgraph_id.eq(&subgraph_id))
+                    .filter(dsl::block_hash.eq(&block_hash)),
+            ).execute(&*conn)
+            .map(|_| ())
+            .map_err(Error::from)
+        })
+    }
+
+    fn add_dynamic_data_sources(
+        &self,
+        subgraph_id: String,
+        block_ptr: EthereumBlockPointer,
+        data_sources: Vec<DynamicDataSource>,
+    ) -> Result<(), Error> {
+        use db_schema::subgraph_dynamic_data_sources::dsl;
+
+        let source_block_hash = EventSource::EthereumBlock(block_ptr.hash).to_string();
+        let rows = data_sources
+            .iter()
+            .map(|data_source| {
+                (
+                    dsl::subgraph_id.eq(&subgraph_id),
+                    dsl::template.eq(&data_source.template),
+                    dsl::address.eq(format!("{:x}", data_source.address)),
+                    dsl::block_hash.eq(&source_block_hash),
+                    dsl::block_number.eq(block_ptr.number as i64),
+                )
+            }).collect::<Vec<_>>();
+
+        insert_into(dsl::subgraph_dynamic_data_sources)
+            .values(rows)
+            .on_conflict((dsl::subgraph_id, dsl::template, dsl::address))
+            .do_nothing()
+            .execute(&*self.get_conn()?)
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+
+    fn dynamic_data_sources(&self, subgraph_id: String) -> Result<Vec<DynamicDataSource>, Error> {
+        use db_schema::subgraph_dynamic_data_sources::dsl;
+
+        dsl::subgraph_dynamic_data_sources
+            .select((dsl::template, dsl::address))
+            .filter(dsl::subgraph_id.eq(&subgraph_id))
+            .order(dsl::id.asc())
+            .load::<(String, String)>(&*self.get_conn()?)?
+            .into_iter()
+            .map(|(template, address)| {
+                Ok(DynamicDataSource {
+                    template,
+                    address: address.parse()?,
+                })
+            }).collect()
+    }
+
+    fn subgraph_health(&self, subgraph_id: String) -> Result<SubgraphHealth, Error> {
+        use db_schema::subgraphs::dsl::*;
+
+        let (health_name, error, block_hash, block_number) = subgraphs
+            .select((
+                health,
+                health_error,
+                health_error_block_hash,
+                health_error_block_number,
+            )).filter(id.eq(&subgraph_id))
+            .first::<(String, Option<String>, Option<String>, Option<i64>)>(&*self.get_conn()?)?;
+
+        match health_name.as_str() {
+            "syncing" => Ok(SubgraphHealth::Syncing),
+            "synced" => Ok(SubgraphHealth::Synced),
+            "failed" => Ok(SubgraphHealth::Failed {
+                error: error.unwrap_or_default(),
+                block_ptr: match (block_hash, block_number) {
+                    (Some(block_hash), Some(block_number)) => {
+                        let block_hash: H256 = block_hash.parse()?;
+                        Some(EthereumBlockPointer::from((block_hash, block_number)))
+                    }
+                    _ => None,
+                },
+            }),
+            _ => Err(format_err!("invalid subgraph health: {}", health_name)),
+        }
+    }
+
+    fn set_subgraph_health(
+        &self,
+        subgraph_id: String,
+        new_health: SubgraphHealth,
+    ) -> Result<(), Error> {
+        use db_schema::subgraphs::dsl::*;
+
+        let (error, block_ptr) = match new_health {
+            SubgraphHealth::Failed {
+                ref error,
+                block_ptr,
+            } => (Some(error.clone()), block_ptr),
+            _ => (None, None),
+        };
+
+        update(subgraphs)
+            .set((
+                health.eq(new_health.to_string()),
+                health_error.eq(error),
+                health_error_block_hash.eq(block_ptr.map(|ptr| format!("{:x}", ptr.hash))),
+                health_error_block_number.eq(block_ptr.map(|ptr| ptr.number as i64)),
+            )).filter(id.eq(&subgraph_id))
+            .execute(&*self.get_conn()?)
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+
+    fn subgraph_indexing_status(
+        &self,
+        subgraph_id: String,
+    ) -> Result<Option<SubgraphIndexingStatus>, Error> {
+        use db_schema::subgraphs::dsl::*;
+
+        let network = match subgraphs
+            .select(network_name)
+            .filter(id.eq(&subgraph_id))
+            .first::<String>(&*self.get_conn()?)
+            .optional()?
+        {
+            Some(network) => network,
+            None => return Ok(None),
+        };
+
+        let entity_count = self.entity_count(&subgraph_id)?;
+
+        Ok(Some(SubgraphIndexingStatus {
+            latest_block_ptr: self.block_ptr(subgraph_id.clone())?,
+            chain_head_block_ptr: self.chain_head_ptr(&network)?,
+            entity_count: entity_count as u64,
+            health: self.subgraph_health(subgraph_id.clone())?,
+            subgraph_id,
+            network_name: network,
+        }))
+    }
+}
+
+impl SubgraphDeploymentStore for Store {
+    fn upsert_subgraph_deployment(
+        &self,
+        deployment_name: String,
+        new_subgraph_id: String,
+    ) -> Result<Option<SubgraphDeployment>, Error> {
+        use db_schema::subgraph_deployments::dsl::*;
+
+        let conn = self.get_conn()?;
+        conn.transaction(|| {
+            let old_deployment = Self::get_subgraph_deployment(&*conn, &deployment_name)?;
+
+            let now = SystemTime::now();
+            let deployed = SubgraphDeploymentStatus::Deployed.to_string();
+            insert_into(subgraph_deployments)
+                .values((
+                    name.eq(&deployment_name),
+                    subgraph_id.eq(&new_subgraph_id),
+                    deployed_at.eq(now),
+                    status.eq(&deployed),
+                )).on_conflict(name)
+                .do_update()
+                .set((
+                    subgraph_id.eq(&new_subgraph_id),
+                    deployed_at.eq(now),
+                    status.eq(&deployed),
+                )).execute(&*conn)?;
+
+            Ok(old_deployment)
+        })
+    }
+
+    fn set_subgraph_deployment_status(
+        &self,
+        deployment_name: String,
+        new_status: SubgraphDeploymentStatus,
+    ) -> Result<(), Error> {
+        use db_schema::subgraph_deployments::dsl::*;
+
+        update(subgraph_deployments)
+            .set(status.eq(new_status.to_string()))
+            .filter(name.eq(&deployment_name))
+            .execute(&*self.get_conn()?)
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+
+    fn remove_subgraph_deployment(&self, deployment_name: String) -> Result<(), Error> {
+        use db_schema::subgraph_deployments::dsl::*;
+
+        delete(subgraph_deployments.filter(name.eq(&deployment_name)))
+            .execute(&*self.get_conn()?)
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+
+    fn subgraph_deployments(&self) -> Result<Vec<SubgraphDeployment>, Error> {
+        use db_schema::subgraph_deployments::dsl::*;
+
+        subgraph_deployments
+            .select((name, subgraph_id, deployed_at, status))
+            .order(name.asc())
+            .load::<SubgraphDeploymentRow>(&*self.get_conn()?)?
+            .into_iter()
+            .map(subgraph_deployment_from_row)
+            .collect()
+    }
 }
@@ -4,18 +4,20 @@ extern crate futures;
 extern crate lazy_static;
 extern crate graph;
 extern crate graph_store_postgres;
+extern crate graphql_parser;
 
 use diesel::pg::PgConnection;
 use diesel::*;
 use std::fmt::Debug;
 use std::panic;
 use std::sync::Mutex;
+use std::time::Duration;
 
 use graph::components::store::{
-    EventSource, StoreFilter, StoreKey, StoreOrder, StoreQuery, StoreRange,
+    EntityOperation, EventSource, StoreFilter, StoreKey, StoreOrder, StoreQuery, StoreRange,
 };
 use graph::prelude::*;
-use graph::web3::types::H256;
+use graph::web3::types::{H160, H256};
 use graph_store_postgres::{db_schema, Store as DieselStore, StoreConfig};
 
 /// Helper function to ensure and obtain the Postgres URL to use for testing.
@@ -99,7 +101,14 @@ fn create_test_entity(
 fn insert_test_data() {
     let logger = Logger::root(slog::Discard, o!());
     let url = postgres_test_url();
-    let mut store = DieselStore::new(StoreConfig { url }, &logger);
+    let store = DieselStore::new(
+        StoreConfig {
+            url,
+            connection_pool_size: 10,
+            query_timeout: None,
+        },
+        &logger,
+    );
 
     let test_entity_1 = create_test_entity(
         String::from("1"),
@@ -162,11 +171,40 @@ fn insert_test_data() {
 /// Removes test data from the database behind the store.
 fn remove_test_data() {
     use db_schema::entities::dsl::*;
+    use db_schema::subgraph_deployments::dsl::subgraph_deployments;
+    use db_schema::subgraph_dynamic_data_sources::dsl::subgraph_dynamic_data_sources;
+    use db_schema::subgraph_entity_blocks::dsl::subgraph_entity_blocks;
+    use db_schema::subgraph_layouts::dsl::subgraph_layouts;
+    use db_schema::subgraphs::dsl::subgraphs;
     let url = postgres_test_url();
     let conn = PgConnection::establish(url.as_str()).expect("Failed to connect to Postgres");
+    let layout_ids = subgraph_layouts
+        .select(db_schema::subgraph_layouts::id)
+        .load::<i32>(&conn)
+        .expect("Failed to load test subgraph layouts");
+    for layout_id in layout_ids {
+        sql_query(format!("DROP SCHEMA sgd{} CASCADE", layout_id))
+            .execute(&conn)
+            .expect("Failed to remove test entity tables");
+    }
+    delete(subgraph_layouts)
+        .execute(&conn)
+        .expect("Failed to remove test subgraph layouts");
     delete(entities)
         .execute(&conn)
         .expect("Failed to remove test data");
+    delete(subgraphs)
+        .execute(&conn)
+        .expect("Failed to remove test subgraphs");
+    delete(subgraph_deployments)
+        .execute(&conn)
+        .expect("Failed to remove test subgraph deployments");
+    delete(subgraph_dynamic_data_sources)
+        .execute(&conn)
+        .expect("Failed to remove test dynamic data sources");
+    delete(subgraph_entity_blocks)
+        .execute(&conn)
+        .expect("Failed to remove test entity blocks");
 }
 
 #[test]
@@ -175,7 +213,14 @@ fn delete_entity() {
         use db_schema::entities::dsl::*;
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let mut store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
 
         let test_key = StoreKey {
             subgraph: String::from("test_subgraph"),
@@ -188,7 +233,7 @@ fn delete_entity() {
         //Get all ids in table
         let all_ids = entities
             .select(id)
-            .load::<String>(&*store.conn.lock().unwrap())
+            .load::<String>(&*store.conn.get().unwrap())
             .unwrap();
 
         // Check that that the deleted entity id is not present
@@ -203,14 +248,21 @@ fn get_entity() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
 
         let key = StoreKey {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
             id: String::from("1"),
         };
-        let result = store.get(key).unwrap();
+        let result = store.get(key).unwrap().unwrap();
 
         let mut expected_entity = Entity::new();
         expected_entity.insert(String::from("name"), Value::String(String::from("Johnton")));
@@ -236,7 +288,14 @@ fn insert_entity() {
 
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let mut store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
 
         let test_entity_1 = create_test_entity(
             String::from("7"),
@@ -255,7 +314,7 @@ fn insert_entity() {
         // Check that new record is in the store
         let all_ids = entities
             .select(id)
-            .load::<String>(&*store.conn.lock().unwrap())
+            .load::<String>(&*store.conn.get().unwrap())
             .unwrap();
         assert!(all_ids.iter().any(|x| x == &String::from("7")));
 
@@ -268,7 +327,14 @@ fn update_existing() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let mut store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
 
         let entity_key = StoreKey {
             subgraph: String::from("test_subgraph"),
@@ -288,7 +354,10 @@ fn update_existing() {
         );
 
         // Verify that the entity before updating is different from what we expect afterwards
-        assert_ne!(store.get(entity_key.clone()).unwrap(), test_entity_1.1);
+        assert_ne!(
+            store.get(entity_key.clone()).unwrap().unwrap(),
+            test_entity_1.1
+        );
 
         // Set test entity; as the entity already exists an update should be performed
         store
@@ -296,7 +365,7 @@ fn update_existing() {
             .expect("Failed to update entity that already exists");
 
         // Verify that the entity in the store has changed to what we have set
-        assert_eq!(store.get(entity_key).unwrap(), test_entity_1.1);
+        assert_eq!(store.get(entity_key).unwrap().unwrap(), test_entity_1.1);
 
         Ok(())
     })
@@ -307,7 +376,14 @@ fn partially_update_existing() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let mut store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
 
         let entity_key = StoreKey {
             subgraph: String::from("test_subgraph"),
@@ -321,7 +397,7 @@ fn partially_update_existing() {
             ("email", Value::Null),
         ]);
 
-        let original_entity = store.get(entity_key.clone()).unwrap();
+        let original_entity = store.get(entity_key.clone()).unwrap().unwrap();
         let event_source = EventSource::EthereumBlock(H256::random());
         // Verify that the entity before updating is different from what we expect afterwards
         assert_ne!(original_entity, partial_entity);
@@ -332,7 +408,7 @@ fn partially_update_existing() {
             .expect("Failed to update entity that already exists");
 
         // Obtain the updated entity from the store
-        let updated_entity = store.get(entity_key).unwrap();
+        let updated_entity = store.get(entity_key).unwrap().unwrap();
 
         // Verify that the values of all attributes we have set were either unset
         // (in the case of Value::Null) or updated to the new values
@@ -354,7 +430,14 @@ fn find_string_contains() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -365,6 +448,10 @@ fn find_string_contains() {
             order_by: None,
             order_direction: None,
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -377,12 +464,58 @@ fn find_string_contains() {
     })
 }
 
+#[test]
+fn find_with_query_timeout() {
+    run_test(|| -> Result<(), ()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 1,
+                query_timeout: Some(Duration::from_secs(30)),
+            },
+            &logger,
+        );
+        let this_query = StoreQuery {
+            subgraph: String::from("test_subgraph"),
+            entity: String::from("user"),
+            filter: None,
+            order_by: None,
+            order_direction: None,
+            range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
+        };
+        let returned_entities = store.find(this_query).expect("store.find operation failed");
+        assert_eq!(returned_entities.len(), 3);
+
+        // The timeout does not outlive the query
+        let statement_timeout = select(dsl::sql::<sql_types::Text>(
+            "current_setting('statement_timeout')",
+        )).get_result::<String>(&*store.conn.get().unwrap())
+        .unwrap();
+        assert_eq!(statement_timeout, "0");
+
+        Ok(())
+    })
+}
+
 #[test]
 fn find_string_equal() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -393,6 +526,10 @@ fn find_string_equal() {
             order_by: None,
             order_direction: None,
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -410,7 +547,14 @@ fn find_string_not_equal() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -421,6 +565,10 @@ fn find_string_not_equal() {
             order_by: None,
             order_direction: None,
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -442,7 +590,14 @@ fn find_string_greater_than() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -453,6 +608,10 @@ fn find_string_greater_than() {
             order_by: None,
             order_direction: None,
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -474,7 +633,14 @@ fn find_string_less_than() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -485,6 +651,10 @@ fn find_string_less_than() {
             order_by: None,
             order_direction: None,
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -506,7 +676,14 @@ fn find_string_less_than_order_by_asc() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -517,6 +694,10 @@ fn find_string_less_than_order_by_asc() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Ascending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let result = store
             .find(this_query)
@@ -548,7 +729,14 @@ fn find_string_less_than_order_by_desc() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -559,6 +747,10 @@ fn find_string_less_than_order_by_desc() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let result = store
             .find(this_query)
@@ -590,7 +782,14 @@ fn find_string_less_than_range() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -601,6 +800,10 @@ fn find_string_less_than_range() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: Some(StoreRange { first: 1, skip: 1 }),
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -622,7 +825,14 @@ fn find_string_multiple_and() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -633,6 +843,10 @@ fn find_string_multiple_and() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -654,7 +868,14 @@ fn find_string_ends_with() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -665,6 +886,10 @@ fn find_string_ends_with() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -686,7 +911,14 @@ fn find_string_not_ends_with() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -697,6 +929,10 @@ fn find_string_not_ends_with() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -718,7 +954,14 @@ fn find_string_in() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -729,6 +972,10 @@ fn find_string_in() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -750,7 +997,14 @@ fn find_string_not_in() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -761,6 +1015,10 @@ fn find_string_not_in() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -783,7 +1041,14 @@ fn find_float_equal() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -794,6 +1059,10 @@ fn find_float_equal() {
             order_by: None,
             order_direction: None,
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -815,7 +1084,14 @@ fn find_float_not_equal() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -826,6 +1102,10 @@ fn find_float_not_equal() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -847,7 +1127,14 @@ fn find_float_greater_than() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -858,6 +1145,10 @@ fn find_float_greater_than() {
             order_by: None,
             order_direction: None,
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -879,7 +1170,14 @@ fn find_float_less_than() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -890,6 +1188,10 @@ fn find_float_less_than() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Ascending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -911,7 +1213,14 @@ fn find_float_less_than_order_by_desc() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -922,6 +1231,10 @@ fn find_float_less_than_order_by_desc() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -943,7 +1256,14 @@ fn find_float_less_than_range() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -954,6 +1274,10 @@ fn find_float_less_than_range() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: Some(StoreRange { first: 1, skip: 1 }),
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
         // Check if the first user in the result vector is "Cindini"
@@ -974,7 +1298,14 @@ fn find_float_in() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -985,6 +1316,10 @@ fn find_float_in() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: Some(StoreRange { first: 5, skip: 0 }),
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -1006,7 +1341,14 @@ fn find_float_not_in() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -1017,6 +1359,10 @@ fn find_float_not_in() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: Some(StoreRange { first: 5, skip: 0 }),
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -1038,7 +1384,14 @@ fn find_int_equal() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -1049,6 +1402,10 @@ fn find_int_equal() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -1070,7 +1427,14 @@ fn find_int_not_equal() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -1081,6 +1445,10 @@ fn find_int_not_equal() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -1102,7 +1470,14 @@ fn find_int_greater_than() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -1113,6 +1488,10 @@ fn find_int_greater_than() {
             order_by: None,
             order_direction: None,
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -1134,7 +1513,14 @@ fn find_int_greater_or_equal() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -1145,6 +1531,10 @@ fn find_int_greater_or_equal() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Ascending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -1166,7 +1556,14 @@ fn find_int_less_than() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -1177,6 +1574,10 @@ fn find_int_less_than() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Ascending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -1198,7 +1599,14 @@ fn find_int_less_or_equal() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -1209,6 +1617,10 @@ fn find_int_less_or_equal() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Ascending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -1230,7 +1642,14 @@ fn find_int_less_than_order_by_desc() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -1241,6 +1660,10 @@ fn find_int_less_than_order_by_desc() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -1262,7 +1685,14 @@ fn find_int_less_than_range() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -1273,6 +1703,10 @@ fn find_int_less_than_range() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: Some(StoreRange { first: 1, skip: 1 }),
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -1294,7 +1728,14 @@ fn find_int_in() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -1305,6 +1746,10 @@ fn find_int_in() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: Some(StoreRange { first: 5, skip: 0 }),
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -1326,7 +1771,14 @@ fn find_int_not_in() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -1337,6 +1789,10 @@ fn find_int_not_in() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: Some(StoreRange { first: 5, skip: 0 }),
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -1358,7 +1814,14 @@ fn find_bool_equal() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -1369,6 +1832,10 @@ fn find_bool_equal() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -1390,7 +1857,14 @@ fn find_bool_not_equal() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -1401,6 +1875,10 @@ fn find_bool_not_equal() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Ascending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find query failed");
 
@@ -1422,7 +1900,14 @@ fn find_bool_in() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -1433,6 +1918,10 @@ fn find_bool_in() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: Some(StoreRange { first: 5, skip: 0 }),
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -1454,7 +1943,14 @@ fn find_bool_not_in() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -1465,6 +1961,10 @@ fn find_bool_not_in() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: Some(StoreRange { first: 5, skip: 0 }),
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
         let returned_entities = store.find(this_query).expect("store.find operation failed");
 
@@ -1481,12 +1981,170 @@ fn find_bool_not_in() {
     })
 }
 
+#[test]
+fn find_after_cursor() {
+    run_test(|| -> Result<(), ()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
+        let this_query = StoreQuery {
+            subgraph: String::from("test_subgraph"),
+            entity: String::from("user"),
+            filter: None,
+            order_by: Some(String::from("name")),
+            order_direction: Some(StoreOrder::Ascending),
+            range: None,
+            after: Some(String::from("2")),
+            before: None,
+            last: None,
+            block: None,
+        };
+        let returned_entities = store.find(this_query).expect("store.find operation failed");
+
+        // Check that only the users after "Cindini" are returned, in order
+        let returned_names = returned_entities
+            .iter()
+            .map(|entity| entity.get(&String::from("name")).unwrap().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            returned_names,
+            vec![
+                Value::String(String::from("Johnton")),
+                Value::String(String::from("Shaqueeena")),
+            ]
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn find_after_cursor_with_null_values() {
+    run_test(|| -> Result<(), ()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
+
+        // Only user 2 has a nickname
+        let (key, mut entity, source) = create_test_entity(
+            String::from("2"),
+            String::from("user"),
+            String::from("Cindini"),
+            String::from("dinici@email.com"),
+            43 as i32,
+            159.1 as f32,
+            true,
+            String::from("b7kJ8ghP6PSITWx4lUZB"),
+        );
+        entity.insert(String::from("nickname"), Value::String(String::from("Cin")));
+        store
+            .set(key, entity, source)
+            .expect("Failed to update entity that already exists");
+
+        let find_names_after = |cursor: &str| {
+            let query = StoreQuery {
+                subgraph: String::from("test_subgraph"),
+                entity: String::from("user"),
+                filter: None,
+                order_by: Some(String::from("nickname")),
+                order_direction: Some(StoreOrder::Ascending),
+                range: None,
+                after: Some(String::from(cursor)),
+                before: None,
+                last: None,
+                block: None,
+            };
+            store
+                .find(query)
+                .expect("store.find operation failed")
+                .iter()
+                .map(|entity| entity.get(&String::from("name")).unwrap().clone())
+                .collect::<Vec<_>>()
+        };
+
+        // Users without a nickname come last, ordered by ID
+        assert_eq!(
+            find_names_after("2"),
+            vec![
+                Value::String(String::from("Johnton")),
+                Value::String(String::from("Shaqueeena")),
+            ]
+        );
+        assert_eq!(
+            find_names_after("1"),
+            vec![Value::String(String::from("Shaqueeena"))]
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn find_last_before_cursor() {
+    run_test(|| -> Result<(), ()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
+        let this_query = StoreQuery {
+            subgraph: String::from("test_subgraph"),
+            entity: String::from("user"),
+            filter: None,
+            order_by: None,
+            order_direction: Some(StoreOrder::Descending),
+            range: None,
+            after: None,
+            before: Some(String::from("1")),
+            last: Some(1),
+            block: None,
+        };
+        let returned_entities = store.find(this_query).expect("store.find operation failed");
+
+        // Ordered by ID descending, the users before user 1 are users 3 and
+        // 2; only the last of them should be returned
+        assert_eq!(1, returned_entities.len());
+        let returned_name = returned_entities[0].get(&String::from("name"));
+        let test_value = Value::String(String::from("Cindini"));
+        assert!(returned_name.is_some());
+        assert_eq!(&test_value, returned_name.unwrap());
+
+        Ok(())
+    })
+}
+
 #[test]
 fn revert_block() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -1497,6 +2155,10 @@ fn revert_block() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
 
         let block_hash = "znuyjijnezBiGFuZAW9Q";
@@ -1504,7 +2166,9 @@ fn revert_block() {
             EventSource::EthereumBlock(H256::from_slice(&block_hash.as_bytes())).to_string();
 
         // Revert all events associated with event_source, "znuyjijnezBiGFuZAW9Q"
-        store.revert_events(event_source, this_query.subgraph.clone());
+        store
+            .revert_events(event_source, this_query.subgraph.clone())
+            .unwrap();
 
         let returned_entities = store
             .find(this_query.clone())
@@ -1535,7 +2199,14 @@ fn revert_block_with_delete() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let mut store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
         let this_query = StoreQuery {
             subgraph: String::from("test_subgraph"),
             entity: String::from("user"),
@@ -1546,6 +2217,10 @@ fn revert_block_with_delete() {
             order_by: Some(String::from("name")),
             order_direction: Some(StoreOrder::Descending),
             range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
         };
 
         // Delete an entity using a randomly created event source
@@ -1563,7 +2238,9 @@ fn revert_block_with_delete() {
             .expect("Store.delete operation failed");
 
         // Revert all events associated with our random event_source
-        store.revert_events(revert_event_source, this_query.subgraph.clone());
+        store
+            .revert_events(revert_event_source, this_query.subgraph.clone())
+            .unwrap();
 
         let returned_entities = store
             .find(this_query.clone())
@@ -1586,7 +2263,9 @@ fn revert_block_with_delete() {
         store
             .delete(del_key.clone(), event_source)
             .expect("Store.delete operation failed");
-        store.revert_events(revert_event_source, this_query.subgraph.clone());
+        store
+            .revert_events(revert_event_source, this_query.subgraph.clone())
+            .unwrap();
         let returned_entities = store
             .find(this_query.clone())
             .expect("store.find operation failed");
@@ -1604,7 +2283,14 @@ fn revert_block_with_partial_update() {
     run_test(|| -> Result<(), ()> {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let mut store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
 
         let entity_key = StoreKey {
             subgraph: String::from("test_subgraph"),
@@ -1618,7 +2304,7 @@ fn revert_block_with_partial_update() {
             ("email", Value::Null),
         ]);
 
-        let original_entity = store.get(entity_key.clone()).unwrap();
+        let original_entity = store.get(entity_key.clone()).unwrap().unwrap();
         let event_source = EventSource::EthereumBlock(H256::random());
         let revert_event_source = event_source.to_string();
 
@@ -1631,18 +2317,22 @@ fn revert_block_with_partial_update() {
             .expect("Failed to update entity that already exists");
 
         // Perform revert operation, reversing the partial update
-        store.revert_events(revert_event_source.clone(), entity_key.subgraph.clone());
+        store
+            .revert_events(revert_event_source.clone(), entity_key.subgraph.clone())
+            .unwrap();
 
         // Obtain the reverted entity from the store
-        let reverted_entity = store.get(entity_key.clone()).unwrap();
+        let reverted_entity = store.get(entity_key.clone()).unwrap().unwrap();
 
         // Verify that the entity has been returned to its original state
         assert_eq!(reverted_entity, original_entity);
 
         // Perform revert operation again and verify the same results to confirm the
         // idempotent nature of the revert_events function
-        store.revert_events(revert_event_source, entity_key.subgraph.clone());
-        let reverted_entity = store.get(entity_key).unwrap();
+        store
+            .revert_events(revert_event_source, entity_key.subgraph.clone())
+            .unwrap();
+        let reverted_entity = store.get(entity_key).unwrap().unwrap();
         assert_eq!(reverted_entity, original_entity);
 
         Ok(())
@@ -1654,7 +2344,14 @@ fn entity_changes_are_fired_and_forwarded_to_subscriptions() {
     run_test(|| {
         let logger = Logger::root(slog::Discard, o!());
         let url = postgres_test_url();
-        let mut store = DieselStore::new(StoreConfig { url }, &logger);
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
 
         // Create a store subscription
         let subscription =
@@ -1761,3 +2458,834 @@ fn entity_changes_are_fired_and_forwarded_to_subscriptions() {
             }).and_then(|_| Ok(()))
     })
 }
+
+#[test]
+fn subgraph_block_ptr() {
+    run_test(|| -> Result<(), ()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
+
+        let subgraph_id = String::from("test_subgraph");
+
+        // Newly added subgraphs have not processed any blocks yet
+        store
+            .add_subgraph_if_missing(subgraph_id.clone(), String::from("mainnet"))
+            .expect("Failed to add subgraph");
+        assert_eq!(store.block_ptr(subgraph_id.clone()).unwrap(), None);
+
+        // Update the block pointer and read it back
+        let block_ptr = EthereumBlockPointer::from((H256::random(), 42u64));
+        store
+            .set_block_ptr(subgraph_id.clone(), block_ptr)
+            .expect("Failed to update block pointer");
+        assert_eq!(
+            store.block_ptr(subgraph_id.clone()).unwrap(),
+            Some(block_ptr)
+        );
+
+        // Adding the subgraph again must not reset its block pointer
+        store
+            .add_subgraph_if_missing(subgraph_id.clone(), String::from("mainnet"))
+            .expect("Failed to add subgraph");
+        assert_eq!(store.block_ptr(subgraph_id).unwrap(), Some(block_ptr));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn transact_block_operations() {
+    run_test(|| -> Result<(), ()> {
+        use db_schema::entities::dsl::*;
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
+
+        let subgraph_id = String::from("test_subgraph");
+        store
+            .add_subgraph_if_missing(subgraph_id.clone(), String::from("mainnet"))
+            .expect("Failed to add subgraph");
+
+        let updated_key = StoreKey {
+            subgraph: subgraph_id.clone(),
+            entity: String::from("user"),
+            id: String::from("1"),
+        };
+        let removed_key = StoreKey {
+            subgraph: subgraph_id.clone(),
+            entity: String::from("user"),
+            id: String::from("2"),
+        };
+
+        // Update one entity and remove another in the same block
+        let block_ptr = EthereumBlockPointer::from((H256::random(), 42u64));
+        store
+            .transact_block_operations(
+                subgraph_id.clone(),
+                block_ptr,
+                vec![
+                    EntityOperation::Set(
+                        updated_key.clone(),
+                        Entity::from(vec![("name", Value::from("Johnny Boy"))]),
+                    ),
+                    EntityOperation::Remove(removed_key),
+                ],
+            ).expect("Failed to transact block operations");
+
+        // Check that the update was merged into the existing entity
+        let updated_entity = store.get(updated_key).unwrap().unwrap();
+        assert_eq!(updated_entity.get("name"), Some(&Value::from("Johnny Boy")));
+        assert_eq!(
+            updated_entity.get("email"),
+            Some(&Value::from("tonofjohn@email.com"))
+        );
+
+        // Check that the removed entity is gone
+        let all_ids = entities
+            .select(id)
+            .load::<String>(&*store.conn.get().unwrap())
+            .unwrap();
+        assert!(!all_ids.contains(&String::from("2")));
+
+        // Check that the block pointer was moved along with the changes
+        assert_eq!(store.block_ptr(subgraph_id).unwrap(), Some(block_ptr));
+
+        Ok(())
+    })
+}
+
+/// The schema of the subgraph whose entities are stored in a table per
+/// entity type.
+const RELATIONAL_SCHEMA: &str = "
+    type Token {
+        id: ID!
+        name: String!
+        supply: BigInt!
+        decimals: Int
+        tags: [String!]
+        holders: [Holder!] @derivedFrom(field: \"token\")
+    }
+
+    type Holder {
+        id: ID!
+        token: Token!
+    }
+";
+
+/// Creates the entity tables of a subgraph with the `RELATIONAL_SCHEMA` and
+/// adds three tokens to them in one block.
+fn insert_relational_test_data(store: &DieselStore) -> EthereumBlockPointer {
+    let subgraph_id = String::from("relational_subgraph");
+    let schema = Schema {
+        name: subgraph_id.clone(),
+        id: subgraph_id.clone(),
+        document: graphql_parser::parse_schema(RELATIONAL_SCHEMA).unwrap(),
+    };
+    store
+        .add_subgraph_if_missing(subgraph_id.clone(), String::from("mainnet")).expect("Failed to add subgraph");
+    store
+        .create_entity_tables(subgraph_id.clone(), &schema)
+        .expect("Failed to create entity tables");
+
+    // Creating the tables again has no effect
+    store
+        .create_entity_tables(subgraph_id.clone(), &schema)
+        .expect("Failed to create entity tables");
+
+    let token = |id: &str, name: &str, supply: &str, tags: Vec<&str>| {
+        EntityOperation::Set(
+            StoreKey {
+                subgraph: subgraph_id.clone(),
+                entity: String::from("Token"),
+                id: String::from(id),
+            },
+            Entity::from(vec![
+                ("name", Value::from(name)),
+                ("supply", Value::BigInt(supply.parse().unwrap())),
+                (
+                    "tags",
+                    Value::List(tags.into_iter().map(Value::from).collect()),
+                ),
+            ]),
+        )
+    };
+
+    let block_ptr = EthereumBlockPointer::from((H256::random(), 1u64));
+    store
+        .transact_block_operations(
+            subgraph_id.clone(),
+            block_ptr,
+            vec![
+                token("1", "Ninety", "90000000000000000000", vec!["rare"]),
+                token("2", "Ten", "10", vec!["common", "rare"]),
+                token("3", "Nine", "9", vec![]),
+            ],
+        ).expect("Failed to transact block operations");
+    block_ptr
+}
+
+fn find_token_ids(store: &DieselStore, filter: Option<StoreFilter>, order_by: &str) -> Vec<String> {
+    store
+        .find(StoreQuery {
+            subgraph: String::from("relational_subgraph"),
+            entity: String::from("Token"),
+            filter,
+            order_by: Some(String::from(order_by)),
+            order_direction: Some(StoreOrder::Ascending),
+            range: None,
+            after: None,
+            before: None,
+            last: None,
+            block: None,
+        }).expect("store.find operation failed")
+        .into_iter()
+        .map(|entity| match entity.get("id") {
+            Some(Value::String(id)) => id.clone(),
+            id => panic!("unexpected token ID: {:?}", id),
+        }).collect()
+}
+
+#[test]
+fn relational_get_and_set() {
+    run_test(|| -> Result<(), ()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
+        insert_relational_test_data(&store);
+
+        let key = StoreKey {
+            subgraph: String::from("relational_subgraph"),
+            entity: String::from("Token"),
+            id: String::from("1"),
+        };
+        let token = store.get(key.clone()).unwrap().unwrap();
+        assert_eq!(token.get("id"), Some(&Value::from("1")));
+        assert_eq!(token.get("name"), Some(&Value::from("Ninety")));
+        assert_eq!(
+            token.get("supply"),
+            Some(&Value::BigInt("90000000000000000000".parse().unwrap()))
+        );
+        assert_eq!(
+            token.get("tags"),
+            Some(&Value::List(vec![Value::from("rare")]))
+        );
+        assert_eq!(token.get("decimals"), None);
+
+        // Updates are merged into the existing entity
+        store
+            .set(
+                key.clone(),
+                Entity::from(vec![("decimals", Value::Int(18))]),
+                EventSource::EthereumBlock(H256::random()),
+            ).unwrap();
+        let token = store.get(key.clone()).unwrap().unwrap();
+        assert_eq!(token.get("name"), Some(&Value::from("Ninety")));
+        assert_eq!(token.get("decimals"), Some(&Value::Int(18)));
+
+        // Values must fit the columns of the entity type
+        assert!(store
+            .set(
+                key.clone(),
+                Entity::from(vec![("decimals", Value::from("eighteen"))]),
+                EventSource::EthereumBlock(H256::random()),
+            ).is_err());
+        assert!(store
+            .set(
+                key.clone(),
+                Entity::from(vec![("color", Value::from("blue"))]),
+                EventSource::EthereumBlock(H256::random()),
+            ).is_err());
+
+        store
+            .delete(key.clone(), EventSource::EthereumBlock(H256::random()))
+            .unwrap();
+        assert_eq!(store.get(key).unwrap(), None);
+
+        let status = store
+            .subgraph_indexing_status(String::from("relational_subgraph"))
+            .unwrap()
+            .expect("Subgraph has no indexing status");
+        assert_eq!(status.entity_count, 2);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn relational_find() {
+    run_test(|| -> Result<(), ()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
+        insert_relational_test_data(&store);
+
+        // Big integers are ordered and compared as numbers, not as text
+        assert_eq!(find_token_ids(&store, None, "supply"), vec!["3", "2", "1"]);
+        assert_eq!(
+            find_token_ids(
+                &store,
+                Some(StoreFilter::GreaterThan(
+                    String::from("supply"),
+                    Value::BigInt("9".parse().unwrap()),
+                )),
+                "supply",
+            ),
+            vec!["2", "1"]
+        );
+
+        assert_eq!(
+            find_token_ids(
+                &store,
+                Some(StoreFilter::Or(vec![
+                    StoreFilter::StartsWith(String::from("name"), Value::from("Nin")),
+                    StoreFilter::Equal(String::from("id"), Value::from("2")),
+                ])),
+                "name",
+            ),
+            vec!["3", "1", "2"]
+        );
+
+        // Lists contain single values or all values of a list
+        assert_eq!(
+            find_token_ids(
+                &store,
+                Some(StoreFilter::Contains(
+                    String::from("tags"),
+                    Value::from("rare"),
+                )),
+                "id",
+            ),
+            vec!["1", "2"]
+        );
+        assert_eq!(
+            find_token_ids(
+                &store,
+                Some(StoreFilter::Contains(
+                    String::from("tags"),
+                    Value::List(vec![Value::from("rare"), Value::from("common")]),
+                )),
+                "id",
+            ),
+            vec!["2"]
+        );
+
+        assert_eq!(
+            find_token_ids(
+                &store,
+                Some(StoreFilter::In(
+                    String::from("id"),
+                    vec![Value::from("1"), Value::from("3")],
+                )),
+                "id",
+            ),
+            vec!["1", "3"]
+        );
+
+        // Filters that do not fit the type of a field are rejected
+        assert!(store
+            .find(StoreQuery {
+                subgraph: String::from("relational_subgraph"),
+                entity: String::from("Token"),
+                filter: Some(StoreFilter::GreaterThan(
+                    String::from("supply"),
+                    Value::from("9"),
+                )),
+                order_by: None,
+                order_direction: None,
+                range: None,
+                after: None,
+                before: None,
+                last: None,
+                block: None,
+            }).is_err());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn relational_revert_block() {
+    run_test(|| -> Result<(), ()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
+        let first_block_ptr = insert_relational_test_data(&store);
+
+        // Update one token and remove another in a second block
+        let subgraph_id = String::from("relational_subgraph");
+        let key = |id: &str| StoreKey {
+            subgraph: subgraph_id.clone(),
+            entity: String::from("Token"),
+            id: String::from(id),
+        };
+        let second_block_ptr = EthereumBlockPointer::from((H256::random(), 2u64));
+        store
+            .transact_block_operations(
+                subgraph_id.clone(),
+                second_block_ptr,
+                vec![
+                    EntityOperation::Set(
+                        key("1"),
+                        Entity::from(vec![("name", Value::from("Nil"))]),
+                    ),
+                    EntityOperation::Remove(key("2")),
+                ],
+            ).expect("Failed to transact block operations");
+        assert_eq!(find_token_ids(&store, None, "id"), vec!["1", "3"]);
+
+        // Reverting the second block restores the tokens as they were
+        store
+            .revert_events(
+                EventSource::EthereumBlock(second_block_ptr.hash).to_string(),
+                subgraph_id.clone(),
+            ).unwrap();
+        assert_eq!(find_token_ids(&store, None, "id"), vec!["1", "2", "3"]);
+        assert_eq!(
+            store.get(key("1")).unwrap().unwrap().get("name"),
+            Some(&Value::from("Ninety"))
+        );
+        assert_eq!(
+            store.get(key("2")).unwrap().unwrap().get("supply"),
+            Some(&Value::BigInt("10".parse().unwrap()))
+        );
+
+        // Reverting the first block removes the tokens
+        store
+            .revert_events(
+                EventSource::EthereumBlock(first_block_ptr.hash).to_string(),
+                subgraph_id.clone(),
+            ).unwrap();
+        assert!(find_token_ids(&store, None, "id").is_empty());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn relational_find_at_block() {
+    run_test(|| -> Result<(), ()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
+        insert_relational_test_data(&store);
+
+        let subgraph_id = String::from("relational_subgraph");
+        let key = |id: &str| StoreKey {
+            subgraph: subgraph_id.clone(),
+            entity: String::from("Token"),
+            id: String::from(id),
+        };
+        let rename = |id: &str, name: &str| {
+            EntityOperation::Set(key(id), Entity::from(vec![("name", Value::from(name))]))
+        };
+
+        // Rename one token and remove another in the second block, then
+        // rename a third token in a block that is reverted again
+        store
+            .transact_block_operations(
+                subgraph_id.clone(),
+                EthereumBlockPointer::from((H256::random(), 2u64)),
+                vec![rename("1", "Nil"), EntityOperation::Remove(key("2"))],
+            ).expect("Failed to transact block operations");
+        let reverted_block_ptr = EthereumBlockPointer::from((H256::random(), 3u64));
+        store
+            .transact_block_operations(
+                subgraph_id.clone(),
+                reverted_block_ptr,
+                vec![rename("3", "Gone")],
+            ).expect("Failed to transact block operations");
+        store
+            .revert_events(
+                EventSource::EthereumBlock(reverted_block_ptr.hash).to_string(),
+                subgraph_id.clone(),
+            ).unwrap();
+
+        let find_at = |block: u64, filter: Option<StoreFilter>, after: Option<&str>| {
+            store
+                .find(StoreQuery {
+                    subgraph: subgraph_id.clone(),
+                    entity: String::from("Token"),
+                    filter,
+                    order_by: Some(String::from("name")),
+                    order_direction: Some(StoreOrder::Ascending),
+                    range: None,
+                    after: after.map(String::from),
+                    before: None,
+                    last: None,
+                    block: Some(block),
+                }).expect("store.find operation failed")
+                .into_iter()
+                .map(|entity| match (entity.get("id"), entity.get("name")) {
+                    (Some(Value::String(id)), Some(Value::String(name))) => {
+                        (id.clone(), name.clone())
+                    }
+                    values => panic!("unexpected token: {:?}", values),
+                }).collect::<Vec<_>>()
+        };
+        let token = |id: &str, name: &str| (String::from(id), String::from(name));
+
+        assert_eq!(
+            find_at(1, None, None),
+            vec![token("3", "Nine"), token("1", "Ninety"), token("2", "Ten")]
+        );
+        assert_eq!(
+            find_at(2, None, None),
+            vec![token("1", "Nil"), token("3", "Nine")]
+        );
+
+        // Changes of reverted blocks are not visible at any block
+        assert_eq!(
+            find_at(3, None, None),
+            vec![token("1", "Nil"), token("3", "Nine")]
+        );
+
+        // Filters and cursors apply to the entities as they were
+        assert_eq!(
+            find_at(
+                1,
+                Some(StoreFilter::Equal(
+                    String::from("name"),
+                    Value::from("Ninety")
+                )),
+                None,
+            ),
+            vec![token("1", "Ninety")]
+        );
+        assert_eq!(find_at(1, None, Some("1")), vec![token("2", "Ten")]);
+
+        // The current state is unaffected
+        assert_eq!(find_token_ids(&store, None, "id"), vec!["1", "3"]);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn unmapped_schema_uses_entities_table() {
+    run_test(|| -> Result<(), ()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
+
+        // Nested lists can't be stored in entity tables
+        let subgraph_id = String::from("unmapped_subgraph");
+        let schema = Schema {
+            name: subgraph_id.clone(),
+            id: subgraph_id.clone(),
+            document: graphql_parser::parse_schema("type Grid { id: ID!, cells: [[Int!]!]! }")
+                .unwrap(),
+        };
+        store
+            .add_subgraph_if_missing(subgraph_id.clone(), String::from("mainnet"))
+            .expect("Failed to add subgraph");
+        store
+            .create_entity_tables(subgraph_id.clone(), &schema)
+            .expect("Failed to fall back to the entities table");
+
+        let key = StoreKey {
+            subgraph: subgraph_id.clone(),
+            entity: String::from("Grid"),
+            id: String::from("1"),
+        };
+        let cells = Value::List(vec![
+            Value::List(vec![Value::Int(1), Value::Int(2)]),
+            Value::List(vec![Value::Int(3)]),
+        ]);
+        store
+            .set(
+                key.clone(),
+                Entity::from(vec![("id", Value::from("1")), ("cells", cells.clone())]),
+                EventSource::EthereumBlock(H256::random()),
+            ).unwrap();
+        let grid = store.get(key).unwrap().unwrap();
+        assert_eq!(grid.get("cells"), Some(&cells));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn subgraph_deployments() {
+    run_test(|| -> Result<(), ()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
+
+        let names_ids_and_statuses = |store: &DieselStore| {
+            store
+                .subgraph_deployments()
+                .unwrap()
+                .into_iter()
+                .map(|deployment| (deployment.name, deployment.subgraph_id, deployment.status))
+                .collect::<Vec<_>>()
+        };
+
+        // Deploy two subgraphs
+        assert!(store.subgraph_deployments().unwrap().is_empty());
+        assert_eq!(
+            store
+                .upsert_subgraph_deployment(String::from("b"), String::from("QmB"))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            store
+                .upsert_subgraph_deployment(String::from("a"), String::from("QmA"))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            names_ids_and_statuses(&store),
+            vec![
+                (
+                    String::from("a"),
+                    String::from("QmA"),
+                    SubgraphDeploymentStatus::Deployed
+                ),
+                (
+                    String::from("b"),
+                    String::from("QmB"),
+                    SubgraphDeploymentStatus::Deployed
+                ),
+            ]
+        );
+
+        // Mark one of them as failed
+        store
+            .set_subgraph_deployment_status(String::from("a"), SubgraphDeploymentStatus::Failed)
+            .unwrap();
+        assert_eq!(
+            names_ids_and_statuses(&store)[0],
+            (
+                String::from("a"),
+                String::from("QmA"),
+                SubgraphDeploymentStatus::Failed
+            )
+        );
+
+        // Replacing a deployment returns the old one and resets the status
+        let old_deployment = store
+            .upsert_subgraph_deployment(String::from("a"), String::from("QmC"))
+            .unwrap()
+            .expect("Replaced deployment is missing");
+        assert_eq!(old_deployment.subgraph_id, "QmA");
+        assert_eq!(old_deployment.status, SubgraphDeploymentStatus::Failed);
+        assert_eq!(
+            names_ids_and_statuses(&store)[0],
+            (
+                String::from("a"),
+                String::from("QmC"),
+                SubgraphDeploymentStatus::Deployed
+            )
+        );
+
+        // Remove a deployment
+        store.remove_subgraph_deployment(String::from("b")).unwrap();
+        assert_eq!(names_ids_and_statuses(&store).len(), 1);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn dynamic_data_sources() {
+    run_test(|| -> Result<(), ()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
+
+        let subgraph_id = String::from("test_subgraph");
+        let first = DynamicDataSource {
+            template: String::from("Exchange"),
+            address: H160::from(1),
+        };
+        let second = DynamicDataSource {
+            template: String::from("Exchange"),
+            address: H160::from(2),
+        };
+
+        // Data sources are returned in the order they were created in, and
+        // adding an existing data source again has no effect
+        assert!(store
+            .dynamic_data_sources(subgraph_id.clone())
+            .unwrap()
+            .is_empty());
+        let first_block_ptr = EthereumBlockPointer::from((H256::from(1), 1u64));
+        let second_block_ptr = EthereumBlockPointer::from((H256::from(2), 2u64));
+        store
+            .add_dynamic_data_sources(subgraph_id.clone(), first_block_ptr, vec![first.clone()])
+            .expect("Failed to add data sources");
+        store
+            .add_dynamic_data_sources(
+                subgraph_id.clone(),
+                second_block_ptr,
+                vec![first.clone(), second.clone()],
+            ).expect("Failed to add data sources");
+        assert_eq!(
+            store.dynamic_data_sources(subgraph_id.clone()).unwrap(),
+            vec![first.clone(), second]
+        );
+
+        // Reverting a block removes the data sources created in it
+        store
+            .revert_events(
+                EventSource::EthereumBlock(second_block_ptr.hash).to_string(),
+                subgraph_id.clone(),
+            ).expect("Failed to revert block");
+        assert_eq!(
+            store.dynamic_data_sources(subgraph_id).unwrap(),
+            vec![first]
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn subgraph_health() {
+    run_test(|| -> Result<(), ()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
+
+        let subgraph_id = String::from("test_subgraph");
+        store
+            .add_subgraph_if_missing(subgraph_id.clone(), String::from("mainnet"))
+            .expect("Failed to add subgraph");
+
+        // Newly added subgraphs are syncing
+        assert_eq!(
+            store.subgraph_health(subgraph_id.clone()).unwrap(),
+            SubgraphHealth::Syncing
+        );
+
+        let failed = SubgraphHealth::Failed {
+            error: String::from("handler trapped"),
+            block_ptr: Some(EthereumBlockPointer::from((H256::from(1), 1u64))),
+        };
+        store
+            .set_subgraph_health(subgraph_id.clone(), failed.clone())
+            .expect("Failed to set subgraph health");
+        assert_eq!(store.subgraph_health(subgraph_id.clone()).unwrap(), failed);
+
+        // The error is cleared once the subgraph is healthy again
+        store
+            .set_subgraph_health(subgraph_id.clone(), SubgraphHealth::Synced)
+            .expect("Failed to set subgraph health");
+        assert_eq!(
+            store.subgraph_health(subgraph_id).unwrap(),
+            SubgraphHealth::Synced
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn subgraph_indexing_status() {
+    run_test(|| -> Result<(), ()> {
+        let logger = Logger::root(slog::Discard, o!());
+        let url = postgres_test_url();
+        let store = DieselStore::new(
+            StoreConfig {
+                url,
+                connection_pool_size: 10,
+                query_timeout: None,
+            },
+            &logger,
+        );
+
+        let subgraph_id = String::from("test_subgraph");
+        assert_eq!(
+            store.subgraph_indexing_status(subgraph_id.clone()).unwrap(),
+            None
+        );
+
+        store
+            .add_subgraph_if_missing(subgraph_id.clone(), String::from("mainnet"))
+            .expect("Failed to add subgraph");
+
+        let status = store
+            .subgraph_indexing_status(subgraph_id.clone())
+            .unwrap()
+            .expect("Subgraph has no indexing status");
+        assert_eq!(status.subgraph_id, subgraph_id);
+        assert_eq!(status.network_name, "mainnet");
+        assert_eq!(status.latest_block_ptr, None);
+        assert_eq!(status.entity_count, 3);
+        assert_eq!(status.health, SubgraphHealth::Syncing);
+
+        Ok(())
+    })
+}